@@ -0,0 +1,171 @@
+//! Throughput of loading a prover, proving and verifying with the storer test circuits,
+//! each measured on its own. Run with `cargo bench`, criterion reports proofs per second.
+//! The `witness` group times witness calculation alone, with and without an idle calculator
+//! to reuse, and with the `native_witness` feature solved from the r1cs instead. With the
+//! `parallel` feature, `witness/batch` times the witnesses of a batch with and without
+//! `set_witness_threads`.
+
+use std::time::{Duration, Instant};
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+
+use codex_storage_proofs::{
+    storage_proofs::{CircuitParams, ProofScheme, StorageProofs},
+    testvectors::openings_inputs,
+};
+
+/// Proof schemes to measure, Groth16 over BN254 being the only one implemented
+const SCHEMES: [ProofScheme; 1] = [ProofScheme::Groth16Bn254];
+
+/// Circuits by the number of chunks they prove, as `(n_samples, wasm, r1cs, zkey)`. The
+/// zkeys come from `scripts/snarkjs-setup.sh`.
+const CIRCUITS: [(usize, &str, &str, &str); 2] = [
+    (
+        3,
+        "./src/circuit_tests/artifacts/storer-test-3_js/storer-test-3.wasm",
+        "./src/circuit_tests/artifacts/storer-test-3.r1cs",
+        "./src/circuit_tests/artifacts/storer-test-3.zkey",
+    ),
+    (
+        4,
+        "./src/circuit_tests/artifacts/storer-test_js/storer-test.wasm",
+        "./src/circuit_tests/artifacts/storer-test.r1cs",
+        "./src/circuit_tests/artifacts/storer-test.zkey",
+    ),
+];
+
+fn new_prover(wasm: &str, r1cs: &str, zkey: &str, params: CircuitParams) -> StorageProofs {
+    StorageProofs::new(
+        wasm.to_string(),
+        r1cs.to_string(),
+        Some(zkey.to_string()),
+        Some(params),
+    )
+    .unwrap()
+}
+
+fn bench_storer(c: &mut Criterion) {
+    for scheme in SCHEMES {
+        let mut group = c.benchmark_group(format!("storer/{:?}", scheme));
+        group.sample_size(10);
+        group.throughput(Throughput::Elements(1));
+
+        for (n_samples, wasm, r1cs, zkey) in CIRCUITS {
+            let params = CircuitParams {
+                n_samples,
+                ..CircuitParams::default()
+            };
+            let id = |name: &str| BenchmarkId::new(name, format!("{} chunks", n_samples));
+
+            group.bench_function(id("new"), |b| {
+                b.iter(|| new_prover(wasm, r1cs, zkey, params))
+            });
+
+            // the key loaded here is reused by every iteration below
+            let prover = new_prover(wasm, r1cs, zkey, params);
+            let inputs = openings_inputs(&params).unwrap();
+            group.bench_function(id("prove"), |b| {
+                b.iter_custom(|iters| {
+                    let mut elapsed = Duration::ZERO;
+                    for _ in 0..iters {
+                        let start = Instant::now();
+                        let output = prover.prove_inputs(&inputs).unwrap();
+                        elapsed += start.elapsed();
+                        // checked outside of the measured time
+                        prover
+                            .verify_borrowed(&output.proof, &output.public_inputs)
+                            .unwrap();
+                    }
+                    elapsed
+                })
+            });
+
+            let output = prover.prove_inputs(&inputs).unwrap();
+            let (proof, public_inputs) = (output.proof.as_slice(), output.public_inputs.as_slice());
+            // prepares the key before measuring
+            prover.verify_borrowed(proof, public_inputs).unwrap();
+            group.bench_function(id("verify"), |b| {
+                b.iter(|| prover.verify_borrowed(proof, public_inputs).unwrap())
+            });
+        }
+
+        group.finish();
+    }
+}
+
+/// Witness calculation with a freshly instantiated calculator, with one reused from an
+/// earlier call, and with the `native_witness` feature without the wasm
+fn bench_witness(c: &mut Criterion) {
+    let (_, wasm, r1cs, zkey) = CIRCUITS[1];
+    let params = CircuitParams::default();
+    let prover = new_prover(wasm, r1cs, zkey, params);
+    let inputs = openings_inputs(&params).unwrap();
+
+    let mut group = c.benchmark_group("witness");
+    group.sample_size(10);
+    group.bench_function("cold", |b| {
+        b.iter_batched(
+            || {
+                // drops the idle calculators, shared with `prover`
+                let mut cold = prover.clone();
+                cold.reset();
+                cold
+            },
+            |cold| cold.calculate_witness(&inputs).unwrap(),
+            BatchSize::SmallInput,
+        )
+    });
+
+    prover.warm_up(1).unwrap();
+    group.bench_function("warm", |b| b.iter(|| prover.calculate_witness(&inputs).unwrap()));
+
+    #[cfg(feature = "native_witness")]
+    {
+        let mut native = prover.clone();
+        native.set_native_witness(true);
+        group.bench_function("native", |b| {
+            b.iter(|| native.calculate_witness(&inputs).unwrap())
+        });
+    }
+    group.finish();
+}
+
+/// The witnesses of a batch of 8 proofs one after the other, and on 4 threads
+#[cfg(feature = "parallel")]
+fn bench_batch_witnesses(c: &mut Criterion) {
+    use codex_storage_proofs::storage_proofs::ProofInputs;
+    use ruint::aliases::U256;
+
+    let (_, wasm, r1cs, zkey) = CIRCUITS[1];
+    let params = CircuitParams::default();
+    let batch = (0..8)
+        .map(|i| ProofInputs {
+            salt: U256::from(i),
+            ..openings_inputs(&params).unwrap()
+        })
+        .collect::<Vec<ProofInputs>>();
+
+    let mut group = c.benchmark_group("witness/batch");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(batch.len() as u64));
+
+    let mut prover = new_prover(wasm, r1cs, zkey, params);
+    prover.warm_up(1).unwrap();
+    group.bench_function("sequential", |b| {
+        b.iter(|| prover.calculate_witnesses(&batch).unwrap())
+    });
+
+    prover.set_witness_threads(4).unwrap();
+    // instantiates a calculator for each thread before measuring
+    prover.calculate_witnesses(&batch).unwrap();
+    group.bench_function("4 threads", |b| {
+        b.iter(|| prover.calculate_witnesses(&batch).unwrap())
+    });
+    group.finish();
+}
+
+#[cfg(feature = "parallel")]
+criterion_group!(benches, bench_storer, bench_witness, bench_batch_witnesses);
+#[cfg(not(feature = "parallel"))]
+criterion_group!(benches, bench_storer, bench_witness);
+criterion_main!(benches);
@@ -65,3 +65,25 @@ template StorageProver(BLOCK_SIZE, QUERY_LEN, LEVELS, DIGEST_CHUNK) {
         merkelizer[i].root === root;
     }
 }
+
+template StorageProverHashed(QUERY_LEN, LEVELS) {
+    // Same as StorageProver, for callers that digest the chunks themselves. The hashes
+    // are trusted as given, only their Merkle paths to the root are checked.
+    signal input siblings[QUERY_LEN][LEVELS];   // siblings hashes of chunks to be proven
+    signal input path[QUERY_LEN];               // path of chunks to be proven
+    signal input hashes[QUERY_LEN];             // hashes of chunks to be proven
+    signal input root;                          // root of the Merkle Tree
+    signal input salt;                          // salt (block hash) to prevent preimage attacks
+
+    signal saltSquare <== salt * salt;
+
+    component merkelizer[QUERY_LEN];
+    for (var i = 0; i < QUERY_LEN; i++) {
+        merkelizer[i] = MerkleProof(LEVELS);
+        merkelizer[i].leaf <== hashes[i];
+        merkelizer[i].pathElements <== siblings[i];
+        merkelizer[i].pathIndices <== path[i];
+
+        merkelizer[i].root === root;
+    }
+}
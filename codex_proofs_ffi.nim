@@ -2,22 +2,179 @@ const EXT_ID_U256_BE* = 51
 
 const EXT_ID_U256_LE* = 50
 
+const MAX_BUFFER_LEN* = 17179869184'u64
+
+const SCHEME_GROTH16_BN254* = 1'u32
+
+
+## Error codes written to the `err` out-parameter of the FFI functions
+type ProofError* {.size: sizeof(cint).} = enum
+  Ok = 0
+  InvalidUtf8
+  BadBufferLength
+  WitnessError
+  ProveError
+  VerifyError
+  ## hex, base64 or JSON input that doesn't decode
+  DecodeError
+  ## stopped by the progress or sink callback
+  Cancelled
+  ## no proving key loaded, see `set_proving_key`
+  NoProvingKey
+  ## the circuit has no public (or, proving raw inputs, input) signal of the given name
+  NoSuchSignal
+  ## the witness calculator ran out of wasm memory, the circuit is too large for it
+  WitnessMemoryExhausted
+  ## a `path` index is negative or past the tree's leaves, or there isn't one per sample
+  InvalidPath
+  ## proving with a verifier only instance, see `init_verifier`
+  NotAProver
+  ## the wasm or r1cs can't be read or don't describe a circuit that can be proved
+  CircuitLoadError
+  ## the zkey or verification key can't be read
+  KeyError
 
 type StorageProofs* {.incompleteStruct.} = object
 
+type ProverPool* {.incompleteStruct.} = object
+
+type ProveHandle* {.incompleteStruct.} = object
+
+## Byte buffer passed over the FFI.
+##
+## Buffers passed into the library are only borrowed for the duration of the call, their
+## contents are copied into owned storage before it returns, so the caller may free them
+## right after. Buffers returned by the library are owned by the caller and must be freed
+## with `free_buffer` (or together with their `ProofCtx`).
+##
+## `data` and `len` must describe a single valid allocation. A null `data` is only
+## accepted with a zero `len`, and lengths above `MAX_BUFFER_LEN` are rejected with
+## `BadBufferLength`; anything else can't be checked and is undefined behavior.
 type Buffer* = object
   data: ptr uint8
   len: uint
 
+## Proof returned over the FFI. Owns the bytes both buffers point to, which stay valid
+## until the ProofCtx is freed with `free_proof_ctx` (or `free_proof_ctx_batch`).
 type ProofCtx* = object
   proof: Buffer
   public_inputs: Buffer
 
+## A `ProofCtx` followed by the witness it proves, returned by `prove_with_witness_out`.
+## `ctx` comes first so a pointer to this can be passed anywhere a ProofCtx is taken,
+## freeing is done with `free_proof_ctx_witness` only.
+type ProofCtxWitness* = object
+  ctx: ProofCtx
+  witness: Buffer
+
+## Outcome of `verify_detailed`
+type VerifyDetails* = object
+  valid: bool
+  ## index of the first offending public input, or -1 if not attributable to one input
+  failed_index: int
+  num_inputs: uint
+  ## little endian bytes of the offending public input
+  failed_value: array[32, uint8]
+
+## # Safety
+#
+# Use after constructing a StorageProofs object with init. Returns the witness as
+# little endian field elements, to be freed with `free_buffer`, or null and sets
+# `err` on failure
+proc calculate_witness*(prover_ptr: ptr StorageProofs,
+                        chunks: ptr Buffer,
+                        siblings: ptr Buffer,
+                        hashes: ptr Buffer,
+                        path: ptr int32,
+                        path_len: uint,
+                        pubkey: ptr Buffer,
+                        root: ptr Buffer,
+                        salt: ptr Buffer,
+                        err: ptr int32): (ptr Buffer) {.importc: "calculate_witness".}
+
+## # Safety
+#
+# Use on a valid pointer to a Buffer returned by this library or panics
+proc free_buffer*(buf: ptr Buffer) {.importc: "free_buffer".}
+
 ## # Safety
 #
 # Use on a valid pointer to ProofCtx or panics
 proc free_proof_ctx*(ctx: ptr ProofCtx) {.importc: "free_proof_ctx".}
 
+## # Safety
+#
+# Use on a valid pointer returned by `prove_with_witness_out` or panics
+proc free_proof_ctx_witness*(ctx: ptr ProofCtxWitness) {.importc: "free_proof_ctx_witness".}
+
+## # Safety
+#
+# Use on a valid pointer returned by `prove_batch`, with the same `len`, or panics
+proc free_proof_ctx_batch*(ctx: ptr ProofCtx, len: uint) {.importc: "free_proof_ctx_batch".}
+
+## # Safety
+#
+# Use after constructing a StorageProofs object with init. Proves and verifies built-in
+# inputs, see `StorageProofs::self_test`. Returns false on failure and sets `err`
+# according to the failing stage, with the stage named in `last_error_message`
+proc self_test*(prover_ptr: ptr StorageProofs, err: ptr int32): bool {.importc: "self_test".}
+
+## Phase timings of a proof in nanoseconds, see `last_prove_timings`
+type ProveTimingsNs* = object
+  build_circuit: uint64
+  witness: uint64
+  prove: uint64
+  total: uint64
+
+## # Safety
+#
+# Writes the timings of the last proof returned on the current thread by `prove` (or
+# `prove_with_seed`, `prove_with_progress`, `prove_streaming`, `prove_with_witness`) to
+# `timings`. Returns false and leaves `timings` untouched if there is none. Only available
+# with the `metrics` feature
+proc last_prove_timings*(timings: ptr ProveTimingsNs): bool {.importc: "last_prove_timings".}
+
+## # Safety
+#
+# Use after constructing a StorageProofs object with init, which stays owned by the
+# caller. Returns a pool of `size` provers sharing its circuit, to be freed with
+# `free_prover_pool`, or null and sets `err` on failure
+proc init_prover_pool*(prover_ptr: ptr StorageProofs,
+                       size: uint,
+                       err: ptr int32): (ptr ProverPool) {.importc: "init_prover_pool".}
+
+## # Safety
+#
+# Use on a pool returned by `init_prover_pool`. Blocks until a prover is idle, which is
+# usable with all functions taking a StorageProofs until handed back with
+# `prover_pool_release`
+proc prover_pool_acquire*(pool: ptr ProverPool): (ptr StorageProofs) {.importc: "prover_pool_acquire".}
+
+## # Safety
+#
+# `prover` must have been acquired from `pool` and not used after this call
+proc prover_pool_release*(pool: ptr ProverPool,
+                          prover: ptr StorageProofs) {.importc: "prover_pool_release".}
+
+## # Safety
+#
+# Use on a valid pointer returned by `init_prover_pool`, once all its provers are released
+proc free_prover_pool*(pool: ptr ProverPool) {.importc: "free_prover_pool".}
+
+## # Safety
+#
+# Use after constructing a StorageProofs object with init. Returns a new handle sharing
+# the loaded circuit and proving key, without reading any files, to be freed with
+# `free_prover` independently of the original
+proc clone_prover*(prover_ptr: ptr StorageProofs): ptr StorageProofs {.importc: "clone_prover".}
+
+## # Safety
+#
+# Use after constructing a StorageProofs object with init, and not concurrently with
+# other calls on the same handle. Drops the idle witness calculators and scratch buffers
+# before an unrelated job, keeping the circuit and proving key, see `StorageProofs::reset`
+proc reset_prover*(prover_ptr: ptr StorageProofs) {.importc: "reset_prover".}
+
 ## # Safety
 #
 # Use on a valid pointer to StorageProofs or panics
@@ -25,14 +182,91 @@ proc free_prover*(prover: ptr StorageProofs) {.importc: "free_prover".}
 
 ## # Safety
 #
-# Construct a StorageProofs object
+# Use on a valid pointer returned by `public_inputs_to_json` or the `*_to_hex` functions,
+# or panics
+proc free_string*(s: cstring) {.importc: "free_string".}
+
+## # Safety
+#
+# Construct a StorageProofs object, returns null and sets `err` on failure
 proc init_storage_proofs*(r1cs: Buffer,
                           wasm: Buffer,
-                          zkey: ptr Buffer): (ptr StorageProofs) {.importc: "init_storage_proofs".}
+                          zkey: ptr Buffer,
+                          err: ptr int32): (ptr StorageProofs) {.importc: "init_storage_proofs".}
+
+## # Safety
+#
+# Construct a StorageProofs object from the raw contents of the r1cs, wasm and
+# (optional) zkey files, returns null and sets `err` on failure
+proc init_storage_proofs_bytes*(r1cs: Buffer,
+                                wasm: Buffer,
+                                zkey: ptr Buffer,
+                                err: ptr int32): (ptr StorageProofs) {.importc: "init_storage_proofs_bytes".}
+
+## # Safety
+#
+# Construct a verifier only StorageProofs object from a verification key in the
+# (compressed) arkworks form of `verification_key`, without loading any circuit. Proving
+# with it fails with `NotAProver`. Returns null and sets `err` on failure
+proc init_verifier*(vk: Buffer, err: ptr int32): (ptr StorageProofs) {.importc: "init_verifier".}
+
+## # Safety
+#
+# Same as `init_storage_proofs_bytes` without a zkey, leaving the prover without a
+# proving key until one is set with `set_proving_key`. Prove calls fail with
+# `NoProvingKey` until then
+proc init_storage_proofs_without_key*(r1cs: Buffer,
+                                      wasm: Buffer,
+                                      err: ptr int32): (ptr StorageProofs) {.importc: "init_storage_proofs_without_key".}
+
+## # Safety
+#
+# Use after constructing a StorageProofs object with init, and not concurrently with
+# other calls on it. Replaces its proving key with the raw contents of a zkey file,
+# returns false and sets `err` on failure, keeping the current key
+proc set_proving_key*(prover_ptr: ptr StorageProofs,
+                      zkey: ptr Buffer,
+                      err: ptr int32): bool {.importc: "set_proving_key".}
 
 ## # Safety
 #
 # Use after constructing a StorageProofs object with init
+proc has_proving_key*(prover_ptr: ptr StorageProofs): bool {.importc: "has_proving_key".}
+
+## Size of a loaded circuit, as read from its r1cs
+type CircuitInfo* = object
+  num_constraints: uint
+  num_public_inputs: uint
+  ## wires of the circuit, i.e. the length of each witness, the constant `1` included
+  num_witness: uint
+  num_private_inputs: uint
+
+## # Safety
+#
+# Use after constructing a StorageProofs object with init. Writes the constraint, input
+# and wire counts of the loaded circuit to `info`, returns false if `info` is null
+proc circuit_info*(prover_ptr: ptr StorageProofs,
+                   info: ptr CircuitInfo): bool {.importc: "circuit_info".}
+
+## # Safety
+#
+# Returns the message of the last error raised by an FFI call on the current thread,
+# or null if the last call succeeded. The pointer is valid until the next FFI call on
+# the same thread.
+proc last_error_message*(): cstring {.importc: "last_error_message".}
+
+## The semver version of the library, for hosts to check the ABI they were built against.
+## The buffer points to static memory and must not be freed; `len` leaves out the
+## trailing nul
+proc library_version*(): Buffer {.importc: "library_version".}
+
+## Bitmask of the proving schemes the library supports, `SCHEME_GROTH16_BN254` and so on
+proc supported_schemes*(): uint32 {.importc: "supported_schemes".}
+
+## # Safety
+#
+# Use after constructing a StorageProofs object with init, returns null and sets `err` on failure.
+# `root` may be null to have it computed from the hashes and siblings
 proc prove*(prover_ptr: ptr StorageProofs,
             chunks: ptr Buffer,
             siblings: ptr Buffer,
@@ -41,17 +275,354 @@ proc prove*(prover_ptr: ptr StorageProofs,
             path_len: uint,
             pubkey: ptr Buffer,
             root: ptr Buffer,
-            salt: ptr Buffer): (ptr ProofCtx) {.importc: "prove".}
+            salt: ptr Buffer,
+            err: ptr int32): (ptr ProofCtx) {.importc: "prove".}
 
 ## # Safety
 #
-# Use after constructing a StorageProofs object with init
+# Same as `prove`, with `pubkey`, `root` and `salt` given as big endian bytes, e.g. the
+# keccak hash of a block as is. The chunks, siblings and hashes are still little endian.
+proc prove_be*(prover_ptr: ptr StorageProofs,
+               chunks: ptr Buffer,
+               siblings: ptr Buffer,
+               hashes: ptr Buffer,
+               path: ptr int32,
+               path_len: uint,
+               pubkey: ptr Buffer,
+               root: ptr Buffer,
+               salt: ptr Buffer,
+               err: ptr int32): (ptr ProofCtx) {.importc: "prove_be".}
+
+## # Safety
+#
+# Same as `prove`, with the prover randomness derived from `seed` so identical inputs
+# and seeds give identical proofs. For test vectors and debugging only
+proc prove_with_seed*(prover_ptr: ptr StorageProofs,
+                      chunks: ptr Buffer,
+                      siblings: ptr Buffer,
+                      hashes: ptr Buffer,
+                      path: ptr int32,
+                      path_len: uint,
+                      pubkey: ptr Buffer,
+                      root: ptr Buffer,
+                      salt: ptr Buffer,
+                      seed: uint64,
+                      err: ptr int32): (ptr ProofCtx) {.importc: "prove_with_seed".}
+
+## # Safety
+#
+# Same as `prove`, also returning the witness that was proved, to be freed with
+# `free_proof_ctx_witness`. For debugging proofs that fail to verify, the witness holds
+# the private inputs
+proc prove_with_witness_out*(prover_ptr: ptr StorageProofs,
+                             chunks: ptr Buffer,
+                             siblings: ptr Buffer,
+                             hashes: ptr Buffer,
+                             path: ptr int32,
+                             path_len: uint,
+                             pubkey: ptr Buffer,
+                             root: ptr Buffer,
+                             salt: ptr Buffer,
+                             err: ptr int32): (ptr ProofCtxWitness) {.importc: "prove_with_witness_out".}
+
+## Steps of `prove_with_progress`, reported in this order
+type ProvePhase* {.size: sizeof(cint).} = enum
+  WitnessStart = 0
+  WitnessDone
+  ProveStart
+  ProveDone
+
+## Callback used by `prove_with_progress`, invoked with each phase as it's reached.
+## Returning false cancels proving.
+type ProgressCallback* = proc(phase: ProvePhase, ctx: pointer): bool {.cdecl.}
+
+## # Safety
+#
+# Same as `prove`, reporting progress to `progress_fn`, which is passed `ctx` untouched.
+# Returns null and sets `err` to `Cancelled` if the callback asked to stop
+proc prove_with_progress*(prover_ptr: ptr StorageProofs,
+                          chunks: ptr Buffer,
+                          siblings: ptr Buffer,
+                          hashes: ptr Buffer,
+                          path: ptr int32,
+                          path_len: uint,
+                          pubkey: ptr Buffer,
+                          root: ptr Buffer,
+                          salt: ptr Buffer,
+                          progress_fn: ProgressCallback,
+                          ctx: pointer,
+                          err: ptr int32): (ptr ProofCtx) {.importc: "prove_with_progress".}
+
+## # Safety
+#
+# Same as `prove`, but returns right away with a handle to the proof running on a
+# background thread, for hosts with an event loop. Poll it with `prove_poll`, then get
+# the proof with `prove_join`, or cancel it with `free_prove_handle`. Returns null and
+# sets `err` if the inputs can't be read
+proc prove_start*(prover_ptr: ptr StorageProofs,
+                  chunks: ptr Buffer,
+                  siblings: ptr Buffer,
+                  hashes: ptr Buffer,
+                  path: ptr int32,
+                  path_len: uint,
+                  pubkey: ptr Buffer,
+                  root: ptr Buffer,
+                  salt: ptr Buffer,
+                  err: ptr int32): (ptr ProveHandle) {.importc: "prove_start".}
+
+## # Safety
+#
+# Use on a handle from `prove_start`. Whether its proof is done, so `prove_join`
+# returns without blocking
+proc prove_poll*(handle: ptr ProveHandle): bool {.importc: "prove_poll".}
+
+## # Safety
+#
+# Use on a handle from `prove_start`, which is freed and must not be used afterwards.
+# Waits for the proof and returns it, or null with `err` set if proving failed
+proc prove_join*(handle: ptr ProveHandle,
+                 err: ptr int32): (ptr ProofCtx) {.importc: "prove_join".}
+
+## # Safety
+#
+# Use on a handle from `prove_start` that wasn't passed to `prove_join`. Cancels the
+# proof, which stops at its next phase on the background thread, and frees the handle
+proc free_prove_handle*(handle: ptr ProveHandle) {.importc: "free_prove_handle".}
+
+## Callback used by `prove_streaming` to pull the `index`th sampled chunk into `out` as
+## little endian field elements, returning 0 on success. The data `out` points to only has
+## to stay valid until the callback is invoked again or `prove_streaming` returns.
+type ChunkCallback* = proc(index: uint, `out`: ptr Buffer, ctx: pointer): int32 {.cdecl.}
+
+## # Safety
+#
+# Same as `prove`, but requests `chunk_count` chunks one by one from `chunk_fn`, which is
+# passed `ctx` untouched. Returns null and sets `err` on failure
+proc prove_streaming*(prover_ptr: ptr StorageProofs,
+                      chunk_count: uint,
+                      chunk_fn: ChunkCallback,
+                      ctx: pointer,
+                      siblings: ptr Buffer,
+                      hashes: ptr Buffer,
+                      path: ptr int32,
+                      path_len: uint,
+                      pubkey: ptr Buffer,
+                      root: ptr Buffer,
+                      salt: ptr Buffer,
+                      err: ptr int32): (ptr ProofCtx) {.importc: "prove_streaming".}
+
+## Callback used by `prove_chunked` to take the next piece of the serialized proof,
+## returning 0 to continue. `chunk` is only valid for the duration of the call.
+type SinkCallback* = proc(chunk: Buffer, ctx: pointer): int32 {.cdecl.}
+
+## # Safety
+#
+# Same as `prove`, but instead of returning a ProofCtx writes the proof, serialized with
+# `ProofOutput::to_bytes` in the compressed format, to `sink_fn` in pieces of at most
+# `chunk_size` bytes (all at once if 0). `ctx` is passed to it untouched. The pieces
+# are passed in order, one call at a time on the calling thread, and concatenate to the
+# whole proof; only once `sink_fn` has taken the last one does this return true. When
+# `sink_fn` returns nonzero no further pieces are passed, `err` is set to `Cancelled`
+# and false returned, as on any other failure
+proc prove_chunked*(prover_ptr: ptr StorageProofs,
+                    chunks: ptr Buffer,
+                    siblings: ptr Buffer,
+                    hashes: ptr Buffer,
+                    path: ptr int32,
+                    path_len: uint,
+                    pubkey: ptr Buffer,
+                    root: ptr Buffer,
+                    salt: ptr Buffer,
+                    chunk_size: uint,
+                    sink_fn: SinkCallback,
+                    ctx: pointer,
+                    err: ptr int32): bool {.importc: "prove_chunked".}
+
+## # Safety
+#
+# Use after constructing a StorageProofs object with init, returns null and sets `err` on failure
 proc prove_mpack_ext*(prover_ptr: ptr StorageProofs,
-                      args: ptr Buffer): (ptr ProofCtx) {.importc: "prove_mpack_ext".}
+                      args: ptr Buffer,
+                      err: ptr int32): (ptr ProofCtx) {.importc: "prove_mpack_ext".}
+
+## # Safety
+#
+# Same as `prove_mpack_ext`, returning the proof as a single mpack Buffer (see
+# `ProofOutput::to_mpack`) to be freed with `free_buffer`. Returns null and sets `err`
+# on failure
+proc prove_mpack_full*(prover_ptr: ptr StorageProofs,
+                       args: ptr Buffer,
+                       err: ptr int32): (ptr Buffer) {.importc: "prove_mpack_full".}
+
+## # Safety
+#
+# Same as `prove_mpack_ext`, with the arguments as a UTF-8 JSON document, see
+# `StorageProofs::prove_json`. Returns null and sets `err` on failure
+proc prove_json_ext*(prover_ptr: ptr StorageProofs,
+                     json: ptr Buffer,
+                     err: ptr int32): (ptr ProofCtx) {.importc: "prove_json_ext".}
+
+## # Safety
+#
+# `args` must point to `len` mpack encoded argument Buffers (see `prove_mpack_ext`).
+# Returns an array of `len` ProofCtx to be freed with `free_proof_ctx_batch`, or null
+# and sets `err` on failure
+proc prove_batch*(prover_ptr: ptr StorageProofs,
+                  args: ptr Buffer,
+                  len: uint,
+                  err: ptr int32): (ptr ProofCtx) {.importc: "prove_batch".}
+
+## # Safety
+#
+# Use after constructing a StorageProofs object with init, `witness` as returned by
+# `calculate_witness`. Returns null and sets `err` on failure
+proc prove_with_witness*(prover_ptr: ptr StorageProofs,
+                         witness: ptr Buffer,
+                         err: ptr int32): (ptr ProofCtx) {.importc: "prove_with_witness".}
+
+## # Safety
+#
+# Returns the public inputs as a JSON array of decimal strings, to be freed with
+# `free_string`, or null and sets `err` on failure
+proc public_inputs_to_json*(public_inputs: ptr Buffer,
+                            err: ptr int32): cstring {.importc: "public_inputs_to_json".}
+
+## # Safety
+#
+# Decode the hex string (with or without a `0x` prefix) in `encoded` into an owned Buffer,
+# to be freed with `free_buffer`, or returns null and sets `err` on failure
+proc buffer_from_hex*(encoded: ptr Buffer,
+                      err: ptr int32): ptr Buffer {.importc: "buffer_from_hex".}
+
+## # Safety
+#
+# Decode the standard (padded) base64 string in `encoded` into an owned Buffer, to be
+# freed with `free_buffer`, or returns null and sets `err` on failure
+proc buffer_from_base64*(encoded: ptr Buffer,
+                         err: ptr int32): ptr Buffer {.importc: "buffer_from_base64".}
+
+## # Safety
+#
+# Returns the proof bytes of `ctx` as a lowercase hex string, to be freed with `free_string`
+proc proof_ctx_to_hex*(ctx: ptr ProofCtx): cstring {.importc: "proof_ctx_to_hex".}
+
+## # Safety
+#
+# Writes the 32 byte fingerprint of `ctx` to `out`, which must point to at least 32
+# writable bytes
+proc proof_ctx_fingerprint*(ctx: ptr ProofCtx,
+                            out: ptr uint8) {.importc: "proof_ctx_fingerprint".}
+
+## # Safety
+#
+# Returns the number of public inputs of `ctx`, so hosts can read them one by one with
+# `proof_ctx_public_at` without knowing how they are serialized. 0 for a null `ctx`
+proc proof_ctx_public_count*(ctx: ptr ProofCtx): uint {.importc: "proof_ctx_public_count".}
+
+## # Safety
+#
+# Writes public input `i` of `ctx`, in circuit order, to `out_le` as 32 little endian
+# bytes, `out_le` must point to at least 32 writable bytes. Returns false, leaving
+# `out_le` untouched, if `i` isn't below `proof_ctx_public_count` or a pointer is null
+proc proof_ctx_public_at*(ctx: ptr ProofCtx,
+                          i: uint,
+                          out_le: ptr uint8): bool {.importc: "proof_ctx_public_at".}
+
+## # Safety
+#
+# Returns the serialized public inputs as a lowercase hex string, to be freed with
+# `free_string`, or null if `public_inputs` isn't a valid Buffer
+proc public_inputs_to_hex*(public_inputs: ptr Buffer): cstring {.importc: "public_inputs_to_hex".}
+
+## # Safety
+#
+# Use after constructing a StorageProofs object with init. Writes the value of the public
+# signal named by the UTF-8 `name` (e.g. `root`) in `public_inputs` to `out` as 32 little
+# endian bytes, `out` must point to at least 32 writable bytes. Returns false and sets
+# `err` to `NoSuchSignal` for names not in the circuit's public input layout, or of array
+# signals
+proc get_public_signal*(prover: ptr StorageProofs,
+                        public_inputs: ptr Buffer,
+                        name: ptr Buffer,
+                        `out`: ptr uint8,
+                        err: ptr int32): bool {.importc: "get_public_signal".}
 
 ## # Safety
 #
 # Should be called on a valid proof and public inputs previously generated by prove
+# Buffers of the wrong length, e.g. empty or truncated ones, return false with `err`
+# set to `VerifyError` without being deserialized
 proc verify*(prover_ptr: ptr StorageProofs,
              proof: ptr Buffer,
-             public_inputs: ptr Buffer): bool {.importc: "verify".}
+             public_inputs: ptr Buffer,
+             err: ptr int32): bool {.importc: "verify".}
+
+## # Safety
+#
+# `proofs` and `public_inputs` must point to `len` Buffers each, and `results` to room for
+# `len` bools. Writes whether each proof is valid to `results`, returns true if all are.
+# Null or mis-sized arrays return false and set `err`, telling bad arguments apart from
+# invalid proofs.
+proc verify_batch*(prover_ptr: ptr StorageProofs,
+                   proofs: ptr Buffer,
+                   public_inputs: ptr Buffer,
+                   len: uint,
+                   results: ptr bool,
+                   err: ptr int32): bool {.importc: "verify_batch".}
+
+## # Safety
+#
+# Use after constructing a StorageProofs object with init. Returns the serialized
+# verification key, to be freed with `free_buffer`, or null and sets `err` on failure
+proc verification_key*(prover_ptr: ptr StorageProofs,
+                       err: ptr int32): ptr Buffer {.importc: "verification_key".}
+
+## # Safety
+#
+# `vk` must hold a verification key returned by `verification_key`, doesn't need a
+# StorageProofs object. Sets `err` if any of the arguments is malformed
+proc verify_with_vk*(vk: ptr Buffer,
+                     proof: ptr Buffer,
+                     public_inputs: ptr Buffer,
+                     err: ptr int32): bool {.importc: "verify_with_vk".}
+
+## # Safety
+#
+# Checks the Merkle path of the leaf at `index` of an `arity`-ary tree from `leaf` to
+# `root` without proving. `siblings` holds `arity - 1` little endian hashes per level.
+# Returns false and sets `err` if the arguments are malformed.
+proc verify_merkle_path*(leaf: ptr Buffer,
+                         root: ptr Buffer,
+                         index: uint,
+                         siblings: ptr Buffer,
+                         arity: uint,
+                         err: ptr int32): bool {.importc: "verify_merkle_path".}
+
+## # Safety
+#
+# Use after constructing a StorageProofs object with init. Checks `claimed` is the digest
+# of the little endian field elements in `chunk`. Returns false and sets `err` if the
+# arguments are malformed.
+proc check_chunk_digest*(prover_ptr: ptr StorageProofs,
+                         chunk: ptr Buffer,
+                         claimed: ptr Buffer,
+                         err: ptr int32): bool {.importc: "check_chunk_digest".}
+
+## # Safety
+#
+# Use after constructing a StorageProofs object with init. Splits the raw bytes in `data`
+# into chunks of the circuit's `chunk_len` (the storer circuit's without circuit params),
+# see `utils::chunkify`. Returns an owned Buffer of the chunks one after another, as
+# little endian field elements, to be freed with `free_buffer`, or null and sets `err`
+proc chunkify*(prover_ptr: ptr StorageProofs,
+               data: ptr Buffer,
+               err: ptr int32): (ptr Buffer) {.importc: "chunkify".}
+
+## # Safety
+#
+# Should be called on a valid proof and public inputs previously generated by prove,
+# `details` must point to a writable VerifyDetails
+proc verify_detailed*(prover_ptr: ptr StorageProofs,
+                      proof: ptr Buffer,
+                      public_inputs: ptr Buffer,
+                      details: ptr VerifyDetails): bool {.importc: "verify_detailed".}
@@ -3,3 +3,8 @@
 circom src/circuit_tests/poseidon-digest-test.circom --r1cs --wasm -o src/circuit_tests/artifacts
 circom src/circuit_tests/poseidon-hash-test.circom --r1cs --wasm -o src/circuit_tests/artifacts
 circom src/circuit_tests/storer-test.circom --r1cs --wasm -o src/circuit_tests/artifacts
+circom src/circuit_tests/storer-test-128.circom --r1cs --wasm -o src/circuit_tests/artifacts
+circom src/circuit_tests/storer-test-3.circom --r1cs --wasm -o src/circuit_tests/artifacts
+circom src/circuit_tests/storer-test-hashed.circom --r1cs --wasm -o src/circuit_tests/artifacts
+circom src/circuit_tests/storer-test-renamed.circom --r1cs --wasm -o src/circuit_tests/artifacts
+circom src/circuit_tests/memory-hog-test.circom --r1cs --wasm -o src/circuit_tests/artifacts
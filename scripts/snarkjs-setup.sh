@@ -0,0 +1,16 @@
+#!/bin/bash
+
+set -e
+
+# groth16 setup of storer-test with snarkjs, to check our key exports against its own, and
+# of storer-test-3 for the benchmarks. Needs the artifacts of circuit-prep.sh
+ARTIFACTS=src/circuit_tests/artifacts
+npx snarkjs powersoftau new bn128 17 $ARTIFACTS/pot17_0000.ptau
+npx snarkjs powersoftau contribute $ARTIFACTS/pot17_0000.ptau $ARTIFACTS/pot17_0001.ptau -e="codex"
+npx snarkjs powersoftau prepare phase2 $ARTIFACTS/pot17_0001.ptau $ARTIFACTS/pot17_final.ptau
+npx snarkjs groth16 setup $ARTIFACTS/storer-test.r1cs $ARTIFACTS/pot17_final.ptau \
+  $ARTIFACTS/storer-test.zkey
+npx snarkjs zkey export verificationkey $ARTIFACTS/storer-test.zkey \
+  $ARTIFACTS/storer-test_verification_key.json
+npx snarkjs groth16 setup $ARTIFACTS/storer-test-3.r1cs $ARTIFACTS/pot17_final.ptau \
+  $ARTIFACTS/storer-test-3.zkey
@@ -0,0 +1,38 @@
+#!/bin/bash
+# Regenerate the fixtures in src/circuit_tests/fixtures from the artifacts built by
+# circuit-prep.sh. The fixture tests only compare against these files, so review the diff
+# before committing: it should only change after a deliberate change to the output.
+set -e
+
+ARTIFACTS=src/circuit_tests/artifacts
+FIXTURES=src/circuit_tests/fixtures
+
+cargo run --bin codex-proof -- testvector \
+  --r1cs $ARTIFACTS/storer-test.r1cs \
+  --wasm $ARTIFACTS/storer-test_js/storer-test.wasm \
+  --seed 1 --out $FIXTURES/storer-test-seed-1.json
+
+# The version 1 proof fixture has to come from the code that wrote version 1, built in a
+# temporary worktree: the last commit before the format moved to version 2 ("Version the
+# proof serialization as v2 with a length prefix")
+V1=bf894c8d387688be46b5dc96cf971843918bd747
+WORKTREE=$(mktemp -d)
+trap 'git worktree remove --force "$WORKTREE"' EXIT
+git worktree add --detach "$WORKTREE" "$V1"
+
+mkdir -p "$WORKTREE/examples"
+cat > "$WORKTREE/examples/proof_v1.rs" <<'RUST'
+use codex_storage_proofs::{storage_proofs::CircuitParams, testvectors::TestVector};
+
+fn main() {
+    let args = std::env::args().collect::<Vec<String>>();
+    let wasm = std::fs::read(&args[1]).unwrap();
+    let r1cs = std::fs::read(&args[2]).unwrap();
+    let vector = TestVector::generate(&wasm, &r1cs, CircuitParams::default(), 1).unwrap();
+    std::fs::write(&args[3], vector.output.to_compressed().unwrap()).unwrap();
+}
+RUST
+cargo run --manifest-path "$WORKTREE/Cargo.toml" --example proof_v1 -- \
+  "$PWD/$ARTIFACTS/storer-test_js/storer-test.wasm" \
+  "$PWD/$ARTIFACTS/storer-test.r1cs" \
+  "$PWD/$FIXTURES/storer-test-seed-1-proof-v1.bin"
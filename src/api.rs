@@ -0,0 +1,177 @@
+//! Safe API over owned inputs and outputs, for crates depending on this one directly
+//! rather than through the C FFI.
+//!
+//! ```
+//! use codex_storage_proofs::api::{CircuitParams, ProofInputs, Prover, PubKey, Root, Salt};
+//! use codex_storage_proofs::merkle::MerkleTree;
+//! use ruint::aliases::U256;
+//!
+//! let params = CircuitParams::default();
+//! let prover = Prover::new(
+//!     "src/circuit_tests/artifacts/storer-test_js/storer-test.wasm",
+//!     "src/circuit_tests/artifacts/storer-test.r1cs",
+//!     None,
+//!     params,
+//! )?;
+//!
+//! let chunks = (0..params.n_samples)
+//!     .map(|i| (0..params.chunk_len).map(|j| U256::from(i * 1000 + j)).collect())
+//!     .collect::<Vec<Vec<U256>>>();
+//! let hashes = chunks
+//!     .iter()
+//!     .map(|c| params.digest(c))
+//!     .collect::<Result<Vec<U256>, String>>()?;
+//!
+//! let tree = MerkleTree::new(&hashes, 2)?;
+//! let mut siblings = Vec::new();
+//! for i in 0..hashes.len() {
+//!     siblings.extend(tree.proof(i)?.siblings);
+//! }
+//!
+//! let inputs = ProofInputs::new(
+//!     chunks.concat(),
+//!     siblings,
+//!     hashes,
+//!     (0..params.n_samples as i32).collect(),
+//!     Some(Root(tree.root())),
+//!     Salt(U256::from(42)),
+//!     PubKey(U256::ZERO),
+//! );
+//!
+//! let proof = prover.prove(&inputs)?;
+//! let vk = prover.verification_key()?;
+//! assert!(proof.verify(&vk)?);
+//! # Ok::<(), String>(())
+//! ```
+//!
+//! The root, salt and public key are all field elements, but typed apart, so passing one
+//! for another doesn't compile:
+//!
+//! ```compile_fail
+//! use codex_storage_proofs::api::{ProofInputs, PubKey, Root, Salt};
+//! use ruint::aliases::U256;
+//!
+//! let (root, salt) = (Root(U256::from(1)), Salt(U256::from(2)));
+//! let inputs = ProofInputs::from_openings(&[], Some(salt), root);
+//! ```
+
+pub use crate::storage_proofs::{
+    CellOpening, CircuitParams, LoadError, ProofInputs, ProofOutput as Proof, ProveError,
+    PubKey, Root, Salt,
+};
+pub use crate::utils::DigestConfig;
+pub use zeroize::Zeroizing;
+
+use crate::storage_proofs::StorageProofs;
+
+/// Prover for a single storer circuit, cheap to clone and safe to share between threads.
+/// The C FFI hands these out as its prover handles.
+#[derive(Debug, Clone)]
+pub struct Prover {
+    inner: StorageProofs,
+}
+
+impl Prover {
+    /// Load the circuit from the wasm, r1cs and (optional) zkey files. Without a zkey,
+    /// random (insecure) parameters are generated, which is only useful for testing.
+    /// Without `params` the inputs are passed to the circuit as they are, unchecked.
+    pub fn new(
+        wasm: &str,
+        r1cs: &str,
+        zkey: Option<&str>,
+        params: impl Into<Option<CircuitParams>>,
+    ) -> Result<Self, LoadError> {
+        let inner = StorageProofs::new(
+            wasm.to_string(),
+            r1cs.to_string(),
+            zkey.map(|z| z.to_string()),
+            params.into(),
+        )?;
+
+        Ok(Self { inner })
+    }
+
+    /// Same as `new`, from the contents of the files
+    pub fn from_bytes(
+        wasm: &[u8],
+        r1cs: &[u8],
+        zkey: Option<&[u8]>,
+        params: impl Into<Option<CircuitParams>>,
+    ) -> Result<Self, LoadError> {
+        let inner = StorageProofs::from_bytes(wasm, r1cs, zkey, params.into())?;
+
+        Ok(Self { inner })
+    }
+
+    /// Same as `from_bytes` without a zkey, proving fails until one is set with
+    /// `StorageProofs::set_proving_key`
+    pub fn without_proving_key(
+        wasm: &[u8],
+        r1cs: &[u8],
+        params: impl Into<Option<CircuitParams>>,
+    ) -> Result<Self, LoadError> {
+        let inner = StorageProofs::without_proving_key(wasm, r1cs, params.into())?;
+
+        Ok(Self { inner })
+    }
+
+    /// A prover that only verifies, from a serialized verification key, see
+    /// `StorageProofs::verifier_only`
+    pub fn verifier_only(vk: &[u8]) -> Result<Self, LoadError> {
+        let inner = StorageProofs::verifier_only(vk)?;
+
+        Ok(Self { inner })
+    }
+
+    pub fn prove(&self, inputs: &ProofInputs) -> Result<Proof, ProveError> {
+        self.inner.prove_inputs(inputs)
+    }
+
+    /// Prove the mpack encoded arguments of `StorageProofs::prove_mpack`
+    pub fn prove_mpack(&self, args: &[u8]) -> Result<Proof, ProveError> {
+        let mut proof = Proof::default();
+        self.inner
+            .prove_mpack(args, &mut proof.proof, &mut proof.public_inputs)?;
+
+        Ok(proof)
+    }
+
+    pub fn prove_batch(&self, inputs: &[ProofInputs]) -> Result<Vec<Proof>, ProveError> {
+        self.inner.prove_batch(inputs)
+    }
+
+    /// Check serialized proof and public input bytes against the loaded key
+    pub fn verify(&self, proof: &[u8], public_inputs: &[u8]) -> Result<(), String> {
+        self.inner.verify(proof, public_inputs)
+    }
+
+    /// Serialized verification key, enough to check proofs with `Proof::verify`
+    pub fn verification_key(&self) -> Result<Vec<u8>, String> {
+        self.inner.verification_key()
+    }
+
+    /// Verification key in the snarkjs `verification_key.json` layout
+    pub fn verification_key_json(&self) -> Result<String, String> {
+        self.inner.verification_key_json()
+    }
+
+    /// The underlying `StorageProofs`, for functionality not mirrored here
+    pub fn storage_proofs(&self) -> &StorageProofs {
+        &self.inner
+    }
+
+    /// Same as `storage_proofs`, for its setters
+    pub fn storage_proofs_mut(&mut self) -> &mut StorageProofs {
+        &mut self.inner
+    }
+
+    pub fn into_storage_proofs(self) -> StorageProofs {
+        self.inner
+    }
+}
+
+impl From<StorageProofs> for Prover {
+    fn from(inner: StorageProofs) -> Self {
+        Self { inner }
+    }
+}
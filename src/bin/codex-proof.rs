@@ -0,0 +1,189 @@
+//! Generate and check storage proofs from files, without going through the FFI.
+//!
+//! ```text
+//! codex-proof prove --r1cs storer.r1cs --wasm storer.wasm [--zkey storer.zkey]
+//!     --inputs inputs.json --out proof.bin [--public public.bin] [--vk vk.bin]
+//!     [--format json|bin]
+//! codex-proof verify --vk vk.bin --proof proof.bin --public public.bin
+//! codex-proof testvector --r1cs storer.r1cs --wasm storer.wasm --seed 1 --out vector.json
+//! ```
+//!
+//! The inputs file uses the layout of a circom `input.json`, as taken by
+//! `StorageProofs::prove_json`: `chunks` and `siblings` hold one array per sampled cell,
+//! `hashes` and `path` one entry per sampled cell, and `root` and `salt` are single values.
+//! Field elements are decimal or `0x` hex strings, `root` can be left out to have it
+//! computed from the rest of the inputs. The circuit params are taken from the shape of
+//! the inputs.
+//!
+//! `verify` reads the `bin` format only, `json` writes the snarkjs `proof.json`,
+//! `public.json` and `verification_key.json` layouts for use with other tooling.
+//!
+//! `testvector` writes the reproducible `TestVector` for a seed, for a circuit with the
+//! default `CircuitParams`.
+
+use std::collections::HashMap;
+use std::fs;
+use std::process::ExitCode;
+
+use codex_storage_proofs::api::{CircuitParams, ProofInputs, Prover};
+use codex_storage_proofs::storage_proofs::verify_with_vk;
+use codex_storage_proofs::testvectors::TestVector;
+
+const USAGE: &str = "usage:
+  codex-proof prove --r1cs <file> --wasm <file> [--zkey <file>] --inputs <file> --out <file>
+                    [--public <file>] [--vk <file>] [--format json|bin]
+  codex-proof verify --vk <file> --proof <file> --public <file>
+  codex-proof testvector --r1cs <file> --wasm <file> --seed <n> --out <file>";
+
+/// `--name value` pairs following the subcommand
+struct Args {
+    values: HashMap<String, String>,
+}
+
+impl Args {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut values = HashMap::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let name = arg
+                .strip_prefix("--")
+                .ok_or_else(|| format!("unexpected argument `{}`", arg))?;
+            let value = args
+                .next()
+                .ok_or_else(|| format!("missing value for `--{}`", name))?;
+            values.insert(name.to_string(), value.to_string());
+        }
+
+        Ok(Self { values })
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(|v| v.as_str())
+    }
+
+    fn required(&self, name: &str) -> Result<&str, String> {
+        self.get(name)
+            .ok_or_else(|| format!("missing required argument `--{}`", name))
+    }
+}
+
+fn read(path: &str) -> Result<Vec<u8>, String> {
+    fs::read(path).map_err(|e| format!("unable to read {}: {}", path, e))
+}
+
+fn write(path: &str, contents: &[u8]) -> Result<(), String> {
+    fs::write(path, contents).map_err(|e| format!("unable to write {}: {}", path, e))
+}
+
+/// Parse the inputs file, along with the circuit parameters implied by its shape
+fn parse_inputs(json: &[u8]) -> Result<(ProofInputs, CircuitParams), String> {
+    let json = std::str::from_utf8(json).map_err(|e| format!("unable to read inputs: {}", e))?;
+    let inputs = ProofInputs::from_json(json)?;
+
+    let n_samples = inputs.hashes.len();
+    if n_samples == 0 {
+        return Err("inputs have no sampled cells".to_string());
+    }
+
+    let params = CircuitParams {
+        chunk_len: inputs.chunks.len() / n_samples,
+        tree_depth: inputs.siblings.len() / n_samples,
+        n_samples,
+        ..CircuitParams::default()
+    };
+
+    Ok((inputs, params))
+}
+
+fn prove(args: &Args) -> Result<(), String> {
+    let out = args.required("out")?;
+    let json = match args.get("format").unwrap_or("bin") {
+        "bin" => false,
+        "json" => true,
+        format => return Err(format!("unknown format `{}`, expected json or bin", format)),
+    };
+
+    let (inputs, params) = parse_inputs(&read(args.required("inputs")?)?)?;
+    let prover = Prover::new(
+        args.required("wasm")?,
+        args.required("r1cs")?,
+        args.get("zkey"),
+        params,
+    )?;
+    let proof = prover.prove(&inputs)?;
+
+    if json {
+        write(out, proof.to_snarkjs_json()?.as_bytes())?;
+    } else {
+        write(out, &proof.proof)?;
+    }
+
+    if let Some(public) = args.get("public") {
+        if json {
+            write(public, proof.to_snarkjs_public_json()?.as_bytes())?;
+        } else {
+            write(public, &proof.public_inputs)?;
+        }
+    }
+
+    if let Some(vk) = args.get("vk") {
+        if json {
+            write(vk, prover.verification_key_json()?.as_bytes())?;
+        } else {
+            write(vk, &prover.verification_key()?)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn verify(args: &Args) -> Result<bool, String> {
+    let vk = read(args.required("vk")?)?;
+    let proof = read(args.required("proof")?)?;
+    let public_inputs = read(args.required("public")?)?;
+
+    verify_with_vk(&vk, &proof, &public_inputs)
+}
+
+fn testvector(args: &Args) -> Result<(), String> {
+    let seed = args
+        .required("seed")?
+        .parse::<u64>()
+        .map_err(|e| format!("invalid seed: {}", e))?;
+    let vector = TestVector::generate(
+        &read(args.required("wasm")?)?,
+        &read(args.required("r1cs")?)?,
+        CircuitParams::default(),
+        seed,
+    )?;
+
+    write(args.required("out")?, vector.to_json()?.as_bytes())
+}
+
+fn run(args: &[String]) -> Result<bool, String> {
+    let (command, rest) = args.split_first().ok_or_else(|| USAGE.to_string())?;
+    let rest = Args::parse(rest).map_err(|e| format!("{}\n{}", e, USAGE))?;
+
+    match command.as_str() {
+        "prove" => prove(&rest).map(|_| true),
+        "verify" => verify(&rest),
+        "testvector" => testvector(&rest).map(|_| true),
+        _ => Err(format!("unknown command `{}`\n{}", command, USAGE)),
+    }
+}
+
+fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect::<Vec<String>>();
+
+    match run(&args) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => {
+            eprintln!("proof is invalid");
+            ExitCode::FAILURE
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::from(2)
+        }
+    }
+}
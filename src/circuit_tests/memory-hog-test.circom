@@ -0,0 +1,30 @@
+pragma circom 2.1.0;
+
+// Takes the storer inputs, but recurses `salt` times calculating the witness, with every
+// call holding on to some memory. A large enough salt exhausts the wasm instance.
+function countdown(n) {
+    var pad[64];
+    for (var i = 0; i < 64; i++) {
+        pad[i] = n + i;
+    }
+
+    if (n == 0) {
+        return 0;
+    }
+    return countdown(n - 1) + pad[0] - n + 1;
+}
+
+template MemoryHog() {
+    signal input chunks[1];
+    signal input siblings[1];
+    signal input hashes[1];
+    signal input path[1];
+    signal input root;
+    signal input salt;
+
+    signal depth;
+    depth <-- countdown(salt);
+    depth === salt;
+}
+
+component main { public [root, salt] } = MemoryHog();
@@ -1,20 +1,51 @@
-pub mod utils;
-
 #[cfg(test)]
-mod test {
-    use ark_bn254::Bn254;
-    use ark_circom::{CircomBuilder, CircomConfig};
+pub(crate) mod test {
+    use std::collections::{HashMap, HashSet};
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+    use std::thread;
+    use std::time::Duration;
+
+    use rmpv::{decode::read_value, encode::write_value, Value};
+
+    use ark_bn254::{Bn254, Fq, Fr, G1Affine, G2Affine};
+    use ark_ec::{AffineCurve, ProjectiveCurve};
+    use ark_ff::{BigInteger256, Field, PrimeField};
+    use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+    use ark_circom::{
+        circom::{R1CSFile, R1CS},
+        CircomBuilder, CircomConfig,
+    };
     use ark_groth16::{
         create_random_proof as prove, generate_random_parameters, prepare_inputs,
-        prepare_verifying_key, verify_proof_with_prepared_inputs, ProvingKey,
+        prepare_verifying_key, verify_proof_with_prepared_inputs, Proof, ProvingKey,
+    };
+    use ark_std::rand::{
+        distributions::Alphanumeric,
+        rngs::{StdRng, ThreadRng},
+        Rng, SeedableRng,
     };
-    use ark_std::rand::{distributions::Alphanumeric, rngs::ThreadRng, Rng};
     use rs_poseidon::poseidon::hash;
-    use ruint::aliases::U256;
+    use ruint::{aliases::U256, uint};
+    use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
 
     use crate::{
-        circuit_tests::utils::{digest, treehash},
-        storage_proofs::StorageProofs,
+        hash::{HashKind, Keccak256Reduced},
+        merkle::{MerkleTree, PADDING_LEAF},
+        mpack::encode_u256_le,
+        pool::ProverPool,
+        testvectors::{openings_inputs, TestVector},
+        utils::{digest, treehash},
+        storage_proofs::{
+            migrate_proof, verify_with_snarkjs_vk, verify_with_vk, AbsenceInputs, CellOpening,
+            ChunkInputs, CircuitParams, Endianness, FieldElementOutOfRange, FieldMode, InvalidPath,
+            LoadError, MmapLayout, MpackError, ProofFormat, ProofHeader, ProofInputs, ProofOutput,
+            ProveError, ProvePhase, PublicInputs, PublicSignal, PublicSignalError, Root, Salt,
+            SelfTestStage, SignalMap, StorageProofs, VerifyError, EXT_ID_U256_BE, EXT_ID_U256_LE,
+            FIELD_MODULUS, PROOF_FORMAT_VERSION, VERIFY_CACHE_HITS, WITNESS_CALCULATORS,
+        },
     };
 
     pub struct CircuitsTests {
@@ -94,7 +125,8 @@ mod test {
     fn test_storer() {
         let r1cs = "./src/circuit_tests/artifacts/storer-test.r1cs";
         let wasm = "./src/circuit_tests/artifacts/storer-test_js/storer-test.wasm";
-        let mut prover = StorageProofs::new(wasm.to_string(), r1cs.to_string(), None);
+        let prover =
+            StorageProofs::new(wasm.to_string(), r1cs.to_string(), None, None).unwrap();
 
         // generate a tuple of (preimages, hash), where preimages is a vector of 256 U256s
         // and hash is the hash of each vector generated using the digest function
@@ -129,25 +161,2493 @@ mod test {
             parent_hash_l,
         ];
 
-        let root = treehash(hashes.as_slice());
-        // let proof_bytes = &mut Vec::new();
-        // let public_inputs_bytes = &mut Vec::new();
-
-        // prover
-        //     .prove(
-        //         chunks.as_slice(),
-        //         siblings,
-        //         hashes.as_slice(),
-        //         path.as_slice(),
-        //         root,
-        //         root, // random salt - block hash
-        //         proof_bytes,
-        //         public_inputs_bytes,
-        //     )
-        //     .unwrap();
-
-        // assert!(prover
-        //     .verify(proof_bytes.as_slice(), public_inputs_bytes.as_slice())
-        //     .is_ok());
+        let root = treehash(hashes.as_slice()).unwrap();
+        let proof_bytes = &mut Vec::new();
+        let public_inputs_bytes = &mut Vec::new();
+
+        prover
+            .prove(
+                chunks.as_slice(),
+                siblings,
+                hashes.as_slice(),
+                path.as_slice(),
+                Root(root),
+                Salt(root), // random salt - block hash
+                proof_bytes,
+                public_inputs_bytes,
+            )
+            .unwrap();
+
+        assert!(prover
+            .verify(proof_bytes.as_slice(), public_inputs_bytes.as_slice())
+            .is_ok());
+    }
+
+    const STORER_R1CS: &str = "./src/circuit_tests/artifacts/storer-test.r1cs";
+    const STORER_WASM: &str = "./src/circuit_tests/artifacts/storer-test_js/storer-test.wasm";
+    const STORER_128_R1CS: &str = "./src/circuit_tests/artifacts/storer-test-128.r1cs";
+    const STORER_128_WASM: &str =
+        "./src/circuit_tests/artifacts/storer-test-128_js/storer-test-128.wasm";
+    const STORER_3_R1CS: &str = "./src/circuit_tests/artifacts/storer-test-3.r1cs";
+    const STORER_3_WASM: &str = "./src/circuit_tests/artifacts/storer-test-3_js/storer-test-3.wasm";
+    const STORER_HASHED_R1CS: &str = "./src/circuit_tests/artifacts/storer-test-hashed.r1cs";
+    const STORER_HASHED_WASM: &str =
+        "./src/circuit_tests/artifacts/storer-test-hashed_js/storer-test-hashed.wasm";
+    const STORER_RENAMED_R1CS: &str = "./src/circuit_tests/artifacts/storer-test-renamed.r1cs";
+    const STORER_RENAMED_WASM: &str =
+        "./src/circuit_tests/artifacts/storer-test-renamed_js/storer-test-renamed.wasm";
+
+    const MEMORY_HOG_R1CS: &str = "./src/circuit_tests/artifacts/memory-hog-test.r1cs";
+    const MEMORY_HOG_WASM: &str =
+        "./src/circuit_tests/artifacts/memory-hog-test_js/memory-hog-test.wasm";
+
+    /// Made by `scripts/snarkjs-setup.sh`, the tests reading them are ignored by default
+    const STORER_ZKEY: &str = "./src/circuit_tests/artifacts/storer-test.zkey";
+    const STORER_VK_JSON: &str =
+        "./src/circuit_tests/artifacts/storer-test_verification_key.json";
+
+    const TESTVECTOR_FIXTURE: &str = "./src/circuit_tests/fixtures/storer-test-seed-1.json";
+    const ABSENCE_FIXTURE: &str = "./src/circuit_tests/fixtures/absence-inputs.json";
+    const PROOF_V1_FIXTURE: &str = "./src/circuit_tests/fixtures/storer-test-seed-1-proof-v1.bin";
+    /// A snarkjs `verification_key.json` of generator points, `IC` being 1, 2 and 3 times G1
+    const VK_JSON_FIXTURE: &str = "./src/circuit_tests/fixtures/generators_verification_key.json";
+
+    pub(crate) fn storer_prover() -> StorageProofs {
+        StorageProofs::new(
+            STORER_WASM.to_string(),
+            STORER_R1CS.to_string(),
+            None,
+            Some(CircuitParams::default()),
+        )
+        .unwrap()
+    }
+
+    /// deterministic inputs for the storer-test circuit, salted with the root
+    pub(crate) fn storer_inputs() -> ProofInputs {
+        let data = (0..4)
+            .map(|i| {
+                let rng = StdRng::seed_from_u64(i);
+                let preimages: Vec<U256> = rng
+                    .sample_iter(Alphanumeric)
+                    .take(256)
+                    .map(|c| U256::from(c))
+                    .collect();
+                let hash = digest(&preimages, Some(16));
+                (preimages, hash)
+            })
+            .collect::<Vec<(Vec<U256>, U256)>>();
+
+        let chunks: Vec<U256> = data.iter().flat_map(|c| c.0.to_vec()).collect();
+        let hashes: Vec<U256> = data.iter().map(|c| c.1).collect();
+        let path = [0, 1, 2, 3].to_vec();
+
+        let tree = MerkleTree::new(&hashes, 2).unwrap();
+        let siblings = path
+            .iter()
+            .flat_map(|i| tree.proof(*i as usize).unwrap().siblings)
+            .collect::<Vec<U256>>();
+        let root = tree.root();
+
+        ProofInputs {
+            chunks,
+            siblings,
+            hashes,
+            path,
+            root: Some(root),
+            salt: root,
+            pubkey: U256::ZERO,
+        }
+    }
+
+    #[test]
+    fn test_storer_verify_detailed() {
+        let prover = storer_prover();
+        let ProofInputs {
+            chunks,
+            siblings,
+            hashes,
+            path,
+            root,
+            ..
+        } = storer_inputs();
+        let salt = U256::from(0xff);
+
+        let proof_bytes = &mut Vec::new();
+        let public_inputs_bytes = &mut Vec::new();
+        prover
+            .prove(
+                chunks.as_slice(),
+                siblings.as_slice(),
+                hashes.as_slice(),
+                path.as_slice(),
+                Root(root.unwrap()),
+                Salt(salt),
+                proof_bytes,
+                public_inputs_bytes,
+            )
+            .unwrap();
+
+        assert!(prover
+            .verify_detailed(proof_bytes.as_slice(), public_inputs_bytes.as_slice())
+            .is_ok());
+
+        // pack the salt big endian, as a confused integrator would
+        let mut swapped = public_inputs_bytes.clone();
+        let salt_offset = 8 + U256::BYTES;
+        swapped[salt_offset..salt_offset + U256::BYTES].reverse();
+
+        let failure = prover
+            .verify_detailed(proof_bytes.as_slice(), swapped.as_slice())
+            .unwrap_err();
+        assert_eq!(failure.index, Some(1));
+        assert_eq!(failure.inputs[0], root.unwrap());
+        assert_eq!(failure.inputs[1], U256::from_be_bytes(salt.to_le_bytes::<32>()));
+    }
+
+    /// The fixture is written by `scripts/update-fixtures.sh`, only after a deliberate change
+    /// to the circuit or the vector format
+    #[test]
+    fn test_testvector_fixture() {
+        let wasm = std::fs::read(STORER_WASM).unwrap();
+        let r1cs = std::fs::read(STORER_R1CS).unwrap();
+        let generate = || TestVector::generate(&wasm, &r1cs, CircuitParams::default(), 1).unwrap();
+
+        let vector = generate();
+        assert_eq!(generate(), vector);
+        assert!(vector.output.verify(&vector.verification_key).unwrap());
+        assert_eq!(vector.output.root().unwrap(), vector.inputs.root.unwrap());
+
+        let fixture = std::fs::read_to_string(TESTVECTOR_FIXTURE).unwrap_or_else(|e| {
+            panic!("{}: {}, see scripts/update-fixtures.sh", TESTVECTOR_FIXTURE, e)
+        });
+        assert_eq!(vector.to_json().unwrap(), fixture);
+    }
+
+    #[test]
+    fn test_storer_set_proving_key() {
+        let wasm = std::fs::read(STORER_WASM).unwrap();
+        let r1cs = std::fs::read(STORER_R1CS).unwrap();
+        let mut prover = StorageProofs::without_proving_key(&wasm, &r1cs, None).unwrap();
+        let inputs = storer_inputs();
+
+        assert!(!prover.has_proving_key());
+        let err = prover.prove_inputs(&inputs).unwrap_err();
+        assert_eq!(err, ProveError::NoProvingKey);
+        assert_eq!(
+            prover.prove_with_progress(&inputs, |_| true).unwrap_err(),
+            ProveError::NoProvingKey
+        );
+        assert!(prover.verification_key().is_err());
+
+        // a zkey that doesn't parse, or is for another circuit, leaves the prover keyless
+        let err = prover.set_proving_key(b"not a zkey").unwrap_err();
+        assert!(err.starts_with("unable to read zkey"), "{}", err);
+        let other = StorageProofs::new(
+            STORER_128_WASM.to_string(),
+            STORER_128_R1CS.to_string(),
+            None,
+            None,
+        )
+        .unwrap();
+        let err = prover
+            .set_proving_key_params(other.proving_key().unwrap().clone())
+            .unwrap_err();
+        assert!(err.starts_with("zkey is for a circuit"), "{}", err);
+        assert!(!prover.has_proving_key());
+
+        let keyed = storer_prover();
+        prover
+            .set_proving_key_params(keyed.proving_key().unwrap().clone())
+            .unwrap();
+        assert!(prover.has_proving_key());
+        let output = prover.prove_inputs(&inputs).unwrap();
+        assert!(output.verify(&keyed.verification_key().unwrap()).unwrap());
+    }
+
+    #[test]
+    fn test_storer_verifier_only() {
+        let prover = storer_prover();
+        let inputs = storer_inputs();
+        let output = prover.prove_inputs(&inputs).unwrap();
+        let (proof, public_inputs) = (output.proof.as_slice(), output.public_inputs.as_slice());
+
+        let verifier = StorageProofs::verifier_only(&prover.verification_key().unwrap()).unwrap();
+        assert!(verifier.is_verifier_only());
+        assert!(!prover.is_verifier_only());
+        verifier.verify(proof, public_inputs).unwrap();
+        verifier.verify_borrowed(proof, public_inputs).unwrap();
+        let mut tampered = output.public_inputs.clone();
+        tampered[8] ^= 1;
+        assert!(verifier.verify(proof, tampered.as_slice()).is_err());
+        assert_eq!(verifier.verification_key(), prover.verification_key());
+        assert_eq!(verifier.circuit_info().num_public_inputs, 2);
+
+        let err = verifier.prove_inputs(&inputs).unwrap_err();
+        assert_eq!(err, ProveError::NotAProver);
+        assert_eq!(
+            verifier.clone().prove_with_progress(&inputs, |_| true).unwrap_err(),
+            ProveError::NotAProver
+        );
+        let err = StorageProofs::verifier_only(b"not a vk").unwrap_err();
+        assert!(matches!(err, LoadError::Key(_)), "{:?}", err);
+        assert!(err.to_string().starts_with("unable to read verification key"), "{}", err);
+    }
+
+    #[test]
+    fn test_storer_verify_cache() {
+        let mut prover = storer_prover();
+        prover.set_verify_cache(2);
+        let output = prover.prove_inputs(&storer_inputs()).unwrap();
+        let (proof, public_inputs) = (output.proof.as_slice(), output.public_inputs.as_slice());
+        let hits = || VERIFY_CACHE_HITS.with(|c| c.get());
+
+        let before = hits();
+        prover.verify_borrowed(proof, public_inputs).unwrap();
+        assert_eq!(hits(), before);
+        prover.verify(proof, public_inputs).unwrap();
+        prover.verify_borrowed(proof, public_inputs).unwrap();
+        assert_eq!(hits(), before + 2);
+
+        // a tampered proof has its own fingerprint, so it's verified and cached as invalid
+        let mut tampered = output.public_inputs.clone();
+        tampered[8] ^= 1;
+        assert!(prover.verify_borrowed(proof, &tampered).is_err());
+        assert_eq!(hits(), before + 2);
+        assert!(prover.verify_borrowed(proof, &tampered).is_err());
+        assert_eq!(hits(), before + 3);
+        // malformed proofs aren't cached
+        assert!(prover.verify_borrowed(&proof[1..], public_inputs).is_err());
+        assert!(prover.verify_borrowed(&proof[1..], public_inputs).is_err());
+        assert_eq!(hits(), before + 3);
+
+        // the clone shares the cache, but results for the old key don't carry over
+        let mut rekeyed = prover.clone();
+        let other = storer_prover();
+        rekeyed
+            .set_proving_key_params(other.proving_key().unwrap().clone())
+            .unwrap();
+        assert!(rekeyed.verify_borrowed(proof, public_inputs).is_err());
+        assert_eq!(hits(), before + 3);
+
+        prover.clear_verify_cache();
+        prover.verify_borrowed(proof, public_inputs).unwrap();
+        assert_eq!(hits(), before + 3);
+        prover.set_verify_cache(0);
+        prover.verify_borrowed(proof, public_inputs).unwrap();
+        assert_eq!(hits(), before + 3);
+    }
+
+    #[test]
+    fn test_storer_rerandomize() {
+        let prover = storer_prover();
+        let vk = prover.verification_key().unwrap();
+        let output = prover.prove_inputs(&storer_inputs()).unwrap();
+        let mut rng = StdRng::seed_from_u64(1);
+
+        let rerandomized = output.rerandomize(&vk, &mut rng).unwrap();
+        assert!(rerandomized.verify(&vk).unwrap());
+        assert_ne!(rerandomized.proof, output.proof);
+        assert_eq!(rerandomized.public_inputs, output.public_inputs);
+        assert_ne!(rerandomized.rerandomize(&vk, &mut rng).unwrap(), rerandomized);
+
+        // a proof for other public inputs stays invalid
+        let mut tampered = rerandomized.clone();
+        tampered.public_inputs[8] ^= 1;
+        assert!(!tampered.rerandomize(&vk, &mut rng).unwrap().verify(&vk).unwrap_or(false));
+    }
+
+    #[test]
+    fn test_storer_from_bytes() {
+        let wasm = std::fs::read(STORER_WASM).unwrap();
+        let r1cs = std::fs::read(STORER_R1CS).unwrap();
+        let prover = StorageProofs::from_bytes(&wasm, &r1cs, None, None).unwrap();
+        let inputs = storer_inputs();
+
+        let proof_bytes = &mut Vec::new();
+        let public_inputs_bytes = &mut Vec::new();
+        prover
+            .prove(
+                inputs.chunks.as_slice(),
+                inputs.siblings.as_slice(),
+                inputs.hashes.as_slice(),
+                inputs.path.as_slice(),
+                Root(inputs.root.unwrap()),
+                Salt(inputs.salt),
+                proof_bytes,
+                public_inputs_bytes,
+            )
+            .unwrap();
+
+        assert!(prover
+            .verify(proof_bytes.as_slice(), public_inputs_bytes.as_slice())
+            .is_ok());
+    }
+
+    #[test]
+    fn test_storer_r1cs_curve() {
+        let wasm = std::fs::read(STORER_WASM).unwrap();
+        let mut r1cs = std::fs::read(STORER_R1CS).unwrap();
+
+        // the storer circuit is compiled for BN254, the field of our Poseidon parameters
+        let prime = FIELD_MODULUS.to_le_bytes::<32>();
+        let offset = r1cs.windows(32).position(|w| w == prime).unwrap();
+        assert!(StorageProofs::from_bytes(&wasm, &r1cs, None, None).is_ok());
+
+        // as if compiled with `circom -p bls12381`, whose hashes can't match ours
+        let bls12_381 =
+            uint!(52435875175126190479447740508185965837690552500527637822603658699938581184513_U256);
+        r1cs[offset..offset + 32].copy_from_slice(&bls12_381.to_le_bytes::<32>());
+        let err = StorageProofs::from_bytes(&wasm, &r1cs, None, None).unwrap_err();
+        assert!(matches!(err, LoadError::Circuit(_)), "{:?}", err);
+        assert!(err.to_string().starts_with("r1cs is for another curve"), "{}", err);
+    }
+
+    #[test]
+    fn test_storer_concurrent() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<StorageProofs>();
+
+        let prover = Arc::new(storer_prover());
+
+        let handles = (0..8)
+            .map(|_| {
+                let prover = prover.clone();
+                thread::spawn(move || {
+                    let inputs = storer_inputs();
+                    let proof_bytes = &mut Vec::new();
+                    let public_inputs_bytes = &mut Vec::new();
+                    prover
+                        .prove(
+                            inputs.chunks.as_slice(),
+                            inputs.siblings.as_slice(),
+                            inputs.hashes.as_slice(),
+                            inputs.path.as_slice(),
+                            Root(inputs.root.unwrap()),
+                            Salt(inputs.salt),
+                            proof_bytes,
+                            public_inputs_bytes,
+                        )
+                        .unwrap();
+
+                    (proof_bytes.clone(), public_inputs_bytes.clone())
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for handle in handles {
+            let (proof_bytes, public_inputs_bytes) = handle.join().unwrap();
+            assert!(prover
+                .verify(proof_bytes.as_slice(), public_inputs_bytes.as_slice())
+                .is_ok());
+        }
+    }
+
+    #[test]
+    fn test_storer_clone() {
+        let prover = storer_prover();
+        prover.warm_up(1).unwrap();
+        let clone = prover.clone();
+        let inputs = storer_inputs();
+
+        // the clone proves with the original's key and shares its idle calculator
+        let before = WITNESS_CALCULATORS.with(|c| c.get());
+        let from_clone = clone.prove_inputs(&inputs).unwrap();
+        let from_original = prover.prove_inputs(&inputs).unwrap();
+        assert_eq!(WITNESS_CALCULATORS.with(|c| c.get()), before);
+
+        for output in [&from_clone, &from_original] {
+            prover
+                .verify(output.proof.as_slice(), output.public_inputs.as_slice())
+                .unwrap();
+            clone
+                .verify(output.proof.as_slice(), output.public_inputs.as_slice())
+                .unwrap();
+        }
+        assert_eq!(clone.verification_key(), prover.verification_key());
+
+        // a key set on the clone stays with the clone
+        let mut rekeyed = prover.clone();
+        rekeyed
+            .set_random_proving_key(&mut StdRng::seed_from_u64(1))
+            .unwrap();
+        assert_ne!(rekeyed.verification_key(), prover.verification_key());
+        let output = prover.prove_inputs(&inputs).unwrap();
+        prover
+            .verify(output.proof.as_slice(), output.public_inputs.as_slice())
+            .unwrap();
+    }
+
+    #[test]
+    fn test_storer_reset() {
+        let mut prover = storer_prover();
+        let first = prover.prove_inputs(&storer_inputs()).unwrap();
+        prover
+            .verify_borrowed(first.proof.as_slice(), first.public_inputs.as_slice())
+            .unwrap();
+
+        // the next job starts from a fresh calculator, still proving with the same key
+        let vk = prover.verification_key().unwrap();
+        prover.reset();
+        let before = WITNESS_CALCULATORS.with(|c| c.get());
+        let second = prover
+            .prove_inputs(&ProofInputs {
+                salt: U256::from(7),
+                ..storer_inputs()
+            })
+            .unwrap();
+        assert_eq!(WITNESS_CALCULATORS.with(|c| c.get()) - before, 1);
+        assert_eq!(prover.verification_key().unwrap(), vk);
+
+        assert_ne!(first.public_inputs, second.public_inputs);
+        for output in [&first, &second] {
+            prover
+                .verify(output.proof.as_slice(), output.public_inputs.as_slice())
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_storer_prove_batch() {
+        let prover = storer_prover();
+        let inputs = (0..16).map(|_| storer_inputs()).collect::<Vec<ProofInputs>>();
+
+        let before = WITNESS_CALCULATORS.with(|c| c.get());
+        let outputs = prover.prove_batch(inputs.as_slice()).unwrap();
+        assert_eq!(WITNESS_CALCULATORS.with(|c| c.get()) - before, 1);
+
+        assert_eq!(outputs.len(), 16);
+        for output in outputs {
+            assert!(prover
+                .verify(output.proof.as_slice(), output.public_inputs.as_slice())
+                .is_ok());
+        }
+    }
+
+    #[test]
+    fn test_storer_verify_batch() {
+        let prover = storer_prover();
+        let inputs = (0..3)
+            .map(|i| {
+                let mut inputs = storer_inputs();
+                inputs.salt = U256::from(i);
+                inputs
+            })
+            .collect::<Vec<ProofInputs>>();
+        let outputs = prover.prove_batch(&inputs).unwrap();
+
+        let mut tampered_inputs = outputs[1].public_inputs.clone();
+        let last = tampered_inputs.len() - U256::BYTES;
+        tampered_inputs[last] ^= 1;
+        let truncated = &outputs[2].proof[..outputs[2].proof.len() - 1];
+
+        let items = [
+            (outputs[0].proof.as_slice(), outputs[0].public_inputs.as_slice()),
+            (outputs[1].proof.as_slice(), tampered_inputs.as_slice()),
+            (outputs[2].proof.as_slice(), outputs[2].public_inputs.as_slice()),
+            (truncated, outputs[2].public_inputs.as_slice()),
+            (outputs[0].proof.as_slice(), outputs[1].public_inputs.as_slice()),
+        ];
+
+        assert_eq!(
+            prover.verify_batch(&items),
+            vec![true, false, true, false, false]
+        );
+        assert!(prover.verify_batch(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_storer_verify_with_vk() {
+        let prover = storer_prover();
+        let output = prover.prove_batch(&[storer_inputs()]).unwrap().remove(0);
+        let other = prover
+            .prove_batch(&[ProofInputs {
+                salt: U256::from(42),
+                ..storer_inputs()
+            }])
+            .unwrap()
+            .remove(0);
+        let vk = prover.verification_key().unwrap();
+        drop(prover);
+
+        assert!(verify_with_vk(&vk, &output.proof, &output.public_inputs).unwrap());
+        assert!(!verify_with_vk(&vk, &output.proof, &other.public_inputs).unwrap());
+        assert!(verify_with_vk(&vk[..vk.len() - 1], &output.proof, &output.public_inputs).is_err());
+    }
+
+    #[test]
+    fn test_storer_verify_uncompressed() {
+        let prover = storer_prover();
+        let output = prover.prove_inputs(&storer_inputs()).unwrap();
+        let mut uncompressed = Vec::new();
+        Proof::<Bn254>::deserialize(output.proof.as_slice())
+            .unwrap()
+            .serialize_uncompressed(&mut uncompressed)
+            .unwrap();
+        assert_eq!(uncompressed.len(), crate::verify::UNCOMPRESSED_PROOF_LEN);
+
+        let vk = prover.verification_key().unwrap();
+        assert!(verify_with_vk(&vk, &uncompressed, &output.public_inputs).unwrap());
+        prover.verify(uncompressed.as_slice(), output.public_inputs.as_slice()).unwrap();
+        prover.verify_borrowed(&uncompressed, &output.public_inputs).unwrap();
+        assert!(prover
+            .verify_borrowed(&uncompressed[1..], &output.public_inputs)
+            .is_err());
+    }
+
+    #[test]
+    fn test_storer_verify_with_inputs() {
+        let prover = storer_prover();
+        let inputs = storer_inputs();
+        let output = prover.prove_inputs(&inputs).unwrap();
+
+        // the public signals of the storer circuit are the root and the salt
+        let public = [inputs.root.unwrap(), inputs.salt];
+        assert!(prover.verify_with_inputs(&output.proof, &public).unwrap());
+        assert!(!prover
+            .verify_with_inputs(&output.proof, &[public[0], public[1] + U256::from(1)])
+            .unwrap());
+        assert!(prover.verify_with_inputs(&output.proof, &public[..1]).is_err());
+        assert!(prover
+            .verify_with_inputs(&output.proof, &[public[0], FIELD_MODULUS])
+            .is_err());
+    }
+
+    #[test]
+    fn test_storer_verify_for_salt() {
+        let prover = storer_prover();
+        let block_hash = U256::from(42);
+        let output = prover
+            .prove_inputs(&ProofInputs {
+                salt: block_hash,
+                ..storer_inputs()
+            })
+            .unwrap();
+        let (proof, public_inputs) = (output.proof.as_slice(), output.public_inputs.as_slice());
+
+        assert_eq!(prover.verify_for_salt(proof, public_inputs, Some(block_hash)), Ok(()));
+        assert_eq!(prover.verify_for_salt(proof, public_inputs, None), Ok(()));
+
+        // replayed for the next block
+        let next_block = U256::from(43);
+        assert_eq!(
+            prover.verify_for_salt(proof, public_inputs, Some(next_block)),
+            Err(VerifyError::SaltMismatch {
+                expected: next_block,
+                actual: block_hash,
+            })
+        );
+        let mut tampered = public_inputs.to_vec();
+        tampered[8] ^= 1;
+        assert!(matches!(
+            prover.verify_for_salt(proof, &tampered, Some(block_hash)),
+            Err(VerifyError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn test_storer_verify_with_input_hash() {
+        let prover = storer_prover();
+        let inputs = storer_inputs();
+        let output = prover.prove_inputs(&inputs).unwrap();
+        let (proof, public_inputs) = (output.proof.as_slice(), output.public_inputs.as_slice());
+
+        // what a verifier committing to the inputs stores, `Poseidon(2)([root, salt])`
+        let input_hash = hash(&[inputs.root.unwrap(), inputs.salt]);
+        assert_eq!(StorageProofs::public_input_hash(public_inputs), Ok(input_hash));
+        assert!(prover.verify_with_input_hash(proof, public_inputs, input_hash).unwrap());
+
+        let other = input_hash + U256::from(1);
+        assert!(!prover.verify_with_input_hash(proof, public_inputs, other).unwrap());
+        let mut tampered = public_inputs.to_vec();
+        tampered[8] ^= 1;
+        let tampered_hash = StorageProofs::public_input_hash(&tampered).unwrap();
+        assert!(!prover
+            .verify_with_input_hash(proof, &tampered, tampered_hash)
+            .unwrap());
+    }
+
+    /// Names of the spans this crate creates while set as the default subscriber
+    struct SpanNames(Arc<std::sync::Mutex<Vec<&'static str>>>);
+
+    impl tracing::Subscriber for SpanNames {
+        fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+            let mut names = self.0.lock().unwrap();
+            if span.metadata().target().starts_with("codex_storage_proofs") {
+                names.push(span.metadata().name());
+            }
+
+            // ids must be nonzero
+            tracing::span::Id::from_u64(names.len() as u64 + 1)
+        }
+
+        fn record(&self, _: &tracing::span::Id, _: &tracing::span::Record<'_>) {}
+
+        fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+
+        fn event(&self, _: &tracing::Event<'_>) {}
+
+        fn enter(&self, _: &tracing::span::Id) {}
+
+        fn exit(&self, _: &tracing::span::Id) {}
+    }
+
+    #[test]
+    fn test_storer_tracing_spans() {
+        let names = Arc::new(std::sync::Mutex::new(Vec::new()));
+        tracing::subscriber::with_default(SpanNames(names.clone()), || {
+            let prover = storer_prover();
+            let output = prover.prove_inputs(&storer_inputs()).unwrap();
+            prover
+                .verify(output.proof.as_slice(), output.public_inputs.as_slice())
+                .unwrap();
+        });
+
+        assert_eq!(*names.lock().unwrap(), vec!["new", "witness", "prove", "verify"]);
+    }
+
+    #[test]
+    fn test_storer_prove_with_seed() {
+        let prover = storer_prover();
+        let inputs = storer_inputs();
+
+        let first = prover.prove_with_seed(&inputs, 7).unwrap();
+        let second = prover.prove_with_seed(&inputs, 7).unwrap();
+        assert_eq!(first.proof, second.proof);
+        assert!(prover.verify(first.proof.as_slice(), first.public_inputs.as_slice()).is_ok());
+
+        assert_ne!(prover.prove_with_seed(&inputs, 8).unwrap().proof, first.proof);
+        assert_ne!(prover.prove_inputs(&inputs).unwrap().proof, first.proof);
+    }
+
+    #[test]
+    fn test_storer_prove_with_witness_out() {
+        let prover = storer_prover();
+        let (output, witness) = prover.prove_with_witness_out(&storer_inputs()).unwrap();
+        assert!(prover.verify(output.proof.as_slice(), output.public_inputs.as_slice()).is_ok());
+
+        let r1cs: R1CS<Bn254> =
+            R1CSFile::<Bn254>::new(std::fs::File::open(STORER_R1CS).unwrap()).unwrap().into();
+        let satisfied = |witness: &[U256]| {
+            let witness = witness
+                .iter()
+                .map(|w| Fr::from_repr(BigInteger256::new(w.into_limbs())).unwrap())
+                .collect::<Vec<Fr>>();
+            let eval = |lc: &[(usize, Fr)]| {
+                lc.iter()
+                    .map(|(i, coeff)| {
+                        let wire = r1cs.wire_mapping.as_ref().map_or(*i, |m| m[*i]);
+                        *coeff * witness[wire]
+                    })
+                    .sum::<Fr>()
+            };
+
+            r1cs.constraints
+                .iter()
+                .all(|(a, b, c)| eval(a) * eval(b) == eval(c))
+        };
+
+        assert!(satisfied(&witness));
+
+        // wire 0 is the constant 1, followed by root and salt, then the first chunk element
+        let mut tampered = witness.clone();
+        tampered[3] += U256::from(1);
+        assert!(!satisfied(&tampered));
+    }
+
+    #[test]
+    fn test_storer_prove_raw_inputs() {
+        let prover = storer_prover();
+        let inputs = storer_inputs();
+        let expected = prover.prove_inputs(&inputs).unwrap();
+
+        let raw = HashMap::from([
+            ("chunks".to_string(), inputs.chunks.clone()),
+            ("siblings".to_string(), inputs.siblings.clone()),
+            ("hashes".to_string(), inputs.hashes.clone()),
+            ("path".to_string(), inputs.path.iter().map(|i| U256::from(*i)).collect()),
+            ("root".to_string(), vec![inputs.resolve_root().unwrap()]),
+            ("salt".to_string(), vec![inputs.salt]),
+        ]);
+        let output = prover.prove_raw_inputs(raw.clone()).unwrap();
+        assert_eq!(output.public_inputs, expected.public_inputs);
+        assert!(prover.verify(output.proof.as_slice(), output.public_inputs.as_slice()).is_ok());
+
+        let mut renamed = raw.clone();
+        let salt = renamed.remove("salt").unwrap();
+        renamed.insert("nonce".to_string(), salt);
+        let err = prover.prove_raw_inputs(renamed).unwrap_err();
+        assert_eq!(err, ProveError::UnknownSignal("nonce".to_string()));
+        let mut missing = raw.clone();
+        missing.remove("salt");
+        let err = prover.prove_raw_inputs(missing).unwrap_err().to_string();
+        assert!(err.starts_with("the circuit takes"), "{}", err);
+        let mut out_of_range = raw;
+        out_of_range.insert("salt".to_string(), vec![FIELD_MODULUS]);
+        let err = prover.prove_raw_inputs(out_of_range).unwrap_err().to_string();
+        assert_eq!(err, "element 0 of salt is not below the field modulus");
+    }
+
+    #[test]
+    fn test_absence_inputs_fixture() {
+        let fixture: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(ABSENCE_FIXTURE).unwrap()).unwrap();
+        let num = |v: &serde_json::Value| v.as_str().unwrap().parse::<U256>().unwrap();
+        let nums = |v: &serde_json::Value| v.as_array().unwrap().iter().map(num).collect();
+        let opening = |v: &serde_json::Value| CellOpening {
+            hash: num(&v["hash"]),
+            siblings: nums(&v["siblings"]),
+            index: v["index"].as_i64().unwrap() as i32,
+            ..Default::default()
+        };
+        let given = &fixture["inputs"];
+        let inputs = AbsenceInputs {
+            index: given["index"].as_u64().unwrap() as usize,
+            left: opening(&given["left"]),
+            right: Some(opening(&given["right"])),
+            root: Root(num(&given["root"])),
+            salt: Salt(num(&given["salt"])),
+        };
+
+        let expected = fixture["signals"]
+            .as_object()
+            .unwrap()
+            .iter()
+            .map(|(name, values)| (name.clone(), nums(values)))
+            .collect::<HashMap<String, Vec<U256>>>();
+        let raw = inputs.raw_inputs().unwrap();
+        assert_eq!(raw, expected);
+        let values = raw.values().map(Vec::len).sum::<usize>();
+        assert_eq!(values, AbsenceInputs::signal_count(2));
+
+        // a gap running to the end of the tree ends at the padding past the last leaf
+        let to_end = AbsenceInputs {
+            index: 3,
+            right: None,
+            ..inputs.clone()
+        };
+        let raw = to_end.raw_inputs().unwrap();
+        assert_eq!(raw["rightIndex"], [U256::from(4)]);
+        assert_eq!(raw["rightHash"], [PADDING_LEAF]);
+        assert_eq!(raw["rightSiblings"], [PADDING_LEAF; 2]);
+
+        let outside = AbsenceInputs {
+            index: 1,
+            ..inputs.clone()
+        };
+        let err = outside.raw_inputs().unwrap_err();
+        assert_eq!(err, "index 1 isn't between its neighbors at 1 and 3");
+        let mut unoccupied = inputs.clone();
+        unoccupied.right.as_mut().unwrap().hash = PADDING_LEAF;
+        assert!(unoccupied.raw_inputs().is_err());
+
+        // the storer circuit doesn't prove absence
+        let err = storer_prover().prove_absence(&inputs).unwrap_err();
+        assert_eq!(err, ProveError::AbsenceCircuitRequired);
+    }
+
+    #[cfg(feature = "debug")]
+    #[test]
+    fn test_storer_debug_unsatisfied() {
+        let mut prover = storer_prover();
+        let inputs = storer_inputs();
+        let witness = prover.calculate_witness(&inputs).unwrap();
+        assert_eq!(prover.unsatisfied_constraint(&witness).unwrap(), None);
+
+        // the first chunk element, see `test_storer_prove_with_witness_out`
+        let mut tampered = witness.clone();
+        tampered[3] += U256::from(1);
+        let r1cs: R1CS<Bn254> =
+            R1CSFile::<Bn254>::new(std::fs::File::open(STORER_R1CS).unwrap()).unwrap().into();
+        let to_fr = |w: &U256| Fr::from_repr(BigInteger256::new(w.into_limbs())).unwrap();
+        let eval = |lc: &[(usize, Fr)]| {
+            lc.iter()
+                .map(|(i, coeff)| *coeff * to_fr(&tampered[*i]))
+                .sum::<Fr>()
+        };
+        let expected = r1cs
+            .constraints
+            .iter()
+            .position(|(a, b, c)| eval(a) * eval(b) != eval(c))
+            .unwrap();
+
+        let unsatisfied = prover.unsatisfied_constraint(&tampered).unwrap().unwrap();
+        assert_eq!(unsatisfied.index, expected);
+        let (a, b, c) = &r1cs.constraints[expected];
+        assert!(a.iter().chain(b).chain(c).any(|(wire, _)| *wire == 3));
+        let (a, b) = (to_fr(&unsatisfied.a), to_fr(&unsatisfied.b));
+        assert_ne!(a * b, to_fr(&unsatisfied.c));
+
+        // without debugging, the witness is proved and the proof fails to verify
+        let output = prover.prove_with_witness(&tampered).unwrap();
+        assert!(prover.verify(output.proof.as_slice(), output.public_inputs.as_slice()).is_err());
+        prover.set_debug_unsatisfied(true);
+        let err = prover.prove_with_witness(&tampered).unwrap_err().to_string();
+        assert_eq!(err, unsatisfied.to_string());
+        assert!(prover.prove_with_witness(&witness).is_ok());
+        let err = prover.unsatisfied_constraint(&witness[1..]).unwrap_err();
+        assert!(err.starts_with("expected"), "{}", err);
+    }
+
+    #[cfg(feature = "native_witness")]
+    #[test]
+    fn test_storer_native_witness() {
+        let prover = storer_prover();
+        let inputs = storer_inputs();
+
+        let wasm = prover.calculate_witness(&inputs).unwrap();
+        let native = prover.native_witness(&inputs).unwrap();
+        let bytes = |w: &[U256]| w.iter().flat_map(|n| n.to_le_bytes::<32>()).collect::<Vec<u8>>();
+        assert_eq!(bytes(&native), bytes(&wasm));
+
+        // proving with it doesn't instantiate a witness calculator
+        let mut prover = storer_prover();
+        prover.set_native_witness(true);
+        let before = WITNESS_CALCULATORS.with(|c| c.get());
+        let output = prover.prove_inputs(&inputs).unwrap();
+        assert_eq!(WITNESS_CALCULATORS.with(|c| c.get()), before);
+        assert!(prover.verify(output.proof.as_slice(), output.public_inputs.as_slice()).is_ok());
+
+        let mut tampered = inputs.clone();
+        tampered.hashes[0] += U256::from(1);
+        let err = prover.native_witness(&tampered).unwrap_err();
+        assert!(err.contains("is not satisfied by the inputs"), "{}", err);
+    }
+
+    #[test]
+    fn test_prover_pool() {
+        let pool = ProverPool::from_prover(2, storer_prover()).unwrap();
+        let in_flight = AtomicUsize::new(0);
+        let max_in_flight = AtomicUsize::new(0);
+
+        let outputs = thread::scope(|s| {
+            let handles = (0..5)
+                .map(|i| {
+                    let (pool, in_flight, max_in_flight) = (&pool, &in_flight, &max_in_flight);
+                    s.spawn(move || {
+                        let prover = pool.acquire();
+                        let running = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                        max_in_flight.fetch_max(running, Ordering::SeqCst);
+
+                        let inputs = ProofInputs {
+                            salt: U256::from(i),
+                            ..storer_inputs()
+                        };
+                        let output = prover.prove_inputs(&inputs).unwrap();
+                        in_flight.fetch_sub(1, Ordering::SeqCst);
+                        (i, output)
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+
+        assert!(max_in_flight.load(Ordering::SeqCst) <= pool.size());
+        let prover = pool.acquire();
+        let layout = prover.public_input_layout();
+        for (i, output) in outputs {
+            let salt = &output.public_inputs[layout[1].offset..layout[1].offset + U256::BYTES];
+            assert_eq!(U256::from_le_slice(salt), U256::from(i));
+            assert!(prover
+                .verify(output.proof.as_slice(), output.public_inputs.as_slice())
+                .is_ok());
+        }
+
+        assert!(ProverPool::from_prover(0, storer_prover()).is_err());
+    }
+
+    #[test]
+    fn test_storer_get_public_signal() {
+        let prover = storer_prover();
+        let inputs = ProofInputs {
+            salt: U256::from(42),
+            ..storer_inputs()
+        };
+        let output = prover.prove_inputs(&inputs).unwrap();
+
+        let signal = |name: &str| prover.get_public_signal(&output.public_inputs, name);
+        assert_eq!(signal("root"), Ok(inputs.root.unwrap()));
+        assert_eq!(signal("salt"), Ok(inputs.salt));
+        assert_eq!(
+            signal("pubkey"),
+            Err(PublicSignalError::NoSuchSignal("pubkey".to_string()))
+        );
+
+        let truncated = &output.public_inputs[..output.public_inputs.len() - 1];
+        assert!(matches!(
+            prover.get_public_signal(truncated, "salt"),
+            Err(PublicSignalError::Decode(_))
+        ));
+    }
+
+    #[test]
+    fn test_storer_prove_typed() {
+        let prover = storer_prover();
+        let inputs = ProofInputs {
+            salt: U256::from(42),
+            ..storer_inputs()
+        };
+        let (output, public) = prover.prove_typed(&inputs).unwrap();
+        assert_eq!(
+            public,
+            PublicInputs {
+                root: inputs.root.unwrap(),
+                salt: inputs.salt,
+            }
+        );
+        assert_eq!(PublicInputs::decode(&output.public_inputs), Ok(public));
+        assert!(prover.verify(output.proof.as_slice(), output.public_inputs.as_slice()).is_ok());
+
+        let mut extended = output.public_inputs.clone();
+        extended[0] = 3;
+        extended.extend([0; 32]);
+        let err = PublicInputs::decode(&extended).unwrap_err();
+        assert_eq!(err, "expected the 2 storer circuit public inputs, got 3");
+    }
+
+    #[test]
+    fn test_storer_check_path() {
+        let prover = storer_prover();
+        assert_eq!(prover.check_path(&[0, 1, 2, 3]), Ok(()));
+        assert_eq!(
+            prover.check_path(&[0, -1, 2, 3]),
+            Err(InvalidPath::Negative {
+                position: 1,
+                index: -1
+            })
+        );
+        assert_eq!(
+            prover.check_path(&[0, 1, 4, 3]),
+            Err(InvalidPath::OutOfRange {
+                position: 2,
+                index: 4,
+                leaves: 4
+            })
+        );
+        assert_eq!(
+            prover.check_path(&[0, 1]),
+            Err(InvalidPath::Length {
+                expected: 4,
+                actual: 2
+            })
+        );
+
+        let inputs = ProofInputs {
+            path: vec![0, 1, 2, i32::MAX],
+            ..storer_inputs()
+        };
+        let err = prover.prove_inputs(&inputs).unwrap_err().to_string();
+        assert_eq!(
+            err,
+            "path index 2147483647 at position 3 is out of range for a tree of 4 leaves"
+        );
+    }
+
+    #[test]
+    fn test_storer_check_chunk_digest() {
+        let prover = storer_prover();
+        let inputs = storer_inputs();
+        let mut chunk = inputs.chunks[..256].to_vec();
+
+        assert!(prover.check_chunk_digest(&chunk, inputs.hashes[0]));
+        assert!(!prover.check_chunk_digest(&chunk, inputs.hashes[1]));
+        assert!(!prover.check_chunk_digest(&chunk[1..], inputs.hashes[0]));
+        chunk[17] += U256::from(1);
+        assert!(!prover.check_chunk_digest(&chunk, inputs.hashes[0]));
+        assert!(!prover.check_chunk_digest(&[], inputs.hashes[0]));
+    }
+
+    #[test]
+    fn test_storer_fingerprint() {
+        let prover = storer_prover();
+        let inputs = storer_inputs();
+
+        let first = prover.prove_with_seed(&inputs, 5).unwrap();
+        let second = prover.prove_with_seed(&inputs, 5).unwrap();
+        assert_eq!(first.fingerprint(), second.fingerprint());
+
+        let mut seen = HashSet::new();
+        assert!(seen.insert(first.clone()));
+        assert!(!seen.insert(second));
+
+        let other = prover
+            .prove_with_seed(&ProofInputs { salt: U256::from(42), ..inputs }, 5)
+            .unwrap();
+        assert_ne!(other.fingerprint(), first.fingerprint());
+        assert!(seen.insert(other));
+
+        // the same bytes split differently between proof and public inputs
+        let split = |at: usize| {
+            let bytes = [first.proof.as_slice(), first.public_inputs.as_slice()].concat();
+            ProofOutput {
+                proof: bytes[..at].to_vec(),
+                public_inputs: bytes[at..].to_vec(),
+                ..Default::default()
+            }
+        };
+        let len = first.proof.len();
+        assert_eq!(split(len).fingerprint(), first.fingerprint());
+        assert_ne!(split(len - 1).fingerprint(), first.fingerprint());
+        assert_ne!(split(len + 1).fingerprint(), first.fingerprint());
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn test_storer_prove_timings() {
+        let prover = storer_prover();
+        let output = prover.prove_inputs(&storer_inputs()).unwrap();
+        let timings = output.timings.unwrap();
+
+        assert!(!timings.build_circuit.is_zero());
+        assert!(!timings.witness.is_zero());
+        assert!(!timings.prove.is_zero());
+
+        // the phases cover everything but the bookkeeping in between
+        let phases = timings.build_circuit + timings.witness + timings.prove;
+        assert!(phases <= timings.total);
+        assert!(timings.total - phases < timings.total / 10, "{:?}", timings);
+
+        // decoded proofs carry no timings and still compare equal
+        let decoded = ProofOutput::from_compressed(&output.to_compressed().unwrap()).unwrap();
+        assert!(decoded.timings.is_none());
+        assert_eq!(decoded, output);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_storer_parallel_witnesses() {
+        let batch = (0..8)
+            .map(|i| ProofInputs {
+                salt: U256::from(i),
+                ..storer_inputs()
+            })
+            .collect::<Vec<ProofInputs>>();
+        let mut prover = storer_prover();
+        let sequential = prover.calculate_witnesses(&batch).unwrap();
+
+        // the speedup is measured by the `witness/batch` benchmark
+        prover.set_witness_threads(4).unwrap();
+        let parallel = prover.calculate_witnesses(&batch).unwrap();
+        assert_eq!(parallel, sequential);
+        assert_eq!(parallel[3], prover.calculate_witness(&batch[3]).unwrap());
+
+        // proofs of a parallel batch check out like any other
+        let outputs = prover.prove_batch(&batch[..2]).unwrap();
+        for output in outputs {
+            prover
+                .verify(output.proof.as_slice(), output.public_inputs.as_slice())
+                .unwrap();
+        }
+
+        prover.clear_witness_threads();
+        assert_eq!(prover.calculate_witnesses(&batch).unwrap(), sequential);
+    }
+
+    #[test]
+    fn test_storer_self_test() {
+        storer_prover().self_test().unwrap();
+
+        // a verifying key that doesn't match the proving key
+        let mut prover = storer_prover();
+        prover.proving_key_mut().vk.gamma_g2 = G2Affine::prime_subgroup_generator();
+        let failure = prover.self_test().unwrap_err();
+        assert_eq!(failure.stage, SelfTestStage::Verify);
+
+        // a key for a circuit with fewer wires is caught before proving
+        let mut prover = storer_prover();
+        prover.proving_key_mut().a_query.pop();
+        let failure = prover.self_test().unwrap_err();
+        assert_eq!(failure.stage, SelfTestStage::Artifacts);
+        assert!(failure.to_string().contains("wires"), "{}", failure);
+
+        // a zkey that doesn't parse is rejected when loading
+        let wasm = std::fs::read(STORER_WASM).unwrap();
+        let r1cs = std::fs::read(STORER_R1CS).unwrap();
+        let err = StorageProofs::from_bytes(&wasm, &r1cs, Some(b"not a zkey"), None).unwrap_err();
+        assert!(matches!(err, LoadError::Key(_)), "{:?}", err);
+        assert!(err.to_string().starts_with("unable to read zkey"), "{}", err);
+
+        // as is a missing file, apart from a missing circuit
+        let missing = "./src/circuit_tests/artifacts/missing.zkey".to_string();
+        let err = StorageProofs::new(
+            STORER_WASM.to_string(),
+            STORER_R1CS.to_string(),
+            Some(missing.clone()),
+            None,
+        )
+        .unwrap_err();
+        assert!(matches!(err, LoadError::Key(_)), "{:?}", err);
+        let err = StorageProofs::new(STORER_WASM.to_string(), missing, None, None).unwrap_err();
+        assert!(matches!(err, LoadError::Circuit(_)), "{:?}", err);
+    }
+
+    #[test]
+    fn test_storer_circuit_info() {
+        let prover = storer_prover();
+        let info = prover.circuit_info();
+
+        // root and salt; 4 samples of 256 elements, 2 siblings, a hash and a path index
+        assert_eq!(info.num_public_inputs, 2);
+        assert_eq!(info.num_private_inputs, 1040);
+        assert_eq!(info.num_private_inputs, CircuitParams::default().private_inputs());
+
+        let witness = prover.calculate_witness(&storer_inputs()).unwrap();
+        assert_eq!(info.num_witness, witness.len());
+        assert_eq!(info.num_witness, prover.proving_key().unwrap().a_query.len());
+        // every private input is constrained at least once
+        assert!(info.num_constraints >= info.num_private_inputs, "{:?}", info);
+    }
+
+    #[test]
+    fn test_storer_public_input_layout() {
+        let prover = storer_prover();
+        // a salt distinct from the root, to tell the two apart
+        let inputs = ProofInputs {
+            salt: U256::from(42),
+            ..storer_inputs()
+        };
+
+        let layout = prover.public_input_layout();
+        assert_eq!(
+            layout,
+            vec![
+                PublicSignal {
+                    name: "root".to_string(),
+                    offset: 8,
+                    len: 1,
+                },
+                PublicSignal {
+                    name: "salt".to_string(),
+                    offset: 40,
+                    len: 1,
+                },
+            ]
+        );
+
+        let output = prover.prove_inputs(&inputs).unwrap();
+        assert_eq!(output.public_inputs.len(), 8 + layout.len() * U256::BYTES);
+        let value = |signal: &PublicSignal| {
+            U256::from_le_slice(&output.public_inputs[signal.offset..signal.offset + U256::BYTES])
+        };
+        assert_eq!(value(&layout[0]), inputs.root.unwrap());
+        assert_eq!(value(&layout[1]), inputs.salt);
+    }
+
+    #[test]
+    fn test_storer_public_signal_arrays() {
+        let mut prover = storer_prover();
+        let inputs = ProofInputs {
+            salt: U256::from(42),
+            ..storer_inputs()
+        };
+        let output = prover.prove_inputs(&inputs).unwrap();
+        let root = inputs.root.unwrap();
+
+        // both public inputs read as a single two element commitment
+        prover.set_public_signals(&[("commitment", 2)]).unwrap();
+        let layout = prover.public_input_layout();
+        assert_eq!(
+            layout,
+            vec![PublicSignal {
+                name: "commitment".to_string(),
+                offset: 8,
+                len: 2,
+            }]
+        );
+        let signal = |prover: &StorageProofs, name: &str| {
+            prover.get_public_signal_array(&output.public_inputs, name)
+        };
+        assert_eq!(signal(&prover, "commitment"), Ok(vec![root, inputs.salt]));
+        assert_eq!(
+            prover.get_public_signal(&output.public_inputs, "commitment"),
+            Err(PublicSignalError::Array {
+                name: "commitment".to_string(),
+                len: 2,
+            })
+        );
+        assert_eq!(
+            signal(&prover, "salt"),
+            Err(PublicSignalError::NoSuchSignal("salt".to_string()))
+        );
+
+        // an array after a single value starts past it
+        prover.set_public_signals(&[("tag", 1), ("rest", 1)]).unwrap();
+        assert_eq!(prover.public_input_layout()[1].offset, 40);
+        assert_eq!(signal(&prover, "rest"), Ok(vec![inputs.salt]));
+        assert_eq!(prover.get_public_signal(&output.public_inputs, "tag"), Ok(root));
+
+        let err = prover.set_public_signals(&[("commitment", 3)]).unwrap_err();
+        assert_eq!(err, "the circuit has 2 public inputs, the signals add up to 3");
+        assert!(prover.set_public_signals(&[("a", 2), ("b", 0)]).is_err());
+        // the clone keeps the names, a failed call kept the last ones
+        assert_eq!(prover.clone().public_input_layout()[0].name, "tag");
+        let truncated = &output.public_inputs[..output.public_inputs.len() - 1];
+        assert!(matches!(
+            prover.get_public_signal_array(truncated, "rest"),
+            Err(PublicSignalError::Decode(_))
+        ));
+    }
+
+    #[test]
+    fn test_storer_compute_root() {
+        let prover = storer_prover();
+        let expected = storer_inputs().root.unwrap();
+        let inputs = ProofInputs {
+            root: None,
+            ..storer_inputs()
+        };
+
+        assert_eq!(inputs.resolve_root().unwrap(), expected);
+        let output = prover.prove_inputs(&inputs).unwrap();
+        assert_eq!(output.root().unwrap(), expected);
+        assert!(prover
+            .verify(output.proof.as_slice(), output.public_inputs.as_slice())
+            .is_ok());
+
+        // the root is left out of the mpack arguments as well
+        let decoded = prover.decode_mpack(&inputs.to_mpack()).unwrap();
+        assert_eq!(decoded.root, None);
+        assert!(prover.prove_mpack(&inputs.to_mpack(), &mut Vec::new(), &mut Vec::new()).is_ok());
+    }
+
+    #[test]
+    fn test_storer_root_mismatch() {
+        let prover = storer_prover();
+        let inputs = ProofInputs {
+            root: Some(U256::from(1)),
+            ..storer_inputs()
+        };
+
+        let err = prover.prove_inputs(&inputs).unwrap_err().to_string();
+        assert!(err.contains("doesn't match the root"), "{}", err);
+
+        // a sibling that doesn't belong to the tree
+        let mut inputs = storer_inputs();
+        inputs.siblings[3] = U256::from(1);
+        assert!(prover.prove_inputs(&inputs).is_err());
+        inputs.root = None;
+        let err = prover.prove_inputs(&inputs).unwrap_err().to_string();
+        assert!(err.contains("different root"), "{}", err);
+    }
+
+    #[test]
+    fn test_storer_swapped_siblings() {
+        let prover = storer_prover();
+        let mut inputs = storer_inputs();
+        // the levels of the second sample's path, given from the root down
+        inputs.siblings.swap(2, 3);
+
+        let err = prover.prove_inputs(&inputs).unwrap_err().to_string();
+        assert_eq!(
+            err,
+            "path of hash 1: siblings are ordered from the root down, they must start at the \
+             leaf's level"
+        );
+        assert_eq!(inputs.resolve_root().unwrap_err(), err);
+    }
+
+    #[test]
+    fn test_witness_memory_exhausted() {
+        let prover = StorageProofs::new(
+            MEMORY_HOG_WASM.to_string(),
+            MEMORY_HOG_R1CS.to_string(),
+            None,
+            None,
+        )
+        .unwrap();
+        // the circuit recurses `salt` times
+        let inputs = |salt: u64| ProofInputs {
+            chunks: vec![U256::from(1)],
+            siblings: vec![U256::from(2)],
+            hashes: vec![U256::from(3)],
+            path: vec![0],
+            root: None,
+            salt: U256::from(salt),
+            ..Default::default()
+        };
+
+        let output = prover.prove_inputs(&inputs(10)).unwrap();
+        prover
+            .verify(output.proof.as_slice(), output.public_inputs.as_slice())
+            .unwrap();
+
+        let err = prover.prove_inputs(&inputs(1 << 40)).unwrap_err();
+        assert_eq!(err, ProveError::WitnessMemoryExhausted);
+        let err = prover.prove_with_progress(&inputs(1 << 40), |_| true).unwrap_err();
+        assert_eq!(err, ProveError::WitnessMemoryExhausted);
+        assert!(err.to_string().contains("chunk_len"));
+        assert_eq!(
+            prover.calculate_witness(&inputs(1 << 40)),
+            Err(ProveError::WitnessMemoryExhausted)
+        );
+
+        // the exhausted instance was dropped, the next proof runs in a fresh one
+        let output = prover.prove_inputs(&inputs(10)).unwrap();
+        prover
+            .verify(output.proof.as_slice(), output.public_inputs.as_slice())
+            .unwrap();
+    }
+
+    #[test]
+    fn test_storer_prove_with_progress() {
+        let prover = storer_prover();
+        let inputs = storer_inputs();
+
+        let mut phases = Vec::new();
+        let output = prover
+            .prove_with_progress(&inputs, |phase| {
+                phases.push(phase);
+                true
+            })
+            .unwrap();
+        assert_eq!(
+            phases,
+            vec![
+                ProvePhase::WitnessStart,
+                ProvePhase::WitnessDone,
+                ProvePhase::ProveStart,
+                ProvePhase::ProveDone
+            ]
+        );
+        assert!(prover
+            .verify(output.proof.as_slice(), output.public_inputs.as_slice())
+            .is_ok());
+
+        let mut phases = Vec::new();
+        let res = prover.prove_with_progress(&inputs, |phase| {
+            phases.push(phase);
+            phase != ProvePhase::WitnessDone
+        });
+        assert_eq!(res, Err(ProveError::Cancelled(ProvePhase::WitnessDone)));
+        assert_eq!(phases, vec![ProvePhase::WitnessStart, ProvePhase::WitnessDone]);
+    }
+
+    #[test]
+    fn test_storer_prove_async() {
+        let prover = storer_prover();
+        let inputs = storer_inputs();
+
+        let mut handle = prover.prove_async(&inputs);
+        while !handle.is_ready() {
+            assert!(handle.try_take().is_none());
+            thread::sleep(Duration::from_millis(10));
+        }
+        let output = handle.try_take().unwrap().unwrap();
+        assert!(handle.try_take().is_none());
+        prover
+            .verify(output.proof.as_slice(), output.public_inputs.as_slice())
+            .unwrap();
+
+        // dropped right away, the proof is cancelled without affecting the prover
+        drop(prover.prove_async(&inputs));
+        let output = prover.prove_async(&inputs).join().unwrap();
+        prover
+            .verify(output.proof.as_slice(), output.public_inputs.as_slice())
+            .unwrap();
+    }
+
+    #[test]
+    fn test_storer_padded_tree() {
+        let params = CircuitParams::default();
+        let prover = storer_prover();
+        let chunks = (0..3)
+            .map(|i| (0..params.chunk_len).map(|j| U256::from(i * 1000 + j)).collect())
+            .collect::<Vec<Vec<U256>>>();
+        let salt = Salt(U256::from(7));
+
+        let inputs = ProofInputs::from_chunks(&params, &chunks, &[0, 1, 2, 2], salt).unwrap();
+        let tree = params.tree(&inputs.hashes[..3]).unwrap();
+        assert_eq!(tree.depth(), params.tree_depth);
+        assert_eq!(tree.leaves()[3], PADDING_LEAF);
+        assert_eq!(inputs.root, Some(tree.root()));
+
+        let output = prover.prove_inputs(&inputs).unwrap();
+        prover
+            .verify(output.proof.as_slice(), output.public_inputs.as_slice())
+            .unwrap();
+
+        let err = ProofInputs::from_chunks(&params, &chunks, &[0, 1, 2, 3], salt).unwrap_err();
+        assert_eq!(err, "sampled chunk 3 out of range for 3 chunks");
+        let too_many = vec![chunks[0].clone(); 5];
+        let err = ProofInputs::from_chunks(&params, &too_many, &[0; 4], salt).unwrap_err();
+        assert_eq!(err, "5 leaves don't fit in a tree of depth 2");
+    }
+
+    #[test]
+    fn test_storer_witness_calculator_reuse() {
+        let prover = storer_prover();
+        let inputs = storer_inputs();
+        let mut other = inputs.clone();
+        other.salt = U256::from(42);
+
+        // only the first call instantiates a calculator, the time saved is measured by the
+        // `witness` group of `cargo bench`
+        let before = WITNESS_CALCULATORS.with(|c| c.get());
+        let first = prover.calculate_witness(&inputs).unwrap();
+        let second = prover.calculate_witness(&other).unwrap();
+        assert_eq!(WITNESS_CALCULATORS.with(|c| c.get()) - before, 1);
+
+        // nothing of the previous call is left in the reused instance
+        assert_ne!(first, second);
+        assert_eq!(prover.calculate_witness(&inputs).unwrap(), first);
+    }
+
+    #[test]
+    fn test_storer_compressed_roundtrip() {
+        let prover = storer_prover();
+        let output = prover.prove_batch(&[storer_inputs()]).unwrap().remove(0);
+
+        let compressed = output.to_compressed().unwrap();
+        let uncompressed = output.to_bytes(ProofFormat::Uncompressed).unwrap();
+        assert!(compressed.len() < uncompressed.len());
+
+        let decoded = ProofOutput::from_compressed(&compressed).unwrap();
+        assert_eq!(decoded, output);
+        assert!(prover
+            .verify(decoded.proof.as_slice(), decoded.public_inputs.as_slice())
+            .is_ok());
+
+        let decoded = ProofOutput::from_bytes(&uncompressed).unwrap();
+        assert_eq!(decoded, output);
+    }
+
+    #[test]
+    fn test_storer_proof_header() {
+        let prover = storer_prover();
+        let output = prover.prove_inputs(&storer_inputs()).unwrap();
+
+        let compressed = output.to_compressed().unwrap();
+        let uncompressed = output.to_bytes(ProofFormat::Uncompressed).unwrap();
+        for (bytes, format) in [
+            (&compressed, ProofFormat::Compressed),
+            (&uncompressed, ProofFormat::Uncompressed),
+        ] {
+            let (header, _) = ProofHeader::decode(bytes).unwrap();
+            assert_eq!(header, ProofHeader::new(format));
+            assert!(prover.verify_serialized(bytes).is_ok());
+            assert_eq!(ProofOutput::from_compressed(bytes).unwrap(), output);
+        }
+
+        // the bare proof and public inputs, as written before there was a header
+        let mut headerless = output.proof.clone();
+        headerless.extend(&output.public_inputs);
+        let err = prover.verify_serialized(&headerless).unwrap_err();
+        assert!(err.starts_with("not a serialized proof"), "{}", err);
+        assert!(ProofOutput::from_bytes(&[]).is_err());
+
+        let mut future = compressed.clone();
+        future[4] = PROOF_FORMAT_VERSION + 1;
+        let err = ProofOutput::from_bytes(&future).unwrap_err();
+        assert!(err.contains("unsupported serialized proof version 3, newer"), "{}", err);
+        future[4] = 0;
+        let err = ProofOutput::from_bytes(&future).unwrap_err();
+        assert_eq!(err, "unsupported serialized proof version 0, expected 1 to 2");
+        // the length is checked against the proof it prefixes
+        let mut bad_len = compressed.clone();
+        bad_len[ProofHeader::LEN] += 1;
+        let err = ProofOutput::from_bytes(&bad_len).unwrap_err();
+        assert!(err.starts_with("proof is 128 bytes"), "{}", err);
+        let mut scheme = compressed;
+        scheme[5] = 7;
+        assert_eq!(ProofOutput::from_bytes(&scheme).unwrap_err(), "unknown proof scheme 7");
+    }
+
+    /// The seed 1 test vector proof as written by version 1 of the format, which must stay
+    /// readable and verifiable. The fixture is made by the code of that version, see
+    /// `scripts/update-fixtures.sh`.
+    #[test]
+    fn test_proof_format_v1_fixture() {
+        let wasm = std::fs::read(STORER_WASM).unwrap();
+        let r1cs = std::fs::read(STORER_R1CS).unwrap();
+        let vector = TestVector::generate(&wasm, &r1cs, CircuitParams::default(), 1).unwrap();
+
+        let fixture = std::fs::read(PROOF_V1_FIXTURE).unwrap_or_else(|e| {
+            panic!("{}: {}, see scripts/update-fixtures.sh", PROOF_V1_FIXTURE, e)
+        });
+        assert_eq!(ProofHeader::decode(&fixture).unwrap().0.version, 1);
+        assert_eq!(ProofOutput::from_bytes(&fixture).unwrap(), vector.output);
+        let verifier = StorageProofs::verifier_only(&vector.verification_key).unwrap();
+        assert!(verifier.verify_serialized(&fixture).is_ok());
+
+        let migrated = migrate_proof(&fixture).unwrap();
+        assert_eq!(ProofHeader::decode(&migrated).unwrap().0.version, PROOF_FORMAT_VERSION);
+        assert_eq!(migrated, vector.output.to_compressed().unwrap());
+        assert_eq!(migrate_proof(&migrated).unwrap(), migrated);
+        assert!(verifier.verify_serialized(&migrated).is_ok());
+        assert!(migrate_proof(&fixture[..fixture.len() - 1]).is_err());
+    }
+
+    #[test]
+    #[ignore = "needs scripts/snarkjs-setup.sh, run with `cargo test --lib -- --ignored`"]
+    fn test_storer_config_digest() {
+        let zkey_prover = || {
+            StorageProofs::new(
+                STORER_WASM.to_string(),
+                STORER_R1CS.to_string(),
+                Some(STORER_ZKEY.to_string()),
+                Some(CircuitParams::default()),
+            )
+            .unwrap()
+        };
+        let (prover, same) = (zkey_prover(), zkey_prover());
+        assert_eq!(prover.config_digest(), same.config_digest());
+        assert_eq!(prover.clone().config_digest(), prover.config_digest());
+
+        // provers with random keys of their own
+        let (random, other) = (storer_prover(), storer_prover());
+        assert_ne!(random.config_digest(), other.config_digest());
+        assert_ne!(random.config_digest(), prover.config_digest());
+        let mut replaced = storer_prover();
+        replaced
+            .set_proving_key(&std::fs::read(STORER_ZKEY).unwrap())
+            .unwrap();
+        assert_eq!(replaced.config_digest(), prover.config_digest());
+
+        let output = prover.prove_inputs(&storer_inputs()).unwrap();
+        let bytes = output
+            .to_bytes_with_config(ProofFormat::Compressed, prover.config_digest())
+            .unwrap();
+        let (header, _) = ProofHeader::decode(&bytes).unwrap();
+        assert_eq!(header.config_digest, Some(prover.config_digest()));
+        assert_eq!(header.format, ProofFormat::Compressed);
+        assert_eq!(ProofOutput::from_bytes(&bytes).unwrap(), output);
+        assert!(prover.verify_serialized(&bytes).is_ok());
+
+        let err = ProofHeader::decode(&bytes[..ProofHeader::LEN + 16]).unwrap_err();
+        assert_eq!(err, "proof header is missing its config digest");
+    }
+
+    #[test]
+    fn test_storer_public_inputs_json() {
+        let prover = storer_prover();
+        let mut inputs = storer_inputs();
+        inputs.salt = U256::from(42);
+        let output = prover.prove_batch(&[inputs.clone()]).unwrap().remove(0);
+
+        let json = StorageProofs::public_inputs_json(&output.public_inputs).unwrap();
+        assert_eq!(json, format!("[\"{}\",\"42\"]", inputs.root.unwrap()));
+
+        assert_eq!(StorageProofs::public_inputs_json(&[]).unwrap(), "[]");
+    }
+
+    /// The layout of the snarkjs files, snarkjs itself checks the proof in
+    /// `test_cli_snarkjs_verify`
+    #[test]
+    fn test_storer_snarkjs_json() {
+        let prover = storer_prover();
+        let inputs = storer_inputs();
+        let output = prover.prove_batch(&[inputs.clone()]).unwrap().remove(0);
+
+        let json: serde_json::Value =
+            serde_json::from_str(&output.to_snarkjs_json().unwrap()).unwrap();
+        assert_eq!(json["protocol"], "groth16");
+        assert_eq!(json["curve"], "bn128");
+        // projective coordinates of affine points, decimal strings below the base field
+        let modulus = U256::from_limbs_slice(Fq::characteristic());
+        let is_coordinate = |c: &serde_json::Value| {
+            c.as_str().map_or(false, |c| c.parse::<U256>().map_or(false, |c| c < modulus))
+        };
+        for point in ["pi_a", "pi_c"] {
+            let coords = json[point].as_array().unwrap();
+            assert_eq!(coords.len(), 3);
+            assert!(coords[..2].iter().all(is_coordinate), "{}", json[point]);
+            assert_eq!(coords[2], "1");
+        }
+        let pi_b = json["pi_b"].as_array().unwrap();
+        assert_eq!(pi_b.len(), 3);
+        for coords in &pi_b[..2] {
+            assert!(coords.as_array().unwrap().iter().all(is_coordinate), "{}", coords);
+        }
+        assert_eq!(pi_b[2], serde_json::json!(["1", "0"]));
+
+        let public: serde_json::Value =
+            serde_json::from_str(&output.to_snarkjs_public_json().unwrap()).unwrap();
+        assert_eq!(
+            public,
+            serde_json::json!([inputs.root.unwrap().to_string(), inputs.salt.to_string()])
+        );
+    }
+
+    #[test]
+    #[ignore = "needs scripts/snarkjs-setup.sh, run with `cargo test --lib -- --ignored`"]
+    fn test_storer_verification_key_json() {
+        // both made by the snarkjs setup in scripts/snarkjs-setup.sh
+        let prover = StorageProofs::new(
+            STORER_WASM.to_string(),
+            STORER_R1CS.to_string(),
+            Some(STORER_ZKEY.to_string()),
+            Some(CircuitParams::default()),
+        )
+        .unwrap();
+        let expected: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(STORER_VK_JSON).unwrap()).unwrap();
+
+        let json: serde_json::Value =
+            serde_json::from_str(&prover.verification_key_json().unwrap()).unwrap();
+        assert_eq!(json, expected);
+        assert_eq!(json["nPublic"], 2);
+    }
+
+    #[test]
+    #[ignore = "needs scripts/snarkjs-setup.sh, run with `cargo test --lib -- --ignored`"]
+    fn test_storer_verify_with_snarkjs_vk() {
+        // proven with the key of the snarkjs setup that exported the vk json
+        let prover = StorageProofs::new(
+            STORER_WASM.to_string(),
+            STORER_R1CS.to_string(),
+            Some(STORER_ZKEY.to_string()),
+            Some(CircuitParams::default()),
+        )
+        .unwrap();
+        let output = prover.prove_inputs(&storer_inputs()).unwrap();
+        let vk_json = std::fs::read_to_string(STORER_VK_JSON).unwrap();
+        let verify = |vk_json: &str, output: &ProofOutput| {
+            verify_with_snarkjs_vk(vk_json, &output.proof, &output.public_inputs)
+        };
+        assert_eq!(verify(&vk_json, &output), Ok(true));
+
+        // a proof for another key, which verifies with that key exported as snarkjs does
+        let other = storer_prover();
+        let other_output = other.prove_inputs(&storer_inputs()).unwrap();
+        assert_eq!(verify(&vk_json, &other_output), Ok(false));
+        assert_eq!(verify(&other.verification_key_json().unwrap(), &other_output), Ok(true));
+
+        let mut json: serde_json::Value = serde_json::from_str(&vk_json).unwrap();
+        json["vk_alpha_1"][0] = "1".into();
+        let err = verify(&json.to_string(), &output).unwrap_err();
+        assert_eq!(err, "vk_alpha_1: not a point of the G1 subgroup");
+        json["IC"].as_array_mut().unwrap().pop();
+        assert!(verify(&json.to_string(), &output).is_err());
+    }
+
+    #[test]
+    fn test_verify_with_snarkjs_vk_fixture() {
+        let vk_json = std::fs::read_to_string(VK_JSON_FIXTURE).unwrap();
+        let (g1, g2) = (
+            G1Affine::prime_subgroup_generator(),
+            G2Affine::prime_subgroup_generator(),
+        );
+        // with a and b the generators too, the pairing check for the public inputs 1 and 2
+        // is 1 = 1 + (1 + 1 * 2 + 2 * 3) + c in the exponents, so c must be -9 * g1
+        let output = |c: G1Affine| {
+            let mut output = ProofOutput::default();
+            Proof::<Bn254> { a: g1, b: g2, c }
+                .serialize(&mut output.proof)
+                .unwrap();
+            vec![Fr::from(1u64), Fr::from(2u64)]
+                .serialize(&mut output.public_inputs)
+                .unwrap();
+            output
+        };
+        let valid = output(g1.mul((-Fr::from(9u64)).into_repr()).into_affine());
+        let verify = |vk_json: &str, output: &ProofOutput| {
+            verify_with_snarkjs_vk(vk_json, &output.proof, &output.public_inputs)
+        };
+        assert_eq!(verify(&vk_json, &valid), Ok(true));
+        assert_eq!(verify(&vk_json, &output(g1)), Ok(false));
+
+        let mut json: serde_json::Value = serde_json::from_str(&vk_json).unwrap();
+        json["IC"][1] = json["IC"][2].clone();
+        assert_eq!(verify(&json.to_string(), &valid), Ok(false));
+        json["vk_alpha_1"][1] = "3".into();
+        let err = verify(&json.to_string(), &valid).unwrap_err();
+        assert_eq!(err, "vk_alpha_1: not a point of the G1 subgroup");
+        json["IC"].as_array_mut().unwrap().pop();
+        let err = verify(&json.to_string(), &valid).unwrap_err();
+        assert_eq!(err, "2 IC points for 2 public inputs, expected 3");
+        json["curve"] = "bls12381".into();
+        let err = verify(&json.to_string(), &valid).unwrap_err();
+        assert_eq!(err, "expected a groth16 bn128 verification key, got \"groth16\" \"bls12381\"");
+    }
+
+    #[test]
+    fn test_solidity_calldata() {
+        // generator points, the G2 one as known from EIP-197
+        let proof = Proof::<Bn254> {
+            a: G1Affine::prime_subgroup_generator(),
+            b: G2Affine::prime_subgroup_generator(),
+            c: G1Affine::prime_subgroup_generator(),
+        };
+        let mut output = ProofOutput::default();
+        proof.serialize(&mut output.proof).unwrap();
+        vec![Fr::from(1u64), Fr::from(2u64)]
+            .serialize(&mut output.public_inputs)
+            .unwrap();
+
+        let word = |n: &str| format!("\"0x{:0>64}\"", n);
+        let expected = format!(
+            "[{}, {}],[[{}, {}],[{}, {}]],[{}, {}],[{},{}]",
+            word("1"),
+            word("2"),
+            word("198e9393920d483a7260bfb731fb5d25f1aa493335a9e71297e485b7aef312c2"),
+            word("1800deef121f1e76426a00665e5c4479674322d4f75edadd46debd5cd992f6ed"),
+            word("090689d0585ff075ec9e99ad690c3395bc4b313370b38ef355acdadcd122975b"),
+            word("12c85ea5db8c6deb4aab71808dcb408fe3d1e7690c43d37b4ce6cc0166fa7daa"),
+            word("1"),
+            word("2"),
+            word("1"),
+            word("2"),
+        );
+
+        assert_eq!(output.to_solidity_calldata().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_storer_prove_with_witness() {
+        let prover = storer_prover();
+        let inputs = storer_inputs();
+
+        let witness = prover.calculate_witness(&inputs).unwrap();
+        let from_witness = prover.prove_with_witness(&witness).unwrap();
+        let direct = prover.prove_batch(&[inputs]).unwrap().remove(0);
+
+        // proofs are randomized, but must prove the same statement
+        assert_eq!(from_witness.public_inputs, direct.public_inputs);
+        assert!(prover
+            .verify(from_witness.proof.as_slice(), from_witness.public_inputs.as_slice())
+            .is_ok());
+        assert!(prover
+            .verify(direct.proof.as_slice(), direct.public_inputs.as_slice())
+            .is_ok());
+    }
+
+    #[test]
+    fn test_storer_circuit_params() {
+        let params = CircuitParams {
+            chunk_len: 128,
+            ..Default::default()
+        };
+
+        let prover = StorageProofs::new(
+            STORER_128_WASM.to_string(),
+            STORER_128_R1CS.to_string(),
+            None,
+            Some(params),
+        )
+        .unwrap();
+
+        let chunk = (0..128).map(U256::from).collect::<Vec<U256>>();
+        assert_eq!(params.digest(&chunk).unwrap(), digest(&chunk, Some(16)));
+        assert!(params.digest(&chunk[1..]).is_err());
+        assert_eq!(prover.circuit_params(), Some(params));
+
+        let err = StorageProofs::new(
+            STORER_WASM.to_string(),
+            STORER_R1CS.to_string(),
+            None,
+            Some(params),
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("expect 528 private inputs (16 when taking the hashes directly)"));
+        assert!(err.contains("but the r1cs has 1040"), "{}", err);
+    }
+
+    #[test]
+    fn test_storer_new_checked() {
+        let checked = |wasm: &str, r1cs: &str, circuit: Option<CircuitParams>| {
+            StorageProofs::new_checked(wasm.to_string(), r1cs.to_string(), None, circuit)
+        };
+
+        assert!(checked(STORER_WASM, STORER_R1CS, Some(CircuitParams::default())).is_ok());
+
+        // an updated circuit's wasm used with the old r1cs only fails when proving unchecked
+        assert!(StorageProofs::new(
+            STORER_128_WASM.to_string(),
+            STORER_R1CS.to_string(),
+            None,
+            None
+        )
+        .is_ok());
+        let err = checked(STORER_128_WASM, STORER_R1CS, None).unwrap_err().to_string();
+        assert!(err.contains("witness values, but the r1cs has"), "{}", err);
+
+        let params = CircuitParams {
+            chunk_len: 128,
+            ..CircuitParams::default()
+        };
+        let err = checked(STORER_WASM, STORER_R1CS, Some(params)).unwrap_err().to_string();
+        assert!(err.contains("private inputs"), "{}", err);
+    }
+
+    #[test]
+    fn test_storer_hash_fn() {
+        let keccak = CircuitParams {
+            hash: HashKind::Keccak256Reduced,
+            ..CircuitParams::default()
+        };
+        let checked = |params: CircuitParams| {
+            StorageProofs::new_checked(
+                STORER_WASM.to_string(),
+                STORER_R1CS.to_string(),
+                None,
+                Some(params),
+            )
+        };
+
+        // the storer circuit hashes with Poseidon, which is only found out from a witness
+        assert!(checked(CircuitParams::default()).is_ok());
+        let err = checked(keccak).unwrap_err().to_string();
+        assert!(err.contains("doesn't hash with Keccak256Reduced"), "{}", err);
+
+        // the same cells make a different tree under each hash, and only the Poseidon one
+        // is accepted by the circuit
+        let inputs = storer_inputs();
+        let chunks = inputs.chunks.chunks(256).collect::<Vec<&[U256]>>();
+        let keccak_hashes = chunks
+            .iter()
+            .map(|c| keccak.digest(c).unwrap())
+            .collect::<Vec<U256>>();
+        let poseidon_hashes = chunks
+            .iter()
+            .map(|c| CircuitParams::default().digest(c).unwrap())
+            .collect::<Vec<U256>>();
+        assert_eq!(poseidon_hashes, inputs.hashes);
+        assert_ne!(keccak_hashes, poseidon_hashes);
+
+        let tree = MerkleTree::with_hash(&keccak_hashes, 2, HashKind::Keccak256Reduced).unwrap();
+        assert_ne!(tree.root(), inputs.root.unwrap());
+        let keccak_inputs = ProofInputs {
+            hashes: keccak_hashes,
+            siblings: inputs
+                .path
+                .iter()
+                .flat_map(|i| tree.proof(*i as usize).unwrap().siblings)
+                .collect(),
+            root: None,
+            ..inputs.clone()
+        };
+        assert_eq!(keccak_inputs.resolve_root_with(&Keccak256Reduced).unwrap(), tree.root());
+
+        let prover = storer_prover();
+        assert!(prover.prove_inputs(&inputs).is_ok());
+        assert!(prover.prove_inputs(&keccak_inputs).is_err());
+    }
+
+    #[test]
+    fn test_storer_multi_opening() {
+        let params = CircuitParams {
+            n_samples: 3,
+            ..CircuitParams::default()
+        };
+        let prover = StorageProofs::new(
+            STORER_3_WASM.to_string(),
+            STORER_3_R1CS.to_string(),
+            None,
+            Some(params),
+        )
+        .unwrap();
+
+        // the 4 cells of the default inputs, of which 3 are sampled
+        let all = storer_inputs();
+        let tree = MerkleTree::new(&all.hashes, 2).unwrap();
+        let openings = [0, 2, 3]
+            .iter()
+            .map(|i| CellOpening {
+                chunk: all.chunks[i * params.chunk_len..(i + 1) * params.chunk_len].to_vec(),
+                hash: all.hashes[*i],
+                siblings: tree.proof(*i).unwrap().siblings,
+                index: *i as i32,
+            })
+            .collect::<Vec<CellOpening>>();
+
+        let inputs = ProofInputs::from_openings(&openings, None, Salt(U256::from(42)));
+        let output = prover.prove_inputs(&inputs).unwrap();
+        assert_eq!(output.root().unwrap(), tree.root());
+        assert!(prover
+            .verify(output.proof.as_slice(), output.public_inputs.as_slice())
+            .is_ok());
+
+        // the number of openings is checked against the params before proving
+        let err = prover.prove_inputs(&all).unwrap_err().to_string();
+        assert!(err.contains("expect 768 chunks for 3 samples, got 1024"), "{}", err);
+    }
+
+    #[test]
+    fn test_openings_inputs() {
+        // 3 samples of a 4 leaf tree, the last leaf padding
+        let params = CircuitParams {
+            n_samples: 3,
+            ..CircuitParams::default()
+        };
+        let prover = StorageProofs::new(
+            STORER_3_WASM.to_string(),
+            STORER_3_R1CS.to_string(),
+            None,
+            Some(params),
+        )
+        .unwrap();
+        let inputs = openings_inputs(&params).unwrap();
+        assert_eq!(inputs.chunks.len(), 3 * params.chunk_len);
+        assert_eq!(inputs.siblings.len(), 3 * params.tree_depth);
+        assert!(prover.prove_inputs(&inputs).is_ok());
+
+        let too_many = CircuitParams {
+            n_samples: 5,
+            ..CircuitParams::default()
+        };
+        assert_eq!(
+            openings_inputs(&too_many).unwrap_err(),
+            "5 samples don't fit a tree of 4 leaves"
+        );
+    }
+
+    #[test]
+    fn test_storer_witness_mismatch() {
+        // the wasm of the 4 sample circuit with the r1cs of the 3 sample one, as left by
+        // recompiling only one of them
+        let prover = StorageProofs::new(
+            STORER_WASM.to_string(),
+            STORER_3_R1CS.to_string(),
+            None,
+            None,
+        )
+        .unwrap();
+        let r1cs_wires = prover.circuit_info().num_witness;
+        let witness = prover.calculate_witnesses(&[storer_inputs()]).unwrap().remove(0);
+        assert_ne!(witness.len(), r1cs_wires);
+
+        let expected = format!(
+            "expected {} witness signals, got {}, constraint ",
+            r1cs_wires,
+            witness.len()
+        );
+        let err = prover.prove_inputs(&storer_inputs()).unwrap_err().to_string();
+        assert!(err.starts_with(&expected), "{}", err);
+        assert!(err.ends_with("is the first not satisfied"), "{}", err);
+        let err = prover.prove_with_witness(&witness[..r1cs_wires - 1]).unwrap_err().to_string();
+        let expected = format!("expected {} witness signals, got {}", r1cs_wires, r1cs_wires - 1);
+        assert!(err.starts_with(&expected), "{}", err);
+    }
+
+    #[test]
+    fn test_storer_prove_from_mmap() {
+        let prover = storer_prover();
+        let inputs = storer_inputs();
+        let layout = MmapLayout::packed(&CircuitParams::default());
+
+        let mut bytes = Vec::new();
+        let elements = inputs.chunks.iter().chain(&inputs.siblings).chain(&inputs.hashes);
+        elements.for_each(|e| bytes.extend(e.to_le_bytes::<32>()));
+        inputs.path.iter().for_each(|i| bytes.extend(i.to_le_bytes()));
+        bytes.extend(inputs.root.unwrap().to_le_bytes::<32>());
+        bytes.extend(inputs.salt.to_le_bytes::<32>());
+        assert_eq!(bytes.len() as u64, layout.file_len());
+
+        let dir = std::env::temp_dir().join(format!("codex-mmap-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("inputs.bin");
+        std::fs::write(&file, &bytes).unwrap();
+
+        let output = prover.prove_from_mmap(&file, layout).unwrap();
+        assert_eq!(output.root().unwrap(), inputs.root.unwrap());
+        assert!(prover
+            .verify(output.proof.as_slice(), output.public_inputs.as_slice())
+            .is_ok());
+
+        // a file cut off in the middle of the salt
+        std::fs::write(&file, &bytes[..bytes.len() - 7]).unwrap();
+        let err = prover.prove_from_mmap(&file, layout).unwrap_err().to_string();
+        assert_eq!(
+            err,
+            format!(
+                "salt needs 32 bytes at offset {}, but the file is only {} bytes",
+                layout.salt,
+                bytes.len() - 7
+            )
+        );
+        // offsets needn't be aligned
+        let mut shifted = vec![0];
+        shifted.extend(&bytes);
+        std::fs::write(&file, &shifted).unwrap();
+        let shifted_layout = MmapLayout {
+            chunks: layout.chunks + 1,
+            siblings: layout.siblings + 1,
+            hashes: layout.hashes + 1,
+            path: layout.path + 1,
+            root: layout.root + 1,
+            salt: layout.salt + 1,
+            ..layout
+        };
+        assert!(prover.prove_from_mmap(&file, shifted_layout).is_ok());
+        let err = prover
+            .prove_from_mmap(&dir.join("missing.bin"), layout)
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("unable to open"), "{}", err);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_storer_prove_partial() {
+        let prover = storer_prover();
+        let inputs = storer_inputs();
+        let tree = MerkleTree::new(&inputs.hashes, 2).unwrap();
+        let chunk = inputs.chunks[256..512].to_vec();
+
+        // a node holding only the second chunk, with its authentication siblings
+        let siblings = tree.proof(1).unwrap().siblings;
+        let partial = MerkleTree::from_partial(
+            2,
+            2,
+            &[(1, inputs.hashes[1])],
+            &[((0, 0), siblings[0]), ((1, 1), siblings[1])],
+        )
+        .unwrap();
+
+        // every sample opens the one chunk known
+        let samples = vec![(1, chunk); 4];
+        let output = prover.prove_partial(&partial, &samples, Salt(inputs.salt)).unwrap();
+        assert_eq!(output.root().unwrap(), tree.root());
+        assert!(prover
+            .verify(output.proof.as_slice(), output.public_inputs.as_slice())
+            .is_ok());
+
+        let err = prover
+            .prove_partial(&partial, &[(0, inputs.chunks[..256].to_vec())], Salt(inputs.salt))
+            .unwrap_err()
+            .to_string();
+        assert_eq!(err, "leaf 0 isn't known");
+    }
+
+    #[test]
+    fn test_storer_hashed_chunks() {
+        let hashed = StorageProofs::new(
+            STORER_HASHED_WASM.to_string(),
+            STORER_HASHED_R1CS.to_string(),
+            None,
+            Some(CircuitParams::default()),
+        )
+        .unwrap();
+        let raw = storer_prover();
+        assert_eq!(hashed.chunk_inputs(), Some(ChunkInputs::Hashed));
+        assert_eq!(raw.chunk_inputs(), Some(ChunkInputs::Raw));
+
+        let mut inputs = storer_inputs();
+        let with_chunks = inputs.clone();
+        inputs.chunks.clear();
+
+        // the hashes are trusted, only their paths are checked
+        let output = hashed.prove_inputs(&inputs).unwrap();
+        assert_eq!(output.root().unwrap(), inputs.root.unwrap());
+        assert!(hashed
+            .verify(output.proof.as_slice(), output.public_inputs.as_slice())
+            .is_ok());
+        let mut decoded = hashed.decode_mpack(&inputs.to_mpack()).unwrap();
+        assert_eq!(decoded, inputs);
+        decoded.hashes[0] += U256::from(1);
+        assert!(hashed.prove_inputs(&decoded).is_err());
+
+        let decimal = |ns: &[U256]| ns.iter().map(|n| n.to_string()).collect::<Vec<String>>();
+        let json = serde_json::json!({
+            "siblings": decimal(&inputs.siblings),
+            "hashes": decimal(&inputs.hashes),
+            "path": inputs.path.clone(),
+            "salt": inputs.salt.to_string(),
+        });
+        let (mut proof, mut public_inputs) = (Vec::new(), Vec::new());
+        hashed
+            .prove_json(&json.to_string(), &mut proof, &mut public_inputs)
+            .unwrap();
+        assert!(hashed.verify(proof.as_slice(), public_inputs.as_slice()).is_ok());
+
+        // the raw circuit digests the chunks, so it needs them
+        assert!(raw.prove_inputs(&with_chunks).is_ok());
+        let err = raw.prove_inputs(&inputs).unwrap_err().to_string();
+        assert!(err.contains("digests the chunks itself"), "{}", err);
+        let err = hashed.prove_inputs(&with_chunks).unwrap_err().to_string();
+        assert!(err.contains("takes the chunk hashes directly"), "{}", err);
+
+        assert!(hashed.self_test().is_ok());
+    }
+
+    #[test]
+    fn test_storer_signal_map() {
+        let params = CircuitParams {
+            signals: SignalMap {
+                chunks: "samples",
+                path: "merklePath",
+                ..SignalMap::default()
+            },
+            ..CircuitParams::default()
+        };
+        let renamed = StorageProofs::new(
+            STORER_RENAMED_WASM.to_string(),
+            STORER_RENAMED_R1CS.to_string(),
+            None,
+            Some(params),
+        )
+        .unwrap();
+
+        let inputs = storer_inputs();
+        let output = renamed.prove_inputs(&inputs).unwrap();
+        assert_eq!(output.root().unwrap(), inputs.root.unwrap());
+        assert!(renamed
+            .verify(output.proof.as_slice(), output.public_inputs.as_slice())
+            .is_ok());
+
+        // the StorageProver names aren't inputs of the renamed circuit
+        let default_names = StorageProofs::new(
+            STORER_RENAMED_WASM.to_string(),
+            STORER_RENAMED_R1CS.to_string(),
+            None,
+            None,
+        )
+        .unwrap();
+        assert!(default_names.prove_inputs(&inputs).is_err());
+    }
+
+    #[test]
+    fn test_storer_inputs_to_mpack() {
+        let prover = storer_prover();
+
+        let proof_bytes = &mut Vec::new();
+        let public_inputs_bytes = &mut Vec::new();
+        prover
+            .prove_mpack(&storer_inputs().to_mpack(), proof_bytes, public_inputs_bytes)
+            .unwrap();
+
+        assert!(prover
+            .verify(proof_bytes.as_slice(), public_inputs_bytes.as_slice())
+            .is_ok());
+    }
+
+    #[test]
+    fn test_proof_inputs_zeroize() {
+        fn zeroize_on_drop<T: ZeroizeOnDrop>() {}
+        zeroize_on_drop::<Zeroizing<ProofInputs>>();
+
+        let mut inputs = storer_inputs();
+        assert_ne!(inputs.salt, U256::ZERO);
+        let chunks = inputs.chunks.as_ptr();
+        let len = inputs.chunks.len();
+
+        inputs.zeroize();
+        assert!(inputs.chunks.is_empty() && inputs.siblings.is_empty());
+        assert_eq!(inputs.root, None);
+        assert_eq!(inputs.salt, U256::ZERO);
+
+        // best effort: the allocation is kept, so what it held can still be inspected
+        let wiped = unsafe { std::slice::from_raw_parts(chunks as *const u8, len * U256::BYTES) };
+        assert!(wiped.iter().all(|b| *b == 0));
+        assert_eq!(inputs.chunks.as_ptr(), chunks);
+    }
+
+    #[test]
+    fn test_proof_inputs_debug_redacted() {
+        let inputs = ProofInputs {
+            chunks: vec![U256::from(123456789u64); 4],
+            salt: U256::from(987654321u64),
+            ..storer_inputs()
+        };
+        let debug = format!("{:?}", inputs);
+
+        assert!(debug.contains("[4 elements]"));
+        assert!(!debug.contains("123456789"));
+        assert!(!debug.contains("987654321"));
+    }
+
+    #[test]
+    fn test_storer_prove_json() {
+        let prover = storer_prover();
+        let inputs = storer_inputs();
+
+        let decimal = |ns: &[U256]| ns.iter().map(|n| n.to_string()).collect::<Vec<String>>();
+        let hex = |ns: &[U256]| {
+            ns.iter()
+                .map(|n| format!("0x{}", ::hex::encode(n.to_be_bytes_vec())))
+                .collect::<Vec<String>>()
+        };
+        let json = serde_json::json!({
+            "chunks": inputs.chunks.chunks(256).map(decimal).collect::<Vec<_>>(),
+            "siblings": hex(&inputs.siblings),
+            "hashes": hex(&inputs.hashes),
+            "path": inputs.path.clone(),
+            "root": hex(&[inputs.root.unwrap()])[0],
+            "salt": inputs.salt.to_string(),
+        })
+        .to_string();
+
+        let from_json = prover.decode_json(&json).unwrap();
+        let from_mpack = prover.decode_mpack(&inputs.to_mpack()).unwrap();
+        assert_eq!(from_json, from_mpack);
+        assert_eq!(
+            prover.prove_with_seed(&from_json, 3).unwrap(),
+            prover.prove_with_seed(&from_mpack, 3).unwrap()
+        );
+
+        let proof_bytes = &mut Vec::new();
+        let public_inputs_bytes = &mut Vec::new();
+        prover
+            .prove_json(&json, proof_bytes, public_inputs_bytes)
+            .unwrap();
+        assert!(prover
+            .verify(proof_bytes.as_slice(), public_inputs_bytes.as_slice())
+            .is_ok());
+
+        let err = prover
+            .decode_json(&json.replace("\"path\"", "\"paths\""))
+            .unwrap_err();
+        assert_eq!(err, "missing required key path");
+        let err = prover.decode_json(r#"{"chunks": [["0xzz"]]}"#).unwrap_err();
+        assert!(err.starts_with("chunk 0 has 1 elements, expected 256"), "{}", err);
+    }
+
+    fn storer_mpack_args() -> Vec<(Value, Value)> {
+        let bytes = storer_inputs().to_mpack();
+        read_value(&mut bytes.as_slice())
+            .unwrap()
+            .as_map()
+            .unwrap()
+            .clone()
+    }
+
+    fn set_mpack_arg(args: &mut [(Value, Value)], key: &str, val: Value) {
+        args.iter_mut()
+            .find(|(k, _)| k.as_str() == Some(key))
+            .unwrap()
+            .1 = val;
+    }
+
+    fn encode_mpack_args(args: Vec<(Value, Value)>) -> Vec<u8> {
+        let mut buf = Vec::new();
+        write_value(&mut buf, &Value::Map(args)).unwrap();
+        buf
+    }
+
+    #[test]
+    fn test_storer_mpack_missing_key() {
+        let prover = storer_prover();
+        let args = storer_mpack_args()
+            .into_iter()
+            .filter(|(k, _)| k.as_str() != Some("salt"))
+            .collect();
+
+        assert_eq!(
+            prover.decode_mpack(&encode_mpack_args(args)),
+            Err(MpackError::MissingKey("salt"))
+        );
+    }
+
+    #[test]
+    fn test_storer_mpack_wrong_ext_id() {
+        let prover = storer_prover();
+        let mut args = storer_mpack_args();
+        let mut hashes = args
+            .iter()
+            .find(|(k, _)| k.as_str() == Some("hashes"))
+            .unwrap()
+            .1
+            .as_array()
+            .unwrap()
+            .clone();
+        hashes[2] = Value::Ext(42, vec![0; 32]);
+        hashes[3] = Value::from("not a number");
+        set_mpack_arg(&mut args, "hashes", Value::Array(hashes.clone()));
+
+        match prover.decode_mpack(&encode_mpack_args(args.clone())) {
+            Err(MpackError::InvalidElement { key, index, .. }) => {
+                assert_eq!(key, "hashes");
+                assert_eq!(index, 2);
+            }
+            res => panic!("unexpected result {:?}", res),
+        }
+
+        hashes[2] = hashes[0].clone();
+        set_mpack_arg(&mut args, "hashes", Value::Array(hashes));
+        match prover.decode_mpack(&encode_mpack_args(args)) {
+            Err(MpackError::InvalidElement { key, index, .. }) => {
+                assert_eq!(key, "hashes");
+                assert_eq!(index, 3);
+            }
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_storer_field_element_range() {
+        let mut prover = storer_prover();
+        let inputs = storer_inputs();
+        let with_salt = |salt: U256| {
+            let mut args = storer_mpack_args();
+            set_mpack_arg(&mut args, "salt", encode_u256_le(&salt));
+            encode_mpack_args(args)
+        };
+
+        // the largest field element is fine
+        let decoded = prover.decode_mpack(&with_salt(FIELD_MODULUS - U256::from(1))).unwrap();
+        assert_eq!(decoded.salt, FIELD_MODULUS - U256::from(1));
+
+        // the modulus itself would wrap to zero in the circuit
+        assert_eq!(
+            prover.decode_mpack(&with_salt(FIELD_MODULUS)),
+            Err(MpackError::FieldElementOutOfRange(FieldElementOutOfRange {
+                key: "salt",
+                index: 0
+            }))
+        );
+        // congruent to the original element, so the chunk still has the same digest
+        let mut chunks = inputs.clone();
+        chunks.chunks[300] += FIELD_MODULUS;
+        assert_eq!(
+            chunks.clone().canonicalize(FieldMode::Strict),
+            Err(FieldElementOutOfRange {
+                key: "chunks",
+                index: 300
+            })
+        );
+        assert!(prover.prove_inputs(&chunks).is_err());
+
+        // unless reducing is asked for
+        prover.set_field_mode(FieldMode::Reduce);
+        let decoded = prover.decode_mpack(&with_salt(FIELD_MODULUS + U256::from(5))).unwrap();
+        assert_eq!(decoded.salt, U256::from(5));
+
+        let output = prover.prove_inputs(&chunks).unwrap();
+        assert_eq!(chunks.canonicalize(FieldMode::Reduce), Ok(()));
+        assert_eq!(chunks, inputs);
+        prover
+            .verify(output.proof.as_slice(), output.public_inputs.as_slice())
+            .unwrap();
+    }
+
+    #[test]
+    fn test_storer_prove_slices_checked() {
+        let prover = storer_prover();
+        let inputs = storer_inputs();
+        let root = Root(inputs.root.unwrap());
+        let prove = |siblings: &[U256], salt: U256| {
+            let (mut proof, mut public_inputs) = (Vec::new(), Vec::new());
+            prover
+                .prove(
+                    &inputs.chunks,
+                    siblings,
+                    &inputs.hashes,
+                    &inputs.path,
+                    root,
+                    Salt(salt),
+                    &mut proof,
+                    &mut public_inputs,
+                )
+                .map_err(|e| e.to_string())
+        };
+        assert!(prove(&inputs.siblings, inputs.salt).is_ok());
+        assert_eq!(
+            prove(&inputs.siblings, FIELD_MODULUS).unwrap_err(),
+            "element 0 of salt is not below the field modulus"
+        );
+        let err = prove(&inputs.siblings[1..], inputs.salt).unwrap_err();
+        assert!(err.contains("expect 8 siblings for 4 samples, got 7"), "{}", err);
+
+        // streamed chunks are checked as they're pulled
+        let chunk_len = CircuitParams::default().chunk_len;
+        let stream = |chunks: &[U256], count: usize| {
+            prover
+                .prove_streaming(
+                    count,
+                    |i| Ok(chunks[i * chunk_len..(i + 1) * chunk_len].to_vec()),
+                    &inputs.siblings,
+                    &inputs.hashes,
+                    &inputs.path,
+                    Some(root),
+                    Salt(inputs.salt),
+                )
+                .map_err(|e| e.to_string())
+        };
+        assert!(stream(&inputs.chunks, 4).is_ok());
+        let mut chunks = inputs.chunks.clone();
+        chunks[300] += FIELD_MODULUS;
+        assert_eq!(
+            stream(&chunks, 4).unwrap_err(),
+            "element 300 of chunks is not below the field modulus"
+        );
+        let err = stream(&inputs.chunks, 3).unwrap_err();
+        assert!(err.contains("expect 4 chunks, got 3"), "{}", err);
+    }
+
+    #[test]
+    fn test_storer_mpack_big_endian() {
+        let prover = storer_prover();
+        let inputs = storer_inputs();
+
+        let value = U256::from(0x0102u64);
+        assert_eq!(Endianness::Little.decode(&[0x02, 0x01]), Some(value));
+        assert_eq!(Endianness::Big.decode(&[0x01, 0x02]), Some(value));
+        let salt = Endianness::Big.encode(&inputs.salt);
+        assert_eq!(Endianness::Big.decode(&salt), Some(inputs.salt));
+        assert_eq!(Endianness::from_ext_id(EXT_ID_U256_BE), Some(Endianness::Big));
+
+        let little = prover.decode_mpack(&inputs.to_mpack()).unwrap();
+        let big = prover
+            .decode_mpack(&inputs.to_mpack_with(Endianness::Big))
+            .unwrap();
+        assert_eq!(little, inputs);
+        assert_eq!(big, inputs);
+
+        // elements of both byte orders can be mixed in one argument map
+        let mut args = storer_mpack_args();
+        let salt = Value::Ext(EXT_ID_U256_BE, inputs.salt.to_be_bytes_vec());
+        set_mpack_arg(&mut args, "salt", salt);
+        assert_eq!(prover.decode_mpack(&encode_mpack_args(args)).unwrap(), inputs);
+    }
+
+    #[test]
+    fn test_storer_proof_mpack() {
+        let prover = storer_prover();
+        let inputs = storer_inputs();
+        let output = prover.prove_inputs(&inputs).unwrap();
+
+        let mpack = output.to_mpack();
+        let value = read_value(&mut mpack.as_slice()).unwrap();
+        let map = value.as_map().unwrap();
+        assert_eq!(map[0].0.as_str(), Some("proof"));
+        assert_eq!(map[0].1, Value::Binary(output.proof.clone()));
+        assert_eq!(map[1].0.as_str(), Some("public_inputs"));
+        assert_eq!(
+            map[1].1,
+            Value::Array(vec![
+                Value::Ext(EXT_ID_U256_LE, inputs.root.unwrap().to_le_bytes_vec()),
+                Value::Ext(EXT_ID_U256_LE, inputs.salt.to_le_bytes_vec()),
+            ])
+        );
+
+        let decoded = ProofOutput::from_mpack(&mpack).unwrap();
+        assert_eq!(decoded, output);
+        assert!(prover
+            .verify(decoded.proof.as_slice(), decoded.public_inputs.as_slice())
+            .is_ok());
+
+        assert_eq!(
+            ProofOutput::from_mpack(&encode_mpack_args(vec![])),
+            Err(MpackError::MissingKey("proof"))
+        );
+    }
+
+    #[test]
+    fn test_storer_mpack_chunk_length() {
+        let prover = storer_prover();
+        let mut args = storer_mpack_args();
+        let mut chunks = args[0].1.as_array().unwrap().clone();
+        let mut short = chunks[1].as_array().unwrap().clone();
+        short.pop();
+        chunks[1] = Value::Array(short);
+        set_mpack_arg(&mut args, "chunks", Value::Array(chunks));
+
+        assert_eq!(
+            prover.decode_mpack(&encode_mpack_args(args)),
+            Err(MpackError::ChunkLength {
+                index: 1,
+                expected: 256,
+                actual: 255
+            })
+        );
     }
 }
@@ -0,0 +1,23 @@
+pragma circom 2.1.0;
+
+include "../../circuits/storer.circom";
+
+// StorageProver with its inputs renamed, as in other circuit versions
+template RenamedStorageProver(BLOCK_SIZE, QUERY_LEN, LEVELS, DIGEST_CHUNK) {
+    signal input samples[QUERY_LEN][BLOCK_SIZE];
+    signal input siblings[QUERY_LEN][LEVELS];
+    signal input merklePath[QUERY_LEN];
+    signal input hashes[QUERY_LEN];
+    signal input root;
+    signal input salt;
+
+    component prover = StorageProver(BLOCK_SIZE, QUERY_LEN, LEVELS, DIGEST_CHUNK);
+    prover.chunks <== samples;
+    prover.siblings <== siblings;
+    prover.path <== merklePath;
+    prover.hashes <== hashes;
+    prover.root <== root;
+    prover.salt <== salt;
+}
+
+component main { public [root, salt] } = RenamedStorageProver(256, 4, 2, 16);
@@ -1,8 +1,35 @@
+use base64::{engine::general_purpose::STANDARD, Engine as _};
 use ruint::aliases::U256;
 
-use crate::storage_proofs::StorageProofs;
+use crate::api::Prover;
+use crate::storage_proofs::{
+    self, CircuitInfo, Endianness, LoadError, ProofFormat, ProofInputs, ProofOutput, ProofScheme,
+    ProveError, ProveHandle, ProvePhase, PubKey, PublicSignalError, Root, Salt, SelfTestStage,
+    StorageProofs, VerifyFailure,
+};
+use crate::merkle::MerklePath;
+use crate::pool::ProverPool;
+use crate::utils;
+use crate::verify::check_lengths;
+#[cfg(feature = "metrics")]
+use crate::storage_proofs::ProveTimings;
+#[cfg(feature = "metrics")]
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::ffi::{c_char, c_void, CString};
 use std::str;
+use zeroize::Zeroizing;
 
+/// Byte buffer passed over the FFI.
+///
+/// Buffers passed into the library are only borrowed for the duration of the call, their
+/// contents are copied into owned storage before it returns, so the caller may free them
+/// right after. Buffers returned by the library are owned by the caller and must be freed
+/// with `free_buffer` (or together with their `ProofCtx`).
+///
+/// `data` and `len` must describe a single valid allocation. A null `data` is only
+/// accepted with a zero `len`, and lengths above `MAX_BUFFER_LEN` are rejected with
+/// `BadBufferLength`; anything else can't be checked and is undefined behavior.
 #[derive(Debug, Clone)]
 #[repr(C)]
 pub struct Buffer {
@@ -10,66 +37,660 @@ pub struct Buffer {
     pub len: usize,
 }
 
-#[derive(Debug, Clone)]
+/// Proof returned over the FFI. Owns the bytes both buffers point to, which stay valid
+/// until the ProofCtx is freed with `free_proof_ctx` (or `free_proof_ctx_batch`).
+#[derive(Debug)]
 #[repr(C)]
 pub struct ProofCtx {
     pub proof: Buffer,
     pub public_inputs: Buffer,
 }
 
+/// A `ProofCtx` followed by the witness it proves, returned by `prove_with_witness_out`.
+/// `ctx` comes first so a pointer to this can be passed anywhere a ProofCtx is taken,
+/// freeing is done with `free_proof_ctx_witness` only.
+#[derive(Debug)]
+#[repr(C)]
+pub struct ProofCtxWitness {
+    pub ctx: ProofCtx,
+    /// little endian field elements, as returned by `calculate_witness`
+    pub witness: Buffer,
+}
+
+impl Buffer {
+    /// Borrow `bytes` for a single FFI call, `bytes` must outlive the call
+    pub fn from_slice(bytes: &[u8]) -> Self {
+        Self {
+            data: bytes.as_ptr(),
+            len: bytes.len(),
+        }
+    }
+
+    /// Hand ownership of `bytes` over to the caller, free with `free_buffer`
+    pub fn from_vec(bytes: Vec<u8>) -> Self {
+        let bytes = bytes.into_boxed_slice();
+        let len = bytes.len();
+
+        Self {
+            data: Box::into_raw(bytes) as *const u8,
+            len,
+        }
+    }
+}
+
+/// A proof whose bytes are owned by Rust, for building a `ProofCtx` that can't leak: it's
+/// only turned into a raw `ProofCtx` when handed over to the caller with `into_raw`, and
+/// dropping it frees everything.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OwnedProofCtx {
+    pub proof: Vec<u8>,
+    pub public_inputs: Vec<u8>,
+}
+
+impl OwnedProofCtx {
+    pub fn new(proof: Vec<u8>, public_inputs: Vec<u8>) -> Self {
+        Self {
+            proof,
+            public_inputs,
+        }
+    }
+
+    /// Hand ownership over to the caller, free with `free_proof_ctx`
+    pub fn into_raw(self) -> *mut ProofCtx {
+        Box::into_raw(Box::new(ProofCtx::new(self.proof, self.public_inputs)))
+    }
+
+    /// # Safety
+    ///
+    /// Take back a ProofCtx returned by `into_raw`, which must not be used afterwards
+    pub unsafe fn from_raw(ctx: *mut ProofCtx) -> Self {
+        // the bytes move into the Vecs, so ProofCtx's own Drop must not free them
+        let ctx = std::mem::ManuallyDrop::new(*Box::from_raw(ctx));
+
+        Self {
+            proof: take_buffer_data(&ctx.proof),
+            public_inputs: take_buffer_data(&ctx.public_inputs),
+        }
+    }
+}
+
+impl From<ProofOutput> for OwnedProofCtx {
+    fn from(output: ProofOutput) -> Self {
+        Self::new(output.proof, output.public_inputs)
+    }
+}
+
 impl ProofCtx {
-    pub fn new(proof: &[u8], public_inputs: &[u8]) -> Self {
+    pub fn new(proof: Vec<u8>, public_inputs: Vec<u8>) -> Self {
+        Self {
+            proof: Buffer::from_vec(proof),
+            public_inputs: Buffer::from_vec(public_inputs),
+        }
+    }
+}
+
+impl Drop for ProofCtx {
+    fn drop(&mut self) {
+        unsafe {
+            drop_buffer_data(&self.proof);
+            drop_buffer_data(&self.public_inputs);
+        }
+    }
+}
+
+impl Drop for ProofCtxWitness {
+    fn drop(&mut self) {
+        unsafe { drop_buffer_data(&self.witness) }
+    }
+}
+
+/// Free the bytes of a Buffer created with `Buffer::from_vec`
+unsafe fn drop_buffer_data(buf: &Buffer) {
+    drop(take_buffer_data(buf))
+}
+
+/// Take back the bytes of a Buffer created with `Buffer::from_vec`
+unsafe fn take_buffer_data(buf: &Buffer) -> Vec<u8> {
+    Box::from_raw(std::ptr::slice_from_raw_parts_mut(
+        buf.data as *mut u8,
+        buf.len,
+    ))
+    .into_vec()
+}
+
+/// Error codes written to the `err` out-parameter of the FFI functions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub enum ProofError {
+    Ok = 0,
+    InvalidUtf8,
+    BadBufferLength,
+    WitnessError,
+    ProveError,
+    VerifyError,
+    /// hex, base64 or JSON input that doesn't decode
+    DecodeError,
+    /// stopped by the progress or sink callback
+    Cancelled,
+    /// no proving key loaded, see `set_proving_key`
+    NoProvingKey,
+    /// the circuit has no public (or, proving raw inputs, input) signal of the given name
+    NoSuchSignal,
+    /// the witness calculator ran out of wasm memory, the circuit is too large for it
+    WitnessMemoryExhausted,
+    /// a `path` index is negative or past the tree's leaves, or there isn't one per sample
+    InvalidPath,
+    /// proving with a verifier only instance, see `init_verifier`
+    NotAProver,
+    /// the wasm or r1cs can't be read or don't describe a circuit that can be proved
+    CircuitLoadError,
+    /// the zkey or verification key can't be read
+    KeyError,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = RefCell::new(None);
+}
+
+/// Phase timings of a proof in nanoseconds, see `last_prove_timings`
+#[cfg(feature = "metrics")]
+#[derive(Debug, Clone, Copy, Default)]
+#[repr(C)]
+pub struct ProveTimingsNs {
+    pub build_circuit: u64,
+    pub witness: u64,
+    pub prove: u64,
+    pub total: u64,
+}
+
+#[cfg(feature = "metrics")]
+impl From<ProveTimings> for ProveTimingsNs {
+    fn from(t: ProveTimings) -> Self {
+        let ns = |d: std::time::Duration| d.as_nanos() as u64;
         Self {
-            proof: Buffer {
-                data: proof.as_ptr(),
-                len: proof.len(),
-            },
-            public_inputs: Buffer {
-                data: public_inputs.as_ptr(),
-                len: public_inputs.len(),
-            },
+            build_circuit: ns(t.build_circuit),
+            witness: ns(t.witness),
+            prove: ns(t.prove),
+            total: ns(t.total),
         }
     }
 }
 
+#[cfg(feature = "metrics")]
+thread_local! {
+    static LAST_TIMINGS: Cell<Option<ProveTimingsNs>> = Cell::new(None);
+}
+
+/// Hand `output` over to the caller, recording its timings for `last_prove_timings`
+fn into_proof_ctx(output: ProofOutput) -> *mut ProofCtx {
+    #[cfg(feature = "metrics")]
+    LAST_TIMINGS.with(|t| t.set(output.timings.map(ProveTimingsNs::from)));
+
+    OwnedProofCtx::from(output).into_raw()
+}
+
+fn set_last_error(msg: Option<String>) {
+    let msg = msg.map(|m| CString::new(m.replace('\0', "")).unwrap_or_default());
+    LAST_ERROR.with(|e| *e.borrow_mut() = msg);
+}
+
+unsafe fn set_error(err: *mut i32, code: ProofError) {
+    if code == ProofError::Ok {
+        set_last_error(None);
+    }
+
+    if !err.is_null() {
+        *err = code as i32;
+    }
+}
+
+unsafe fn fail(err: *mut i32, code: ProofError, msg: String) {
+    set_error(err, code);
+    set_last_error(Some(msg));
+}
+
+/// Error code for a failed prove call, telling a verifier only instance, a missing proving
+/// key and a witness calculation that ran out of memory apart from the rest
+fn prove_error(e: &ProveError) -> ProofError {
+    match e {
+        ProveError::Cancelled(_) => ProofError::Cancelled,
+        ProveError::NoProvingKey => ProofError::NoProvingKey,
+        ProveError::NotAProver => ProofError::NotAProver,
+        ProveError::WitnessMemoryExhausted => ProofError::WitnessMemoryExhausted,
+        ProveError::UnknownSignal(_) => ProofError::NoSuchSignal,
+        ProveError::AbsenceCircuitRequired | ProveError::Failed(_) => ProofError::ProveError,
+    }
+}
+
+/// Error code for a failed init call
+fn load_error(e: LoadError) -> (ProofError, String) {
+    match e {
+        LoadError::Circuit(msg) => (ProofError::CircuitLoadError, msg),
+        LoadError::Key(msg) => (ProofError::KeyError, msg),
+    }
+}
+
+/// Error code for a failed witness calculation
+fn witness_error(e: &ProveError) -> ProofError {
+    if *e == ProveError::WitnessMemoryExhausted {
+        ProofError::WitnessMemoryExhausted
+    } else {
+        ProofError::WitnessError
+    }
+}
+
+/// Largest buffer accepted over the FFI, far above the size of any artifact or input
+pub const MAX_BUFFER_LEN: u64 = 16 << 30;
+
+/// Bit of `supported_schemes` set when Groth16 proofs over BN254 are available
+pub const SCHEME_GROTH16_BN254: u32 = 1 << ProofScheme::Groth16Bn254 as u32;
+
+/// Semver version of the library, nul terminated so the bytes can be read as a C string too
+static LIBRARY_VERSION: &str = concat!(env!("CARGO_PKG_VERSION"), "\0");
+
+/// Borrow `len` elements at `data`, rejecting null pointers and implausible lengths
+/// instead of handing them to `slice::from_raw_parts`
+unsafe fn raw_slice<'a, T>(
+    data: *const T,
+    len: usize,
+    name: &str,
+) -> Result<&'a [T], (ProofError, String)> {
+    if len == 0 {
+        return Ok(&[]);
+    }
+
+    if data.is_null() {
+        return Err((
+            ProofError::BadBufferLength,
+            format!("{}: null data with length {}", name, len),
+        ));
+    }
+
+    let bytes = (len as u64).saturating_mul(std::mem::size_of::<T>() as u64);
+    if bytes > MAX_BUFFER_LEN || bytes > isize::MAX as u64 {
+        return Err((
+            ProofError::BadBufferLength,
+            format!(
+                "{}: length of {} bytes exceeds the maximum of {}",
+                name, bytes, MAX_BUFFER_LEN
+            ),
+        ));
+    }
+
+    Ok(std::slice::from_raw_parts(data, len))
+}
+
+unsafe fn buffer_slice<'a>(buf: &Buffer, name: &str) -> Result<&'a [u8], (ProofError, String)> {
+    raw_slice(buf.data, buf.len, name)
+}
+
+/// The proof and public input bytes of a verify call, rejected with `VerifyError` if
+/// they can't be a serialized proof, see `check_lengths`
+unsafe fn verify_args<'a>(
+    proof: &Buffer,
+    public_inputs: &Buffer,
+) -> Result<(&'a [u8], &'a [u8]), (ProofError, String)> {
+    let proof = buffer_slice(proof, "proof")?;
+    let public_inputs = buffer_slice(public_inputs, "public_inputs")?;
+    check_lengths(proof, public_inputs).map_err(|e| (ProofError::VerifyError, e))?;
+
+    Ok((proof, public_inputs))
+}
+
+unsafe fn buffer_to_string(buf: &Buffer, name: &str) -> Result<String, (ProofError, String)> {
+    let slice = buffer_slice(buf, name)?;
+    str::from_utf8(slice)
+        .map(|s| s.to_string())
+        .map_err(|e| (ProofError::InvalidUtf8, format!("{}: {}", name, e)))
+}
+
+unsafe fn buffer_to_u256(buf: &Buffer, name: &str) -> Result<U256, (ProofError, String)> {
+    buffer_to_u256_with(buf, name, Endianness::Little)
+}
+
+unsafe fn buffer_to_u256_with(
+    buf: &Buffer,
+    name: &str,
+    endianness: Endianness,
+) -> Result<U256, (ProofError, String)> {
+    let slice = buffer_slice(buf, name)?;
+    endianness.decode(slice).ok_or((
+        ProofError::BadBufferLength,
+        format!(
+            "{}: value doesn't fit in {} bytes, got {}",
+            name,
+            U256::BYTES,
+            buf.len
+        ),
+    ))
+}
+
+unsafe fn buffer_to_u256s(buf: &Buffer, name: &str) -> Result<Vec<U256>, (ProofError, String)> {
+    // a trailing partial element is rejected rather than padded, it almost always means
+    // the caller packed the values wrongly
+    let rem = buf.len % U256::BYTES;
+    if rem != 0 {
+        return Err((
+            ProofError::BadBufferLength,
+            format!(
+                "{}: length {} is not a multiple of {}, expected {} or {} bytes ({} trailing)",
+                name,
+                buf.len,
+                U256::BYTES,
+                buf.len - rem,
+                buf.len - rem + U256::BYTES,
+                rem
+            ),
+        ));
+    }
+
+    let slice = buffer_slice(buf, name)?;
+    Ok(slice.chunks(U256::BYTES).map(U256::from_le_slice).collect())
+}
+
+#[allow(clippy::too_many_arguments)]
+unsafe fn read_proof_inputs(
+    prover: &StorageProofs,
+    chunks: *const Buffer,
+    siblings: *const Buffer,
+    hashes: *const Buffer,
+    path: *const i32,
+    path_len: usize,
+    pubkey: *const Buffer,
+    root: *const Buffer,
+    salt: *const Buffer,
+) -> Result<ProofInputs, (ProofError, String)> {
+    read_proof_inputs_with(
+        prover,
+        chunks,
+        siblings,
+        hashes,
+        path,
+        path_len,
+        pubkey,
+        root,
+        salt,
+        Endianness::Little,
+    )
+}
+
+/// Same as `read_proof_inputs`, with `pubkey`, `root` and `salt` in the given byte order.
+/// The chunks, siblings and hashes are always little endian.
+#[allow(clippy::too_many_arguments)]
+unsafe fn read_proof_inputs_with(
+    prover: &StorageProofs,
+    chunks: *const Buffer,
+    siblings: *const Buffer,
+    hashes: *const Buffer,
+    path: *const i32,
+    path_len: usize,
+    pubkey: *const Buffer,
+    root: *const Buffer,
+    salt: *const Buffer,
+    endianness: Endianness,
+) -> Result<ProofInputs, (ProofError, String)> {
+    let path = raw_slice(path, path_len, "path")?;
+    check_path(prover, path)?;
+
+    let root = if root.is_null() {
+        None
+    } else {
+        Some(Root(buffer_to_u256_with(&*root, "root", endianness)?))
+    };
+
+    Ok(ProofInputs::new(
+        buffer_to_u256s(&*chunks, "chunks")?,
+        buffer_to_u256s(&*siblings, "siblings")?,
+        buffer_to_u256s(&*hashes, "hashes")?,
+        path.to_vec(),
+        root,
+        Salt(buffer_to_u256_with(&*salt, "salt", endianness)?),
+        PubKey(buffer_to_u256_with(&*pubkey, "pubkey", endianness)?),
+    ))
+}
+
+/// Reject a `path` the circuit can't open before it reaches the witness calculator
+fn check_path(prover: &StorageProofs, path: &[i32]) -> Result<(), (ProofError, String)> {
+    prover
+        .check_path(path)
+        .map_err(|e| (ProofError::InvalidPath, e.to_string()))
+}
+
+/// # Safety
+///
+/// Returns the message of the last error raised by an FFI call on the current thread,
+/// or null if the last call succeeded. The pointer is valid until the next FFI call on
+/// the same thread.
+#[no_mangle]
+pub unsafe extern "C" fn last_error_message() -> *const c_char {
+    LAST_ERROR.with(|e| match e.borrow().as_ref() {
+        Some(msg) => msg.as_ptr(),
+        None => std::ptr::null(),
+    })
+}
+
+/// The semver version of the library, for hosts to check the ABI they were built against.
+/// The buffer points to static memory and must not be freed; `len` leaves out the
+/// trailing nul
+#[no_mangle]
+pub extern "C" fn library_version() -> Buffer {
+    let version = LIBRARY_VERSION.as_bytes();
+    Buffer::from_slice(&version[..version.len() - 1])
+}
+
+/// Bitmask of the proving schemes the library supports, `SCHEME_GROTH16_BN254` and so on
+#[no_mangle]
+pub extern "C" fn supported_schemes() -> u32 {
+    SCHEME_GROTH16_BN254
+}
+
 /// # Safety
 ///
-/// Construct a StorageProofs object
+/// Construct a StorageProofs object (an `api::Prover` handle), returns null and sets `err`
+/// on failure
 #[no_mangle]
 pub unsafe extern "C" fn init_storage_proofs(
     r1cs: Buffer,
     wasm: Buffer,
     zkey: *const Buffer,
-) -> *mut StorageProofs {
-    let r1cs = {
+    err: *mut i32,
+) -> *mut Prover {
+    let args = || -> Result<(String, String, Option<String>), (ProofError, String)> {
+        let r1cs = buffer_to_string(&r1cs, "r1cs")?;
+        let wasm = buffer_to_string(&wasm, "wasm")?;
+        let zkey = if !zkey.is_null() {
+            Some(buffer_to_string(&*zkey, "zkey")?)
+        } else {
+            None
+        };
 
-        let slice = std::slice::from_raw_parts((r1cs).data, (r1cs).len);
-        str::from_utf8(slice).unwrap().to_string().to_owned()
+        Ok((r1cs, wasm, zkey))
     };
 
-    let wasm = {
-        let slice = std::slice::from_raw_parts((wasm).data, (wasm).len);
-        str::from_utf8(slice).unwrap().to_string().to_owned()
+    let (r1cs, wasm, zkey) = match args() {
+        Ok(args) => args,
+        Err((code, msg)) => {
+            fail(err, code, msg);
+            return std::ptr::null_mut();
+        }
     };
 
-    let zkey = {
-        if !zkey.is_null() {
-            let slice = std::slice::from_raw_parts((*zkey).data, (*zkey).len);
-            Some(str::from_utf8(slice).unwrap().to_string().to_owned())
+    match Prover::new(&wasm, &r1cs, zkey.as_deref(), None).map_err(load_error) {
+        Ok(prover) => {
+            set_error(err, ProofError::Ok);
+            Box::into_raw(Box::new(prover))
+        }
+        Err((code, msg)) => {
+            fail(err, code, msg);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// # Safety
+///
+/// Construct a StorageProofs object from the raw contents of the r1cs, wasm and
+/// (optional) zkey files, returns null and sets `err` on failure
+#[no_mangle]
+pub unsafe extern "C" fn init_storage_proofs_bytes(
+    r1cs: Buffer,
+    wasm: Buffer,
+    zkey: *const Buffer,
+    err: *mut i32,
+) -> *mut Prover {
+    let args = || -> Result<_, (ProofError, String)> {
+        let r1cs = buffer_slice(&r1cs, "r1cs")?;
+        let wasm = buffer_slice(&wasm, "wasm")?;
+        let zkey = if !zkey.is_null() {
+            Some(buffer_slice(&*zkey, "zkey")?)
         } else {
             None
+        };
+
+        Ok((r1cs, wasm, zkey))
+    };
+
+    let (r1cs, wasm, zkey) = match args() {
+        Ok(args) => args,
+        Err((code, msg)) => {
+            fail(err, code, msg);
+            return std::ptr::null_mut();
+        }
+    };
+
+    match Prover::from_bytes(wasm, r1cs, zkey, None).map_err(load_error) {
+        Ok(prover) => {
+            set_error(err, ProofError::Ok);
+            Box::into_raw(Box::new(prover))
+        }
+        Err((code, msg)) => {
+            fail(err, code, msg);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// # Safety
+///
+/// Construct a verifier only StorageProofs object from a verification key in the
+/// (compressed) arkworks form of `verification_key`, without loading any circuit. Proving
+/// with it fails with `NotAProver`. Returns null and sets `err` on failure
+#[no_mangle]
+pub unsafe extern "C" fn init_verifier(vk: Buffer, err: *mut i32) -> *mut Prover {
+    let vk = match buffer_slice(&vk, "vk") {
+        Ok(vk) => vk,
+        Err((code, msg)) => {
+            fail(err, code, msg);
+            return std::ptr::null_mut();
         }
     };
 
-    Box::into_raw(Box::new(StorageProofs::new(wasm, r1cs, zkey)))
+    match Prover::verifier_only(vk).map_err(load_error) {
+        Ok(verifier) => {
+            set_error(err, ProofError::Ok);
+            Box::into_raw(Box::new(verifier))
+        }
+        Err((code, msg)) => {
+            fail(err, code, msg);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// # Safety
+///
+/// Same as `init_storage_proofs_bytes` without a zkey, leaving the prover without a
+/// proving key until one is set with `set_proving_key`. Prove calls fail with
+/// `NoProvingKey` until then
+#[no_mangle]
+pub unsafe extern "C" fn init_storage_proofs_without_key(
+    r1cs: Buffer,
+    wasm: Buffer,
+    err: *mut i32,
+) -> *mut Prover {
+    let prover = buffer_slice(&r1cs, "r1cs").and_then(|r1cs| {
+        let wasm = buffer_slice(&wasm, "wasm")?;
+        Prover::without_proving_key(wasm, r1cs, None).map_err(load_error)
+    });
+
+    match prover {
+        Ok(prover) => {
+            set_error(err, ProofError::Ok);
+            Box::into_raw(Box::new(prover))
+        }
+        Err((code, msg)) => {
+            fail(err, code, msg);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// # Safety
+///
+/// Use after constructing a StorageProofs object with init, and not concurrently with
+/// other calls on it. Replaces its proving key with the raw contents of a zkey file,
+/// returns false and sets `err` on failure, keeping the current key
+#[no_mangle]
+pub unsafe extern "C" fn set_proving_key(
+    prover_ptr: *mut Prover,
+    zkey: *const Buffer,
+    err: *mut i32,
+) -> bool {
+    let _prover = (*prover_ptr).storage_proofs_mut();
+    let res = buffer_slice(&*zkey, "zkey").and_then(|zkey| {
+        _prover
+            .set_proving_key(zkey)
+            .map_err(|e| (ProofError::DecodeError, e))
+    });
+
+    match res {
+        Ok(()) => {
+            set_error(err, ProofError::Ok);
+            true
+        }
+        Err((code, msg)) => {
+            fail(err, code, msg);
+            false
+        }
+    }
 }
 
 /// # Safety
 ///
 /// Use after constructing a StorageProofs object with init
 #[no_mangle]
+pub unsafe extern "C" fn has_proving_key(prover_ptr: *const Prover) -> bool {
+    let _prover = (*prover_ptr).storage_proofs();
+    _prover.has_proving_key()
+}
+
+/// # Safety
+///
+/// Use after constructing a StorageProofs object with init. Writes the constraint, input
+/// and wire counts of the loaded circuit to `info`, returns false if `info` is null
+#[no_mangle]
+pub unsafe extern "C" fn circuit_info(
+    prover_ptr: *const Prover,
+    info: *mut CircuitInfo,
+) -> bool {
+    let _prover = (*prover_ptr).storage_proofs();
+    if info.is_null() {
+        return false;
+    }
+
+    *info = _prover.circuit_info();
+    true
+}
+
+/// # Safety
+///
+/// Use after constructing a StorageProofs object with init, returns null and sets `err` on failure.
+/// `root` may be null to have it computed from the hashes and siblings, and `chunks` is empty
+/// for circuits taking the hashes directly. Proves with `api::Prover::prove`, so a failing
+/// witness calculation is a `ProveError` too, see `calculate_witness` to tell it apart
+#[no_mangle]
 pub unsafe extern "C" fn prove(
-    prover_ptr: *mut StorageProofs,
+    prover_ptr: *mut Prover,
     chunks: *const Buffer,
     siblings: *const Buffer,
     hashes: *const Buffer,
@@ -78,126 +699,1392 @@ pub unsafe extern "C" fn prove(
     pubkey: *const Buffer,
     root: *const Buffer,
     salt: *const Buffer,
+    err: *mut i32,
 ) -> *mut ProofCtx {
-    let chunks = {
-        let slice = std::slice::from_raw_parts((*chunks).data, (*chunks).len);
-        slice
-            .chunks(U256::BYTES)
-            .map(|c| U256::try_from_le_slice(c).unwrap())
-            .collect::<Vec<U256>>()
-    };
-    // println!("prove:args: {}", "chunks");
-    // for n in chunks {
-    //     println!("\t{}", n);
-    // }
-
-    let siblings = {
-        let slice = std::slice::from_raw_parts((*siblings).data, (*siblings).len);
-        slice
-            .chunks(U256::BYTES)
-            .map(|c| U256::try_from_le_slice(c).unwrap())
-            .collect::<Vec<U256>>()
-    };
-
-    let hashes = {
-        let slice = std::slice::from_raw_parts((*hashes).data, (*hashes).len);
-        slice
-            .chunks(U256::BYTES)
-            .map(|c| U256::try_from_le_slice(c).unwrap())
-            .collect::<Vec<U256>>()
-    };
-
-    let path = {
-        let slice = std::slice::from_raw_parts(path, path_len);
-        slice.to_vec()
-    };
-
-    let _pubkey =
-        U256::try_from_le_slice(std::slice::from_raw_parts((*pubkey).data, (*pubkey).len)).unwrap();
-
-    let root =
-        U256::try_from_le_slice(std::slice::from_raw_parts((*root).data, (*root).len)).unwrap();
-
-    let salt =
-        U256::try_from_le_slice(std::slice::from_raw_parts((*salt).data, (*salt).len)).unwrap();
-
-    let proof_bytes = &mut Vec::new();
-    let public_inputs_bytes = &mut Vec::new();
-
-    let mut _prover = &mut *prover_ptr;
-    _prover
-        .prove(
-            chunks.as_slice(),
-            siblings.as_slice(),
-            hashes.as_slice(),
-            path.as_slice(),
-            root,
-            salt,
-            proof_bytes,
-            public_inputs_bytes,
-        )
-        .unwrap();
-
-    Box::into_raw(Box::new(ProofCtx::new(proof_bytes, public_inputs_bytes)))
+    let inputs = read_proof_inputs(
+        (*prover_ptr).storage_proofs(),
+        chunks,
+        siblings,
+        hashes,
+        path,
+        path_len,
+        pubkey,
+        root,
+        salt,
+    );
+    prove_read_inputs(prover_ptr, inputs, err)
 }
 
 /// # Safety
 ///
-/// Use after constructing a StorageProofs object with init
+/// Same as `prove`, with `pubkey`, `root` and `salt` given as big endian bytes, e.g. the
+/// keccak hash of a block as is. The chunks, siblings and hashes are still little endian.
 #[no_mangle]
-pub unsafe extern "C" fn prove_mpack_ext(
-    prover_ptr: *mut StorageProofs,
-    args: *const Buffer,
+pub unsafe extern "C" fn prove_be(
+    prover_ptr: *mut Prover,
+    chunks: *const Buffer,
+    siblings: *const Buffer,
+    hashes: *const Buffer,
+    path: *const i32,
+    path_len: usize,
+    pubkey: *const Buffer,
+    root: *const Buffer,
+    salt: *const Buffer,
+    err: *mut i32,
 ) -> *mut ProofCtx {
-    let inputs = std::slice::from_raw_parts((*args).data, (*args).len);
-
-    let proof_bytes = &mut Vec::new();
-    let public_inputs_bytes = &mut Vec::new();
+    let inputs = read_proof_inputs_with(
+        (*prover_ptr).storage_proofs(),
+        chunks,
+        siblings,
+        hashes,
+        path,
+        path_len,
+        pubkey,
+        root,
+        salt,
+        Endianness::Big,
+    );
+    prove_read_inputs(prover_ptr, inputs, err)
+}
 
-    let mut _prover = &mut *prover_ptr;
-    _prover
-        .prove_mpack(
-            inputs,
-            proof_bytes,
-            public_inputs_bytes,
-        )
-        .unwrap();
+/// Prove the inputs read by `prove` or `prove_be`, failing with their error
+unsafe fn prove_read_inputs(
+    prover_ptr: *mut Prover,
+    inputs: Result<ProofInputs, (ProofError, String)>,
+    err: *mut i32,
+) -> *mut ProofCtx {
+    let inputs = match inputs {
+        Ok(inputs) => Zeroizing::new(inputs),
+        Err((code, msg)) => {
+            fail(err, code, msg);
+            return std::ptr::null_mut();
+        }
+    };
 
-    Box::into_raw(Box::new(ProofCtx::new(proof_bytes, public_inputs_bytes)))
+    let _prover = &*prover_ptr;
+    match _prover.prove(&inputs) {
+        Ok(output) => {
+            set_error(err, ProofError::Ok);
+            into_proof_ctx(output)
+        }
+        Err(e) => {
+            fail(err, prove_error(&e), e.to_string());
+            std::ptr::null_mut()
+        }
+    }
 }
 
+/// # Safety
+///
+/// Same as `prove`, with the prover randomness derived from `seed` so identical inputs
+/// and seeds give identical proofs. For test vectors and debugging only
 #[no_mangle]
+pub unsafe extern "C" fn prove_with_seed(
+    prover_ptr: *mut Prover,
+    chunks: *const Buffer,
+    siblings: *const Buffer,
+    hashes: *const Buffer,
+    path: *const i32,
+    path_len: usize,
+    pubkey: *const Buffer,
+    root: *const Buffer,
+    salt: *const Buffer,
+    seed: u64,
+    err: *mut i32,
+) -> *mut ProofCtx {
+    let inputs = match read_proof_inputs(
+        (*prover_ptr).storage_proofs(),
+        chunks,
+        siblings,
+        hashes,
+        path,
+        path_len,
+        pubkey,
+        root,
+        salt,
+    ) {
+        Ok(inputs) => Zeroizing::new(inputs),
+        Err((code, msg)) => {
+            fail(err, code, msg);
+            return std::ptr::null_mut();
+        }
+    };
+
+    let _prover = (*prover_ptr).storage_proofs();
+    match _prover.prove_with_seed(&inputs, seed) {
+        Ok(output) => {
+            set_error(err, ProofError::Ok);
+            into_proof_ctx(output)
+        }
+        Err(e) => {
+            fail(err, prove_error(&e), e.to_string());
+            std::ptr::null_mut()
+        }
+    }
+}
+
 /// # Safety
 ///
-/// Should be called on a valid proof and public inputs previously generated by prove
-pub unsafe extern "C" fn verify(
-    prover_ptr: *mut StorageProofs,
-    proof: *const Buffer,
-    public_inputs: *const Buffer,
-) -> bool {
-    let proof = std::slice::from_raw_parts((*proof).data, (*proof).len);
-    let public_inputs = std::slice::from_raw_parts((*public_inputs).data, (*public_inputs).len);
-    let mut _prover = &mut *prover_ptr;
-    _prover.verify(proof, public_inputs).is_ok()
+/// Same as `prove`, also returning the witness that was proved, to be freed with
+/// `free_proof_ctx_witness`. For debugging proofs that fail to verify, the witness holds
+/// the private inputs
+#[no_mangle]
+pub unsafe extern "C" fn prove_with_witness_out(
+    prover_ptr: *mut Prover,
+    chunks: *const Buffer,
+    siblings: *const Buffer,
+    hashes: *const Buffer,
+    path: *const i32,
+    path_len: usize,
+    pubkey: *const Buffer,
+    root: *const Buffer,
+    salt: *const Buffer,
+    err: *mut i32,
+) -> *mut ProofCtxWitness {
+    let inputs = match read_proof_inputs(
+        (*prover_ptr).storage_proofs(),
+        chunks,
+        siblings,
+        hashes,
+        path,
+        path_len,
+        pubkey,
+        root,
+        salt,
+    ) {
+        Ok(inputs) => Zeroizing::new(inputs),
+        Err((code, msg)) => {
+            fail(err, code, msg);
+            return std::ptr::null_mut();
+        }
+    };
+
+    let _prover = (*prover_ptr).storage_proofs();
+    match _prover.prove_with_witness_out(&inputs) {
+        Ok((output, witness)) => {
+            set_error(err, ProofError::Ok);
+            #[cfg(feature = "metrics")]
+            LAST_TIMINGS.with(|t| t.set(output.timings.map(ProveTimingsNs::from)));
+
+            let witness = witness
+                .iter()
+                .flat_map(|w| w.to_le_bytes_vec())
+                .collect::<Vec<u8>>();
+            Box::into_raw(Box::new(ProofCtxWitness {
+                ctx: ProofCtx::new(output.proof, output.public_inputs),
+                witness: Buffer::from_vec(witness),
+            }))
+        }
+        Err(e) => {
+            fail(err, prove_error(&e), e.to_string());
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Callback used by `prove_with_progress`, invoked with each phase as it's reached.
+/// Returning false cancels proving.
+pub type ProgressCallback = extern "C" fn(phase: ProvePhase, ctx: *mut c_void) -> bool;
+
+/// # Safety
+///
+/// Same as `prove`, reporting progress to `progress_fn`, which is passed `ctx` untouched.
+/// Returns null and sets `err` to `Cancelled` if the callback asked to stop
+#[no_mangle]
+pub unsafe extern "C" fn prove_with_progress(
+    prover_ptr: *mut Prover,
+    chunks: *const Buffer,
+    siblings: *const Buffer,
+    hashes: *const Buffer,
+    path: *const i32,
+    path_len: usize,
+    pubkey: *const Buffer,
+    root: *const Buffer,
+    salt: *const Buffer,
+    progress_fn: ProgressCallback,
+    ctx: *mut c_void,
+    err: *mut i32,
+) -> *mut ProofCtx {
+    let inputs = match read_proof_inputs(
+        (*prover_ptr).storage_proofs(),
+        chunks,
+        siblings,
+        hashes,
+        path,
+        path_len,
+        pubkey,
+        root,
+        salt,
+    ) {
+        Ok(inputs) => Zeroizing::new(inputs),
+        Err((code, msg)) => {
+            fail(err, code, msg);
+            return std::ptr::null_mut();
+        }
+    };
+
+    let _prover = (*prover_ptr).storage_proofs();
+    proved_ctx(
+        _prover.prove_with_progress(&inputs, |phase| progress_fn(phase, ctx)),
+        err,
+    )
+}
+
+/// The `ProofCtx` of a successful `prove_with_progress`, or null with `err` set to the
+/// code of its `ProveError`
+unsafe fn proved_ctx(res: Result<ProofOutput, ProveError>, err: *mut i32) -> *mut ProofCtx {
+    match res {
+        Ok(output) => {
+            set_error(err, ProofError::Ok);
+            into_proof_ctx(output)
+        }
+        Err(e) => {
+            fail(err, prove_error(&e), e.to_string());
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// # Safety
+///
+/// Same as `prove`, but returns right away with a handle to the proof running on a
+/// background thread, for hosts with an event loop. Poll it with `prove_poll`, then get
+/// the proof with `prove_join`, or cancel it with `free_prove_handle`. Returns null and
+/// sets `err` if the inputs can't be read
+#[no_mangle]
+pub unsafe extern "C" fn prove_start(
+    prover_ptr: *mut Prover,
+    chunks: *const Buffer,
+    siblings: *const Buffer,
+    hashes: *const Buffer,
+    path: *const i32,
+    path_len: usize,
+    pubkey: *const Buffer,
+    root: *const Buffer,
+    salt: *const Buffer,
+    err: *mut i32,
+) -> *mut ProveHandle {
+    let inputs = match read_proof_inputs(
+        (*prover_ptr).storage_proofs(),
+        chunks,
+        siblings,
+        hashes,
+        path,
+        path_len,
+        pubkey,
+        root,
+        salt,
+    ) {
+        Ok(inputs) => Zeroizing::new(inputs),
+        Err((code, msg)) => {
+            fail(err, code, msg);
+            return std::ptr::null_mut();
+        }
+    };
+
+    let _prover = (*prover_ptr).storage_proofs();
+    set_error(err, ProofError::Ok);
+    Box::into_raw(Box::new(_prover.prove_async(&inputs)))
+}
+
+/// # Safety
+///
+/// Use on a handle from `prove_start`. Whether its proof is done, so `prove_join`
+/// returns without blocking
+#[no_mangle]
+pub unsafe extern "C" fn prove_poll(handle: *const ProveHandle) -> bool {
+    (*handle).is_ready()
+}
+
+/// # Safety
+///
+/// Use on a handle from `prove_start`, which is freed and must not be used afterwards.
+/// Waits for the proof and returns it, or null with `err` set if proving failed
+#[no_mangle]
+pub unsafe extern "C" fn prove_join(handle: *mut ProveHandle, err: *mut i32) -> *mut ProofCtx {
+    let handle = Box::from_raw(handle);
+    proved_ctx(handle.join(), err)
+}
+
+/// # Safety
+///
+/// Use on a handle from `prove_start` that wasn't passed to `prove_join`. Cancels the
+/// proof, which stops at its next phase on the background thread, and frees the handle
+#[no_mangle]
+pub unsafe extern "C" fn free_prove_handle(handle: *mut ProveHandle) {
+    if handle.is_null() {
+        return;
+    }
+
+    unsafe { drop(Box::from_raw(handle)) }
+}
+
+/// Callback used by `prove_streaming` to pull the `index`th sampled chunk into `out` as
+/// little endian field elements, returning 0 on success. The data `out` points to only has
+/// to stay valid until the callback is invoked again or `prove_streaming` returns.
+pub type ChunkCallback = extern "C" fn(index: usize, out: *mut Buffer, ctx: *mut c_void) -> i32;
+
+/// # Safety
+///
+/// Same as `prove`, but requests `chunk_count` chunks one by one from `chunk_fn`, which is
+/// passed `ctx` untouched. As with `prove`, `root` may be null to have it computed from the
+/// hashes and siblings. Returns null and sets `err` on failure
+#[no_mangle]
+pub unsafe extern "C" fn prove_streaming(
+    prover_ptr: *mut Prover,
+    chunk_count: usize,
+    chunk_fn: ChunkCallback,
+    ctx: *mut c_void,
+    siblings: *const Buffer,
+    hashes: *const Buffer,
+    path: *const i32,
+    path_len: usize,
+    pubkey: *const Buffer,
+    root: *const Buffer,
+    salt: *const Buffer,
+    err: *mut i32,
+) -> *mut ProofCtx {
+    let args = || -> Result<_, (ProofError, String)> {
+        let siblings = buffer_to_u256s(&*siblings, "siblings")?;
+        let hashes = buffer_to_u256s(&*hashes, "hashes")?;
+        let path = raw_slice(path, path_len, "path")?.to_vec();
+        check_path((*prover_ptr).storage_proofs(), &path)?;
+        let _pubkey = PubKey(buffer_to_u256(&*pubkey, "pubkey")?);
+        let root = if root.is_null() {
+            None
+        } else {
+            Some(Root(buffer_to_u256(&*root, "root")?))
+        };
+        let salt = Salt(buffer_to_u256(&*salt, "salt")?);
+
+        Ok((siblings, hashes, path, root, salt))
+    };
+
+    let (siblings, hashes, path, root, salt) = match args() {
+        Ok(args) => args,
+        Err((code, msg)) => {
+            fail(err, code, msg);
+            return std::ptr::null_mut();
+        }
+    };
+
+    // errors raised while pulling chunks keep their own code, not ProveError
+    let mut chunk_error = None;
+    let chunk = |index: usize| -> Result<Vec<U256>, String> {
+        let mut out = Buffer {
+            data: std::ptr::null(),
+            len: 0,
+        };
+
+        let res = chunk_fn(index, &mut out, ctx);
+        let values = if res != 0 {
+            Err((
+                ProofError::WitnessError,
+                format!("chunk callback failed for chunk {} with {}", index, res),
+            ))
+        } else {
+            buffer_to_u256s(&out, "chunk")
+        };
+
+        values.map_err(|(code, msg)| {
+            chunk_error = Some(code);
+            msg
+        })
+    };
+
+    let _prover = (*prover_ptr).storage_proofs();
+    let res = _prover.prove_streaming(
+        chunk_count,
+        chunk,
+        siblings.as_slice(),
+        hashes.as_slice(),
+        path.as_slice(),
+        root,
+        salt,
+    );
+
+    match res {
+        Ok(output) => {
+            set_error(err, ProofError::Ok);
+            into_proof_ctx(output)
+        }
+        Err(e) => {
+            fail(err, chunk_error.unwrap_or(prove_error(&e)), e.to_string());
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Callback used by `prove_chunked` to take the next piece of the serialized proof,
+/// returning 0 to continue. `chunk` is only valid for the duration of the call.
+pub type SinkCallback = extern "C" fn(chunk: Buffer, ctx: *mut c_void) -> i32;
+
+/// # Safety
+///
+/// Same as `prove`, but instead of returning a ProofCtx writes the proof, serialized with
+/// `ProofOutput::to_bytes` in the compressed format, to `sink_fn` in pieces of at most
+/// `chunk_size` bytes (all at once if 0). `ctx` is passed to it untouched. The pieces
+/// are passed in order, one call at a time on the calling thread, and concatenate to the
+/// whole proof; only once `sink_fn` has taken the last one does this return true. When
+/// `sink_fn` returns nonzero no further pieces are passed, `err` is set to `Cancelled`
+/// and false returned, as on any other failure
+#[no_mangle]
+pub unsafe extern "C" fn prove_chunked(
+    prover_ptr: *mut Prover,
+    chunks: *const Buffer,
+    siblings: *const Buffer,
+    hashes: *const Buffer,
+    path: *const i32,
+    path_len: usize,
+    pubkey: *const Buffer,
+    root: *const Buffer,
+    salt: *const Buffer,
+    chunk_size: usize,
+    sink_fn: SinkCallback,
+    ctx: *mut c_void,
+    err: *mut i32,
+) -> bool {
+    let inputs = match read_proof_inputs(
+        (*prover_ptr).storage_proofs(),
+        chunks,
+        siblings,
+        hashes,
+        path,
+        path_len,
+        pubkey,
+        root,
+        salt,
+    ) {
+        Ok(inputs) => Zeroizing::new(inputs),
+        Err((code, msg)) => {
+            fail(err, code, msg);
+            return false;
+        }
+    };
+
+    let _prover = (*prover_ptr).storage_proofs();
+    let bytes = _prover
+        .prove_inputs(&inputs)
+        .and_then(|output| {
+            output
+                .to_bytes(ProofFormat::Compressed)
+                .map_err(ProveError::Failed)
+        });
+    let bytes = match bytes {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            fail(err, prove_error(&e), e.to_string());
+            return false;
+        }
+    };
+
+    let chunk_size = if chunk_size == 0 { bytes.len().max(1) } else { chunk_size };
+    for (index, chunk) in bytes.chunks(chunk_size).enumerate() {
+        let res = sink_fn(Buffer::from_slice(chunk), ctx);
+        if res != 0 {
+            let msg = format!("sink callback failed for piece {} with {}", index, res);
+            fail(err, ProofError::Cancelled, msg);
+            return false;
+        }
+    }
+
+    set_error(err, ProofError::Ok);
+    true
+}
+
+/// # Safety
+///
+/// Use after constructing a StorageProofs object with init. Returns the witness as
+/// little endian field elements, to be freed with `free_buffer`, or null and sets
+/// `err` on failure
+#[no_mangle]
+pub unsafe extern "C" fn calculate_witness(
+    prover_ptr: *mut Prover,
+    chunks: *const Buffer,
+    siblings: *const Buffer,
+    hashes: *const Buffer,
+    path: *const i32,
+    path_len: usize,
+    pubkey: *const Buffer,
+    root: *const Buffer,
+    salt: *const Buffer,
+    err: *mut i32,
+) -> *mut Buffer {
+    let inputs = match read_proof_inputs(
+        (*prover_ptr).storage_proofs(),
+        chunks,
+        siblings,
+        hashes,
+        path,
+        path_len,
+        pubkey,
+        root,
+        salt,
+    ) {
+        Ok(inputs) => Zeroizing::new(inputs),
+        Err((code, msg)) => {
+            fail(err, code, msg);
+            return std::ptr::null_mut();
+        }
+    };
+
+    let _prover = (*prover_ptr).storage_proofs();
+    match _prover.calculate_witness(&inputs) {
+        Ok(witness) => {
+            set_error(err, ProofError::Ok);
+            let bytes = witness
+                .iter()
+                .flat_map(|w| w.to_le_bytes_vec())
+                .collect::<Vec<u8>>();
+            Box::into_raw(Box::new(Buffer::from_vec(bytes)))
+        }
+        Err(e) => {
+            fail(err, witness_error(&e), e.to_string());
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// # Safety
+///
+/// Use after constructing a StorageProofs object with init, `witness` as returned by
+/// `calculate_witness`. Returns null and sets `err` on failure
+#[no_mangle]
+pub unsafe extern "C" fn prove_with_witness(
+    prover_ptr: *mut Prover,
+    witness: *const Buffer,
+    err: *mut i32,
+) -> *mut ProofCtx {
+    let witness = match buffer_to_u256s(&*witness, "witness") {
+        Ok(witness) => witness,
+        Err((code, msg)) => {
+            fail(err, code, msg);
+            return std::ptr::null_mut();
+        }
+    };
+
+    let _prover = (*prover_ptr).storage_proofs();
+    match _prover.prove_with_witness(&witness) {
+        Ok(output) => {
+            set_error(err, ProofError::Ok);
+            into_proof_ctx(output)
+        }
+        Err(e) => {
+            fail(err, prove_error(&e), e.to_string());
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// # Safety
+///
+/// Use after constructing a StorageProofs object with init, returns null and sets `err` on failure
+#[no_mangle]
+pub unsafe extern "C" fn prove_mpack_ext(
+    prover_ptr: *mut Prover,
+    args: *const Buffer,
+    err: *mut i32,
+) -> *mut ProofCtx {
+    let inputs = match buffer_slice(&*args, "args") {
+        Ok(inputs) => inputs,
+        Err((code, msg)) => {
+            fail(err, code, msg);
+            return std::ptr::null_mut();
+        }
+    };
+
+    let _prover = &*prover_ptr;
+    match _prover.prove_mpack(inputs) {
+        Ok(output) => {
+            set_error(err, ProofError::Ok);
+            OwnedProofCtx::new(output.proof, output.public_inputs).into_raw()
+        }
+        Err(e) => {
+            fail(err, prove_error(&e), e.to_string());
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// # Safety
+///
+/// Same as `prove_mpack_ext`, returning the proof as a single mpack Buffer (see
+/// `ProofOutput::to_mpack`) to be freed with `free_buffer`. Returns null and sets `err`
+/// on failure
+#[no_mangle]
+pub unsafe extern "C" fn prove_mpack_full(
+    prover_ptr: *mut Prover,
+    args: *const Buffer,
+    err: *mut i32,
+) -> *mut Buffer {
+    let output = buffer_slice(&*args, "args").and_then(|args| {
+        let _prover = &*prover_ptr;
+        let output = _prover
+            .prove_mpack(args)
+            .map_err(|e| (prove_error(&e), e.to_string()))?;
+
+        Ok(output.to_mpack())
+    });
+
+    owned_buffer(output, err)
+}
+
+/// # Safety
+///
+/// Same as `prove_mpack_ext`, with the arguments as a UTF-8 JSON document, see
+/// `StorageProofs::prove_json`. Returns null and sets `err` on failure
+#[no_mangle]
+pub unsafe extern "C" fn prove_json_ext(
+    prover_ptr: *mut Prover,
+    json: *const Buffer,
+    err: *mut i32,
+) -> *mut ProofCtx {
+    let _prover = &*prover_ptr;
+    let inputs = match buffer_to_string(&*json, "json") {
+        Ok(json) => _prover
+            .storage_proofs()
+            .decode_json(&json)
+            .map_err(|e| (ProofError::DecodeError, e)),
+        Err(e) => Err(e),
+    };
+    let inputs = match inputs {
+        Ok(inputs) => Zeroizing::new(inputs),
+        Err((code, msg)) => {
+            fail(err, code, msg);
+            return std::ptr::null_mut();
+        }
+    };
+
+    match _prover.prove(&inputs) {
+        Ok(output) => {
+            set_error(err, ProofError::Ok);
+            into_proof_ctx(output)
+        }
+        Err(e) => {
+            fail(err, prove_error(&e), e.to_string());
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// # Safety
+///
+/// `args` must point to `len` mpack encoded argument Buffers (see `prove_mpack_ext`).
+/// Returns an array of `len` ProofCtx to be freed with `free_proof_ctx_batch`, or null
+/// and sets `err` on failure
+#[no_mangle]
+pub unsafe extern "C" fn prove_batch(
+    prover_ptr: *mut Prover,
+    args: *const Buffer,
+    len: usize,
+    err: *mut i32,
+) -> *mut ProofCtx {
+    let inputs = raw_slice(args, len, "args").and_then(|args| {
+        args.iter()
+            .enumerate()
+            .map(|(i, b)| buffer_slice(b, &format!("args[{}]", i)))
+            .collect::<Result<Vec<&[u8]>, _>>()
+    });
+    let inputs = match inputs {
+        Ok(inputs) => inputs,
+        Err((code, msg)) => {
+            fail(err, code, msg);
+            return std::ptr::null_mut();
+        }
+    };
+
+    let _prover = (*prover_ptr).storage_proofs();
+    let outputs = match _prover.prove_mpack_batch(inputs.as_slice()) {
+        Ok(outputs) => outputs,
+        Err(e) => {
+            fail(err, prove_error(&e), e.to_string());
+            return std::ptr::null_mut();
+        }
+    };
+
+    let ctxs = outputs
+        .into_iter()
+        .map(|o| ProofCtx::new(o.proof, o.public_inputs))
+        .collect::<Box<[ProofCtx]>>();
+
+    set_error(err, ProofError::Ok);
+    Box::into_raw(ctxs) as *mut ProofCtx
+}
+
+#[no_mangle]
+/// # Safety
+///
+/// Should be called on a valid proof and public inputs previously generated by prove
+/// Buffers of the wrong length, e.g. empty or truncated ones, return false with `err`
+/// set to `VerifyError` without being deserialized
+pub unsafe extern "C" fn verify(
+    prover_ptr: *mut Prover,
+    proof: *const Buffer,
+    public_inputs: *const Buffer,
+    err: *mut i32,
+) -> bool {
+    let (proof, public_inputs) = match verify_args(&*proof, &*public_inputs) {
+        Ok(args) => args,
+        Err((code, msg)) => {
+            fail(err, code, msg);
+            return false;
+        }
+    };
+
+    let _prover = &*prover_ptr;
+    match _prover.verify(proof, public_inputs) {
+        Ok(()) => {
+            set_error(err, ProofError::Ok);
+            true
+        }
+        Err(e) => {
+            fail(err, ProofError::VerifyError, e);
+            false
+        }
+    }
+}
+
+#[no_mangle]
+/// # Safety
+///
+/// `proofs` and `public_inputs` must point to `len` Buffers each, and `results` to room for
+/// `len` bools. Writes whether each proof is valid to `results`, returns true if all are.
+/// Null or mis-sized arrays return false and set `err`, telling bad arguments apart from
+/// invalid proofs.
+pub unsafe extern "C" fn verify_batch(
+    prover_ptr: *mut Prover,
+    proofs: *const Buffer,
+    public_inputs: *const Buffer,
+    len: usize,
+    results: *mut bool,
+    err: *mut i32,
+) -> bool {
+    let args = raw_slice(proofs, len, "proofs").and_then(|proofs| {
+        let public_inputs = raw_slice(public_inputs, len, "public_inputs")?;
+        if len > 0 && results.is_null() {
+            return Err((
+                ProofError::BadBufferLength,
+                format!("results: null with length {}", len),
+            ));
+        }
+
+        Ok((proofs, public_inputs))
+    });
+    let (proofs, public_inputs) = match args {
+        Ok(args) => args,
+        Err((code, msg)) => {
+            fail(err, code, msg);
+            return false;
+        }
+    };
+
+    // an unreadable buffer is passed on empty, which counts as an invalid proof
+    let items = proofs
+        .iter()
+        .zip(public_inputs.iter())
+        .map(|(p, i)| {
+            (
+                buffer_slice(p, "proof").unwrap_or_default(),
+                buffer_slice(i, "public_inputs").unwrap_or_default(),
+            )
+        })
+        .collect::<Vec<(&[u8], &[u8])>>();
+
+    let _prover = (*prover_ptr).storage_proofs();
+    let valid = _prover.verify_batch(items.as_slice());
+    if len > 0 {
+        std::slice::from_raw_parts_mut(results, len).copy_from_slice(&valid);
+    }
+
+    set_error(err, ProofError::Ok);
+    valid.iter().all(|v| *v)
+}
+
+/// # Safety
+///
+/// Use after constructing a StorageProofs object with init. Returns the serialized
+/// verification key, to be freed with `free_buffer`, or null and sets `err` on failure
+#[no_mangle]
+pub unsafe extern "C" fn verification_key(
+    prover_ptr: *mut Prover,
+    err: *mut i32,
+) -> *mut Buffer {
+    let _prover = &*prover_ptr;
+    match _prover.verification_key() {
+        Ok(vk) => {
+            set_error(err, ProofError::Ok);
+            Box::into_raw(Box::new(Buffer::from_vec(vk)))
+        }
+        Err(e) => {
+            fail(err, ProofError::VerifyError, e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// # Safety
+///
+/// `vk` must hold a verification key returned by `verification_key`, doesn't need a
+/// StorageProofs object. Sets `err` if any of the arguments is malformed
+#[no_mangle]
+pub unsafe extern "C" fn verify_with_vk(
+    vk: *const Buffer,
+    proof: *const Buffer,
+    public_inputs: *const Buffer,
+    err: *mut i32,
+) -> bool {
+    let args = buffer_slice(&*vk, "vk")
+        .and_then(|vk| verify_args(&*proof, &*public_inputs).map(|args| (vk, args)));
+    let (vk, (proof, public_inputs)) = match args {
+        Ok(args) => args,
+        Err((code, msg)) => {
+            fail(err, code, msg);
+            return false;
+        }
+    };
+
+    match storage_proofs::verify_with_vk(vk, proof, public_inputs) {
+        Ok(valid) => {
+            set_error(err, ProofError::Ok);
+            valid
+        }
+        Err(e) => {
+            fail(err, ProofError::VerifyError, e);
+            false
+        }
+    }
+}
+
+/// # Safety
+///
+/// Checks the Merkle path of the leaf at `index` of an `arity`-ary tree from `leaf` to
+/// `root` without proving, see `MerklePath::verify`. `siblings` holds `arity - 1` little
+/// endian hashes per level. Returns false and sets `err` if the arguments are malformed.
+#[no_mangle]
+pub unsafe extern "C" fn verify_merkle_path(
+    leaf: *const Buffer,
+    root: *const Buffer,
+    index: usize,
+    siblings: *const Buffer,
+    arity: usize,
+    err: *mut i32,
+) -> bool {
+    let args = buffer_to_u256(&*leaf, "leaf").and_then(|leaf| {
+        let root = buffer_to_u256(&*root, "root")?;
+        let path = MerklePath::new(index, buffer_to_u256s(&*siblings, "siblings")?, arity)
+            .map_err(|e| (ProofError::DecodeError, e))?;
+        Ok((leaf, root, path))
+    });
+
+    match args {
+        Ok((leaf, root, path)) => {
+            set_error(err, ProofError::Ok);
+            path.verify(leaf, root)
+        }
+        Err((code, msg)) => {
+            fail(err, code, msg);
+            false
+        }
+    }
+}
+
+/// # Safety
+///
+/// Use after constructing a StorageProofs object with init. Checks `claimed` is the digest
+/// of the little endian field elements in `chunk`, see `StorageProofs::check_chunk_digest`.
+/// Returns false and sets `err` if the arguments are malformed.
+#[no_mangle]
+pub unsafe extern "C" fn check_chunk_digest(
+    prover_ptr: *const Prover,
+    chunk: *const Buffer,
+    claimed: *const Buffer,
+    err: *mut i32,
+) -> bool {
+    let args = buffer_to_u256s(&*chunk, "chunk")
+        .and_then(|chunk| Ok((chunk, buffer_to_u256(&*claimed, "claimed")?)));
+    let (chunk, claimed) = match args {
+        Ok(args) => args,
+        Err((code, msg)) => {
+            fail(err, code, msg);
+            return false;
+        }
+    };
+
+    set_error(err, ProofError::Ok);
+    let _prover = (*prover_ptr).storage_proofs();
+    _prover.check_chunk_digest(&chunk, claimed)
+}
+
+/// # Safety
+///
+/// Use after constructing a StorageProofs object with init. Splits the raw bytes in `data`
+/// into chunks of the circuit's `chunk_len` (the storer circuit's without circuit params),
+/// see `utils::chunkify`. Returns an owned Buffer of the chunks one after another, as
+/// little endian field elements, to be freed with `free_buffer`, or null and sets `err`
+#[no_mangle]
+pub unsafe extern "C" fn chunkify(
+    prover_ptr: *const Prover,
+    data: *const Buffer,
+    err: *mut i32,
+) -> *mut Buffer {
+    let _prover = (*prover_ptr).storage_proofs();
+    let params = _prover.circuit_params().unwrap_or_default();
+    let chunks = buffer_slice(&*data, "data").map(|data| {
+        utils::chunkify(data, &params)
+            .iter()
+            .flatten()
+            .flat_map(|n| n.to_le_bytes::<32>())
+            .collect()
+    });
+
+    owned_buffer(chunks, err)
+}
+
+/// Outcome of `verify_detailed`
+#[derive(Debug, Clone)]
+#[repr(C)]
+pub struct VerifyDetails {
+    pub valid: bool,
+    /// index of the first offending public input, or -1 if not attributable to one input
+    pub failed_index: isize,
+    pub num_inputs: usize,
+    /// little endian bytes of the offending public input
+    pub failed_value: [u8; 32],
+}
+
+#[no_mangle]
+/// # Safety
+///
+/// Should be called on a valid proof and public inputs previously generated by prove,
+/// `details` must point to a writable VerifyDetails
+pub unsafe extern "C" fn verify_detailed(
+    prover_ptr: *mut Prover,
+    proof: *const Buffer,
+    public_inputs: *const Buffer,
+    details: *mut VerifyDetails,
+) -> bool {
+    let _prover = (*prover_ptr).storage_proofs();
+    let args = (
+        buffer_slice(&*proof, "proof"),
+        buffer_slice(&*public_inputs, "public_inputs"),
+    );
+    let res = match args {
+        (Ok(proof), Ok(public_inputs)) => _prover.verify_detailed(proof, public_inputs),
+        (Err((_, reason)), _) | (_, Err((_, reason))) => Err(VerifyFailure {
+            index: None,
+            inputs: Vec::new(),
+            reason,
+        }),
+    };
+    let mut out = VerifyDetails {
+        valid: res.is_ok(),
+        failed_index: -1,
+        num_inputs: 0,
+        failed_value: [0; 32],
+    };
+
+    match res {
+        Ok(()) => set_last_error(None),
+        Err(failure) => {
+            out.num_inputs = failure.inputs.len();
+            if let Some(index) = failure.index {
+                out.failed_index = index as isize;
+                out.failed_value = failure.inputs[index].to_le_bytes();
+            }
+            set_last_error(Some(failure.reason));
+        }
+    }
+
+    if !details.is_null() {
+        *details = out.clone();
+    }
+
+    out.valid
+}
+
+/// # Safety
+///
+/// Returns the public inputs as a JSON array of decimal strings, to be freed with
+/// `free_string`, or null and sets `err` on failure
+#[no_mangle]
+pub unsafe extern "C" fn public_inputs_to_json(
+    public_inputs: *const Buffer,
+    err: *mut i32,
+) -> *mut c_char {
+    let json = buffer_slice(&*public_inputs, "public_inputs").and_then(|public_inputs| {
+        StorageProofs::public_inputs_json(public_inputs)
+            .map_err(|e| (ProofError::BadBufferLength, e))
+    });
+
+    match json {
+        Ok(json) => {
+            set_error(err, ProofError::Ok);
+            CString::new(json).unwrap_or_default().into_raw()
+        }
+        Err((code, msg)) => {
+            fail(err, code, msg);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// # Safety
+///
+/// Decode the hex string (with or without a `0x` prefix) in `encoded` into an owned Buffer,
+/// to be freed with `free_buffer`, or returns null and sets `err` on failure
+#[no_mangle]
+pub unsafe extern "C" fn buffer_from_hex(encoded: *const Buffer, err: *mut i32) -> *mut Buffer {
+    let decoded = buffer_to_string(&*encoded, "hex").and_then(|s| {
+        hex::decode(s.strip_prefix("0x").unwrap_or(&s))
+            .map_err(|e| (ProofError::DecodeError, format!("hex: {}", e)))
+    });
+
+    owned_buffer(decoded, err)
+}
+
+/// # Safety
+///
+/// Decode the standard (padded) base64 string in `encoded` into an owned Buffer, to be
+/// freed with `free_buffer`, or returns null and sets `err` on failure
+#[no_mangle]
+pub unsafe extern "C" fn buffer_from_base64(encoded: *const Buffer, err: *mut i32) -> *mut Buffer {
+    let decoded = buffer_to_string(&*encoded, "base64").and_then(|s| {
+        STANDARD
+            .decode(s)
+            .map_err(|e| (ProofError::DecodeError, format!("base64: {}", e)))
+    });
+
+    owned_buffer(decoded, err)
+}
+
+/// # Safety
+///
+/// Returns the proof bytes of `ctx` as a lowercase hex string, to be freed with `free_string`
+#[no_mangle]
+pub unsafe extern "C" fn proof_ctx_to_hex(ctx: *const ProofCtx) -> *mut c_char {
+    let proof = std::slice::from_raw_parts((*ctx).proof.data, (*ctx).proof.len);
+    CString::new(hex::encode(proof)).unwrap_or_default().into_raw()
+}
+
+/// # Safety
+///
+/// Writes the 32 byte `ProofOutput::fingerprint` of `ctx` to `out`, which must point to
+/// at least 32 writable bytes
+#[no_mangle]
+pub unsafe extern "C" fn proof_ctx_fingerprint(ctx: *const ProofCtx, out: *mut u8) {
+    let output = ProofOutput {
+        proof: std::slice::from_raw_parts((*ctx).proof.data, (*ctx).proof.len).to_vec(),
+        public_inputs: std::slice::from_raw_parts(
+            (*ctx).public_inputs.data,
+            (*ctx).public_inputs.len,
+        )
+        .to_vec(),
+        ..Default::default()
+    };
+
+    std::ptr::copy_nonoverlapping(output.fingerprint().as_ptr(), out, 32);
+}
+
+/// The serialized public inputs of `ctx` after their length prefix, 32 little endian
+/// bytes per field element, empty for a null `ctx`
+unsafe fn proof_ctx_public_elements<'a>(ctx: *const ProofCtx) -> &'a [u8] {
+    if ctx.is_null() {
+        return &[];
+    }
+
+    let public_inputs = &(*ctx).public_inputs;
+    match buffer_slice(public_inputs, "public_inputs") {
+        Ok(bytes) => bytes.get(std::mem::size_of::<u64>()..).unwrap_or_default(),
+        Err(_) => &[],
+    }
+}
+
+/// # Safety
+///
+/// Returns the number of public inputs of `ctx`, so hosts can read them one by one with
+/// `proof_ctx_public_at` without knowing how they are serialized. 0 for a null `ctx`
+#[no_mangle]
+pub unsafe extern "C" fn proof_ctx_public_count(ctx: *const ProofCtx) -> usize {
+    proof_ctx_public_elements(ctx).len() / U256::BYTES
+}
+
+/// # Safety
+///
+/// Writes public input `i` of `ctx`, in circuit order, to `out_le` as 32 little endian
+/// bytes, `out_le` must point to at least 32 writable bytes. Returns false, leaving
+/// `out_le` untouched, if `i` isn't below `proof_ctx_public_count` or a pointer is null
+#[no_mangle]
+pub unsafe extern "C" fn proof_ctx_public_at(
+    ctx: *const ProofCtx,
+    i: usize,
+    out_le: *mut u8,
+) -> bool {
+    let element = proof_ctx_public_elements(ctx)
+        .chunks_exact(U256::BYTES)
+        .nth(i);
+    match element {
+        Some(element) if !out_le.is_null() => {
+            std::ptr::copy_nonoverlapping(element.as_ptr(), out_le, U256::BYTES);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// # Safety
+///
+/// Returns the serialized public inputs as a lowercase hex string, to be freed with
+/// `free_string`, or null if `public_inputs` isn't a valid Buffer
+#[no_mangle]
+pub unsafe extern "C" fn public_inputs_to_hex(public_inputs: *const Buffer) -> *mut c_char {
+    match buffer_slice(&*public_inputs, "public_inputs") {
+        Ok(public_inputs) => CString::new(hex::encode(public_inputs))
+            .unwrap_or_default()
+            .into_raw(),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// # Safety
+///
+/// Use after constructing a StorageProofs object with init. Writes the value of the public
+/// signal named by the UTF-8 `name` (e.g. `root`) in `public_inputs` to `out` as 32 little
+/// endian bytes, `out` must point to at least 32 writable bytes. Returns false and sets
+/// `err` to `NoSuchSignal` for names not in the circuit's public input layout, or of array
+/// signals
+#[no_mangle]
+pub unsafe extern "C" fn get_public_signal(
+    prover_ptr: *const Prover,
+    public_inputs: *const Buffer,
+    name: *const Buffer,
+    out: *mut u8,
+    err: *mut i32,
+) -> bool {
+    let args = buffer_slice(&*public_inputs, "public_inputs")
+        .and_then(|public_inputs| Ok((public_inputs, buffer_to_string(&*name, "name")?)));
+    let (public_inputs, name) = match args {
+        Ok(args) => args,
+        Err((code, msg)) => {
+            fail(err, code, msg);
+            return false;
+        }
+    };
+
+    let _prover = (*prover_ptr).storage_proofs();
+    match _prover.get_public_signal(public_inputs, &name) {
+        Ok(value) => {
+            let bytes = value.to_le_bytes::<{ U256::BYTES }>();
+            std::ptr::copy_nonoverlapping(bytes.as_ptr(), out, U256::BYTES);
+            set_error(err, ProofError::Ok);
+            true
+        }
+        Err(e) => {
+            let code = match e {
+                PublicSignalError::NoSuchSignal(_) | PublicSignalError::Array { .. } => {
+                    ProofError::NoSuchSignal
+                }
+                PublicSignalError::Decode(_) => ProofError::DecodeError,
+            };
+            fail(err, code, e.to_string());
+            false
+        }
+    }
+}
+
+unsafe fn owned_buffer(bytes: Result<Vec<u8>, (ProofError, String)>, err: *mut i32) -> *mut Buffer {
+    match bytes {
+        Ok(bytes) => {
+            set_error(err, ProofError::Ok);
+            Box::into_raw(Box::new(Buffer::from_vec(bytes)))
+        }
+        Err((code, msg)) => {
+            fail(err, code, msg);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// # Safety
+///
+/// Use on a valid pointer returned by `public_inputs_to_json` or the `*_to_hex` functions,
+/// or panics
+#[no_mangle]
+pub unsafe extern "C" fn free_string(s: *mut c_char) {
+    if s.is_null() {
+        return;
+    }
+
+    drop(CString::from_raw(s))
+}
+
+/// # Safety
+///
+/// Use on a valid pointer to a Buffer returned by this library or panics
+#[no_mangle]
+pub unsafe extern "C" fn free_buffer(buf: *mut Buffer) {
+    if buf.is_null() {
+        return;
+    }
+
+    drop_buffer_data(&Box::from_raw(buf))
+}
+
+/// # Safety
+///
+/// Writes the timings of the last proof returned on the current thread by `prove` (or
+/// `prove_with_seed`, `prove_with_progress`, `prove_streaming`, `prove_with_witness`) to
+/// `timings`. Returns false and leaves `timings` untouched if there is none. Only available
+/// with the `metrics` feature
+#[cfg(feature = "metrics")]
+#[no_mangle]
+pub unsafe extern "C" fn last_prove_timings(timings: *mut ProveTimingsNs) -> bool {
+    match LAST_TIMINGS.with(|t| t.get()) {
+        Some(last) if !timings.is_null() => {
+            *timings = last;
+            true
+        }
+        _ => false,
+    }
+}
+
+/// # Safety
+///
+/// Use after constructing a StorageProofs object with init. Proves and verifies built-in
+/// inputs, see `StorageProofs::self_test`. Returns false on failure and sets `err`
+/// according to the failing stage, with the stage named in `last_error_message`
+#[no_mangle]
+pub unsafe extern "C" fn self_test(prover_ptr: *mut Prover, err: *mut i32) -> bool {
+    let _prover = (*prover_ptr).storage_proofs();
+    match _prover.self_test() {
+        Ok(()) => {
+            set_error(err, ProofError::Ok);
+            true
+        }
+        Err(failure) => {
+            let code = match failure.stage {
+                SelfTestStage::Witness => ProofError::WitnessError,
+                SelfTestStage::Verify => ProofError::VerifyError,
+                SelfTestStage::Artifacts | SelfTestStage::Inputs | SelfTestStage::Prove => {
+                    ProofError::ProveError
+                }
+            };
+            fail(err, code, failure.to_string());
+            false
+        }
+    }
+}
+
+/// # Safety
+///
+/// Use after constructing a StorageProofs object with init, which stays owned by the
+/// caller. Returns a pool of `size` provers sharing its circuit, to be freed with
+/// `free_prover_pool`, or null and sets `err` on failure
+#[no_mangle]
+pub unsafe extern "C" fn init_prover_pool(
+    prover_ptr: *const Prover,
+    size: usize,
+    err: *mut i32,
+) -> *mut ProverPool {
+    let _prover = (*prover_ptr).storage_proofs();
+    match ProverPool::from_prover(size, _prover.clone()) {
+        Ok(pool) => {
+            set_error(err, ProofError::Ok);
+            Box::into_raw(Box::new(pool))
+        }
+        Err(e) => {
+            fail(err, ProofError::WitnessError, e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// # Safety
+///
+/// Use on a pool returned by `init_prover_pool`. Blocks until a prover is idle, which is
+/// usable with all functions taking a StorageProofs until handed back with
+/// `prover_pool_release`
+#[no_mangle]
+pub unsafe extern "C" fn prover_pool_acquire(pool: *const ProverPool) -> *mut Prover {
+    let pool = &*pool;
+    Box::into_raw(Box::new(Prover::from(pool.acquire().into_inner())))
+}
+
+/// # Safety
+///
+/// `prover` must have been acquired from `pool` and not used after this call
+#[no_mangle]
+pub unsafe extern "C" fn prover_pool_release(pool: *const ProverPool, prover: *mut Prover) {
+    if prover.is_null() {
+        return;
+    }
+
+    let pool = &*pool;
+    pool.release(Box::from_raw(prover).into_storage_proofs())
+}
+
+/// # Safety
+///
+/// Use on a valid pointer returned by `init_prover_pool`, once all its provers are released
+#[no_mangle]
+pub unsafe extern "C" fn free_prover_pool(pool: *mut ProverPool) {
+    if pool.is_null() {
+        return;
+    }
+
+    drop(Box::from_raw(pool))
+}
+
+/// # Safety
+///
+/// Use after constructing a StorageProofs object with init. Returns a new handle sharing
+/// the loaded circuit and proving key, without reading any files, to be freed with
+/// `free_prover` independently of the original
+#[no_mangle]
+pub unsafe extern "C" fn clone_prover(prover_ptr: *const Prover) -> *mut Prover {
+    let _prover = &*prover_ptr;
+    Box::into_raw(Box::new(_prover.clone()))
+}
+
+/// # Safety
+///
+/// Use after constructing a StorageProofs object with init, and not concurrently with
+/// other calls on the same handle. Drops the idle witness calculators and scratch buffers
+/// before an unrelated job, keeping the circuit and proving key, see `StorageProofs::reset`
+#[no_mangle]
+pub unsafe extern "C" fn reset_prover(prover_ptr: *mut Prover) {
+    let _prover = (*prover_ptr).storage_proofs_mut();
+    _prover.reset()
+}
+
+/// # Safety
+///
+/// Use on a valid pointer returned by one of the init functions or panics
+#[no_mangle]
+pub unsafe extern "C" fn free_prover(prover: *mut Prover) {
+    if prover.is_null() {
+        return;
+    }
+
+    unsafe { drop(Box::from_raw(prover)) }
 }
 
 /// # Safety
 ///
-/// Use on a valid pointer to StorageProofs or panics
+/// Use on a valid pointer to ProofCtx or panics
 #[no_mangle]
-pub unsafe extern "C" fn free_prover(prover: *mut StorageProofs) {
-    if prover.is_null() {
+pub unsafe extern "C" fn free_proof_ctx(ctx: *mut ProofCtx) {
+    if ctx.is_null() {
         return;
     }
 
-    unsafe { drop(Box::from_raw(prover)) }
+    drop(OwnedProofCtx::from_raw(ctx))
 }
 
 /// # Safety
 ///
-/// Use on a valid pointer to ProofCtx or panics
+/// Use on a valid pointer returned by `prove_with_witness_out` or panics
 #[no_mangle]
-pub unsafe extern "C" fn free_proof_ctx(ctx: *mut ProofCtx) {
+pub unsafe extern "C" fn free_proof_ctx_witness(ctx: *mut ProofCtxWitness) {
     if ctx.is_null() {
         return;
     }
@@ -205,21 +2092,47 @@ pub unsafe extern "C" fn free_proof_ctx(ctx: *mut ProofCtx) {
     drop(Box::from_raw(ctx))
 }
 
+/// # Safety
+///
+/// Use on a valid pointer returned by `prove_batch`, with the same `len`, or panics
+#[no_mangle]
+pub unsafe extern "C" fn free_proof_ctx_batch(ctx: *mut ProofCtx, len: usize) {
+    if ctx.is_null() {
+        return;
+    }
+
+    drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(ctx, len)))
+}
+
 #[cfg(test)]
 mod tests {
+    use std::ffi::{c_char, c_void, CStr};
     use std::fs::File;
     use std::io::prelude::*;
 
 
     use ark_std::rand::{distributions::Alphanumeric, rngs::StdRng, Rng, SeedableRng};
-    use rs_poseidon::poseidon::hash;
     use ruint::aliases::U256;
 
     use crate::{
-        circuit_tests::utils::{digest, treehash}, storage_proofs::EXT_ID_U256_LE, ffi::prove_mpack_ext
+        api::Prover,
+        utils::{dechunkify, digest, treehash}, storage_proofs::{ProofInputs, ProofOutput, Salt},
+        mpack::EXT_ID_U256_LE,
+        ffi::prove_mpack_ext,
+        merkle::MerkleTree,
+        circuit_tests::test::{storer_inputs, storer_prover},
     };
 
-    use super::{init_storage_proofs, prove, Buffer};
+    use super::{
+        buffer_from_base64, buffer_from_hex, buffer_to_u256s, check_chunk_digest, chunkify,
+        circuit_info, clone_prover, free_buffer, free_prove_handle, free_prover, free_proof_ctx,
+        free_proof_ctx_batch, free_proof_ctx_witness, free_string, init_storage_proofs,
+        init_storage_proofs_bytes, init_verifier, last_error_message, library_version,
+        proof_ctx_public_at, proof_ctx_public_count, proof_ctx_to_hex, prove, prove_be,
+        prove_chunked, prove_join, prove_poll, prove_start, prove_streaming, public_inputs_to_hex,
+        reset_prover, supported_schemes, verify, verify_batch, verify_merkle_path, Buffer,
+        CircuitInfo, OwnedProofCtx, ProofCtx, ProofCtxWitness, ProofError, SCHEME_GROTH16_BN254,
+    };
 
     use rmpv::Value;
     use rmpv::encode::write_value;
@@ -339,26 +2252,19 @@ mod tests {
         let path = [0, 1, 2, 3];
         let path_mpk = Value::Array(path.iter().map(|i| rmpv::Value::from(*i)).collect());
 
-        let parent_hash_l = hash(&[hashes[0], hashes[1]]);
-        let parent_hash_r = hash(&[hashes[2], hashes[3]]);
-
-        let sibling_hashes = &[
-            hashes[1],
-            parent_hash_r,
-            hashes[0],
-            parent_hash_r,
-            hashes[3],
-            parent_hash_l,
-            hashes[2],
-            parent_hash_l,
-        ];
+        // per sample the sibling on the leaf's level, then the one above, see `MerklePath`
+        let tree = MerkleTree::new(&hashes, 2).unwrap();
+        let sibling_hashes = path
+            .iter()
+            .flat_map(|i| tree.proof(*i as usize).unwrap().siblings)
+            .collect::<Vec<U256>>();
 
         let siblings_mpk: Value = Value::Array(sibling_hashes
             .iter()
             .map(u256_to_mpack)
             .collect::<Vec<Value>>());
 
-        let root = treehash(hashes.as_slice());
+        let root = treehash(hashes.as_slice()).unwrap();
 
         // let root_bytes: [u8; U256::BYTES] = root.to_le_bytes();
         let root_mpk = u256_to_mpack(&root);
@@ -396,15 +2302,20 @@ mod tests {
             len: wasm_path.len(),
         };
 
-        let prover_ptr = unsafe { init_storage_proofs(r1cs, wasm, std::ptr::null()) };
+        let mut err: i32 = -1;
+        let prover_ptr = unsafe { init_storage_proofs(r1cs, wasm, std::ptr::null(), &mut err) };
+        assert_eq!(err, ProofError::Ok as i32);
+
         let prove_ctx: *mut crate::ffi::ProofCtx = unsafe {
             prove_mpack_ext(
                 prover_ptr,
                 &args_buff as *const Buffer,
+                &mut err,
             )
         };
 
         assert!(prove_ctx.is_null() == false);
+        assert_eq!(err, ProofError::Ok as i32);
     }
 
     #[test]
@@ -439,19 +2350,12 @@ mod tests {
         let hashes_slice: Vec<u8> = hashes.iter().map(|c| c.to_le_bytes_vec()).flatten().collect();
 
         let path = [0, 1, 2, 3];
-        let parent_hash_l = hash(&[hashes[0], hashes[1]]);
-        let parent_hash_r = hash(&[hashes[2], hashes[3]]);
-
-        let sibling_hashes = &[
-            hashes[1],
-            parent_hash_r,
-            hashes[0],
-            parent_hash_r,
-            hashes[3],
-            parent_hash_l,
-            hashes[2],
-            parent_hash_l,
-        ];
+        // per sample the sibling on the leaf's level, then the one above, see `MerklePath`
+        let tree = MerkleTree::new(&hashes, 2).unwrap();
+        let sibling_hashes = path
+            .iter()
+            .flat_map(|i| tree.proof(*i as usize).unwrap().siblings)
+            .collect::<Vec<U256>>();
 
         let siblings: Vec<u8> = sibling_hashes
             .iter()
@@ -459,7 +2363,7 @@ mod tests {
             .flatten()
             .collect();
 
-        let root = treehash(hashes.as_slice());
+        let root = treehash(hashes.as_slice()).unwrap();
         let chunks_buff = Buffer {
             data: chunks.as_ptr() as *const u8,
             len: chunks.len(),
@@ -494,7 +2398,10 @@ mod tests {
             len: wasm_path.len(),
         };
 
-        let prover_ptr = unsafe { init_storage_proofs(r1cs, wasm, std::ptr::null()) };
+        let mut err: i32 = -1;
+        let prover_ptr = unsafe { init_storage_proofs(r1cs, wasm, std::ptr::null(), &mut err) };
+        assert_eq!(err, ProofError::Ok as i32);
+
         let prove_ctx: *mut crate::ffi::ProofCtx = unsafe {
             prove(
                 prover_ptr,
@@ -506,9 +2413,993 @@ mod tests {
                 &root_buff as *const Buffer, // root
                 &root_buff as *const Buffer, // pubkey
                 &root_buff as *const Buffer, // salt/block hash
+                &mut err,
             )
         };
 
         assert!(prove_ctx.is_null() == false);
+        assert_eq!(err, ProofError::Ok as i32);
+
+        // the proof bytes outlive the call that produced them
+        let valid = unsafe {
+            verify(
+                prover_ptr,
+                &(*prove_ctx).proof,
+                &(*prove_ctx).public_inputs,
+                &mut err,
+            )
+        };
+        assert!(valid);
+
+        unsafe {
+            free_proof_ctx(prove_ctx);
+            free_prover(prover_ptr);
+        }
+    }
+
+    // doesn't touch the prover, so it can run under miri:
+    // cargo +nightly miri test test_proof_ctx_owns_bytes
+    #[test]
+    fn test_proof_ctx_owns_bytes() {
+        let ctx = {
+            let proof = vec![1u8, 2, 3];
+            let public_inputs = vec![4u8; 40];
+            Box::into_raw(Box::new(ProofCtx::new(proof, public_inputs)))
+        };
+
+        let (proof, public_inputs) = unsafe {
+            (
+                std::slice::from_raw_parts((*ctx).proof.data, (*ctx).proof.len).to_vec(),
+                std::slice::from_raw_parts((*ctx).public_inputs.data, (*ctx).public_inputs.len)
+                    .to_vec(),
+            )
+        };
+        assert_eq!(proof, vec![1, 2, 3]);
+        assert_eq!(public_inputs, vec![4; 40]);
+        unsafe { free_proof_ctx(ctx) };
+
+        let batch = (0..3u8)
+            .map(|i| ProofCtx::new(vec![i; 8], Vec::new()))
+            .collect::<Box<[ProofCtx]>>();
+        let batch = Box::into_raw(batch) as *mut ProofCtx;
+        let last = unsafe { &*batch.add(2) };
+        assert_eq!(unsafe { *last.proof.data.add(7) }, 2);
+        unsafe { free_proof_ctx_batch(batch, 3) };
+
+        let with_witness = Box::into_raw(Box::new(ProofCtxWitness {
+            ctx: ProofCtx::new(vec![5u8; 4], Vec::new()),
+            witness: Buffer::from_vec(vec![6u8; 64]),
+        }));
+        // usable as a plain ProofCtx
+        let ctx = with_witness as *const ProofCtx;
+        assert_eq!(unsafe { *(*ctx).proof.data.add(3) }, 5);
+        assert_eq!(unsafe { *(*with_witness).witness.data.add(63) }, 6);
+        unsafe { free_proof_ctx_witness(with_witness) };
+    }
+
+    // cargo +nightly miri test test_owned_proof_ctx, which reports leaked allocations
+    #[test]
+    fn test_owned_proof_ctx() {
+        for i in 0..100u8 {
+            let owned = OwnedProofCtx::new(vec![i; i as usize], vec![i; 40]);
+            if i % 2 == 0 {
+                drop(owned);
+                continue;
+            }
+
+            // through the FFI edge and back
+            let ctx = owned.clone().into_raw();
+            assert_eq!(unsafe { (*ctx).proof.len }, i as usize);
+            let back = unsafe { OwnedProofCtx::from_raw(ctx) };
+            assert_eq!(back, owned);
+
+            unsafe { free_proof_ctx(owned.into_raw()) };
+        }
+        unsafe { free_proof_ctx(std::ptr::null_mut()) };
+    }
+
+    #[test]
+    fn test_storer_ffi_inputs_dropped() {
+        let inputs = storer_inputs();
+        let prover_ptr = Box::into_raw(Box::new(Prover::from(storer_prover())));
+        let mut err: i32 = -1;
+
+        let prove_ctx = {
+            let u256s = |values: &[U256]| -> Vec<u8> {
+                values.iter().flat_map(|v| v.to_le_bytes_vec()).collect()
+            };
+            let chunks = u256s(&inputs.chunks);
+            let siblings = u256s(&inputs.siblings);
+            let hashes = u256s(&inputs.hashes);
+            let root = u256s(&[inputs.root.unwrap()]);
+            let salt = u256s(&[inputs.salt]);
+            let path = inputs.path.clone();
+
+            let ctx = unsafe {
+                prove(
+                    prover_ptr,
+                    &Buffer::from_slice(&chunks),
+                    &Buffer::from_slice(&siblings),
+                    &Buffer::from_slice(&hashes),
+                    path.as_ptr(),
+                    path.len(),
+                    &Buffer::from_slice(&salt), // pubkey
+                    &Buffer::from_slice(&root),
+                    &Buffer::from_slice(&salt),
+                    &mut err,
+                )
+            };
+
+            drop((chunks, siblings, hashes, root, salt, path));
+            ctx
+        };
+        assert!(!prove_ctx.is_null());
+        assert_eq!(err, ProofError::Ok as i32);
+
+        // reuse the freed memory, in case anything still pointed into it
+        let garbage = vec![0xffu8; inputs.chunks.len() * U256::BYTES];
+
+        let output = unsafe {
+            ProofOutput {
+                proof: std::slice::from_raw_parts((*prove_ctx).proof.data, (*prove_ctx).proof.len)
+                    .to_vec(),
+                public_inputs: std::slice::from_raw_parts(
+                    (*prove_ctx).public_inputs.data,
+                    (*prove_ctx).public_inputs.len,
+                )
+                .to_vec(),
+                #[cfg(feature = "metrics")]
+                timings: None,
+            }
+        };
+        assert_eq!(output.root().unwrap(), inputs.root.unwrap());
+        let valid = unsafe {
+            verify(
+                prover_ptr,
+                &(*prove_ctx).proof,
+                &(*prove_ctx).public_inputs,
+                &mut err,
+            )
+        };
+        assert!(valid);
+
+        drop(garbage);
+        unsafe {
+            free_proof_ctx(prove_ctx);
+            free_prover(prover_ptr);
+        }
+    }
+
+    type ChunkFn<'a> = &'a mut dyn FnMut(usize, &mut Buffer) -> i32;
+
+    extern "C" fn call_chunk_fn(index: usize, out: *mut Buffer, ctx: *mut c_void) -> i32 {
+        let chunk_fn = unsafe { &mut *(ctx as *mut ChunkFn) };
+        chunk_fn(index, unsafe { &mut *out })
+    }
+
+    #[test]
+    fn test_storer_ffi_streaming() {
+        let inputs = storer_inputs();
+        let chunks = inputs
+            .chunks
+            .chunks(inputs.chunks.len() / inputs.hashes.len())
+            .map(|c| c.iter().flat_map(|v| v.to_le_bytes_vec()).collect())
+            .collect::<Vec<Vec<u8>>>();
+
+        let u256s = |values: &[U256]| values.iter().flat_map(|v| v.to_le_bytes_vec()).collect();
+        let siblings: Vec<u8> = u256s(&inputs.siblings);
+        let hashes: Vec<u8> = u256s(&inputs.hashes);
+        let root: Vec<u8> = u256s(&[inputs.root.unwrap()]);
+        let salt: Vec<u8> = u256s(&[inputs.salt]);
+        let buffer = |bytes: &Vec<u8>| Buffer {
+            data: bytes.as_ptr(),
+            len: bytes.len(),
+        };
+
+        let prover_ptr = Box::into_raw(Box::new(Prover::from(storer_prover())));
+        let mut pulled = Vec::new();
+        let mut next_chunk = |index: usize, out: &mut Buffer| {
+            pulled.push(index);
+            *out = buffer(&chunks[index]);
+            0
+        };
+        let mut chunk_fn: ChunkFn = &mut next_chunk;
+
+        let mut err: i32 = -1;
+        let prove_ctx = unsafe {
+            prove_streaming(
+                prover_ptr,
+                chunks.len(),
+                call_chunk_fn,
+                &mut chunk_fn as *mut ChunkFn as *mut c_void,
+                &buffer(&siblings),
+                &buffer(&hashes),
+                inputs.path.as_ptr(),
+                inputs.path.len(),
+                &buffer(&root), // pubkey
+                &buffer(&root),
+                &buffer(&salt),
+                &mut err,
+            )
+        };
+
+        assert!(!prove_ctx.is_null());
+        assert_eq!(err, ProofError::Ok as i32);
+        assert_eq!(pulled, vec![0, 1, 2, 3]);
+        unsafe { free_proof_ctx(prove_ctx) };
+
+        // a null root is computed from the hashes and siblings
+        let mut next_chunk = |index: usize, out: &mut Buffer| {
+            *out = buffer(&chunks[index]);
+            0
+        };
+        let mut chunk_fn: ChunkFn = &mut next_chunk;
+        let prove_ctx = unsafe {
+            prove_streaming(
+                prover_ptr,
+                chunks.len(),
+                call_chunk_fn,
+                &mut chunk_fn as *mut ChunkFn as *mut c_void,
+                &buffer(&siblings),
+                &buffer(&hashes),
+                inputs.path.as_ptr(),
+                inputs.path.len(),
+                &buffer(&root),
+                std::ptr::null(),
+                &buffer(&salt),
+                &mut err,
+            )
+        };
+
+        assert!(!prove_ctx.is_null());
+        assert_eq!(err, ProofError::Ok as i32);
+        unsafe { free_proof_ctx(prove_ctx) };
+
+        let mut failing = |index: usize, _: &mut Buffer| if index == 2 { 7 } else { 0 };
+        let mut chunk_fn: ChunkFn = &mut failing;
+        let prove_ctx = unsafe {
+            prove_streaming(
+                prover_ptr,
+                chunks.len(),
+                call_chunk_fn,
+                &mut chunk_fn as *mut ChunkFn as *mut c_void,
+                &buffer(&siblings),
+                &buffer(&hashes),
+                inputs.path.as_ptr(),
+                inputs.path.len(),
+                &buffer(&root),
+                &buffer(&root),
+                &buffer(&salt),
+                &mut err,
+            )
+        };
+
+        assert!(prove_ctx.is_null());
+        assert_eq!(err, ProofError::WitnessError as i32);
+
+        unsafe { free_prover(prover_ptr) };
+    }
+
+    type SinkFn<'a> = &'a mut dyn FnMut(&[u8]) -> i32;
+
+    extern "C" fn call_sink_fn(chunk: Buffer, ctx: *mut c_void) -> i32 {
+        let sink_fn = unsafe { &mut *(ctx as *mut SinkFn) };
+        sink_fn(unsafe { std::slice::from_raw_parts(chunk.data, chunk.len) })
+    }
+
+    #[test]
+    fn test_storer_ffi_chunked() {
+        let inputs = storer_inputs();
+        let u256s = |values: &[U256]| values.iter().flat_map(|v| v.to_le_bytes_vec()).collect();
+        let chunks: Vec<u8> = u256s(&inputs.chunks);
+        let siblings: Vec<u8> = u256s(&inputs.siblings);
+        let hashes: Vec<u8> = u256s(&inputs.hashes);
+        let root: Vec<u8> = u256s(&[inputs.root.unwrap()]);
+        let salt: Vec<u8> = u256s(&[inputs.salt]);
+        let buffer = |bytes: &Vec<u8>| Buffer {
+            data: bytes.as_ptr(),
+            len: bytes.len(),
+        };
+
+        let prover = storer_prover();
+        let prover_ptr = Box::into_raw(Box::new(Prover::from(prover.clone())));
+        let prove_to = |chunk_size: usize, sink_fn: &mut SinkFn, err: &mut i32| unsafe {
+            prove_chunked(
+                prover_ptr,
+                &buffer(&chunks),
+                &buffer(&siblings),
+                &buffer(&hashes),
+                inputs.path.as_ptr(),
+                inputs.path.len(),
+                &buffer(&root), // pubkey
+                &buffer(&root),
+                &buffer(&salt),
+                chunk_size,
+                call_sink_fn,
+                sink_fn as *mut SinkFn as *mut c_void,
+                err,
+            )
+        };
+
+        let mut pieces = Vec::new();
+        let mut collect = |chunk: &[u8]| {
+            pieces.push(chunk.to_vec());
+            0
+        };
+        let mut err: i32 = -1;
+        assert!(prove_to(16, &mut (&mut collect as SinkFn), &mut err));
+        assert_eq!(err, ProofError::Ok as i32);
+
+        assert!(pieces.len() > 1);
+        assert!(pieces.iter().all(|p| p.len() <= 16));
+        let reassembled = pieces.concat();
+        assert!(prover.verify_serialized(&reassembled).is_ok());
+
+        // a sink that gives up after the first piece isn't passed any more of them
+        let mut calls = 0;
+        let mut failing = |_: &[u8]| {
+            calls += 1;
+            5
+        };
+        assert!(!prove_to(16, &mut (&mut failing as SinkFn), &mut err));
+        assert_eq!(err, ProofError::Cancelled as i32);
+        assert_eq!(calls, 1);
+
+        unsafe { free_prover(prover_ptr) };
+    }
+
+    #[test]
+    fn test_hex_base64_roundtrip() {
+        let output = storer_prover().prove_inputs(&storer_inputs()).unwrap();
+        let ctx = ProofCtx::new(output.proof.clone(), output.public_inputs.clone());
+
+        let text = |s: *mut c_char| {
+            let owned = unsafe { CStr::from_ptr(s) }.to_str().unwrap().to_string();
+            unsafe { free_string(s) };
+            owned
+        };
+        let decode = |f: unsafe extern "C" fn(*const Buffer, *mut i32) -> *mut Buffer,
+                      encoded: &str| {
+            let mut err: i32 = -1;
+            let encoded = Buffer {
+                data: encoded.as_ptr(),
+                len: encoded.len(),
+            };
+            let buf = unsafe { f(&encoded, &mut err) };
+            if buf.is_null() {
+                return Err(err);
+            }
+
+            let bytes = unsafe { std::slice::from_raw_parts((*buf).data, (*buf).len) }.to_vec();
+            unsafe { free_buffer(buf) };
+            Ok(bytes)
+        };
+
+        let proof_hex = text(unsafe { proof_ctx_to_hex(&ctx) });
+        assert_eq!(decode(buffer_from_hex, &proof_hex).unwrap(), output.proof);
+        assert_eq!(
+            decode(buffer_from_hex, &format!("0x{}", proof_hex)).unwrap(),
+            output.proof
+        );
+
+        let inputs_hex = text(unsafe { public_inputs_to_hex(&ctx.public_inputs) });
+        assert_eq!(decode(buffer_from_hex, &inputs_hex).unwrap(), output.public_inputs);
+
+        assert_eq!(decode(buffer_from_base64, "AAEC/w==").unwrap(), vec![0, 1, 2, 255]);
+        assert_eq!(
+            decode(buffer_from_hex, "0xabc"),
+            Err(ProofError::DecodeError as i32)
+        );
+        assert_eq!(
+            decode(buffer_from_base64, "not base64!"),
+            Err(ProofError::DecodeError as i32)
+        );
+    }
+
+    #[test]
+    fn test_buffer_to_u256s() {
+        let bytes: Vec<u8> = (0..U256::BYTES * 2).map(|i| i as u8).collect();
+
+        let aligned = Buffer {
+            data: bytes.as_ptr(),
+            len: bytes.len(),
+        };
+        let values = unsafe { buffer_to_u256s(&aligned, "chunks") }.unwrap();
+        assert_eq!(
+            values,
+            vec![
+                U256::from_le_slice(&bytes[..U256::BYTES]),
+                U256::from_le_slice(&bytes[U256::BYTES..])
+            ]
+        );
+
+        let short = Buffer {
+            data: bytes.as_ptr(),
+            len: bytes.len() - 1,
+        };
+        let (code, msg) = unsafe { buffer_to_u256s(&short, "chunks") }.unwrap_err();
+        assert_eq!(code, ProofError::BadBufferLength);
+        assert!(msg.contains("chunks: length 63"));
+        assert!(msg.contains("expected 32 or 64 bytes"));
+    }
+
+    #[test]
+    fn test_storer_ffi_circuit_info() {
+        let prover = Prover::from(storer_prover());
+        let mut info = CircuitInfo::default();
+
+        assert!(unsafe { circuit_info(&prover, &mut info) });
+        assert_eq!(info, prover.storage_proofs().circuit_info());
+        assert!(!unsafe { circuit_info(&prover, std::ptr::null_mut()) });
+    }
+
+    #[test]
+    fn test_storer_ffi_clone_prover() {
+        let prover_ptr = Box::into_raw(Box::new(Prover::from(storer_prover())));
+        let clone_ptr = unsafe { clone_prover(prover_ptr) };
+        unsafe { free_prover(prover_ptr) };
+
+        // the clone outlives the original
+        let clone = unsafe { &*clone_ptr };
+        let output = clone.prove(&storer_inputs()).unwrap();
+        clone
+            .verify(output.proof.as_slice(), output.public_inputs.as_slice())
+            .unwrap();
+        unsafe { free_prover(clone_ptr) };
+    }
+
+    #[test]
+    fn test_storer_ffi_reset_prover() {
+        let prover_ptr = Box::into_raw(Box::new(Prover::from(storer_prover())));
+        let prover = unsafe { (*prover_ptr).storage_proofs() };
+        let first = prover.prove_inputs(&storer_inputs()).unwrap();
+
+        unsafe { reset_prover(prover_ptr) };
+        let prover = unsafe { (*prover_ptr).storage_proofs() };
+        let second = prover.prove_inputs(&storer_inputs()).unwrap();
+        for output in [&first, &second] {
+            prover
+                .verify(output.proof.as_slice(), output.public_inputs.as_slice())
+                .unwrap();
+        }
+        unsafe { free_prover(prover_ptr) };
+    }
+
+    #[test]
+    fn test_storer_ffi_check_chunk_digest() {
+        let prover = storer_prover();
+        let inputs = storer_inputs();
+        let claimed = inputs.hashes[0].to_le_bytes_vec();
+        let check = |chunk: &[U256], err: &mut i32| {
+            let chunk = chunk
+                .iter()
+                .flat_map(|c| c.to_le_bytes_vec())
+                .collect::<Vec<u8>>();
+            unsafe {
+                check_chunk_digest(
+                    &prover,
+                    &Buffer::from_slice(&chunk),
+                    &Buffer::from_slice(&claimed),
+                    err,
+                )
+            }
+        };
+
+        let mut err: i32 = -1;
+        let mut chunk = inputs.chunks[..256].to_vec();
+        assert!(check(&chunk, &mut err));
+        assert_eq!(err, ProofError::Ok as i32);
+
+        chunk[0] += U256::from(1);
+        assert!(!check(&chunk, &mut err));
+        assert_eq!(err, ProofError::Ok as i32);
+    }
+
+    #[test]
+    fn test_storer_ffi_chunkify() {
+        let prover = Prover::from(storer_prover());
+        let data = (0..10000).map(|i| (i % 251) as u8).collect::<Vec<u8>>();
+
+        let mut err: i32 = -1;
+        let buf = unsafe { chunkify(&prover, &Buffer::from_slice(&data), &mut err) };
+        assert_eq!(err, ProofError::Ok as i32);
+        let chunks = unsafe { std::slice::from_raw_parts((*buf).data, (*buf).len) }
+            .chunks(256 * U256::BYTES)
+            .map(|c| c.chunks(U256::BYTES).map(U256::from_le_slice).collect())
+            .collect::<Vec<Vec<U256>>>();
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(dechunkify(&chunks, data.len()).unwrap(), data);
+        unsafe { free_buffer(buf) };
+    }
+
+    #[test]
+    fn test_verify_merkle_path() {
+        let leaves = (0..4).map(U256::from).collect::<Vec<U256>>();
+        let tree = MerkleTree::new(&leaves, 2).unwrap();
+        let root = tree.root().to_le_bytes_vec();
+        let leaf = leaves[2].to_le_bytes_vec();
+        let verify_path = |siblings: &[U256], err: &mut i32| {
+            let siblings = siblings
+                .iter()
+                .flat_map(|s| s.to_le_bytes_vec())
+                .collect::<Vec<u8>>();
+            unsafe {
+                verify_merkle_path(
+                    &Buffer::from_slice(&leaf),
+                    &Buffer::from_slice(&root),
+                    2,
+                    &Buffer::from_slice(&siblings),
+                    2,
+                    err,
+                )
+            }
+        };
+
+        let mut err: i32 = -1;
+        let mut siblings = tree.proof(2).unwrap().siblings;
+        assert!(verify_path(&siblings, &mut err));
+        assert_eq!(err, ProofError::Ok as i32);
+
+        siblings[1] += U256::from(1);
+        assert!(!verify_path(&siblings, &mut err));
+        assert_eq!(err, ProofError::Ok as i32);
+
+        let mut err: i32 = -1;
+        let valid = unsafe {
+            verify_merkle_path(
+                &Buffer::from_slice(&leaf),
+                &Buffer::from_slice(&root),
+                2,
+                &Buffer::from_slice(&[]),
+                1,
+                &mut err,
+            )
+        };
+        assert!(!valid);
+        assert_eq!(err, ProofError::DecodeError as i32);
+    }
+
+    #[test]
+    fn test_storer_ffi_truncated_buffer() {
+        let chunks: Vec<u8> = vec![0; U256::BYTES * 4 - 1];
+        let siblings: Vec<u8> = vec![0; U256::BYTES * 8];
+        let hashes: Vec<u8> = vec![0; U256::BYTES * 4];
+        let root: Vec<u8> = vec![0; U256::BYTES];
+        let path = [0, 1, 2, 3];
+
+        let chunks_buff = Buffer {
+            data: chunks.as_ptr(),
+            len: chunks.len(),
+        };
+
+        let siblings_buff = Buffer {
+            data: siblings.as_ptr(),
+            len: siblings.len(),
+        };
+
+        let hashes_buff = Buffer {
+            data: hashes.as_ptr(),
+            len: hashes.len(),
+        };
+
+        let root_buff = Buffer {
+            data: root.as_ptr(),
+            len: root.len(),
+        };
+
+        let r1cs_path = "src/circuit_tests/artifacts/storer-test.r1cs";
+        let wasm_path = "src/circuit_tests/artifacts/storer-test_js/storer-test.wasm";
+
+        let r1cs = Buffer {
+            data: r1cs_path.as_ptr(),
+            len: r1cs_path.len(),
+        };
+
+        let wasm = Buffer {
+            data: wasm_path.as_ptr(),
+            len: wasm_path.len(),
+        };
+
+        let mut err: i32 = -1;
+        let prover_ptr = unsafe { init_storage_proofs(r1cs, wasm, std::ptr::null(), &mut err) };
+        assert_eq!(err, ProofError::Ok as i32);
+
+        let prove_ctx = unsafe {
+            prove(
+                prover_ptr,
+                &chunks_buff as *const Buffer,
+                &siblings_buff as *const Buffer,
+                &hashes_buff as *const Buffer,
+                &path as *const i32,
+                path.len(),
+                &root_buff as *const Buffer,
+                &root_buff as *const Buffer,
+                &root_buff as *const Buffer,
+                &mut err,
+            )
+        };
+
+        assert!(prove_ctx.is_null());
+        assert_eq!(err, ProofError::BadBufferLength as i32);
+    }
+
+    /// The storer test inputs, with their chunks, siblings, hashes and salt as the little
+    /// endian buffers the ffi prove functions take
+    struct FfiProveFixture {
+        inputs: ProofInputs,
+        chunks: Vec<u8>,
+        siblings: Vec<u8>,
+        hashes: Vec<u8>,
+        salt: Vec<u8>,
+    }
+
+    impl FfiProveFixture {
+        /// ffi `prove` of the fixture along `path`, with the salt as the public key too
+        fn prove(&self, prover_ptr: *mut Prover, path: &[i32], err: &mut i32) -> *mut ProofCtx {
+            unsafe {
+                prove(
+                    prover_ptr,
+                    &Buffer::from_slice(&self.chunks),
+                    &Buffer::from_slice(&self.siblings),
+                    &Buffer::from_slice(&self.hashes),
+                    path.as_ptr(),
+                    path.len(),
+                    &Buffer::from_slice(&self.salt),
+                    std::ptr::null(),
+                    &Buffer::from_slice(&self.salt),
+                    err,
+                )
+            }
+        }
+    }
+
+    fn ffi_prove_fixture() -> FfiProveFixture {
+        let inputs = storer_inputs();
+        let bytes = |ns: &[U256]| {
+            ns.iter()
+                .flat_map(|n| n.to_le_bytes::<32>())
+                .collect::<Vec<u8>>()
+        };
+
+        FfiProveFixture {
+            chunks: bytes(&inputs.chunks),
+            siblings: bytes(&inputs.siblings),
+            hashes: bytes(&inputs.hashes),
+            salt: bytes(&[inputs.salt]),
+            inputs,
+        }
+    }
+
+    #[test]
+    fn test_storer_ffi_invalid_path() {
+        let prover_ptr = Box::into_raw(Box::new(Prover::from(storer_prover())));
+        let fixture = ffi_prove_fixture();
+
+        let prove_path = |path: &[i32]| {
+            let mut err: i32 = -1;
+            let ctx = fixture.prove(prover_ptr, path, &mut err);
+            assert!(ctx.is_null());
+            assert_eq!(err, ProofError::InvalidPath as i32);
+            unsafe { CStr::from_ptr(last_error_message()) }
+                .to_str()
+                .unwrap()
+                .to_string()
+        };
+
+        assert_eq!(prove_path(&[0, -1, 2, 3]), "path index -1 at position 1 is negative");
+        assert_eq!(
+            prove_path(&[0, 1, 4, 3]),
+            "path index 4 at position 2 is out of range for a tree of 4 leaves"
+        );
+        assert_eq!(prove_path(&[0, 1, 2]), "expected a path of 4 indices, got 3");
+        unsafe { free_prover(prover_ptr) };
+    }
+
+    #[test]
+    fn test_storer_ffi_prove_be() {
+        let prover = storer_prover();
+        let fixture = ffi_prove_fixture();
+        let inputs = &fixture.inputs;
+        let output = prover.prove_inputs(inputs).unwrap();
+        let prover_ptr = Box::into_raw(Box::new(Prover::from(prover)));
+        // a hash as most hash functions output it, most significant byte first
+        let salt_hash = inputs.salt.to_be_bytes::<32>();
+        let pubkey_hash = inputs.pubkey.to_be_bytes::<32>();
+        assert_eq!(Salt::from_be_hash(salt_hash), Salt(inputs.salt));
+
+        let mut err: i32 = -1;
+        let ctx = unsafe {
+            prove_be(
+                prover_ptr,
+                &Buffer::from_slice(&fixture.chunks),
+                &Buffer::from_slice(&fixture.siblings),
+                &Buffer::from_slice(&fixture.hashes),
+                inputs.path.as_ptr(),
+                inputs.path.len(),
+                &Buffer::from_slice(&pubkey_hash),
+                std::ptr::null(),
+                &Buffer::from_slice(&salt_hash),
+                &mut err,
+            )
+        };
+        assert_eq!(err, ProofError::Ok as i32);
+        let public_inputs = unsafe {
+            std::slice::from_raw_parts((*ctx).public_inputs.data, (*ctx).public_inputs.len)
+        };
+        // the same public inputs as proving the little endian salt
+        assert_eq!(public_inputs, output.public_inputs.as_slice());
+        let salt = unsafe { (*prover_ptr).storage_proofs() }
+            .get_public_signal(public_inputs, "salt")
+            .unwrap();
+        assert_eq!(salt, inputs.salt);
+        unsafe { free_proof_ctx(ctx) };
+        unsafe { free_prover(prover_ptr) };
+    }
+
+    #[test]
+    fn test_storer_ffi_proof_ctx_public_at() {
+        let prover_ptr = Box::into_raw(Box::new(Prover::from(storer_prover())));
+        let fixture = ffi_prove_fixture();
+        let inputs = &fixture.inputs;
+
+        let mut err: i32 = -1;
+        let ctx = fixture.prove(prover_ptr, &inputs.path, &mut err);
+        assert_eq!(err, ProofError::Ok as i32);
+
+        let count = unsafe { proof_ctx_public_count(ctx) };
+        assert_eq!(count, 2);
+        let public = (0..count)
+            .map(|i| {
+                let mut out = [0u8; 32];
+                assert!(unsafe { proof_ctx_public_at(ctx, i, out.as_mut_ptr()) });
+                U256::from_le_bytes(out)
+            })
+            .collect::<Vec<U256>>();
+        assert_eq!(public, [inputs.resolve_root().unwrap(), inputs.salt]);
+
+        let mut out = [7u8; 32];
+        assert!(!unsafe { proof_ctx_public_at(ctx, count, out.as_mut_ptr()) });
+        assert_eq!(out, [7u8; 32]);
+        assert!(!unsafe { proof_ctx_public_at(ctx, 0, std::ptr::null_mut()) });
+        assert_eq!(unsafe { proof_ctx_public_count(std::ptr::null()) }, 0);
+        unsafe { free_proof_ctx(ctx) };
+        unsafe { free_prover(prover_ptr) };
+    }
+
+    #[test]
+    fn test_storer_ffi_prove_async() {
+        let prover_ptr = Box::into_raw(Box::new(Prover::from(storer_prover())));
+        let fixture = ffi_prove_fixture();
+
+        let start = || {
+            let mut err: i32 = -1;
+            let handle = unsafe {
+                prove_start(
+                    prover_ptr,
+                    &Buffer::from_slice(&fixture.chunks),
+                    &Buffer::from_slice(&fixture.siblings),
+                    &Buffer::from_slice(&fixture.hashes),
+                    fixture.inputs.path.as_ptr(),
+                    fixture.inputs.path.len(),
+                    &Buffer::from_slice(&fixture.salt),
+                    std::ptr::null(),
+                    &Buffer::from_slice(&fixture.salt),
+                    &mut err,
+                )
+            };
+            assert_eq!(err, ProofError::Ok as i32);
+            assert!(!handle.is_null());
+            handle
+        };
+
+        let handle = start();
+        while !unsafe { prove_poll(handle) } {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        let mut err: i32 = -1;
+        let ctx = unsafe { prove_join(handle, &mut err) };
+        assert_eq!(err, ProofError::Ok as i32);
+        let valid =
+            unsafe { verify(prover_ptr, &(*ctx).proof, &(*ctx).public_inputs, &mut err) };
+        assert!(valid);
+        unsafe { free_proof_ctx(ctx) };
+
+        // freeing a running handle cancels it
+        unsafe { free_prove_handle(start()) };
+        unsafe { free_prove_handle(std::ptr::null_mut()) };
+        unsafe { free_prover(prover_ptr) };
+    }
+
+    #[test]
+    fn test_storer_ffi_init_verifier() {
+        let prover = storer_prover();
+        let fixture = ffi_prove_fixture();
+        let output = prover.prove_inputs(&fixture.inputs).unwrap();
+        let vk = prover.verification_key().unwrap();
+
+        let mut err: i32 = -1;
+        let verifier = unsafe { init_verifier(Buffer::from_slice(&vk), &mut err) };
+        assert_eq!(err, ProofError::Ok as i32);
+        let (proof, public_inputs) = (
+            Buffer::from_slice(&output.proof),
+            Buffer::from_slice(&output.public_inputs),
+        );
+        assert!(unsafe { verify(verifier, &proof, &public_inputs, &mut err) });
+
+        let ctx = fixture.prove(verifier, &fixture.inputs.path, &mut err);
+        assert!(ctx.is_null());
+        assert_eq!(err, ProofError::NotAProver as i32);
+        unsafe { free_prover(verifier) };
+
+        let bad = unsafe { init_verifier(Buffer::from_slice(&vk[1..]), &mut err) };
+        assert!(bad.is_null());
+        assert_eq!(err, ProofError::KeyError as i32);
+    }
+
+    #[test]
+    fn test_storer_ffi_verify_bad_lengths() {
+        let prover_ptr = Box::into_raw(Box::new(Prover::from(storer_prover())));
+        let output = unsafe { &*prover_ptr }.prove(&storer_inputs()).unwrap();
+        assert_eq!(output.proof.len(), crate::verify::PROOF_LEN);
+
+        let proof = Buffer::from_slice(&output.proof);
+        let public_inputs = Buffer::from_slice(&output.public_inputs);
+        let empty = Buffer {
+            data: std::ptr::null(),
+            len: 0,
+        };
+        let truncated = Buffer::from_slice(&output.proof[..output.proof.len() - 1]);
+        let mut err: i32 = -1;
+
+        for bad in [&empty, &truncated] {
+            err = -1;
+            assert!(!unsafe { verify(prover_ptr, bad, &public_inputs, &mut err) });
+            assert_eq!(err, ProofError::VerifyError as i32);
+            let msg = unsafe { CStr::from_ptr(last_error_message()) }.to_str().unwrap();
+            assert!(msg.starts_with("expected a 128 byte proof"), "{}", msg);
+        }
+
+        let short = Buffer::from_slice(&output.public_inputs[..output.public_inputs.len() - 1]);
+        assert!(!unsafe { verify(prover_ptr, &proof, &short, &mut err) });
+        assert_eq!(err, ProofError::VerifyError as i32);
+        assert!(!unsafe { verify(prover_ptr, &proof, &empty, &mut err) });
+        assert_eq!(err, ProofError::VerifyError as i32);
+
+        assert!(unsafe { verify(prover_ptr, &proof, &public_inputs, &mut err) });
+        assert_eq!(err, ProofError::Ok as i32);
+
+        let proofs = [truncated, proof];
+        let inputs = [Buffer::from_slice(&output.public_inputs), public_inputs];
+        let mut results = [true, false];
+        err = -1;
+        let all = unsafe {
+            verify_batch(
+                prover_ptr,
+                proofs.as_ptr(),
+                inputs.as_ptr(),
+                2,
+                results.as_mut_ptr(),
+                &mut err,
+            )
+        };
+        assert!(!all);
+        assert_eq!(results, [false, true]);
+        assert_eq!(err, ProofError::Ok as i32);
+
+        // bad arguments rather than invalid proofs
+        let null = std::ptr::null();
+        let all = unsafe {
+            verify_batch(prover_ptr, proofs.as_ptr(), null, 2, results.as_mut_ptr(), &mut err)
+        };
+        assert!(!all);
+        assert_eq!(err, ProofError::BadBufferLength as i32);
+        let msg = unsafe { CStr::from_ptr(last_error_message()) }.to_str().unwrap();
+        assert!(msg.starts_with("public_inputs:"), "{}", msg);
+        let all = unsafe {
+            verify_batch(
+                prover_ptr,
+                proofs.as_ptr(),
+                inputs.as_ptr(),
+                2,
+                std::ptr::null_mut(),
+                &mut err,
+            )
+        };
+        assert!(!all);
+        assert_eq!(err, ProofError::BadBufferLength as i32);
+
+        unsafe { free_prover(prover_ptr) };
+    }
+
+    #[test]
+    fn test_last_error_missing_wasm() {
+        let r1cs_path = "src/circuit_tests/artifacts/storer-test.r1cs";
+        let wasm_path = "src/circuit_tests/artifacts/missing.wasm";
+
+        let r1cs = Buffer {
+            data: r1cs_path.as_ptr(),
+            len: r1cs_path.len(),
+        };
+
+        let wasm = Buffer {
+            data: wasm_path.as_ptr(),
+            len: wasm_path.len(),
+        };
+
+        let mut err: i32 = -1;
+        let prover_ptr = unsafe { init_storage_proofs(r1cs, wasm, std::ptr::null(), &mut err) };
+        assert!(prover_ptr.is_null());
+        assert_eq!(err, ProofError::CircuitLoadError as i32);
+
+        let msg = unsafe { std::ffi::CStr::from_ptr(last_error_message()) };
+        assert!(msg.to_str().unwrap().contains(wasm_path));
+    }
+
+    #[test]
+    fn test_init_invalid_buffers() {
+        let last_error = || unsafe {
+            std::ffi::CStr::from_ptr(last_error_message())
+                .to_str()
+                .unwrap()
+                .to_string()
+        };
+        let wasm_path = "src/circuit_tests/artifacts/storer-test_js/storer-test.wasm";
+
+        // a path that isn't valid UTF-8
+        let r1cs_path = [0x73, 0x72, 0xff, 0xfe];
+        let mut err: i32 = -1;
+        let prover_ptr = unsafe {
+            init_storage_proofs(
+                Buffer::from_slice(&r1cs_path),
+                Buffer::from_slice(wasm_path.as_bytes()),
+                std::ptr::null(),
+                &mut err,
+            )
+        };
+        assert!(prover_ptr.is_null());
+        assert_eq!(err, ProofError::InvalidUtf8 as i32);
+        assert!(last_error().starts_with("r1cs: "), "{}", last_error());
+
+        // a null data pointer with a nonzero length
+        let null = Buffer {
+            data: std::ptr::null(),
+            len: 16,
+        };
+        let mut err: i32 = -1;
+        let prover_ptr = unsafe {
+            init_storage_proofs_bytes(null, Buffer::from_slice(b"wasm"), std::ptr::null(), &mut err)
+        };
+        assert!(prover_ptr.is_null());
+        assert_eq!(err, ProofError::BadBufferLength as i32);
+        assert_eq!(last_error(), "r1cs: null data with length 16");
+
+        // a length that can't be a real allocation is rejected before it's dereferenced
+        let oversized = Buffer {
+            data: wasm_path.as_ptr(),
+            len: usize::MAX,
+        };
+        let mut err: i32 = -1;
+        let prover_ptr = unsafe {
+            init_storage_proofs(
+                Buffer::from_slice(b"r1cs"),
+                oversized,
+                std::ptr::null(),
+                &mut err,
+            )
+        };
+        assert!(prover_ptr.is_null());
+        assert_eq!(err, ProofError::BadBufferLength as i32);
+        assert!(last_error().starts_with("wasm: length of"), "{}", last_error());
+    }
+
+    #[test]
+    fn test_library_version() {
+        let version = library_version();
+        let bytes = unsafe { std::slice::from_raw_parts(version.data, version.len) };
+        assert_eq!(std::str::from_utf8(bytes).unwrap(), env!("CARGO_PKG_VERSION"));
+        let c_str = unsafe { CStr::from_ptr(version.data as *const c_char) };
+        assert_eq!(c_str.to_bytes(), bytes);
+
+        assert_ne!(supported_schemes() & SCHEME_GROTH16_BN254, 0);
     }
 }
@@ -0,0 +1,112 @@
+//! Hash functions for the Merkle tree and chunk digests. The storer circuit uses Poseidon.
+//! `Keccak256Reduced` is Keccak-256 taken modulo the BN254 scalar field, for trees built
+//! outside of this crate with a circuit doing the same reduction; none of the circuits
+//! here hash with it.
+
+use ruint::aliases::U256;
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::storage_proofs::FIELD_MODULUS;
+use crate::utils::poseidon_hash;
+
+/// Compression of one or more field elements into a single one
+pub trait HashFn {
+    fn hash(&self, inputs: &[U256]) -> Result<U256, String>;
+}
+
+/// circomlib's `Poseidon(n)`, see `poseidon_hash`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Poseidon;
+
+/// Keccak-256 of the inputs as 32 byte big endian words, like Solidity's
+/// `keccak256(abi.encodePacked(..))`, reduced modulo the BN254 scalar field so the result
+/// can be used as a circuit input again. This is its own hash: most Keccak-256 outputs are
+/// above the modulus, so it only agrees with plain Keccak-256 on the few that aren't.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Keccak256Reduced;
+
+/// Runtime choice between the hash functions, as selected in `CircuitParams`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HashKind {
+    #[default]
+    Poseidon,
+    Keccak256Reduced,
+}
+
+impl HashFn for Poseidon {
+    fn hash(&self, inputs: &[U256]) -> Result<U256, String> {
+        poseidon_hash(inputs)
+    }
+}
+
+impl HashFn for Keccak256Reduced {
+    fn hash(&self, inputs: &[U256]) -> Result<U256, String> {
+        if inputs.is_empty() {
+            return Err("keccak256 takes at least 1 input".to_string());
+        }
+
+        let mut keccak = Keccak::v256();
+        inputs
+            .iter()
+            .for_each(|n| keccak.update(&n.to_be_bytes::<{ U256::BYTES }>()));
+        let mut out = [0u8; 32];
+        keccak.finalize(&mut out);
+
+        Ok(U256::from_be_bytes(out).reduce_mod(FIELD_MODULUS))
+    }
+}
+
+impl HashFn for HashKind {
+    fn hash(&self, inputs: &[U256]) -> Result<U256, String> {
+        match self {
+            HashKind::Poseidon => Poseidon.hash(inputs),
+            HashKind::Keccak256Reduced => Keccak256Reduced.hash(inputs),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ruint::aliases::U256;
+
+    use super::{HashFn, HashKind, Keccak256Reduced, Poseidon};
+    use crate::storage_proofs::FIELD_MODULUS;
+    use crate::utils::poseidon_hash;
+
+    #[test]
+    fn test_hash_kinds() {
+        let inputs = [U256::from(1), U256::from(2)];
+
+        assert_eq!(Poseidon.hash(&inputs), poseidon_hash(&inputs));
+        assert_eq!(HashKind::Poseidon.hash(&inputs), Poseidon.hash(&inputs));
+        assert_eq!(HashKind::Keccak256Reduced.hash(&inputs), Keccak256Reduced.hash(&inputs));
+        assert_ne!(Keccak256Reduced.hash(&inputs), Poseidon.hash(&inputs));
+        assert!(Keccak256Reduced.hash(&inputs).unwrap() < FIELD_MODULUS);
+        assert!(Keccak256Reduced.hash(&[]).is_err());
+
+        // keccak256(abi.encodePacked(uint256(0))), which is below the modulus
+        assert_eq!(
+            Keccak256Reduced.hash(&[U256::ZERO]).unwrap(),
+            U256::from_str_radix(
+                "290decd9548b62a8d60345a988386fc84ba6bc95484008f6362f93160ef3e563",
+                16
+            )
+            .unwrap()
+        );
+        // keccak256(abi.encodePacked(uint256(1))) is above the modulus, so it's reduced
+        let keccak = U256::from_str_radix(
+            "b10e2d527612073b26eecdfd717e6a320cf44b4afac2b0732d9fcbe2b7fa0cf6",
+            16,
+        )
+        .unwrap();
+        assert!(keccak >= FIELD_MODULUS);
+        assert_eq!(
+            Keccak256Reduced.hash(&[U256::from(1)]).unwrap(),
+            U256::from_str_radix(
+                "1fe141f9d27d26bdfdfdfcd9ecfa611a945892718d965ebf61f9eb26e7fa0cf3",
+                16
+            )
+            .unwrap()
+        );
+    }
+}
@@ -1,3 +1,30 @@
+//! Storage proofs over the circom storer circuit. Everything but `verify` needs the `std`
+//! feature, which is on by default.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
+pub mod api;
+#[cfg(feature = "std")]
 pub mod ffi;
+#[cfg(feature = "std")]
+pub mod hash;
+#[cfg(feature = "std")]
+pub mod merkle;
+#[cfg(feature = "std")]
+pub mod mpack;
+#[cfg(feature = "native_witness")]
+mod native_witness;
+#[cfg(feature = "std")]
+pub mod pool;
+#[cfg(feature = "std")]
 pub mod storage_proofs;
+#[cfg(feature = "std")]
+pub mod testvectors;
+#[cfg(feature = "std")]
+pub mod utils;
+pub mod verify;
+#[cfg(feature = "std")]
 mod circuit_tests;
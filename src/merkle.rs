@@ -0,0 +1,948 @@
+use std::collections::HashMap;
+
+use ruint::aliases::U256;
+
+use crate::hash::{HashFn, Poseidon};
+use crate::mpack::{encode_u256_le, encode_value};
+use crate::utils::POSEIDON_MAX_INPUTS;
+
+/// Value used to pad the leaves up to a full tree
+pub const PADDING_LEAF: U256 = U256::ZERO;
+
+/// Merkle tree of arbitrary arity, hashed with Poseidon unless built `with_hash`.
+///
+/// Leaf counts that aren't a power of the arity are padded with `PADDING_LEAF`
+/// (zero) up to the next full level, or the depth given to `with_depth`, rather
+/// than duplicating the last leaf, so the tree shape only depends on the number of
+/// leaves.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerkleTree<H = Poseidon> {
+    hash: H,
+    arity: usize,
+    /// levels[0] are the (padded) leaves, the last level is the root
+    levels: Vec<Vec<U256>>,
+}
+
+/// Authentication path of a single leaf, as expected by the storer circuit.
+///
+/// The circuit takes the siblings in a fixed order, and any other gives a root that
+/// doesn't match rather than an error: level by level from the leaf's up to the root's
+/// children, and within a level in the order of their positions, the node's own left
+/// out. For the leaf at index 0 of a binary tree that's the leaf at index 1, then the
+/// hash of leaves 2 and 3, and so on. `validate_ordering` tells a path in another order
+/// apart before proving with it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerklePath {
+    /// index of the leaf in the tree
+    pub index: usize,
+    /// `arity - 1` sibling hashes per level, starting at the leaves
+    pub siblings: Vec<U256>,
+    /// position of the node among its siblings on each level
+    pub path: Vec<usize>,
+}
+
+impl MerkleTree {
+    pub fn new(leaves: &[U256], arity: usize) -> Result<Self, String> {
+        Self::with_hash(leaves, arity, Poseidon)
+    }
+
+    /// Same as `new`, padded with `PADDING_LEAF` up to a full tree of `depth` levels, as
+    /// circuits of a fixed tree depth expect. More than `arity^depth` leaves is an error.
+    pub fn with_depth(leaves: &[U256], arity: usize, depth: usize) -> Result<Self, String> {
+        Self::with_hash_and_depth(leaves, arity, depth, Poseidon)
+    }
+}
+
+impl<H: HashFn> MerkleTree<H> {
+    /// Same as `new`, hashing with `hash`. Poseidon limits the arity to
+    /// `POSEIDON_MAX_INPUTS`, which is enforced for every hash function.
+    pub fn with_hash(leaves: &[U256], arity: usize, hash: H) -> Result<Self, String> {
+        check_arity(arity)?;
+
+        let mut width = 1;
+        while width < leaves.len() {
+            width *= arity;
+        }
+
+        Self::padded(leaves, arity, width, hash)
+    }
+
+    /// Same as `with_depth`, hashing with `hash`
+    pub fn with_hash_and_depth(
+        leaves: &[U256],
+        arity: usize,
+        depth: usize,
+        hash: H,
+    ) -> Result<Self, String> {
+        check_arity(arity)?;
+
+        let width = arity
+            .checked_pow(depth as u32)
+            .ok_or_else(|| format!("a tree of arity {} and depth {} is too large", arity, depth))?;
+        if leaves.len() > width {
+            return Err(format!(
+                "{} leaves don't fit in a tree of depth {}",
+                leaves.len(),
+                depth
+            ));
+        }
+
+        Self::padded(leaves, arity, width, hash)
+    }
+
+    /// The tree of `leaves` padded to `width`, a power of `arity`
+    fn padded(leaves: &[U256], arity: usize, width: usize, hash: H) -> Result<Self, String> {
+        if leaves.is_empty() {
+            return Err("can't build a tree without leaves".to_string());
+        }
+
+        let mut level = leaves.to_vec();
+        level.resize(width, PADDING_LEAF);
+
+        let mut levels = vec![level];
+        while levels[levels.len() - 1].len() > 1 {
+            let next = levels[levels.len() - 1]
+                .chunks(arity)
+                .map(|children| hash.hash(children))
+                .collect::<Result<Vec<U256>, String>>()?;
+            levels.push(next);
+        }
+
+        Ok(Self {
+            hash,
+            arity,
+            levels,
+        })
+    }
+
+    pub fn hash_fn(&self) -> &H {
+        &self.hash
+    }
+
+    pub fn root(&self) -> U256 {
+        self.levels[self.levels.len() - 1][0]
+    }
+
+    pub fn arity(&self) -> usize {
+        self.arity
+    }
+
+    /// Number of levels between the leaves and the root
+    pub fn depth(&self) -> usize {
+        self.levels.len() - 1
+    }
+
+    /// The leaves, including padding
+    pub fn leaves(&self) -> &[U256] {
+        &self.levels[0]
+    }
+
+    pub fn proof(&self, index: usize) -> Result<MerklePath, String> {
+        if index >= self.leaves().len() {
+            return Err(format!(
+                "leaf index {} out of range for {} leaves",
+                index,
+                self.leaves().len()
+            ));
+        }
+
+        let mut siblings = Vec::with_capacity(self.depth() * (self.arity - 1));
+        let mut path = Vec::with_capacity(self.depth());
+        let mut i = index;
+
+        for level in &self.levels[..self.depth()] {
+            let pos = i % self.arity;
+            let start = i - pos;
+            siblings.extend(
+                level[start..start + self.arity]
+                    .iter()
+                    .enumerate()
+                    .filter(|(j, _)| *j != pos)
+                    .map(|(_, n)| *n),
+            );
+            path.push(pos);
+            i /= self.arity;
+        }
+
+        Ok(MerklePath {
+            index,
+            siblings,
+            path,
+        })
+    }
+}
+
+/// The part of a Merkle tree known to a node holding only a slice of the dataset: some of
+/// the leaves and cached hashes of other nodes, e.g. the siblings on the paths of its
+/// leaves. Built with `MerkleTree::from_partial`, paths can be taken for any leaf whose
+/// siblings are known or computable from what is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartialMerkleTree<H = Poseidon> {
+    hash: H,
+    arity: usize,
+    depth: usize,
+    /// known and computed nodes by level and index, level 0 being the leaves
+    nodes: HashMap<(usize, usize), U256>,
+}
+
+impl MerkleTree {
+    /// The partial view of an `arity`-ary tree of `depth` levels given by `known_leaves`,
+    /// as `(index, leaf)`, and `cached_nodes` as `((level, index), hash)` with level 0 the
+    /// leaves. Every node whose children are all known is computed; a cached node that
+    /// disagrees with the one computed from its children is an error.
+    pub fn from_partial(
+        arity: usize,
+        depth: usize,
+        known_leaves: &[(usize, U256)],
+        cached_nodes: &[((usize, usize), U256)],
+    ) -> Result<PartialMerkleTree, String> {
+        PartialMerkleTree::with_hash(arity, depth, known_leaves, cached_nodes, Poseidon)
+    }
+}
+
+impl<H: HashFn> PartialMerkleTree<H> {
+    /// Same as `MerkleTree::from_partial`, hashing with `hash`
+    pub fn with_hash(
+        arity: usize,
+        depth: usize,
+        known_leaves: &[(usize, U256)],
+        cached_nodes: &[((usize, usize), U256)],
+        hash: H,
+    ) -> Result<Self, String> {
+        check_arity(arity)?;
+
+        let mut nodes = HashMap::new();
+        let leaves = known_leaves.iter().map(|(i, leaf)| ((0, *i), *leaf));
+        for ((level, index), node) in leaves.chain(cached_nodes.iter().copied()) {
+            if level > depth || index >= arity.pow((depth - level) as u32) {
+                return Err(format!(
+                    "node {} of level {} out of range for a tree of depth {}",
+                    index, level, depth
+                ));
+            }
+            if matches!(nodes.insert((level, index), node), Some(n) if n != node) {
+                return Err(format!("conflicting hashes for node {} of level {}", index, level));
+            }
+        }
+
+        for level in 1..=depth {
+            let mut parents = nodes
+                .keys()
+                .filter(|(l, _)| *l == level - 1)
+                .map(|(_, i)| i / arity)
+                .collect::<Vec<usize>>();
+            parents.sort_unstable();
+            parents.dedup();
+
+            for parent in parents {
+                let children = (parent * arity..(parent + 1) * arity)
+                    .map(|i| nodes.get(&(level - 1, i)).copied())
+                    .collect::<Option<Vec<U256>>>();
+                let children = match children {
+                    Some(children) => children,
+                    None => continue,
+                };
+
+                let node = hash.hash(&children)?;
+                if matches!(nodes.insert((level, parent), node), Some(n) if n != node) {
+                    return Err(format!(
+                        "cached node {} of level {} doesn't match its children",
+                        parent, level
+                    ));
+                }
+            }
+        }
+
+        Ok(Self {
+            hash,
+            arity,
+            depth,
+            nodes,
+        })
+    }
+
+    pub fn hash_fn(&self) -> &H {
+        &self.hash
+    }
+
+    pub fn arity(&self) -> usize {
+        self.arity
+    }
+
+    /// Number of levels between the leaves and the root
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// The root, if enough of the tree is known to compute it
+    pub fn root(&self) -> Result<U256, String> {
+        self.nodes
+            .get(&(self.depth, 0))
+            .copied()
+            .ok_or_else(|| "not enough of the tree is known to compute its root".to_string())
+    }
+
+    /// The leaf at `index`, if known
+    pub fn leaf(&self, index: usize) -> Option<U256> {
+        self.nodes.get(&(0, index)).copied()
+    }
+
+    /// Same as `MerkleTree::proof`, for a known leaf whose siblings are all known
+    pub fn proof(&self, index: usize) -> Result<MerklePath, String> {
+        if self.leaf(index).is_none() {
+            return Err(format!("leaf {} isn't known", index));
+        }
+
+        let mut siblings = Vec::with_capacity(self.depth * (self.arity - 1));
+        let mut path = Vec::with_capacity(self.depth);
+        let mut i = index;
+
+        for level in 0..self.depth {
+            let pos = i % self.arity;
+            let start = i - pos;
+            for j in (start..start + self.arity).filter(|j| *j != i) {
+                let sibling = self.nodes.get(&(level, j)).ok_or_else(|| {
+                    format!(
+                        "missing sibling {} of level {} on the path of leaf {}",
+                        j, level, index
+                    )
+                })?;
+                siblings.push(*sibling);
+            }
+            path.push(pos);
+            i /= self.arity;
+        }
+
+        Ok(MerklePath {
+            index,
+            siblings,
+            path,
+        })
+    }
+}
+
+/// Merkle tree that's grown one leaf at a time, with the same shape and padding as a
+/// `MerkleTree` built from all leaves at once, and hashed with Poseidon unless built
+/// `with_hash`. Appending only rehashes the path of the new leaf, untouched padding
+/// subtrees are represented by their (cached) hashes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IncrementalMerkleTree<H = Poseidon> {
+    hash: H,
+    arity: usize,
+    /// levels[0] are the leaves, without padding
+    levels: Vec<Vec<U256>>,
+    /// zeros[l] is the hash of an all padding subtree of height l
+    zeros: Vec<U256>,
+}
+
+impl IncrementalMerkleTree {
+    pub fn new(arity: usize) -> Result<Self, String> {
+        Self::with_hash(arity, Poseidon)
+    }
+}
+
+impl<H: HashFn> IncrementalMerkleTree<H> {
+    /// Same as `new`, hashing with `hash`
+    pub fn with_hash(arity: usize, hash: H) -> Result<Self, String> {
+        check_arity(arity)?;
+
+        Ok(Self {
+            hash,
+            arity,
+            levels: vec![Vec::new()],
+            zeros: vec![PADDING_LEAF],
+        })
+    }
+
+    pub fn hash_fn(&self) -> &H {
+        &self.hash
+    }
+
+    pub fn arity(&self) -> usize {
+        self.arity
+    }
+
+    pub fn len(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.levels[0].is_empty()
+    }
+
+    /// Number of levels between the leaves and the root
+    pub fn depth(&self) -> usize {
+        let mut depth = 0;
+        let mut width = 1;
+        while width < self.len() {
+            width *= self.arity;
+            depth += 1;
+        }
+
+        depth
+    }
+
+    /// Append a leaf, returning its index
+    pub fn append(&mut self, leaf: U256) -> Result<usize, String> {
+        let index = self.len();
+        self.levels[0].push(leaf);
+
+        let depth = self.depth();
+        while self.levels.len() <= depth {
+            self.levels.push(Vec::new());
+        }
+        while self.zeros.len() <= depth {
+            let zero = self
+                .hash
+                .hash(&vec![self.zeros[self.zeros.len() - 1]; self.arity])?;
+            self.zeros.push(zero);
+        }
+
+        let mut i = index;
+        for level in 1..=depth {
+            i /= self.arity;
+            let children = (i * self.arity..(i + 1) * self.arity)
+                .map(|j| self.node(level - 1, j))
+                .collect::<Vec<U256>>();
+            let parent = self.hash.hash(&children)?;
+
+            // nodes of a level are created in order, as leaves are appended
+            match self.levels[level].get_mut(i) {
+                Some(node) => *node = parent,
+                None => self.levels[level].push(parent),
+            }
+        }
+
+        Ok(index)
+    }
+
+    /// The root, or None if no leaves were appended yet
+    pub fn root(&self) -> Option<U256> {
+        if self.is_empty() {
+            return None;
+        }
+
+        Some(self.node(self.depth(), 0))
+    }
+
+    pub fn proof(&self, index: usize) -> Result<MerklePath, String> {
+        if index >= self.len() {
+            return Err(format!(
+                "leaf index {} out of range for {} leaves",
+                index,
+                self.len()
+            ));
+        }
+
+        let depth = self.depth();
+        let mut siblings = Vec::with_capacity(depth * (self.arity - 1));
+        let mut path = Vec::with_capacity(depth);
+        let mut i = index;
+
+        for level in 0..depth {
+            let pos = i % self.arity;
+            let start = i - pos;
+            siblings.extend(
+                (start..start + self.arity)
+                    .filter(|j| *j != i)
+                    .map(|j| self.node(level, j)),
+            );
+            path.push(pos);
+            i /= self.arity;
+        }
+
+        Ok(MerklePath {
+            index,
+            siblings,
+            path,
+        })
+    }
+
+    fn node(&self, level: usize, index: usize) -> U256 {
+        self.levels[level]
+            .get(index)
+            .copied()
+            .unwrap_or(self.zeros[level])
+    }
+}
+
+/// Root of the tree that has `leaf` at `index`, given its `arity - 1` siblings per level
+/// as returned in `MerklePath::siblings`
+pub fn root_from_siblings(
+    leaf: U256,
+    index: usize,
+    siblings: &[U256],
+    arity: usize,
+) -> Result<U256, String> {
+    root_from_siblings_with(leaf, index, siblings, arity, &Poseidon)
+}
+
+/// Same as `root_from_siblings`, for a tree hashed with `hash`
+pub fn root_from_siblings_with<H: HashFn>(
+    leaf: U256,
+    index: usize,
+    siblings: &[U256],
+    arity: usize,
+    hash: &H,
+) -> Result<U256, String> {
+    check_arity(arity)?;
+
+    if siblings.len() % (arity - 1) != 0 {
+        return Err(format!(
+            "{} siblings don't make up full levels of a {}-ary tree",
+            siblings.len(),
+            arity
+        ));
+    }
+
+    let mut node = leaf;
+    let mut i = index;
+    for level in siblings.chunks(arity - 1) {
+        let mut children = level.to_vec();
+        children.insert(i % arity, node);
+        node = hash.hash(&children)?;
+        i /= arity;
+    }
+
+    if i != 0 {
+        return Err(format!(
+            "leaf index {} out of range for a tree of depth {}",
+            index,
+            siblings.len() / (arity - 1)
+        ));
+    }
+
+    Ok(node)
+}
+
+fn check_arity(arity: usize) -> Result<(), String> {
+    if !(2..=POSEIDON_MAX_INPUTS).contains(&arity) {
+        return Err(format!(
+            "tree arity must be between 2 and {}, got {}",
+            POSEIDON_MAX_INPUTS, arity
+        ));
+    }
+
+    Ok(())
+}
+
+impl MerklePath {
+    /// Path of the leaf at `index` of an `arity`-ary tree, from its siblings as returned
+    /// in `siblings`
+    pub fn new(index: usize, siblings: Vec<U256>, arity: usize) -> Result<Self, String> {
+        check_arity(arity)?;
+
+        if siblings.len() % (arity - 1) != 0 {
+            return Err(format!(
+                "{} siblings don't make up full levels of a {}-ary tree",
+                siblings.len(),
+                arity
+            ));
+        }
+
+        let mut i = index;
+        let path = (0..siblings.len() / (arity - 1))
+            .map(|_| {
+                let pos = i % arity;
+                i /= arity;
+                pos
+            })
+            .collect();
+
+        Ok(Self {
+            index,
+            siblings,
+            path,
+        })
+    }
+
+    /// Check the path leads from `leaf` to `root`, with plain Poseidon hashing rather than
+    /// a proof. Catches inconsistent `siblings`, `path`, `hashes` and `root` before paying
+    /// for proving them. Paths that don't fit a tree, e.g. a position that disagrees with
+    /// `index`, don't verify.
+    pub fn verify(&self, leaf: U256, root: U256) -> bool {
+        self.verify_with(leaf, root, &Poseidon)
+    }
+
+    /// Same as `verify`, for a tree hashed with `hash`
+    pub fn verify_with<H: HashFn>(&self, leaf: U256, root: U256, hash: &H) -> bool {
+        let depth = self.path.len();
+        if depth == 0 {
+            return self.siblings.is_empty() && self.index == 0 && leaf == root;
+        }
+        if self.siblings.len() % depth != 0 {
+            return false;
+        }
+
+        let arity = self.siblings.len() / depth + 1;
+        let mut i = self.index;
+        let positions_match = self.path.iter().all(|pos| {
+            let expected = i % arity;
+            i /= arity;
+            *pos == expected
+        });
+
+        positions_match
+            && root_from_siblings_with(leaf, self.index, &self.siblings, arity, hash) == Ok(root)
+    }
+
+    /// Same as `verify`, but errors with what's wrong: siblings that don't make up full
+    /// levels, positions that disagree with `index`, or levels given from the root down
+    /// rather than in the order the circuit expects (see `MerklePath`)
+    pub fn validate_ordering(&self, leaf: U256, root: U256) -> Result<(), String> {
+        self.validate_ordering_with(leaf, root, &Poseidon)
+    }
+
+    /// Same as `validate_ordering`, for a tree hashed with `hash`
+    pub fn validate_ordering_with<H: HashFn>(
+        &self,
+        leaf: U256,
+        root: U256,
+        hash: &H,
+    ) -> Result<(), String> {
+        let depth = self.path.len();
+        if depth == 0 {
+            if !self.siblings.is_empty() || leaf != root {
+                return Err("a path without levels only leads to the leaf itself".to_string());
+            }
+            return Ok(());
+        }
+        if self.siblings.len() % depth != 0 {
+            return Err(format!(
+                "{} siblings don't make up {} levels",
+                self.siblings.len(),
+                depth
+            ));
+        }
+
+        let arity = self.siblings.len() / depth + 1;
+        let mut i = self.index;
+        for (level, pos) in self.path.iter().enumerate() {
+            let expected = i % arity;
+            i /= arity;
+            if *pos != expected {
+                return Err(format!(
+                    "position {} on level {} doesn't match leaf index {}, expected {}",
+                    pos, level, self.index, expected
+                ));
+            }
+        }
+
+        let computed = root_from_siblings_with(leaf, self.index, &self.siblings, arity, hash)?;
+        if computed == root {
+            return Ok(());
+        }
+
+        let reversed = self
+            .siblings
+            .chunks(arity - 1)
+            .rev()
+            .flatten()
+            .copied()
+            .collect::<Vec<U256>>();
+        if root_from_siblings_with(leaf, self.index, &reversed, arity, hash) == Ok(root) {
+            return Err(
+                "siblings are ordered from the root down, they must start at the leaf's level"
+                    .to_string(),
+            );
+        }
+
+        Err(format!(
+            "the siblings lead to {}, which doesn't match the root {}",
+            computed, root
+        ))
+    }
+
+    /// Encode the `siblings` and `path` entries of the `prove_mpack` argument map
+    pub fn to_mpack(&self) -> Vec<u8> {
+        let siblings = rmpv::Value::Array(self.siblings.iter().map(encode_u256_le).collect());
+        let path = rmpv::Value::Array(vec![rmpv::Value::from(self.index as u64)]);
+
+        encode_value(&rmpv::Value::Map(vec![
+            ("siblings".into(), siblings),
+            ("path".into(), path),
+        ]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rs_poseidon::poseidon::hash;
+    use ruint::aliases::U256;
+
+    use rmpv::decode::read_value;
+
+    use super::{
+        root_from_siblings, root_from_siblings_with, IncrementalMerkleTree, MerklePath, MerkleTree,
+        PADDING_LEAF,
+    };
+    use crate::{
+        hash::{HashFn, HashKind, Keccak256Reduced, Poseidon},
+        mpack::EXT_ID_U256_LE,
+        utils::{treehash, treehash_with},
+    };
+
+    #[test]
+    fn test_matches_treehash() {
+        let leaves = (0..4).map(U256::from).collect::<Vec<U256>>();
+        let tree = MerkleTree::new(&leaves, 2).unwrap();
+
+        assert_eq!(tree.root(), treehash(&leaves).unwrap());
+        assert_eq!(tree.depth(), 2);
+
+        // an odd count is padded like the tree, not read past its end
+        let odd = (0..5).map(U256::from).collect::<Vec<U256>>();
+        assert_eq!(treehash(&odd).unwrap(), MerkleTree::new(&odd, 2).unwrap().root());
+        assert_eq!(treehash(&odd[..1]).unwrap(), odd[0]);
+        assert!(treehash(&[]).is_err());
+    }
+
+    #[test]
+    fn test_proof() {
+        let leaves = (0..4).map(U256::from).collect::<Vec<U256>>();
+        let tree = MerkleTree::new(&leaves, 2).unwrap();
+        let parent_r = hash(&[leaves[2], leaves[3]]);
+
+        let proof = tree.proof(1).unwrap();
+        assert_eq!(proof.siblings, vec![leaves[0], parent_r]);
+        assert_eq!(proof.path, vec![1, 0]);
+        assert!(tree.proof(4).is_err());
+    }
+
+    #[test]
+    fn test_root_from_siblings() {
+        let leaves = (0..9).map(U256::from).collect::<Vec<U256>>();
+
+        for arity in [2, 3] {
+            let tree = MerkleTree::new(&leaves, arity).unwrap();
+            for (i, leaf) in tree.leaves().iter().enumerate() {
+                let proof = tree.proof(i).unwrap();
+                assert_eq!(
+                    root_from_siblings(*leaf, i, &proof.siblings, arity).unwrap(),
+                    tree.root()
+                );
+            }
+        }
+
+        let tree = MerkleTree::new(&leaves[..4], 2).unwrap();
+        let proof = tree.proof(1).unwrap();
+        assert_ne!(
+            root_from_siblings(leaves[1], 2, &proof.siblings, 2).unwrap(),
+            tree.root()
+        );
+        assert!(root_from_siblings(leaves[1], 4, &proof.siblings, 2).is_err());
+        assert!(root_from_siblings(leaves[1], 1, &[leaves[0]; 3], 3).is_err());
+    }
+
+    #[test]
+    fn test_path_verify() {
+        let leaves = (0..9).map(U256::from).collect::<Vec<U256>>();
+
+        for arity in [2, 3] {
+            let tree = MerkleTree::new(&leaves, arity).unwrap();
+            for (i, leaf) in tree.leaves().iter().enumerate() {
+                let proof = tree.proof(i).unwrap();
+                assert!(proof.verify(*leaf, tree.root()));
+                assert_eq!(
+                    MerklePath::new(i, proof.siblings.clone(), arity).unwrap(),
+                    proof
+                );
+            }
+        }
+
+        let tree = MerkleTree::new(&leaves[..4], 2).unwrap();
+        let proof = tree.proof(1).unwrap();
+        assert!(!proof.verify(leaves[2], tree.root()));
+        assert!(!proof.verify(leaves[1], leaves[1]));
+
+        for i in 0..proof.siblings.len() {
+            let mut corrupted = proof.clone();
+            corrupted.siblings[i] += U256::from(1);
+            assert!(!corrupted.verify(leaves[1], tree.root()));
+        }
+
+        let mut moved = proof.clone();
+        moved.path[0] = 0;
+        assert!(!moved.verify(leaves[1], tree.root()));
+        let mut truncated = proof;
+        truncated.siblings.pop();
+        assert!(!truncated.verify(leaves[1], tree.root()));
+        assert!(MerklePath::new(1, vec![leaves[0]; 3], 3).is_err());
+    }
+
+    #[test]
+    fn test_validate_ordering() {
+        let leaves = (0..8).map(U256::from).collect::<Vec<U256>>();
+        let tree = MerkleTree::new(&leaves, 2).unwrap();
+        let proof = tree.proof(5).unwrap();
+        assert_eq!(proof.validate_ordering(leaves[5], tree.root()), Ok(()));
+
+        let mut reversed = proof.clone();
+        reversed.siblings.reverse();
+        assert!(!reversed.verify(leaves[5], tree.root()));
+        assert_eq!(
+            reversed.validate_ordering(leaves[5], tree.root()).unwrap_err(),
+            "siblings are ordered from the root down, they must start at the leaf's level"
+        );
+
+        let mut swapped = proof.clone();
+        swapped.siblings.swap(0, 1);
+        let err = swapped.validate_ordering(leaves[5], tree.root()).unwrap_err();
+        assert!(err.ends_with(&format!("doesn't match the root {}", tree.root())), "{}", err);
+
+        let mut moved = proof.clone();
+        moved.path[1] = 1;
+        assert_eq!(
+            moved.validate_ordering(leaves[5], tree.root()).unwrap_err(),
+            "position 1 on level 1 doesn't match leaf index 5, expected 0"
+        );
+        let mut truncated = proof;
+        truncated.siblings.pop();
+        assert!(truncated.validate_ordering(leaves[5], tree.root()).is_err());
+    }
+
+    #[test]
+    fn test_hash_fns() {
+        let leaves = (0..4).map(U256::from).collect::<Vec<U256>>();
+        let poseidon = MerkleTree::new(&leaves, 2).unwrap();
+        let keccak = MerkleTree::with_hash(&leaves, 2, Keccak256Reduced).unwrap();
+
+        assert_ne!(poseidon.root(), keccak.root());
+        assert_eq!(keccak.root(), treehash_with(&leaves, &Keccak256Reduced).unwrap());
+        assert_eq!(
+            MerkleTree::with_hash(&leaves, 2, HashKind::Poseidon).unwrap().root(),
+            poseidon.root()
+        );
+        let left = Keccak256Reduced.hash(&leaves[..2]).unwrap();
+        let right = Keccak256Reduced.hash(&leaves[2..]).unwrap();
+        assert_eq!(keccak.root(), Keccak256Reduced.hash(&[left, right]).unwrap());
+
+        for (i, leaf) in leaves.iter().enumerate() {
+            let proof = keccak.proof(i).unwrap();
+            assert_eq!(
+                root_from_siblings_with(*leaf, i, &proof.siblings, 2, &Keccak256Reduced).unwrap(),
+                keccak.root()
+            );
+            assert!(proof.verify_with(*leaf, keccak.root(), &Keccak256Reduced));
+            assert!(!proof.verify_with(*leaf, keccak.root(), &Poseidon));
+
+            let proof = poseidon.proof(i).unwrap();
+            assert!(!proof.verify_with(*leaf, poseidon.root(), &Keccak256Reduced));
+        }
+    }
+
+    #[test]
+    fn test_padding() {
+        let leaves = (0..3).map(U256::from).collect::<Vec<U256>>();
+        let padded = [leaves.clone(), vec![U256::ZERO]].concat();
+
+        assert_eq!(
+            MerkleTree::new(&leaves, 2).unwrap().root(),
+            MerkleTree::new(&padded, 2).unwrap().root()
+        );
+
+        let tree = MerkleTree::new(&leaves, 4).unwrap();
+        assert_eq!(tree.depth(), 1);
+        assert_eq!(tree.root(), hash(&padded));
+    }
+
+    #[test]
+    fn test_with_depth() {
+        let leaves = (0..3).map(U256::from).collect::<Vec<U256>>();
+        let padded = [leaves.clone(), vec![PADDING_LEAF; 5]].concat();
+
+        let tree = MerkleTree::with_depth(&leaves, 2, 3).unwrap();
+        assert_eq!(tree.depth(), 3);
+        assert_eq!(tree.leaves(), padded.as_slice());
+        assert_eq!(tree.root(), MerkleTree::new(&padded, 2).unwrap().root());
+        assert_eq!(tree.proof(2).unwrap().siblings.len(), 3);
+        assert_eq!(MerkleTree::with_depth(&leaves[..1], 2, 0).unwrap().root(), leaves[0]);
+
+        let err = MerkleTree::with_depth(&padded, 2, 2).unwrap_err();
+        assert_eq!(err, "8 leaves don't fit in a tree of depth 2");
+    }
+
+    #[test]
+    fn test_partial() {
+        let leaves = (0..8).map(U256::from).collect::<Vec<U256>>();
+        let tree = MerkleTree::new(&leaves, 2).unwrap();
+        let full = tree.proof(5).unwrap();
+
+        // leaf 5 and its authentication siblings only
+        let siblings = [
+            ((0, 4), leaves[4]),
+            ((1, 3), tree.levels[1][3]),
+            ((2, 0), tree.levels[2][0]),
+        ];
+        let partial = MerkleTree::from_partial(2, 3, &[(5, leaves[5])], &siblings).unwrap();
+        assert_eq!(partial.root(), Ok(tree.root()));
+        assert_eq!(partial.proof(5), Ok(full));
+
+        assert!(partial.proof(4).is_err());
+        let err = MerkleTree::from_partial(2, 3, &[(5, leaves[5])], &siblings[1..])
+            .unwrap()
+            .proof(5)
+            .unwrap_err();
+        assert_eq!(err, "missing sibling 4 of level 0 on the path of leaf 5");
+
+        let mut wrong = siblings;
+        wrong[2].1 += U256::from(1);
+        let partial = MerkleTree::from_partial(2, 3, &[(5, leaves[5])], &wrong).unwrap();
+        assert_ne!(partial.root(), Ok(tree.root()));
+        let cached = [&wrong[..], &[((3, 0), tree.root())]].concat();
+        let err = MerkleTree::from_partial(2, 3, &[(5, leaves[5])], &cached).unwrap_err();
+        assert_eq!(err, "cached node 0 of level 3 doesn't match its children");
+        assert!(MerkleTree::from_partial(2, 3, &[(8, leaves[0])], &[]).is_err());
+    }
+
+    #[test]
+    fn test_incremental() {
+        let leaves = (1..=10).map(U256::from).collect::<Vec<U256>>();
+
+        for arity in [2, 3] {
+            let mut tree = IncrementalMerkleTree::new(arity).unwrap();
+            assert_eq!(tree.root(), None);
+
+            for (n, leaf) in leaves.iter().enumerate() {
+                assert_eq!(tree.append(*leaf).unwrap(), n);
+
+                let full = MerkleTree::new(&leaves[..=n], arity).unwrap();
+                assert_eq!(tree.root(), Some(full.root()));
+                assert_eq!(tree.depth(), full.depth());
+                for i in 0..=n {
+                    assert_eq!(tree.proof(i).unwrap(), full.proof(i).unwrap());
+                }
+                assert!(tree.proof(n + 1).is_err());
+            }
+        }
+
+        let mut tree = IncrementalMerkleTree::with_hash(2, Keccak256Reduced).unwrap();
+        for leaf in &leaves {
+            tree.append(*leaf).unwrap();
+        }
+        let full = MerkleTree::with_hash(&leaves, 2, Keccak256Reduced).unwrap();
+        assert_eq!(tree.root(), Some(full.root()));
+        assert_ne!(tree.root(), Some(MerkleTree::new(&leaves, 2).unwrap().root()));
+        for i in 0..leaves.len() {
+            assert_eq!(tree.proof(i).unwrap(), full.proof(i).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_path_to_mpack() {
+        let leaves = (0..4).map(U256::from).collect::<Vec<U256>>();
+        let proof = MerkleTree::new(&leaves, 2).unwrap().proof(2).unwrap();
+
+        let bytes = proof.to_mpack();
+        let args = read_value(&mut bytes.as_slice()).unwrap();
+
+        assert_eq!(args["path"][0].as_u64(), Some(2));
+        let siblings = args["siblings"].as_array().unwrap();
+        assert_eq!(siblings.len(), 2);
+        for (val, expected) in siblings.iter().zip(proof.siblings.iter()) {
+            let (id, bytes) = val.as_ext().unwrap();
+            assert_eq!(id, EXT_ID_U256_LE);
+            assert_eq!(U256::try_from_le_slice(bytes).unwrap(), *expected);
+        }
+    }
+}
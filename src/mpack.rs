@@ -0,0 +1,121 @@
+//! The mpack encoding of field elements used by `prove_mpack` and friends. A `U256` is an
+//! ext value holding its bytes, with the ext id telling their order: `EXT_ID_U256_LE` for
+//! little endian, which is what this crate writes, or `EXT_ID_U256_BE`. Plain integers are
+//! accepted as well when decoding.
+
+use rmpv::encode::write_value;
+pub use rmpv::Value;
+use ruint::aliases::U256;
+
+/// Ext id of a little endian `U256`
+pub const EXT_ID_U256_LE: i8 = 50;
+/// Ext id of a big endian `U256`
+pub const EXT_ID_U256_BE: i8 = 51;
+
+/// Byte order of a `U256` serialized as raw bytes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Endianness {
+    #[default]
+    Little,
+    Big,
+}
+
+impl Endianness {
+    /// The mpack ext id tagging `U256`s in this byte order
+    pub fn ext_id(self) -> i8 {
+        match self {
+            Endianness::Little => EXT_ID_U256_LE,
+            Endianness::Big => EXT_ID_U256_BE,
+        }
+    }
+
+    pub fn from_ext_id(id: i8) -> Option<Self> {
+        match id {
+            EXT_ID_U256_LE => Some(Endianness::Little),
+            EXT_ID_U256_BE => Some(Endianness::Big),
+            _ => None,
+        }
+    }
+
+    /// Decode at most `U256::BYTES` bytes, shorter values are zero extended
+    pub fn decode(self, bytes: &[u8]) -> Option<U256> {
+        match self {
+            Endianness::Little => U256::try_from_le_slice(bytes),
+            Endianness::Big => U256::try_from_be_slice(bytes),
+        }
+    }
+
+    pub fn encode(self, n: &U256) -> Vec<u8> {
+        match self {
+            Endianness::Little => n.to_le_bytes_vec(),
+            Endianness::Big => n.to_be_bytes_vec(),
+        }
+    }
+}
+
+/// `n` as an `EXT_ID_U256_LE` ext value
+pub fn encode_u256_le(n: &U256) -> Value {
+    encode_u256(n, Endianness::Little)
+}
+
+/// `n` as an ext value in the given byte order
+pub fn encode_u256(n: &U256, endianness: Endianness) -> Value {
+    Value::Ext(endianness.ext_id(), endianness.encode(n))
+}
+
+/// Decode a `U256` ext value in either byte order, or a non-negative integer
+pub fn decode_u256(val: &Value) -> Result<U256, String> {
+    match val {
+        // the ext id tells the byte order, so big endian hosts don't need to swap
+        Value::Ext(id, bytes) => match Endianness::from_ext_id(*id) {
+            Some(endianness) => endianness
+                .decode(bytes)
+                .ok_or_else(|| format!("{} bytes don't fit in a u256", bytes.len())),
+            None => Err(format!("unhandled ext id {}", id)),
+        },
+        Value::Integer(val) => match (val.as_u64(), val.as_i64()) {
+            (Some(val), _) => Ok(U256::from(val)),
+            (None, Some(val)) => Ok(U256::from(val)),
+            _ => Err("unexpected integer kind".to_string()),
+        },
+        _ => Err("expected ext mpack kind or integer".to_string()),
+    }
+}
+
+pub(crate) fn encode_value(val: &Value) -> Vec<u8> {
+    let mut buf = Vec::new();
+    write_value(&mut buf, val).expect("writing to a Vec can't fail");
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use ruint::aliases::U256;
+
+    use super::{decode_u256, encode_u256, encode_u256_le, Endianness, Value, EXT_ID_U256_LE};
+
+    #[test]
+    fn test_u256_roundtrip() {
+        let values = [U256::ZERO, U256::from(1), U256::from(0x0102), U256::MAX];
+
+        for n in values {
+            assert_eq!(decode_u256(&encode_u256_le(&n)), Ok(n));
+            assert_eq!(decode_u256(&encode_u256(&n, Endianness::Big)), Ok(n));
+        }
+        assert_eq!(
+            encode_u256_le(&U256::from(0x0102)),
+            Value::Ext(EXT_ID_U256_LE, U256::from(0x0102).to_le_bytes_vec())
+        );
+        assert_eq!(decode_u256(&Value::from(7)), Ok(U256::from(7)));
+    }
+
+    #[test]
+    fn test_decode_u256_errors() {
+        let bytes = U256::from(1).to_le_bytes_vec();
+
+        let err = decode_u256(&Value::Ext(49, bytes)).unwrap_err();
+        assert_eq!(err, "unhandled ext id 49");
+        assert!(decode_u256(&Value::Ext(EXT_ID_U256_LE, vec![1; 33])).is_err());
+        assert!(decode_u256(&Value::from("1")).is_err());
+    }
+}
@@ -0,0 +1,301 @@
+//! Witness generation without the circuit's wasm, for `StorageProofs::set_native_witness`.
+//!
+//! The wires of a compiled circuit are whatever circom's simplification left over, so
+//! rather than mirroring the circuit template by template, the witness is solved from the
+//! r1cs: with the inputs assigned, every constraint with a single unknown wire left in it
+//! fixes that wire (the Poseidon rounds, switchers and Merkle hashes of the storer circuit
+//! are all of that form), and the bits of `Num2Bits` are read off the number they
+//! decompose once nothing else is left to solve. The witness is unique for the storer
+//! circuits, so it's the one the wasm computes. `cargo bench --features native_witness`
+//! compares it with the wasm in the `witness` group.
+
+use std::collections::VecDeque;
+
+use ark_bn254::{Bn254, Fr};
+use ark_circom::circom::R1CS;
+use ark_ff::{Field, One, PrimeField, Zero};
+use num_bigint::{BigInt, Sign};
+use ruint::aliases::U256;
+
+use crate::storage_proofs::eval_lc;
+
+type Lc = [(usize, Fr)];
+
+/// Solve the witness of `r1cs` given the values of its input wires, the ones following the
+/// constant `1` in the order circom numbers them: public inputs first, then private ones
+pub(crate) fn solve(r1cs: &R1CS<Bn254>, inputs: &[Fr]) -> Result<Vec<Fr>, String> {
+    if inputs.len() >= r1cs.num_variables {
+        return Err(format!(
+            "{} inputs given for a circuit of {} wires",
+            inputs.len(),
+            r1cs.num_variables
+        ));
+    }
+
+    let mut solver = Solver::new(r1cs);
+    solver.assign(0, Fr::one());
+    for (i, input) in inputs.iter().enumerate() {
+        solver.assign(i + 1, *input);
+    }
+    solver.queue.extend((0..r1cs.constraints.len()).filter(|c| solver.unknown[*c] == 1));
+
+    loop {
+        solver.propagate();
+        if solver.left == 0 {
+            break;
+        }
+        if !solver.decompose_bits() {
+            let wire = solver.wires.iter().position(Option::is_none).unwrap_or_default();
+            return Err(format!("wire {} isn't determined by the inputs", wire));
+        }
+    }
+
+    let witness = solver
+        .wires
+        .into_iter()
+        .map(|w| w.expect("all wires are solved"))
+        .collect::<Vec<Fr>>();
+    let eval = |lc: &Lc| eval_lc(r1cs, &witness, lc);
+    let unsatisfied = r1cs.constraints.iter().position(|(a, b, c)| {
+        match (eval(a), eval(b), eval(c)) {
+            (Some(a), Some(b), Some(c)) => a * b != c,
+            _ => true,
+        }
+    });
+    if let Some(index) = unsatisfied {
+        return Err(format!("constraint {} is not satisfied by the inputs", index));
+    }
+
+    Ok(witness)
+}
+
+/// An input signal value as a field element, negative ones (e.g. a `path` of -1) included
+pub(crate) fn input_to_fr(n: &BigInt) -> Fr {
+    let magnitude = Fr::from_le_bytes_mod_order(&n.magnitude().to_bytes_le());
+    if n.sign() == Sign::Minus {
+        -magnitude
+    } else {
+        magnitude
+    }
+}
+
+struct Solver<'a> {
+    r1cs: &'a R1CS<Bn254>,
+    wires: Vec<Option<Fr>>,
+    /// number of wires still unknown
+    left: usize,
+    /// distinct wires of each constraint
+    constraint_wires: Vec<Vec<usize>>,
+    /// unknown wires left in each constraint
+    unknown: Vec<usize>,
+    /// constraints each wire is in
+    users: Vec<Vec<usize>>,
+    /// wires constrained to be 0 or 1
+    boolean: Vec<bool>,
+    /// constraints that were down to a single unknown wire
+    queue: VecDeque<usize>,
+}
+
+impl<'a> Solver<'a> {
+    fn new(r1cs: &'a R1CS<Bn254>) -> Self {
+        let mut users = vec![Vec::new(); r1cs.num_variables];
+        let constraint_wires = r1cs
+            .constraints
+            .iter()
+            .enumerate()
+            .map(|(i, (a, b, c))| {
+                let mut wires = a.iter().chain(b).chain(c).map(|(w, _)| *w).collect::<Vec<_>>();
+                wires.sort_unstable();
+                wires.dedup();
+                wires.iter().for_each(|w| users[*w].push(i));
+                wires
+            })
+            .collect::<Vec<Vec<usize>>>();
+
+        Self {
+            r1cs,
+            wires: vec![None; r1cs.num_variables],
+            left: r1cs.num_variables,
+            unknown: constraint_wires.iter().map(Vec::len).collect(),
+            constraint_wires,
+            users,
+            boolean: vec![false; r1cs.num_variables],
+            queue: VecDeque::new(),
+        }
+    }
+
+    fn assign(&mut self, wire: usize, value: Fr) {
+        self.wires[wire] = Some(value);
+        self.left -= 1;
+        for i in 0..self.users[wire].len() {
+            let constraint = self.users[wire][i];
+            self.unknown[constraint] -= 1;
+            if self.unknown[constraint] == 1 {
+                self.queue.push_back(constraint);
+            }
+        }
+    }
+
+    /// Solve the queued constraints for their last unknown wire, until none are left
+    fn propagate(&mut self) {
+        let r1cs = self.r1cs;
+        while let Some(constraint) = self.queue.pop_front() {
+            if self.unknown[constraint] != 1 {
+                continue;
+            }
+            let wire = self.constraint_wires[constraint]
+                .iter()
+                .copied()
+                .find(|w| self.wires[*w].is_none())
+                .expect("one wire is unknown");
+
+            let (a, b, c) = &r1cs.constraints[constraint];
+            let ((ka, ca), (kb, cb), (kc, cc)) =
+                (self.split(a, wire), self.split(b, wire), self.split(c, wire));
+            // (ka + ca w) * (kb + cb w) = kc + cc w is linear in w unless it's in both factors
+            if ca.is_zero() || cb.is_zero() {
+                if let Some(inv) = (ca * kb + cb * ka - cc).inverse() {
+                    self.assign(wire, (kc - ka * kb) * inv);
+                }
+            } else {
+                let f = |w: Fr| (ka + ca * w) * (kb + cb * w) - kc - cc * w;
+                if f(Fr::zero()).is_zero() && f(Fr::one()).is_zero() {
+                    self.boolean[wire] = true;
+                }
+            }
+        }
+    }
+
+    /// Known part of `lc`, and the coefficient of `wire`, its only unknown wire
+    fn split(&self, lc: &Lc, wire: usize) -> (Fr, Fr) {
+        lc.iter()
+            .fold((Fr::zero(), Fr::zero()), |(known, coeff), (w, c)| match self.wires[*w] {
+                Some(value) => (known + value * c, coeff),
+                None if *w == wire => (known, coeff + c),
+                None => (known, coeff),
+            })
+    }
+
+    /// Assign the bits of a linear constraint summing unknown boolean wires weighted by
+    /// powers of two (`Num2Bits`), returning whether any was found
+    fn decompose_bits(&mut self) -> bool {
+        let r1cs = self.r1cs;
+        for (constraint, (a, b, c)) in r1cs.constraints.iter().enumerate() {
+            if self.unknown[constraint] == 0 {
+                continue;
+            }
+            let known = |lc: &Lc| {
+                lc.iter()
+                    .map(|(w, coeff)| self.wires[*w].map(|v| v * coeff))
+                    .sum::<Option<Fr>>()
+            };
+            let (ka, kb) = match (known(a), known(b)) {
+                (Some(ka), Some(kb)) => (ka, kb),
+                _ => continue,
+            };
+
+            let mut bits = Vec::new();
+            let mut target = ka * kb;
+            for (w, coeff) in c {
+                match self.wires[*w] {
+                    Some(value) => target -= value * coeff,
+                    None if self.boolean[*w] => bits.push((*w, *coeff)),
+                    None => break,
+                }
+            }
+            if bits.len() != self.unknown[constraint] {
+                continue;
+            }
+
+            if let Some(values) = bit_values(&bits, target) {
+                for (wire, value) in values {
+                    self.assign(wire, value);
+                }
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+/// Values of the boolean wires in `bits`, as `(wire, coefficient)`, summing to `target`,
+/// if the coefficients are a common unit times distinct powers of two
+fn bit_values(bits: &[(usize, Fr)], target: Fr) -> Option<Vec<(usize, Fr)>> {
+    let to_u256 = |f: Fr| U256::from_limbs(f.into_repr().0);
+
+    bits.iter().find_map(|(_, unit)| {
+        let inv = unit.inverse()?;
+        let mut mask = U256::ZERO;
+        let exponents = bits
+            .iter()
+            .map(|(_, coeff)| {
+                let weight = to_u256(*coeff * inv);
+                let exponent = weight.trailing_zeros();
+                if weight.count_ones() != 1 || mask.bit(exponent) {
+                    return None;
+                }
+                mask.set_bit(exponent, true);
+                Some(exponent)
+            })
+            .collect::<Option<Vec<usize>>>()?;
+
+        let sum = to_u256(target * inv);
+        if sum & !mask != U256::ZERO {
+            return None;
+        }
+        let values = bits
+            .iter()
+            .zip(exponents)
+            .map(|((wire, _), exponent)| (*wire, Fr::from(sum.bit(exponent) as u64)))
+            .collect();
+
+        Some(values)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_bn254::{Bn254, Fr};
+    use ark_circom::circom::R1CS;
+    use ark_ff::{One, Zero};
+    use num_bigint::BigInt;
+
+    use super::{input_to_fr, solve};
+
+    /// `Num2Bits(3)` of input wire 1 into wires 2 to 4, and wire 5 the square of the input
+    fn num2bits() -> R1CS<Bn254> {
+        let one = Fr::one();
+        let mut constraints = (2..5)
+            .map(|bit| (vec![(bit, one)], vec![(bit, one), (0, -one)], vec![]))
+            .collect::<Vec<_>>();
+        constraints.push((
+            vec![],
+            vec![],
+            vec![(2, one), (3, Fr::from(2u64)), (4, Fr::from(4u64)), (1, -one)],
+        ));
+        constraints.push((vec![(1, one)], vec![(1, one)], vec![(5, one)]));
+
+        R1CS {
+            num_inputs: 1,
+            num_aux: 5,
+            num_variables: 6,
+            constraints,
+            wire_mapping: None,
+        }
+    }
+
+    #[test]
+    fn test_solve() {
+        let r1cs = num2bits();
+
+        let witness = solve(&r1cs, &[Fr::from(6u64)]).unwrap();
+        let expected = [1u64, 6, 0, 1, 1, 36].map(Fr::from);
+        assert_eq!(witness, expected);
+
+        let err = solve(&r1cs, &[Fr::from(8u64)]).unwrap_err();
+        assert_eq!(err, "wire 2 isn't determined by the inputs");
+        assert_eq!(input_to_fr(&BigInt::from(-1)), -Fr::one());
+        assert_eq!(input_to_fr(&BigInt::from(0)), Fr::zero());
+    }
+}
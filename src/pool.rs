@@ -0,0 +1,108 @@
+//! A fixed number of ready to use provers, for nodes proving many requests concurrently.
+//!
+//! All provers of a pool share the loaded circuit and proving key. What the pool adds is
+//! an upfront witness calculator per prover and a bound on how many proofs run at once:
+//! `acquire` blocks while every prover is handed out.
+
+use std::ops::Deref;
+use std::sync::{Condvar, Mutex};
+
+use crate::storage_proofs::StorageProofs;
+
+#[derive(Debug)]
+pub struct ProverPool {
+    idle: Mutex<Vec<StorageProofs>>,
+    released: Condvar,
+    size: usize,
+}
+
+/// A prover taken from a `ProverPool`, returned to it when dropped
+#[derive(Debug)]
+pub struct PooledProver<'a> {
+    pool: &'a ProverPool,
+    prover: Option<StorageProofs>,
+}
+
+impl ProverPool {
+    /// Load the circuit once and warm up `size` witness calculators for it, see
+    /// `StorageProofs::new` for the arguments
+    pub fn new(size: usize, wasm: &str, r1cs: &str, zkey: Option<&str>) -> Result<Self, String> {
+        let prover = StorageProofs::new(
+            wasm.to_string(),
+            r1cs.to_string(),
+            zkey.map(|z| z.to_string()),
+            None,
+        )?;
+
+        Self::from_prover(size, prover)
+    }
+
+    /// Same as `new`, for an already loaded prover
+    pub fn from_prover(size: usize, prover: StorageProofs) -> Result<Self, String> {
+        if size == 0 {
+            return Err("a prover pool needs at least one prover".to_string());
+        }
+
+        prover.warm_up(size)?;
+
+        Ok(Self {
+            idle: Mutex::new(vec![prover; size]),
+            released: Condvar::new(),
+            size,
+        })
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Take a prover out of the pool, waiting for one to be returned if none is idle
+    pub fn acquire(&self) -> PooledProver<'_> {
+        let mut idle = self.idle.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            if let Some(prover) = idle.pop() {
+                return PooledProver {
+                    pool: self,
+                    prover: Some(prover),
+                };
+            }
+
+            idle = self
+                .released
+                .wait(idle)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    /// Return a prover detached with `PooledProver::into_inner`
+    pub(crate) fn release(&self, prover: StorageProofs) {
+        self.idle
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(prover);
+        self.released.notify_one();
+    }
+}
+
+impl PooledProver<'_> {
+    /// Detach the prover from the guard, it has to be handed back with `ProverPool::release`
+    pub(crate) fn into_inner(mut self) -> StorageProofs {
+        self.prover.take().expect("only taken on drop or here")
+    }
+}
+
+impl Deref for PooledProver<'_> {
+    type Target = StorageProofs;
+
+    fn deref(&self) -> &StorageProofs {
+        self.prover.as_ref().expect("only taken on drop")
+    }
+}
+
+impl Drop for PooledProver<'_> {
+    fn drop(&mut self) {
+        if let Some(prover) = self.prover.take() {
+            self.pool.release(prover);
+        }
+    }
+}
@@ -1,229 +1,4081 @@
-use std::fs::File;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::Cursor;
+use std::ops::{Deref, DerefMut};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+#[cfg(feature = "metrics")]
+use std::time::{Duration, Instant};
 
-use ark_bn254::{Bn254, Fr};
-use ark_circom::{read_zkey, CircomBuilder, CircomConfig, CircomCircuit};
+use ark_bn254::{Bn254, Fq, Fq2, Fq6, Fr, G1Affine, G2Affine};
+use ark_circom::{
+    circom::{R1CSFile, R1CS},
+    read_zkey, CircomCircuit, WitnessCalculator,
+};
+use ark_ec::{AffineCurve, PairingEngine, ProjectiveCurve};
+use ark_ff::{BigInteger256, Field, One, PrimeField, UniformRand};
 use ark_groth16::{
-    create_random_proof as prove, generate_random_parameters, prepare_verifying_key, verify_proof,
-    Proof, ProvingKey,
+    create_random_proof as prove, generate_random_parameters, prepare_verifying_key,
+    PreparedVerifyingKey, Proof, ProvingKey, VerifyingKey,
 };
 use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Read};
-use ark_std::rand::rngs::ThreadRng;
-use ruint::aliases::U256;
+use ark_std::rand::{
+    rngs::{StdRng, ThreadRng},
+    Rng, SeedableRng,
+};
+use memmap2::Mmap;
+use num_bigint::{BigInt, Sign};
+use once_cell::sync::OnceCell;
+use tiny_keccak::{Hasher, Keccak};
+use ruint::{aliases::U256, uint};
 
 use rmpv;
 use rmpv::decode::read_value;
+use wasmer::{MemoryError, Module, RuntimeError, Store, TrapCode};
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::hash::{HashFn, HashKind};
+use crate::merkle::{root_from_siblings_with, MerklePath, MerkleTree, PartialMerkleTree};
+use crate::mpack::{decode_u256, encode_u256, encode_u256_le, encode_value};
+#[cfg(feature = "native_witness")]
+use crate::native_witness;
+use crate::utils::{
+    check_field, digest_with_hash, hash_bytes, poseidon_hash, DigestConfig, POSEIDON_MAX_INPUTS,
+};
+use crate::verify::{check_lengths, verify_prepared, verify_prepared_with};
+pub use crate::mpack::{Endianness, EXT_ID_U256_BE, EXT_ID_U256_LE};
+pub use crate::verify::verify_with_vk;
 
 type Params256Ty = ark_ec::bn::Bn<ark_bn254::Parameters>;
 
-pub const EXT_ID_U256_LE: i8 = 50;
-pub const EXT_ID_U256_BE: i8 = 51;
+/// Circuit inputs by signal name, as consumed by the witness calculator. Built from the
+/// private inputs, so always held in `Zeroizing`.
+#[derive(Default)]
+struct Inputs(HashMap<String, Vec<BigInt>>);
 
+impl Deref for Inputs {
+    type Target = HashMap<String, Vec<BigInt>>;
 
-#[derive(Debug, Clone)]
-pub struct StorageProofs {
-    builder: CircomBuilder<Bn254>,
-    params: ProvingKey<Bn254>,
-    rng: ThreadRng,
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
 }
 
-impl StorageProofs {
-    // TODO: add rng
-    pub fn new(
-        wtns: String,
-        r1cs: String,
-        zkey: Option<String>, /* , rng: Option<ThreadRng> */
-    ) -> Self {
-        let mut rng = ThreadRng::default();
-        let builder = CircomBuilder::new(CircomConfig::<Bn254>::new(wtns, r1cs).unwrap());
-        let params: ProvingKey<Bn254> = match zkey {
-            Some(zkey) => {
-                let mut file = File::open(zkey).unwrap();
-                read_zkey(&mut file).unwrap().0
-            }
-            None => generate_random_parameters::<Bn254, _, _>(builder.setup(), &mut rng).unwrap(),
-        };
+impl DerefMut for Inputs {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+/// Overwrites every value with zeros, num-bigint has no `Zeroize` of its own
+impl Zeroize for Inputs {
+    fn zeroize(&mut self) {
+        self.0.values_mut().flatten().for_each(zeroize_bigint);
+        self.0.clear();
+    }
+}
+
+/// Overwrite the digits of `n` in place before setting it to zero. No more digits are
+/// written than `n` already has, so its storage isn't reallocated (and left behind).
+fn zeroize_bigint(n: &mut BigInt) {
+    let digits = (n.bits() as usize + 31) / 32;
+    n.assign_from_slice(Sign::Plus, &vec![0; digits]);
+}
+
+/// Number of field elements hashed at once by the chunk digest (`DIGEST_CHUNK`)
+pub const DIGEST_CHUNK: usize = 16;
+
+/// Arity of the Merkle tree checked by the storer circuit
+pub const TREE_ARITY: usize = 2;
+
+/// BN254 scalar field modulus, public inputs must be strictly smaller
+pub const FIELD_MODULUS: U256 =
+    uint!(21888242871839275222246405745257275088548364400416034343698204186575808495617_U256);
+
+/// Why `verify_detailed` rejected a proof
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifyFailure {
+    /// index of the first public input that isn't a valid field element, if the
+    /// failure can be attributed to a single input
+    pub index: Option<usize>,
+    /// the public inputs decoded as little endian `U256`s
+    pub inputs: Vec<U256>,
+    pub reason: String,
+}
+
+/// Shape of the storer circuit, see `StorageProver` in `circuits/storer.circom`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CircuitParams {
+    /// number of field elements per chunk (`BLOCK_SIZE`)
+    pub chunk_len: usize,
+    /// depth of the Merkle tree (`LEVELS`)
+    pub tree_depth: usize,
+    /// number of chunks proven at once (`QUERY_LEN`)
+    pub n_samples: usize,
+    /// how chunks are digested (`DIGEST_CHUNK`)
+    pub digest_config: DigestConfig,
+    /// hash of the chunk digests and the tree, which must be the one the circuit uses.
+    /// The r1cs doesn't say, `check_artifacts` finds out by calculating a witness.
+    pub hash: HashKind,
+    /// names of the circuit's input signals
+    pub signals: SignalMap,
+}
+
+/// Names of the circuit's input signals, for circuits whose `main` renames those of
+/// `StorageProver`, e.g. `samples` for `chunks`. Defaults to the `StorageProver` names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SignalMap {
+    pub chunks: &'static str,
+    pub siblings: &'static str,
+    pub hashes: &'static str,
+    pub path: &'static str,
+    pub root: &'static str,
+    pub salt: &'static str,
+}
 
+impl SignalMap {
+    /// All the signal names, in field order
+    pub fn names(&self) -> [&'static str; 6] {
+        [
+            self.chunks,
+            self.siblings,
+            self.hashes,
+            self.path,
+            self.root,
+            self.salt,
+        ]
+    }
+}
+
+impl Default for SignalMap {
+    fn default() -> Self {
         Self {
-            builder,
-            params,
-            rng,
+            chunks: "chunks",
+            siblings: "siblings",
+            hashes: "hashes",
+            path: "path",
+            root: "root",
+            salt: "salt",
         }
     }
+}
 
-    pub fn prove_mpack(
-        &mut self,
-        inputs: &[u8],
-        proof_bytes: &mut Vec<u8>,
-        public_inputs_bytes: &mut Vec<u8>,
-    ) -> Result<(), String> {
-        let mut builder: CircomBuilder<Params256Ty> = self.builder.clone();
+/// How a circuit receives the sampled chunks, told apart by its number of private inputs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkInputs {
+    /// the chunks themselves, digested in the circuit and checked against `hashes`
+    /// (`StorageProver`)
+    Raw,
+    /// only their `hashes`, which are trusted as given; `chunks` must be left out
+    /// (`StorageProverHashed`)
+    Hashed,
+}
 
-        parse_mpack_args(&mut builder, inputs)?;
+impl Default for CircuitParams {
+    /// parameters of the `storer-test` circuit
+    fn default() -> Self {
+        Self {
+            chunk_len: 256,
+            tree_depth: 2,
+            n_samples: 4,
+            digest_config: DigestConfig::default(),
+            hash: HashKind::Poseidon,
+            signals: SignalMap::default(),
+        }
+    }
+}
 
-        let circuit: CircomCircuit<Params256Ty> = builder.build()
-            .map_err(|e| e.to_string())?;
+impl CircuitParams {
+    /// Number of private inputs - chunks, siblings, path and hashes - per proof
+    pub fn private_inputs(&self) -> usize {
+        self.n_samples * (self.chunk_len + self.tree_depth + 2)
+    }
 
-        let inputs = circuit
-            .get_public_inputs()
-            .ok_or("Unable to get public inputs!")?;
-        let proof =
-            prove(circuit, &self.params, &mut self.rng)
-            .map_err(|e| e.to_string())?;
+    /// Digest a single chunk, which must be exactly `chunk_len` elements long
+    pub fn digest(&self, chunk: &[U256]) -> Result<U256, String> {
+        if chunk.len() != self.chunk_len {
+            return Err(format!(
+                "expected chunk of {} elements, got {}",
+                self.chunk_len,
+                chunk.len()
+            ));
+        }
 
-        proof
-            .serialize(proof_bytes)
-            .map_err(|e| e.to_string())?;
-        inputs
-            .serialize(public_inputs_bytes)
-            .map_err(|e| e.to_string())?;
+        Ok(digest_with_hash(chunk, &self.digest_config, &self.hash))
+    }
+
+    /// The Merkle tree of the chunk digests `leaves`, padded with `PADDING_LEAF` to the
+    /// `tree_depth` of the circuit. Errors if there are more leaves than the circuit's tree
+    /// holds.
+    pub fn tree(&self, leaves: &[U256]) -> Result<MerkleTree<HashKind>, String> {
+        MerkleTree::with_hash_and_depth(leaves, TREE_ARITY, self.tree_depth, self.hash)
+    }
+
+    /// Number of private inputs of a circuit taking the chunk hashes directly
+    pub fn hashed_private_inputs(&self) -> usize {
+        self.n_samples * (self.tree_depth + 2)
+    }
+
+    /// Check `inputs` holds `n_samples` complete openings. `chunks` may be empty, for
+    /// circuits taking the hashes directly.
+    pub fn check_inputs(&self, inputs: &ProofInputs) -> Result<(), String> {
+        let chunks = match inputs.chunks.len() {
+            0 => 0,
+            _ => self.n_samples * self.chunk_len,
+        };
+        let expected = [
+            ("chunks", inputs.chunks.len(), chunks),
+            ("siblings", inputs.siblings.len(), self.n_samples * self.tree_depth),
+            ("hashes", inputs.hashes.len(), self.n_samples),
+            ("path", inputs.path.len(), self.n_samples),
+        ];
+
+        for (name, actual, expected) in expected {
+            if actual != expected {
+                return Err(format!(
+                    "circuit params {:?} expect {} {} for {} samples, got {}",
+                    self, expected, name, self.n_samples, actual
+                ));
+            }
+        }
 
         Ok(())
     }
 
-    pub fn prove(
-        &mut self,
-        chunks: &[U256],
-        siblings: &[U256],
-        hashes: &[U256],
-        path: &[i32],
-        root: U256,
-        salt: U256,
-        proof_bytes: &mut Vec<u8>,
-        public_inputs_bytes: &mut Vec<u8>,
-    ) -> Result<(), String> {
-        let mut builder = self.builder.clone();
+    fn check_r1cs(&self, r1cs: &R1CSFile<Bn254>) -> Result<ChunkInputs, String> {
+        match r1cs.header.n_prv_in as usize {
+            n if n == self.private_inputs() => Ok(ChunkInputs::Raw),
+            n if n == self.hashed_private_inputs() => Ok(ChunkInputs::Hashed),
+            n => Err(format!(
+                "circuit params {:?} expect {} private inputs ({} when taking the hashes \
+                 directly), but the r1cs has {}",
+                self,
+                self.private_inputs(),
+                self.hashed_private_inputs(),
+                n
+            )),
+        }
+    }
+}
 
-        // vec of vecs is flattened, since wasm expects a contiguous array in memory
-        chunks.iter().for_each(|c| builder.push_input("chunks", *c));
+/// Malformed `prove_mpack` arguments
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MpackError {
+    Decode(String),
+    NotAMap,
+    MissingKey(&'static str),
+    /// the value under this key has the wrong mpack kind
+    InvalidKind(&'static str),
+    InvalidElement {
+        key: String,
+        index: usize,
+        reason: String,
+    },
+    ChunkLength {
+        index: usize,
+        expected: usize,
+        actual: usize,
+    },
+    FieldElementOutOfRange(FieldElementOutOfRange),
+}
 
-        siblings
-            .iter()
-            .for_each(|c| builder.push_input("siblings", *c));
+impl fmt::Display for MpackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MpackError::Decode(e) => write!(f, "unable to decode mpack: {}", e),
+            MpackError::NotAMap => write!(f, "args must be a map of string to arrays"),
+            MpackError::MissingKey(key) => write!(f, "missing required key {}", key),
+            MpackError::InvalidKind(key) => write!(f, "unexpected mpack kind for key {}", key),
+            MpackError::InvalidElement { key, index, reason } => {
+                write!(f, "invalid element {} of {}: {}", index, key, reason)
+            }
+            MpackError::ChunkLength {
+                index,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "chunk {} has {} elements, expected {}",
+                index, actual, expected
+            ),
+            MpackError::FieldElementOutOfRange(e) => write!(f, "{}", e),
+        }
+    }
+}
 
-        hashes.iter().for_each(|c| builder.push_input("hashes", *c));
-        path.iter().for_each(|c| builder.push_input("path", *c));
+/// What to do with input field elements that aren't below `FIELD_MODULUS`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FieldMode {
+    /// reject them with `FieldElementOutOfRange`
+    #[default]
+    Strict,
+    /// reduce them modulo the field, which is what the circuit would compute with
+    Reduce,
+}
 
-        builder.push_input("root", root);
-        builder.push_input("salt", salt);
+/// An input field element that is `FIELD_MODULUS` or larger, found by
+/// `ProofInputs::canonicalize`. The value itself isn't kept, it may be private data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldElementOutOfRange {
+    /// `chunks`, `siblings`, `hashes`, `root`, `salt` or `pubkey`
+    pub key: &'static str,
+    /// position within the flattened `key`, 0 for single values
+    pub index: usize,
+}
 
-        let circuit = builder.build().map_err(|e| e.to_string())?;
-        let inputs = circuit
-            .get_public_inputs()
-            .ok_or("Unable to get public inputs!")?;
-        let proof = prove(circuit, &self.params, &mut self.rng).map_err(|e| e.to_string())?;
+impl fmt::Display for FieldElementOutOfRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "element {} of {} is not below the field modulus",
+            self.index, self.key
+        )
+    }
+}
 
-        proof.serialize(proof_bytes).map_err(|e| e.to_string())?;
-        inputs
-            .serialize(public_inputs_bytes)
-            .map_err(|e| e.to_string())?;
+/// The first r1cs constraint a witness doesn't satisfy, see `unsatisfied_constraint`
+#[cfg(feature = "debug")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnsatisfiedConstraint {
+    /// position of the constraint in the r1cs
+    pub index: usize,
+    /// the linear combinations of the constraint evaluated on the witness, `a * b != c`
+    pub a: U256,
+    pub b: U256,
+    pub c: U256,
+}
 
-        Ok(())
+#[cfg(feature = "debug")]
+impl fmt::Display for UnsatisfiedConstraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "constraint {} is not satisfied by the witness: {} * {} != {}",
+            self.index, self.a, self.b, self.c
+        )
+    }
+}
+
+/// Steps of `prove_with_progress`, reported in this order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub enum ProvePhase {
+    WitnessStart,
+    WitnessDone,
+    ProveStart,
+    ProveDone,
+}
+
+/// Why proving (`prove_inputs` and the other `prove` variants) didn't return a proof
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProveError {
+    /// the progress callback asked to stop at the given phase
+    Cancelled(ProvePhase),
+    /// loaded with `StorageProofs::without_proving_key` and no key set since
+    NoProvingKey,
+    /// constructed with `StorageProofs::verifier_only`, without a circuit to prove with
+    NotAProver,
+    /// the circuit's wasm ran out of memory (or stack) calculating the witness
+    WitnessMemoryExhausted,
+    /// `prove_absence` with a circuit that doesn't take `AbsenceInputs`
+    AbsenceCircuitRequired,
+    /// `prove_raw_inputs` was given a signal that isn't in the circuit params' `SignalMap`
+    UnknownSignal(String),
+    Failed(String),
+}
+
+impl fmt::Display for ProveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProveError::Cancelled(phase) => write!(f, "proving cancelled at {:?}", phase),
+            ProveError::NoProvingKey => write!(f, "no proving key, see `set_proving_key`"),
+            ProveError::NotAProver => write!(f, "verifier only instance, can't prove"),
+            ProveError::WitnessMemoryExhausted => write!(
+                f,
+                "witness calculation ran out of wasm memory, use a circuit with a smaller \
+                 `chunk_len` or `n_samples`"
+            ),
+            ProveError::AbsenceCircuitRequired => write!(
+                f,
+                "the loaded circuit doesn't prove absence, load a non-membership circuit"
+            ),
+            ProveError::UnknownSignal(name) => {
+                write!(f, "the circuit has no input signal {}", name)
+            }
+            ProveError::Failed(e) => write!(f, "{}", e),
+        }
     }
+}
 
-    pub fn verify<RR: Read>(
-        &mut self,
-        proof_bytes: RR,
-        mut public_inputs: RR,
-    ) -> Result<(), String> {
-        let inputs: Vec<Fr> =
-            CanonicalDeserialize::deserialize(&mut public_inputs).map_err(|e| e.to_string())?;
-        let proof = Proof::<Bn254>::deserialize(proof_bytes).map_err(|e| e.to_string())?;
-        let vk = prepare_verifying_key(&self.params.vk);
+impl From<ProveError> for String {
+    fn from(e: ProveError) -> Self {
+        e.to_string()
+    }
+}
+
+/// Why a `StorageProofs` couldn't be constructed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadError {
+    /// the wasm or r1cs can't be read, or don't describe a circuit this crate can prove
+    Circuit(String),
+    /// the zkey or verification key can't be read, or no key could be generated
+    Key(String),
+}
 
-        verify_proof(&vk, &proof, inputs.as_slice()).map_err(|e| e.to_string())?;
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Circuit(e) | LoadError::Key(e) => write!(f, "{}", e),
+        }
+    }
+}
 
-        Ok(())
+impl From<LoadError> for String {
+    fn from(e: LoadError) -> Self {
+        e.to_string()
     }
 }
 
-fn decode_number(val: &rmpv::Value) -> Result<U256, String> {
-    match val {
-        rmpv::Value::Ext(id, val) => {
-            match *id {
-                EXT_ID_U256_LE =>
-                    match U256::try_from_le_slice(val) {
-                        Some(i) => Ok(i),
-                        None => Err("error parsing 256".to_string()),
-                    }
-                num => return Err(format!("unhandled ext id {}", num)),
+/// A proof running on a background thread, see `StorageProofs::prove_async`. Dropping the
+/// handle before taking the proof cancels it: proving stops at the next `ProvePhase` and
+/// the thread exits on its own, dropping the (zeroized) inputs.
+#[derive(Debug)]
+pub struct ProveHandle {
+    state: Arc<AsyncProof>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+#[derive(Debug, Default)]
+struct AsyncProof {
+    result: Mutex<Option<Result<ProofOutput, ProveError>>>,
+    ready: AtomicBool,
+    cancelled: AtomicBool,
+}
+
+impl AsyncProof {
+    fn finish(&self, result: Result<ProofOutput, ProveError>) {
+        if let Ok(mut slot) = self.result.lock() {
+            *slot = Some(result);
+        }
+        self.ready.store(true, Ordering::Release);
+    }
+}
+
+impl ProveHandle {
+    /// Whether proving is done, successfully or not, so `try_take` has the outcome
+    pub fn is_ready(&self) -> bool {
+        self.state.ready.load(Ordering::Acquire)
+    }
+
+    /// The outcome once ready, `None` while proving is still running or after it was taken
+    pub fn try_take(&mut self) -> Option<Result<ProofOutput, ProveError>> {
+        if !self.is_ready() {
+            return None;
+        }
+
+        self.state.result.lock().ok()?.take()
+    }
+
+    /// Block until proving is done and return the outcome
+    pub fn join(mut self) -> Result<ProofOutput, ProveError> {
+        if let Some(thread) = self.thread.take() {
+            thread
+                .join()
+                .map_err(|_| ProveError::Failed("proving thread panicked".to_string()))?;
+        }
+
+        self.try_take()
+            .unwrap_or_else(|| Err(ProveError::Failed("proof was already taken".to_string())))
+    }
+}
+
+impl Drop for ProveHandle {
+    fn drop(&mut self) {
+        self.state.cancelled.store(true, Ordering::Release);
+    }
+}
+
+/// Stages of `StorageProofs::self_test`, run in this order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub enum SelfTestStage {
+    /// `check_artifacts`
+    Artifacts,
+    /// building the built-in inputs for the circuit params
+    Inputs,
+    Witness,
+    Prove,
+    Verify,
+}
+
+/// The first stage `StorageProofs::self_test` failed at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelfTestFailure {
+    pub stage: SelfTestStage,
+    pub reason: String,
+}
+
+impl fmt::Display for SelfTestFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "self test failed at {:?}: {}", self.stage, self.reason)
+    }
+}
+
+/// Why `StorageProofs::get_public_signal` couldn't read a signal
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PublicSignalError {
+    /// not a name in `StorageProofs::public_input_layout`
+    NoSuchSignal(String),
+    /// the signal is an array of `len` elements, read with `get_public_signal_array`
+    Array { name: String, len: usize },
+    /// the public inputs aren't serialized as expected, or too short to hold the signal
+    Decode(String),
+}
+
+impl fmt::Display for PublicSignalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PublicSignalError::NoSuchSignal(name) => write!(f, "no public signal {}", name),
+            PublicSignalError::Array { name, len } => {
+                write!(f, "public signal {} is an array of {} elements", name, len)
             }
-        },
-        rmpv::Value::Integer(val) => {
-            if let Some(val) = val.as_u64() {
-                return Ok(U256::from(val));
-            } else if let Some(val) = val.as_i64() {
-                return Ok(U256::from(val));
-            } else {
-                return Err("unexpected integer kind".to_string());
+            PublicSignalError::Decode(e) => write!(f, "unable to decode public inputs: {}", e),
+        }
+    }
+}
+
+/// A `path` of leaf indices the circuit can't open, see `StorageProofs::check_path`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvalidPath {
+    /// the circuit takes one index per sample
+    Length { expected: usize, actual: usize },
+    Negative { position: usize, index: i32 },
+    /// past the last of the tree's `leaves`
+    OutOfRange {
+        position: usize,
+        index: i32,
+        leaves: usize,
+    },
+}
+
+impl fmt::Display for InvalidPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidPath::Length { expected, actual } => {
+                write!(f, "expected a path of {} indices, got {}", expected, actual)
+            }
+            InvalidPath::Negative { position, index } => {
+                write!(f, "path index {} at position {} is negative", index, position)
             }
+            InvalidPath::OutOfRange {
+                position,
+                index,
+                leaves,
+            } => write!(
+                f,
+                "path index {} at position {} is out of range for a tree of {} leaves",
+                index, position, leaves
+            ),
         }
-        _ => return Err("expected ext mpack kind or integer".to_string()),
     }
 }
 
-fn parse_mpack_arrays(
-    builder: &mut CircomBuilder<Params256Ty>,
-    name: &str,
-    array: &Vec<rmpv::Value>
-) -> Result<(), String> {
-
-    println!("deserde: array: {} size: {}", name, array.len());
-    if array.len() > 0 && array[0].is_array() {
-        println!("deserde: arrayOfArrays: {}", name);
-        for element in array {
-            match element .as_array() {
-                Some(element ) => {
-                    parse_mpack_arrays(builder, name, element)?;
-                },
-                _ => {
-                    print!("error expected array: {}", name);
-                    return Err("expected inner array of u256".to_string())
-                },
+/// Why `StorageProofs::verify_for_salt` rejected a proof
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyError {
+    /// the proof is for another salt (block hash) than the expected one, e.g. replayed
+    /// from an earlier block
+    SaltMismatch { expected: U256, actual: U256 },
+    /// the proof doesn't verify, or the arguments are malformed
+    Invalid(String),
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyError::SaltMismatch { expected, actual } => {
+                write!(f, "proof is for salt {}, expected {}", actual, expected)
             }
+            VerifyError::Invalid(e) => write!(f, "{}", e),
         }
-    } else {
-        println!("deserde: name: {}", name);
-        for val in array {
-            let n = decode_number(val)?;
-            println!("\t{}", n);
-            builder.push_input(name, n);
+    }
+}
+
+/// Public inputs of the storer circuit, in the order of its `main { public [root, salt] }`
+pub const STORER_PUBLIC_SIGNALS: [&str; 2] = ["root", "salt"];
+
+/// A public signal of the circuit and where its value sits in serialized public inputs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublicSignal {
+    pub name: String,
+    /// byte offset of the little endian value, past the `u64` length prefix
+    pub offset: usize,
+    /// number of field elements, more than 1 for array signals, which take that many
+    /// consecutive values from `offset` on
+    pub len: usize,
+}
+
+/// The public inputs of a storer circuit proof, decoded from their serialized form.
+/// These are the circuit's `STORER_PUBLIC_SIGNALS`, nothing else is public.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PublicInputs {
+    /// root of the Merkle tree the proven chunks are in
+    pub root: U256,
+    /// the salt (block hash) the proof was made for
+    pub salt: U256,
+}
+
+impl PublicInputs {
+    /// Decode public inputs serialized by `prove` and friends, which must hold exactly
+    /// the storer circuit's signals
+    pub fn decode(public_inputs: &[u8]) -> Result<Self, String> {
+        match decode_public_inputs(public_inputs)?[..] {
+            [root, salt] => Ok(Self { root, salt }),
+            ref signals => Err(format!(
+                "expected the {} storer circuit public inputs, got {}",
+                STORER_PUBLIC_SIGNALS.len(),
+                signals.len()
+            )),
         }
-        println!("done: name: {}", name);
     }
+}
 
-    Ok(())
+/// Keccak-256 of the artifacts a `StorageProofs` was loaded from
+#[derive(Debug, Clone, Copy, Default)]
+struct ArtifactDigests {
+    wasm: [u8; 32],
+    r1cs: [u8; 32],
+    /// `None` for proving keys that didn't come from a zkey
+    zkey: Option<[u8; 32]>,
 }
 
-fn parse_mpack_args(
-    builder: &mut CircomBuilder<Params256Ty>,
-    mut inputs: &[u8]
-) -> Result<(), String> {
-    let values: rmpv::Value = read_value(&mut inputs).map_err(|e| e.to_string())?;
-    let args: &Vec<(rmpv::Value, rmpv::Value)> = match values.as_map() {
-        Some(args) => args,
-        None => return Err("args must be a map of string to arrays".to_string()),
-    };
+/// Size of a loaded circuit, as read from its r1cs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[repr(C)]
+pub struct CircuitInfo {
+    pub num_constraints: usize,
+    pub num_public_inputs: usize,
+    /// wires of the circuit, i.e. the length of each witness, the constant `1` included
+    pub num_witness: usize,
+    pub num_private_inputs: usize,
+}
+
+/// A single sampled cell and its Merkle path, one of the `n_samples` openings checked by a proof
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CellOpening {
+    pub chunk: Vec<U256>,
+    /// digest of `chunk`, the leaf of the tree
+    pub hash: U256,
+    /// `tree_depth` sibling hashes, starting at the leaf
+    pub siblings: Vec<U256>,
+    /// index of the leaf in the tree
+    pub index: i32,
+}
 
-    for (key, val) in args {
-        let name = match key.as_str() {
-            Some(n) => n,
-            None => return Err(format!("expected string value")),
+/// Inputs to a proof that no cell is stored at `index` of a binary tree padded with
+/// `PADDING_LEAF`, for a non-membership circuit: the occupied leaves on either side of the
+/// gap `index` falls in, opened against `root`. The circuit checks both openings and that
+/// every leaf between them is padding. No such circuit ships with this crate yet, see
+/// `StorageProofs::prove_absence`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AbsenceInputs {
+    pub index: usize,
+    /// the closest occupied leaf before `index`, its `chunk` isn't needed
+    pub left: CellOpening,
+    /// the closest occupied leaf after `index`, `None` if the gap runs to the end of the
+    /// tree
+    pub right: Option<CellOpening>,
+    pub root: Root,
+    pub salt: Salt,
+}
+
+impl AbsenceInputs {
+    /// Inputs of the non-membership circuit, the private ones followed by `root` and
+    /// `salt`, for a tree as deep as `left` has siblings
+    pub fn signal_count(tree_depth: usize) -> usize {
+        5 + 2 * tree_depth + 2
+    }
+
+    /// Pack the inputs into the signals of the non-membership circuit, as taken by
+    /// `StorageProofs::prove_raw_inputs`: `index`, then `leftIndex`, `leftHash` and
+    /// `leftSiblings`, the same for the right neighbor, and `root` and `salt`. Without a
+    /// right neighbor its index is the number of leaves of the tree, its hash and
+    /// siblings `PADDING_LEAF`. The neighbors are checked to be occupied and on either
+    /// side of `index`, their openings are left to the circuit.
+    pub fn raw_inputs(&self) -> Result<HashMap<String, Vec<U256>>, String> {
+        let depth = self.left.siblings.len();
+        // leaf indices are `i32`s, as in `CellOpening`
+        if depth >= 31 {
+            return Err(format!("a tree of depth {} is too deep", depth));
+        }
+        let leaves = 1usize << depth;
+        if self.index >= leaves {
+            return Err(format!(
+                "index {} is out of range for a tree of {} leaves",
+                self.index, leaves
+            ));
+        }
+
+        let padding = CellOpening {
+            siblings: vec![PADDING_LEAF; depth],
+            index: leaves as i32,
+            ..Default::default()
         };
-        match val {
-            // add a (name, Vec<u256>) or (name, Vev<Vec<u256>>) arrays
-            rmpv::Value::Array(vals) => {
-                parse_mpack_arrays(builder, name, vals)?;
-            },
-            // directly add a (name,u256) arg pair 
-            rmpv::Value::Ext(_, _) => {
-                let n = decode_number(val)?;
-                println!("deserde: name: {} u256: {}", name, n);
-                builder.push_input(name, n);
-            },
-            _ => return Err("unhandled argument kind".to_string()),
+        let right = self.right.as_ref().unwrap_or(&padding);
+        for (side, neighbor) in [("left", &self.left), ("right", right)] {
+            if neighbor.siblings.len() != depth {
+                return Err(format!(
+                    "the {} neighbor has {} siblings, expected {}",
+                    side,
+                    neighbor.siblings.len(),
+                    depth
+                ));
+            }
+        }
+        let occupied = |n: &CellOpening| n.hash != PADDING_LEAF;
+        if !occupied(&self.left) || !self.right.as_ref().map_or(true, occupied) {
+            return Err("the neighbors must be occupied leaves".to_string());
+        }
+        let before = self.left.index >= 0 && (self.left.index as usize) < self.index;
+        let after = right.index >= 0 && right.index as usize > self.index;
+        if !(before && after) {
+            return Err(format!(
+                "index {} isn't between its neighbors at {} and {}",
+                self.index, self.left.index, right.index
+            ));
+        }
+
+        Ok(HashMap::from([
+            ("index".to_string(), vec![U256::from(self.index)]),
+            ("leftIndex".to_string(), vec![U256::from(self.left.index)]),
+            ("leftHash".to_string(), vec![self.left.hash]),
+            ("leftSiblings".to_string(), self.left.siblings.clone()),
+            ("rightIndex".to_string(), vec![U256::from(right.index)]),
+            ("rightHash".to_string(), vec![right.hash]),
+            ("rightSiblings".to_string(), right.siblings.clone()),
+            ("root".to_string(), vec![self.root.0]),
+            ("salt".to_string(), vec![self.salt.0]),
+        ]))
+    }
+}
+
+/// Where the inputs of `StorageProofs::prove_from_mmap` are in a file, as byte offsets of
+/// back to back sections. Field elements are 32 byte little endian values and path indices
+/// 4 byte little endian `i32`s; offsets needn't be aligned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MmapLayout {
+    pub n_samples: usize,
+    /// elements per chunk, 0 for circuits taking only the hashes
+    pub chunk_len: usize,
+    pub tree_depth: usize,
+    /// `n_samples` chunks of `chunk_len` elements
+    pub chunks: u64,
+    /// `tree_depth` siblings per sample
+    pub siblings: u64,
+    pub hashes: u64,
+    pub path: u64,
+    pub root: u64,
+    pub salt: u64,
+}
+
+impl MmapLayout {
+    /// Sections in field order from the start of the file, chunks first and salt last, for
+    /// a circuit described by `params`
+    pub fn packed(params: &CircuitParams) -> Self {
+        let element = U256::BYTES as u64;
+        let chunks = 0;
+        let siblings = chunks + (params.n_samples * params.chunk_len) as u64 * element;
+        let hashes = siblings + (params.n_samples * params.tree_depth) as u64 * element;
+        let path = hashes + params.n_samples as u64 * element;
+        let root = path + params.n_samples as u64 * 4;
+
+        Self {
+            n_samples: params.n_samples,
+            chunk_len: params.chunk_len,
+            tree_depth: params.tree_depth,
+            chunks,
+            siblings,
+            hashes,
+            path,
+            root,
+            salt: root + element,
+        }
+    }
+
+    /// Size of a file holding the `packed` layout
+    pub fn file_len(&self) -> u64 {
+        self.salt + U256::BYTES as u64
+    }
+}
+
+/// Root of the Merkle tree of the proven dataset, typed so it can't be passed as a salt
+/// or public key by mistake
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Root(pub U256);
+
+/// Salt a proof is made for (the block hash), see `Root`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Salt(pub U256);
+
+/// Public key a proof is made by, not (yet) constrained by the circuit, see `Root`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct PubKey(pub U256);
+
+impl Root {
+    /// The root given as a big endian 32 byte hash, the way most hash functions output it
+    pub fn from_be_hash(hash: [u8; 32]) -> Self {
+        Root(U256::from_be_bytes(hash))
+    }
+}
+
+impl Salt {
+    /// The salt given as a big endian 32 byte hash, e.g. the keccak of a block
+    pub fn from_be_hash(hash: [u8; 32]) -> Self {
+        Salt(U256::from_be_bytes(hash))
+    }
+}
+
+impl PubKey {
+    /// The public key given as big endian bytes, see `Root::from_be_hash`
+    pub fn from_be_hash(hash: [u8; 32]) -> Self {
+        PubKey(U256::from_be_bytes(hash))
+    }
+}
+
+/// Inputs to a single storage proof, the openings of all sampled cells flattened.
+///
+/// The chunk preimages and salt are sensitive, wrap inputs in `zeroize::Zeroizing` to
+/// have them wiped when dropped. The proving functions wipe the copies they decode and
+/// the witness they hold themselves; the copies made by the witness calculator and the
+/// arkworks prover are out of reach and aren't wiped.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct ProofInputs {
+    pub chunks: Vec<U256>,
+    pub siblings: Vec<U256>,
+    pub hashes: Vec<U256>,
+    pub path: Vec<i32>,
+    /// computed from the hashes and their paths when not given, checked against them otherwise
+    pub root: Option<U256>,
+    pub salt: U256,
+    /// not (yet) constrained by the circuit
+    pub pubkey: U256,
+}
+
+/// Shows the chunks, siblings and hashes by their sizes and leaves out the salt, so inputs
+/// can be logged without leaking them
+impl fmt::Debug for ProofInputs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProofInputs")
+            .field("chunks", &format_args!("[{} elements]", self.chunks.len()))
+            .field("siblings", &format_args!("[{} elements]", self.siblings.len()))
+            .field("hashes", &format_args!("[{} elements]", self.hashes.len()))
+            .field("path", &self.path)
+            .field("root", &self.root)
+            .field("salt", &"<redacted>")
+            .field("pubkey", &self.pubkey)
+            .finish()
+    }
+}
+
+/// Overwrites every value with zeros and empties the vectors, keeping their allocations
+impl Zeroize for ProofInputs {
+    fn zeroize(&mut self) {
+        zeroize_u256s(&mut self.chunks);
+        zeroize_u256s(&mut self.siblings);
+        zeroize_u256s(&mut self.hashes);
+        self.path.zeroize();
+        if let Some(root) = self.root.as_mut() {
+            zeroize_u256(root);
+        }
+        self.root = None;
+        zeroize_u256(&mut self.salt);
+        zeroize_u256(&mut self.pubkey);
+    }
+}
+
+fn zeroize_u256(n: &mut U256) {
+    // SAFETY: every bit pattern of the limbs is a valid U256
+    unsafe { n.as_limbs_mut() }.zeroize();
+}
+
+/// Check or reduce the `values` of `key` as `ProofInputs::canonicalize` does, `offset` being
+/// the position of the first one within `key`
+fn canonicalize_field(
+    key: &'static str,
+    values: &mut [U256],
+    offset: usize,
+    mode: FieldMode,
+) -> Result<(), FieldElementOutOfRange> {
+    for (index, n) in values.iter_mut().enumerate() {
+        if *n < FIELD_MODULUS {
+            continue;
+        }
+
+        match mode {
+            FieldMode::Strict => {
+                return Err(FieldElementOutOfRange {
+                    key,
+                    index: offset + index,
+                })
+            }
+            FieldMode::Reduce => *n = n.reduce_mod(FIELD_MODULUS),
         }
     }
 
-    println!("parse_mpack_args DONE!");
     Ok(())
 }
+
+pub(crate) fn zeroize_u256s(ns: &mut Vec<U256>) {
+    ns.iter_mut().for_each(zeroize_u256);
+    ns.clear();
+}
+
+impl ProofInputs {
+    /// Parse a `prove_json` document, see `StorageProofs::prove_json`. Field elements
+    /// must be below the modulus (`FieldMode::Strict`).
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        decode_json_args(json, None, FieldMode::Strict)
+    }
+
+    /// Check every field element is below `FIELD_MODULUS`, or with `FieldMode::Reduce`
+    /// reduce those that aren't. Returns the first element out of range in strict mode.
+    pub fn canonicalize(&mut self, mode: FieldMode) -> Result<(), FieldElementOutOfRange> {
+        let root: &mut [U256] = match &mut self.root {
+            Some(root) => std::slice::from_mut(root),
+            None => &mut [],
+        };
+        let fields: [(&'static str, &mut [U256]); 6] = [
+            ("chunks", &mut self.chunks),
+            ("siblings", &mut self.siblings),
+            ("hashes", &mut self.hashes),
+            ("root", root),
+            ("salt", std::slice::from_mut(&mut self.salt)),
+            ("pubkey", std::slice::from_mut(&mut self.pubkey)),
+        ];
+
+        for (key, values) in fields {
+            canonicalize_field(key, values, 0, mode)?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether every field element is below `FIELD_MODULUS`
+    fn is_canonical(&self) -> bool {
+        self.chunks
+            .iter()
+            .chain(&self.siblings)
+            .chain(&self.hashes)
+            .chain(&self.root)
+            .chain([&self.salt, &self.pubkey])
+            .all(|n| *n < FIELD_MODULUS)
+    }
+
+    /// Inputs from their fields, with the public ones typed so they can't be swapped. The
+    /// fields themselves are plain `U256`s, prefer this over a struct literal.
+    pub fn new(
+        chunks: Vec<U256>,
+        siblings: Vec<U256>,
+        hashes: Vec<U256>,
+        path: Vec<i32>,
+        root: Option<Root>,
+        salt: Salt,
+        pubkey: PubKey,
+    ) -> Self {
+        ProofInputs {
+            chunks,
+            siblings,
+            hashes,
+            path,
+            root: root.map(|r| r.0),
+            salt: salt.0,
+            pubkey: pubkey.0,
+        }
+    }
+
+    /// Flatten the openings of several sampled cells into the layout the circuit expects
+    pub fn from_openings(openings: &[CellOpening], root: Option<Root>, salt: Salt) -> Self {
+        Self::new(
+            openings.iter().flat_map(|o| o.chunk.clone()).collect(),
+            openings.iter().flat_map(|o| o.siblings.clone()).collect(),
+            openings.iter().map(|o| o.hash).collect(),
+            openings.iter().map(|o| o.index).collect(),
+            root,
+            salt,
+            PubKey::default(),
+        )
+    }
+
+    /// Openings of the `samples`th of `chunks`, in the tree of their digests padded to the
+    /// circuit's `tree_depth` (see `CircuitParams::tree`), when the dataset has fewer
+    /// chunks than the tree has leaves. Only real chunks can be sampled.
+    pub fn from_chunks(
+        params: &CircuitParams,
+        chunks: &[Vec<U256>],
+        samples: &[usize],
+        salt: Salt,
+    ) -> Result<Self, String> {
+        let hashes = chunks
+            .iter()
+            .map(|c| params.digest(c))
+            .collect::<Result<Vec<U256>, String>>()?;
+        let tree = params.tree(&hashes)?;
+
+        let openings = samples
+            .iter()
+            .map(|&index| {
+                if index >= chunks.len() {
+                    return Err(format!(
+                        "sampled chunk {} out of range for {} chunks",
+                        index,
+                        chunks.len()
+                    ));
+                }
+                Ok(CellOpening {
+                    chunk: chunks[index].clone(),
+                    hash: hashes[index],
+                    siblings: tree.proof(index)?.siblings,
+                    index: index as i32,
+                })
+            })
+            .collect::<Result<Vec<CellOpening>, String>>()?;
+
+        Ok(Self::from_openings(&openings, Some(Root(tree.root())), salt))
+    }
+
+    /// Encode as the argument map expected by `prove_mpack`
+    pub fn to_mpack(&self) -> Vec<u8> {
+        self.to_mpack_with(Endianness::Little)
+    }
+
+    /// Same as `to_mpack`, with the field elements tagged and encoded in the given byte order
+    pub fn to_mpack_with(&self, endianness: Endianness) -> Vec<u8> {
+        let number = |n: &U256| encode_u256(n, endianness);
+        let numbers = |ns: &[U256]| rmpv::Value::Array(ns.iter().map(number).collect());
+
+        // one inner array per sampled chunk
+        let chunk_len = match self.hashes.len() {
+            0 => self.chunks.len().max(1),
+            n => (self.chunks.len() / n).max(1),
+        };
+        let chunks = rmpv::Value::Array(self.chunks.chunks(chunk_len).map(numbers).collect());
+        let path = rmpv::Value::Array(self.path.iter().map(|i| rmpv::Value::from(*i)).collect());
+
+        let mut args = vec![
+            ("chunks".into(), chunks),
+            ("siblings".into(), numbers(&self.siblings)),
+            ("hashes".into(), numbers(&self.hashes)),
+            ("path".into(), path),
+        ];
+        if let Some(root) = &self.root {
+            args.push(("root".into(), number(root)));
+        }
+        args.push(("salt".into(), number(&self.salt)));
+
+        encode_value(&rmpv::Value::Map(args))
+    }
+
+    /// Root the sampled hashes' Merkle paths lead to, which must match `root` if it's set.
+    /// With a `root`, each path is checked with `MerklePath::validate_ordering`, so siblings
+    /// in the wrong order are reported as such rather than as an invalid proof.
+    pub fn resolve_root(&self) -> Result<U256, String> {
+        self.resolve_root_with(&HashKind::Poseidon)
+    }
+
+    /// Same as `resolve_root`, for a tree hashed with `hash`
+    pub fn resolve_root_with<H: HashFn>(&self, hash: &H) -> Result<U256, String> {
+        if self.hashes.is_empty() || self.siblings.len() % self.hashes.len() != 0 {
+            return Err(format!(
+                "expected the same number of siblings for each of the {} hashes, got {}",
+                self.hashes.len(),
+                self.siblings.len()
+            ));
+        }
+
+        if self.path.len() != self.hashes.len() {
+            return Err(format!(
+                "expected a path index for each of the {} hashes, got {}",
+                self.hashes.len(),
+                self.path.len()
+            ));
+        }
+
+        let depth = self.siblings.len() / self.hashes.len();
+        if let Some(root) = self.root {
+            for (i, (leaf, index)) in self.hashes.iter().zip(self.path.iter()).enumerate() {
+                let index = usize::try_from(*index)
+                    .map_err(|_| format!("negative path index {} for hash {}", index, i))?;
+                let siblings = self.siblings[i * depth..(i + 1) * depth].to_vec();
+                MerklePath::new(index, siblings, TREE_ARITY)?
+                    .validate_ordering_with(*leaf, root, hash)
+                    .map_err(|e| format!("path of hash {}: {}", i, e))?;
+            }
+
+            return Ok(root);
+        }
+
+        let mut roots = self
+            .hashes
+            .iter()
+            .zip(self.path.iter())
+            .enumerate()
+            .map(|(i, (leaf, index))| {
+                let index = usize::try_from(*index)
+                    .map_err(|_| format!("negative path index {} for hash {}", index, i))?;
+                let siblings = &self.siblings[i * depth..(i + 1) * depth];
+
+                root_from_siblings_with(*leaf, index, siblings, TREE_ARITY, hash)
+            });
+
+        let root = roots.next().expect("hashes are not empty")?;
+        for (i, other) in roots.enumerate() {
+            if other? != root {
+                return Err(format!("path of hash {} leads to a different root than hash 0", i + 1));
+            }
+        }
+
+        Ok(root)
+    }
+}
+
+/// Serialized proof and public inputs
+#[derive(Debug, Clone, Default)]
+pub struct ProofOutput {
+    pub proof: Vec<u8>,
+    pub public_inputs: Vec<u8>,
+    /// how long proving took, `None` for proofs that were decoded rather than generated
+    #[cfg(feature = "metrics")]
+    pub timings: Option<ProveTimings>,
+}
+
+/// Outputs are equal when they hold the same proof, timings are ignored. Both buffers are
+/// canonically serialized, so this agrees with comparing `fingerprint`s.
+impl PartialEq for ProofOutput {
+    fn eq(&self, other: &Self) -> bool {
+        self.proof == other.proof && self.public_inputs == other.public_inputs
+    }
+}
+
+impl Eq for ProofOutput {}
+
+impl std::hash::Hash for ProofOutput {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.proof.hash(state);
+        self.public_inputs.hash(state);
+    }
+}
+
+/// Time spent in each phase of generating a proof
+#[cfg(feature = "metrics")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProveTimings {
+    /// copying the loaded r1cs into the circuit to prove and reading its public inputs
+    pub build_circuit: Duration,
+    /// witness calculation, zero when proving a precomputed witness
+    pub witness: Duration,
+    /// the Groth16 prover
+    pub prove: Duration,
+    pub total: Duration,
+}
+
+#[cfg(feature = "metrics")]
+impl ProveTimings {
+    /// Account for a witness calculation that took `witness` before proving started
+    pub fn with_witness(self, witness: Duration) -> Self {
+        Self {
+            witness,
+            total: self.total + witness,
+            ..self
+        }
+    }
+}
+
+/// Wire format of a `ProofOutput`, see `ProofOutput::to_bytes`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProofFormat {
+    Compressed,
+    Uncompressed,
+}
+
+/// First bytes of every serialized `ProofOutput`
+pub const PROOF_MAGIC: [u8; 4] = *b"CXSP";
+
+/// Version of the serialized proof layout written by this crate. Version 1 had the proof
+/// follow the header directly, version 2 prefixes it with its length.
+pub const PROOF_FORMAT_VERSION: u8 = 2;
+
+/// Oldest serialized proof version still read, see `migrate_proof`
+pub const MIN_PROOF_FORMAT_VERSION: u8 = 1;
+
+/// Proof system a serialized proof belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ProofScheme {
+    Groth16Bn254 = 0,
+}
+
+/// Header of a serialized `ProofOutput`: `PROOF_MAGIC`, then one byte each for the
+/// version, the scheme and flags, bit 0 telling whether the points are compressed and
+/// bit 1 whether the 32 byte `StorageProofs::config_digest` of the prover follows. Lets
+/// consumers of the raw bytes tell how to read them. From version 2 on, the proof after
+/// the header starts with its length as a little endian `u16`, so it can be split from
+/// the public inputs without decoding any points.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProofHeader {
+    pub version: u8,
+    pub scheme: ProofScheme,
+    pub format: ProofFormat,
+    /// digest of the artifacts the proof was made with, see `ProofOutput::to_bytes_with_config`
+    pub config_digest: Option<[u8; 32]>,
+}
+
+/// Flag bits of the last `ProofHeader` byte
+const HEADER_COMPRESSED: u8 = 1;
+const HEADER_CONFIG_DIGEST: u8 = 1 << 1;
+
+impl ProofHeader {
+    /// Length of a header without a config digest
+    pub const LEN: usize = PROOF_MAGIC.len() + 3;
+
+    /// Header of the current version for a Groth16 proof in `format`
+    pub fn new(format: ProofFormat) -> Self {
+        Self {
+            version: PROOF_FORMAT_VERSION,
+            scheme: ProofScheme::Groth16Bn254,
+            format,
+            config_digest: None,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = PROOF_MAGIC.to_vec();
+        let mut flags = 0;
+        if self.format == ProofFormat::Compressed {
+            flags |= HEADER_COMPRESSED;
+        }
+        if self.config_digest.is_some() {
+            flags |= HEADER_CONFIG_DIGEST;
+        }
+        bytes.extend([self.version, self.scheme as u8, flags]);
+        if let Some(digest) = &self.config_digest {
+            bytes.extend(digest);
+        }
+
+        bytes
+    }
+
+    /// Read the header off the front of `bytes`, returning it and the bytes after it
+    pub fn decode(bytes: &[u8]) -> Result<(Self, &[u8]), String> {
+        if bytes.len() < Self::LEN || bytes[..PROOF_MAGIC.len()] != PROOF_MAGIC {
+            return Err(format!(
+                "not a serialized proof, expected it to start with {:?}",
+                String::from_utf8_lossy(&PROOF_MAGIC)
+            ));
+        }
+
+        let (header, rest) = bytes.split_at(Self::LEN);
+        if header[4] > PROOF_FORMAT_VERSION {
+            return Err(format!(
+                "unsupported serialized proof version {}, newer than {}, the latest this \
+                 build reads",
+                header[4], PROOF_FORMAT_VERSION
+            ));
+        }
+        if header[4] < MIN_PROOF_FORMAT_VERSION {
+            return Err(format!(
+                "unsupported serialized proof version {}, expected {} to {}",
+                header[4], MIN_PROOF_FORMAT_VERSION, PROOF_FORMAT_VERSION
+            ));
+        }
+        let scheme = match header[5] {
+            0 => ProofScheme::Groth16Bn254,
+            scheme => return Err(format!("unknown proof scheme {}", scheme)),
+        };
+        let flags = header[6];
+        if flags & !(HEADER_COMPRESSED | HEADER_CONFIG_DIGEST) != 0 {
+            return Err(format!("invalid header flags {:#04x}", flags));
+        }
+        let format = match flags & HEADER_COMPRESSED {
+            0 => ProofFormat::Uncompressed,
+            _ => ProofFormat::Compressed,
+        };
+        let (config_digest, rest) = match flags & HEADER_CONFIG_DIGEST {
+            0 => (None, rest),
+            _ if rest.len() < 32 => {
+                return Err("proof header is missing its config digest".to_string())
+            }
+            _ => {
+                let (digest, rest) = rest.split_at(32);
+                (Some(digest.try_into().expect("32 bytes")), rest)
+            }
+        };
+
+        Ok((
+            Self {
+                version: header[4],
+                scheme,
+                format,
+                config_digest,
+            },
+            rest,
+        ))
+    }
+}
+
+impl ProofOutput {
+    /// Add the time the witness calculation took ahead of proving to the timings
+    #[cfg(feature = "metrics")]
+    pub(crate) fn with_witness_time(self, witness: Duration) -> Self {
+        Self {
+            timings: self.timings.map(|t| t.with_witness(witness)),
+            ..self
+        }
+    }
+
+    /// Stable content hash of the proof and public inputs, for deduplicating proofs. The
+    /// little endian bytes of `hash_bytes` over the proof and then the public inputs, each
+    /// preceded by its length as a little endian u64, so no bytes can move between them.
+    pub fn fingerprint(&self) -> [u8; 32] {
+        fingerprint(&self.proof, &self.public_inputs)
+    }
+
+    /// Encode as an mpack map, mirroring the `prove_mpack` arguments: `proof` holds the
+    /// serialized proof as binary, `public_inputs` an array of field elements tagged with
+    /// `EXT_ID_U256_LE`
+    pub fn to_mpack(&self) -> Vec<u8> {
+        // skip the length prefix, the array carries its own
+        let public_inputs = self
+            .public_inputs
+            .get(8..)
+            .unwrap_or_default()
+            .chunks(U256::BYTES)
+            .map(|i| encode_u256_le(&U256::from_le_slice(i)))
+            .collect();
+
+        encode_value(&rmpv::Value::Map(vec![
+            ("proof".into(), rmpv::Value::Binary(self.proof.clone())),
+            ("public_inputs".into(), rmpv::Value::Array(public_inputs)),
+        ]))
+    }
+
+    /// Decode the output of `to_mpack`
+    pub fn from_mpack(mut bytes: &[u8]) -> Result<Self, MpackError> {
+        let value = read_value(&mut bytes).map_err(|e| MpackError::Decode(e.to_string()))?;
+        let map = value.as_map().ok_or(MpackError::NotAMap)?;
+
+        let proof = match mpack_value(map, "proof")? {
+            rmpv::Value::Binary(proof) => proof.clone(),
+            _ => return Err(MpackError::InvalidKind("proof")),
+        };
+        let inputs = mpack_numbers("public_inputs", mpack_array(map, "public_inputs")?)?;
+
+        Ok(Self {
+            proof,
+            public_inputs: encode_public_inputs(&inputs),
+            ..Default::default()
+        })
+    }
+
+    /// Serialize the proof followed by the public inputs, using arkworks point compression,
+    /// see `to_bytes`
+    pub fn to_compressed(&self) -> Result<Vec<u8>, String> {
+        self.to_bytes(ProofFormat::Compressed)
+    }
+
+    /// Same as `from_bytes`, the header tells whether `bytes` are actually compressed
+    pub fn from_compressed(bytes: &[u8]) -> Result<Self, String> {
+        Self::from_bytes(bytes)
+    }
+
+    /// Proof in the `proof.json` layout produced by snarkjs. G2 coordinates are
+    /// emitted as `[c0, c1]`, the swap to `[c1, c0]` only happens in solidity calldata.
+    pub fn to_snarkjs_json(&self) -> Result<String, String> {
+        let proof = Proof::<Bn254>::deserialize(self.proof.as_slice()).map_err(|e| e.to_string())?;
+
+        let json = serde_json::json!({
+            "pi_a": [fq_to_decimal(&proof.a.x), fq_to_decimal(&proof.a.y), "1"],
+            "pi_b": [
+                [fq_to_decimal(&proof.b.x.c0), fq_to_decimal(&proof.b.x.c1)],
+                [fq_to_decimal(&proof.b.y.c0), fq_to_decimal(&proof.b.y.c1)],
+                ["1", "0"],
+            ],
+            "pi_c": [fq_to_decimal(&proof.c.x), fq_to_decimal(&proof.c.y), "1"],
+            "protocol": "groth16",
+            "curve": "bn128",
+        });
+
+        Ok(json.to_string())
+    }
+
+    /// Arguments of the snarkjs generated solidity verifier's `verifyProof(a, b, c, input)`,
+    /// formatted like `snarkjs zkey export soliditycalldata`. The G2 point `b` has its
+    /// coordinates swapped to `[c1, c0]`, as expected by the EVM pairing precompile.
+    pub fn to_solidity_calldata(&self) -> Result<String, String> {
+        let proof = Proof::<Bn254>::deserialize(self.proof.as_slice()).map_err(|e| e.to_string())?;
+        let inputs = decode_public_inputs(&self.public_inputs)?
+            .iter()
+            .map(u256_to_hex)
+            .collect::<Vec<String>>();
+        let hex = |f: &Fq| u256_to_hex(&U256::from_limbs(f.into_repr().0));
+
+        Ok(format!(
+            "[{}, {}],[[{}, {}],[{}, {}]],[{}, {}],[{}]",
+            hex(&proof.a.x),
+            hex(&proof.a.y),
+            hex(&proof.b.x.c1),
+            hex(&proof.b.x.c0),
+            hex(&proof.b.y.c1),
+            hex(&proof.b.y.c0),
+            hex(&proof.c.x),
+            hex(&proof.c.y),
+            inputs.join(","),
+        ))
+    }
+
+    /// The tree root the proof was made for, the first public input of the storer circuit
+    pub fn root(&self) -> Result<U256, String> {
+        decode_public_inputs(&self.public_inputs)?
+            .first()
+            .copied()
+            .ok_or_else(|| "proof has no public inputs".to_string())
+    }
+
+    /// Check the proof against a verification key from `StorageProofs::verification_key`
+    pub fn verify(&self, vk: &[u8]) -> Result<bool, String> {
+        verify_with_vk(vk, &self.proof, &self.public_inputs)
+    }
+
+    /// A fresh proof of the same statement, unlinkable to this one, for the verification
+    /// key `vk` the proof is valid for. With random `r1` and `r2`, `A` becomes `A / r1`,
+    /// `B` becomes `r1 * B + r1 * r2 * delta` and `C` becomes `C + r2 * A`, which leaves
+    /// the pairing check unchanged. The public inputs are kept as they are.
+    pub fn rerandomize<R: Rng>(&self, vk: &[u8], rng: &mut R) -> Result<ProofOutput, String> {
+        let vk = VerifyingKey::<Bn254>::deserialize(vk)
+            .map_err(|e| format!("unable to read verification key: {}", e))?;
+        let proof = Proof::<Bn254>::deserialize(self.proof.as_slice()).map_err(|e| e.to_string())?;
+
+        let (r1, r1_inv) = loop {
+            let r1 = Fr::rand(rng);
+            if let Some(r1_inv) = r1.inverse() {
+                break (r1, r1_inv);
+            }
+        };
+        let r2 = Fr::rand(rng);
+        let rerandomized = Proof::<Bn254> {
+            a: proof.a.mul(r1_inv.into_repr()).into_affine(),
+            b: (proof.b.mul(r1.into_repr()) + vk.delta_g2.mul((r1 * r2).into_repr()))
+                .into_affine(),
+            c: (proof.c.into_projective() + proof.a.mul(r2.into_repr())).into_affine(),
+        };
+
+        let mut bytes = Vec::new();
+        rerandomized.serialize(&mut bytes).map_err(|e| e.to_string())?;
+
+        Ok(ProofOutput {
+            proof: bytes,
+            public_inputs: self.public_inputs.clone(),
+            ..Default::default()
+        })
+    }
+
+    /// Public inputs in the `public.json` layout produced by snarkjs
+    pub fn to_snarkjs_public_json(&self) -> Result<String, String> {
+        StorageProofs::public_inputs_json(&self.public_inputs)
+    }
+
+    /// Serialize as a `ProofHeader` followed by the proof and the public inputs in `format`
+    pub fn to_bytes(&self, format: ProofFormat) -> Result<Vec<u8>, String> {
+        self.encode_with_header(ProofHeader::new(format))
+    }
+
+    /// Same as `to_bytes`, with the `StorageProofs::config_digest` of the prover in the
+    /// header, so verifiers can check which artifacts the proof was made with
+    pub fn to_bytes_with_config(
+        &self,
+        format: ProofFormat,
+        config_digest: [u8; 32],
+    ) -> Result<Vec<u8>, String> {
+        self.encode_with_header(ProofHeader {
+            config_digest: Some(config_digest),
+            ..ProofHeader::new(format)
+        })
+    }
+
+    fn encode_with_header(&self, header: ProofHeader) -> Result<Vec<u8>, String> {
+        let proof = Proof::<Bn254>::deserialize(self.proof.as_slice()).map_err(|e| e.to_string())?;
+        let inputs: Vec<Fr> = CanonicalDeserialize::deserialize(self.public_inputs.as_slice())
+            .map_err(|e| e.to_string())?;
+
+        let (mut proof_bytes, mut inputs_bytes) = (Vec::new(), Vec::new());
+        match header.format {
+            ProofFormat::Compressed => {
+                proof.serialize(&mut proof_bytes).map_err(|e| e.to_string())?;
+                inputs.serialize(&mut inputs_bytes).map_err(|e| e.to_string())?;
+            }
+            ProofFormat::Uncompressed => {
+                proof
+                    .serialize_uncompressed(&mut proof_bytes)
+                    .map_err(|e| e.to_string())?;
+                inputs
+                    .serialize_uncompressed(&mut inputs_bytes)
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+
+        let mut bytes = header.encode();
+        if header.version >= 2 {
+            let len = u16::try_from(proof_bytes.len()).map_err(|e| e.to_string())?;
+            bytes.extend(len.to_le_bytes());
+        }
+        bytes.extend(proof_bytes);
+        bytes.extend(inputs_bytes);
+
+        Ok(bytes)
+    }
+
+    /// Decode the output of `to_bytes` in either format, as told by its header, of this
+    /// or an earlier version down to `MIN_PROOF_FORMAT_VERSION`
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        Self::decode_with_header(bytes).map(|(output, _)| output)
+    }
+
+    fn decode_with_header(bytes: &[u8]) -> Result<(Self, ProofHeader), String> {
+        let (header, mut bytes) = ProofHeader::decode(bytes)?;
+        let proof_len = match header.version {
+            1 => None,
+            _ if bytes.len() < 2 => return Err("proof length is missing".to_string()),
+            _ => {
+                let len = u16::from_le_bytes([bytes[0], bytes[1]]) as usize;
+                bytes = &bytes[2..];
+                Some(len)
+            }
+        };
+
+        let before = bytes.len();
+        let proof = match header.format {
+            ProofFormat::Compressed => Proof::<Bn254>::deserialize(&mut bytes),
+            ProofFormat::Uncompressed => Proof::<Bn254>::deserialize_uncompressed(&mut bytes),
+        }
+        .map_err(|e| e.to_string())?;
+        if let Some(len) = proof_len {
+            if before - bytes.len() != len {
+                return Err(format!(
+                    "proof is {} bytes, but its length says {}",
+                    before - bytes.len(),
+                    len
+                ));
+            }
+        }
+        let inputs = match header.format {
+            ProofFormat::Compressed => Vec::<Fr>::deserialize(&mut bytes),
+            ProofFormat::Uncompressed => Vec::<Fr>::deserialize_uncompressed(&mut bytes),
+        }
+        .map_err(|e| e.to_string())?;
+
+        if !bytes.is_empty() {
+            return Err(format!("{} trailing bytes after proof", bytes.len()));
+        }
+
+        let mut output = ProofOutput::default();
+        proof
+            .serialize(&mut output.proof)
+            .map_err(|e| e.to_string())?;
+        inputs
+            .serialize(&mut output.public_inputs)
+            .map_err(|e| e.to_string())?;
+
+        Ok((output, header))
+    }
+}
+
+/// Re-encode a proof serialized by `ProofOutput::to_bytes` of an earlier version in the
+/// current one, keeping its format and config digest. Proofs of the current version come
+/// back unchanged, ones that don't decode are an error.
+pub fn migrate_proof(old: &[u8]) -> Result<Vec<u8>, String> {
+    let (output, header) = ProofOutput::decode_with_header(old)?;
+    output.encode_with_header(ProofHeader {
+        version: PROOF_FORMAT_VERSION,
+        ..header
+    })
+}
+
+#[cfg(test)]
+thread_local! {
+    /// number of witness calculators instantiated on the current thread
+    pub(crate) static WITNESS_CALCULATORS: std::cell::Cell<usize> = std::cell::Cell::new(0);
+    /// number of verifications answered by the verify cache on the current thread
+    pub(crate) static VERIFY_CACHE_HITS: std::cell::Cell<usize> = std::cell::Cell::new(0);
+}
+
+/// Verification results by verifying key digest and proof fingerprint, evicting the least
+/// recently used once `capacity` is reached. See `StorageProofs::set_verify_cache`.
+///
+/// Finding the least recently used entry scans them all, so an insert into a full cache is
+/// O(capacity). That's far below the cost of the verification it follows for the
+/// capacities this is meant for (thousands); a linked LRU would be needed for much larger
+/// ones.
+#[derive(Debug)]
+struct VerifyCache {
+    capacity: usize,
+    /// whether the proof is valid, and when it was last looked up
+    entries: HashMap<([u8; 32], [u8; 32]), (bool, u64)>,
+    tick: u64,
+}
+
+impl VerifyCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::with_capacity(capacity),
+            tick: 0,
+        }
+    }
+
+    fn get(&mut self, key: &([u8; 32], [u8; 32])) -> Option<bool> {
+        self.tick += 1;
+        let tick = self.tick;
+        self.entries.get_mut(key).map(|(valid, used)| {
+            *used = tick;
+            *valid
+        })
+    }
+
+    fn insert(&mut self, key: ([u8; 32], [u8; 32]), valid: bool) {
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&key) {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(key, _)| *key);
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.tick += 1;
+        self.entries.insert(key, (valid, self.tick));
+    }
+}
+
+/// The loaded circuit and proving key, shared (read only) between all proving calls.
+/// Each call takes its own witness calculator from a pool, so proving works concurrently
+/// from any number of threads on a single instance.
+#[derive(Debug)]
+pub struct StorageProofs {
+    /// the circuit, `None` for a `verifier_only` instance
+    module: Option<Arc<Module>>,
+    r1cs: Option<Arc<R1CS<Bn254>>>,
+    /// `None` when loaded with `without_proving_key`, until `set_proving_key` is called
+    params: Option<Arc<ProvingKey<Bn254>>>,
+    /// the verifying key of a `verifier_only` instance, which has no proving key
+    vk: Option<Arc<VerifyingKey<Bn254>>>,
+    circuit: Option<CircuitParams>,
+    /// told from the r1cs when `circuit` is given
+    chunk_inputs: Option<ChunkInputs>,
+    /// kept from the r1cs header, `R1CS` doesn't tell private inputs apart
+    info: CircuitInfo,
+    /// values of all input signals, public and private, see `prove_raw_inputs`
+    input_values: usize,
+    /// names and lengths of the public signals, see `set_public_signals`
+    public_signals: Option<Vec<(String, usize)>>,
+    /// handling of inputs not below the field modulus, see `set_field_mode`
+    field_mode: FieldMode,
+    /// idle witness calculators, see `with_calculator`
+    calculators: Arc<Mutex<Vec<WitnessCalculator>>>,
+    /// prepared on first use by `verify_borrowed`, replaced along with the proving key
+    prepared_vk: Arc<OnceCell<PreparedVerifyingKey<Bn254>>>,
+    /// public inputs buffer reused by `verify_borrowed`
+    scratch: Arc<Mutex<Vec<Fr>>>,
+    /// hash of the verifying key keying `verify_cache`, replaced along with the proving key
+    vk_digest: Arc<OnceCell<[u8; 32]>>,
+    /// `None` unless enabled with `set_verify_cache`
+    verify_cache: Option<Arc<Mutex<VerifyCache>>>,
+    /// threads calculating the witnesses of a batch, see `set_witness_threads`
+    #[cfg(feature = "parallel")]
+    witness_pool: Option<Arc<rayon::ThreadPool>>,
+    /// hashes of the artifacts the prover was loaded from, see `config_digest`
+    digests: ArtifactDigests,
+    /// witnesses are solved from the r1cs instead, see `set_native_witness`
+    #[cfg(feature = "native_witness")]
+    native_witness: bool,
+    /// witnesses are checked against every constraint before proving, see
+    /// `set_debug_unsatisfied`
+    #[cfg(feature = "debug")]
+    debug_unsatisfied: bool,
+}
+
+/// Cloning is cheap and doesn't read any files: the wasm module, r1cs and proving key are
+/// shared with the original, and so is the pool of idle witness calculators, which are
+/// only ever used by one call at a time. Scratch buffers are fresh for the clone, the verify
+/// cache is shared. A key set on either afterwards (`set_proving_key`) only replaces it on
+/// that handle.
+impl Clone for StorageProofs {
+    fn clone(&self) -> Self {
+        Self {
+            module: self.module.clone(),
+            r1cs: self.r1cs.clone(),
+            params: self.params.clone(),
+            vk: self.vk.clone(),
+            circuit: self.circuit,
+            chunk_inputs: self.chunk_inputs,
+            info: self.info,
+            input_values: self.input_values,
+            public_signals: self.public_signals.clone(),
+            field_mode: self.field_mode,
+            calculators: Arc::clone(&self.calculators),
+            prepared_vk: Arc::clone(&self.prepared_vk),
+            scratch: Arc::new(Mutex::new(Vec::new())),
+            vk_digest: Arc::clone(&self.vk_digest),
+            verify_cache: self.verify_cache.clone(),
+            #[cfg(feature = "parallel")]
+            witness_pool: self.witness_pool.clone(),
+            digests: self.digests,
+            #[cfg(feature = "native_witness")]
+            native_witness: self.native_witness,
+            #[cfg(feature = "debug")]
+            debug_unsatisfied: self.debug_unsatisfied,
+        }
+    }
+}
+
+impl StorageProofs {
+    // TODO: add rng
+    pub fn new(
+        wtns: String,
+        r1cs: String,
+        zkey: Option<String>, /* , rng: Option<ThreadRng> */
+        circuit: Option<CircuitParams>,
+    ) -> Result<Self, LoadError> {
+        let read = |path: &str| {
+            fs::read(path).map_err(|e| format!("unable to read {}: {:#}", path, e))
+        };
+
+        let wasm = read(&wtns).map_err(LoadError::Circuit)?;
+        let r1cs = read(&r1cs).map_err(LoadError::Circuit)?;
+        let zkey = match zkey {
+            Some(zkey) => Some(read(&zkey).map_err(LoadError::Key)?),
+            None => None,
+        };
+
+        Self::from_bytes(&wasm, &r1cs, zkey.as_deref(), circuit)
+    }
+
+    /// Same as `new`, but also runs `check_artifacts`, so a wasm, r1cs and zkey that don't
+    /// belong to the same circuit are caught at load time rather than while proving
+    pub fn new_checked(
+        wtns: String,
+        r1cs: String,
+        zkey: Option<String>,
+        circuit: Option<CircuitParams>,
+    ) -> Result<Self, LoadError> {
+        let prover = Self::new(wtns, r1cs, zkey, circuit)?;
+        prover.check_artifacts().map_err(LoadError::Circuit)?;
+
+        Ok(prover)
+    }
+
+    /// Construct from in-memory wasm, r1cs and (optionally) zkey contents. When `circuit`
+    /// is given, it's checked against the r1cs inputs.
+    pub fn from_bytes(
+        wasm: &[u8],
+        r1cs: &[u8],
+        zkey: Option<&[u8]>,
+        circuit: Option<CircuitParams>,
+    ) -> Result<Self, LoadError> {
+        let _span = tracing::debug_span!("new", wasm_len = wasm.len(), r1cs_len = r1cs.len())
+            .entered();
+        let mut prover = Self::load(wasm, r1cs, circuit).map_err(LoadError::Circuit)?;
+
+        match zkey {
+            Some(zkey) => {
+                prover.install_proving_key(read_proving_key(zkey).map_err(LoadError::Key)?);
+                prover.digests.zkey = Some(keccak256(zkey));
+            }
+            None => prover
+                .set_random_proving_key(&mut ThreadRng::default())
+                .map_err(LoadError::Key)?,
+        }
+
+        Ok(prover)
+    }
+
+    /// Load the circuit only, for flows that get the zkey later. Proving fails with
+    /// `ProveError::NoProvingKey` until one is set with `set_proving_key`.
+    pub fn without_proving_key(
+        wasm: &[u8],
+        r1cs: &[u8],
+        circuit: Option<CircuitParams>,
+    ) -> Result<Self, LoadError> {
+        let _span = tracing::debug_span!("new", wasm_len = wasm.len(), r1cs_len = r1cs.len())
+            .entered();
+
+        Self::load(wasm, r1cs, circuit).map_err(LoadError::Circuit)
+    }
+
+    /// An instance that only verifies, from a verification key in the (compressed)
+    /// arkworks form of `verification_key`, for nodes that never prove: no wasm, r1cs or
+    /// zkey is loaded. Proving fails with `ProveError::NotAProver`, and as the circuit
+    /// isn't known, `circuit_info` only has the number of public inputs.
+    pub fn verifier_only(vk: &[u8]) -> Result<Self, LoadError> {
+        let vk = VerifyingKey::<Bn254>::deserialize(vk)
+            .map_err(|e| LoadError::Key(format!("unable to read verification key: {}", e)))?;
+        let info = CircuitInfo {
+            num_public_inputs: vk.gamma_abc_g1.len().saturating_sub(1),
+            ..Default::default()
+        };
+
+        Ok(Self {
+            module: None,
+            r1cs: None,
+            params: None,
+            vk: Some(Arc::new(vk)),
+            circuit: None,
+            chunk_inputs: None,
+            info,
+            input_values: 0,
+            public_signals: None,
+            field_mode: FieldMode::default(),
+            calculators: Arc::new(Mutex::new(Vec::new())),
+            prepared_vk: Arc::new(OnceCell::new()),
+            scratch: Arc::new(Mutex::new(Vec::new())),
+            vk_digest: Arc::new(OnceCell::new()),
+            verify_cache: None,
+            #[cfg(feature = "parallel")]
+            witness_pool: None,
+            digests: ArtifactDigests::default(),
+            #[cfg(feature = "native_witness")]
+            native_witness: false,
+            #[cfg(feature = "debug")]
+            debug_unsatisfied: false,
+        })
+    }
+
+    fn load(wasm: &[u8], r1cs: &[u8], circuit: Option<CircuitParams>) -> Result<Self, String> {
+        let digests = ArtifactDigests {
+            wasm: keccak256(wasm),
+            r1cs: keccak256(r1cs),
+            zkey: None,
+        };
+        let module = Module::new(&Store::default(), wasm)
+            .map_err(|e| format!("unable to compile wasm: {:#}", e))?;
+        let r1cs = R1CSFile::<Bn254>::new(Cursor::new(r1cs))
+            .map_err(|e| format!("unable to read r1cs: {:#}", e))?;
+        // fields of the same size parse alike, but the circuit's hashes only match ours
+        // over BN254's
+        let prime = U256::try_from_le_slice(&r1cs.header.prime_size)
+            .ok_or("r1cs field prime doesn't fit in a u256")?;
+        check_field(prime).map_err(|e| format!("r1cs is for another curve: {}", e))?;
+        let chunk_inputs = match &circuit {
+            Some(circuit) => Some(circuit.check_r1cs(&r1cs)?),
+            None => None,
+        };
+        let private_inputs = r1cs.header.n_prv_in as usize;
+        let input_values = (r1cs.header.n_pub_in + r1cs.header.n_prv_in) as usize;
+        let r1cs: R1CS<Bn254> = r1cs.into();
+        let info = CircuitInfo {
+            num_constraints: r1cs.constraints.len(),
+            num_public_inputs: r1cs.num_inputs - 1,
+            num_witness: r1cs.num_variables,
+            num_private_inputs: private_inputs,
+        };
+        tracing::debug!(
+            constraints = r1cs.constraints.len(),
+            variables = r1cs.num_variables,
+            "loaded r1cs"
+        );
+
+        Ok(Self {
+            module: Some(Arc::new(module)),
+            r1cs: Some(Arc::new(r1cs)),
+            params: None,
+            vk: None,
+            circuit,
+            chunk_inputs,
+            info,
+            input_values,
+            public_signals: None,
+            field_mode: FieldMode::default(),
+            calculators: Arc::new(Mutex::new(Vec::new())),
+            prepared_vk: Arc::new(OnceCell::new()),
+            scratch: Arc::new(Mutex::new(Vec::new())),
+            vk_digest: Arc::new(OnceCell::new()),
+            verify_cache: None,
+            #[cfg(feature = "parallel")]
+            witness_pool: None,
+            digests,
+            #[cfg(feature = "native_witness")]
+            native_witness: false,
+            #[cfg(feature = "debug")]
+            debug_unsatisfied: false,
+        })
+    }
+
+    /// Replace the proving key with the one in `zkey`, e.g. after a setup ceremony update,
+    /// keeping the loaded circuit and witness calculators. The key is checked against the
+    /// r1cs first, on error the current key (if any) is kept.
+    pub fn set_proving_key(&mut self, zkey: &[u8]) -> Result<(), String> {
+        self.set_proving_key_params(read_proving_key(zkey)?)?;
+        self.digests.zkey = Some(keccak256(zkey));
+
+        Ok(())
+    }
+
+    pub(crate) fn set_proving_key_params(
+        &mut self,
+        params: ProvingKey<Bn254>,
+    ) -> Result<(), String> {
+        check_proving_key(self.r1cs()?, &params)?;
+        self.install_proving_key(params);
+
+        Ok(())
+    }
+
+    fn install_proving_key(&mut self, params: ProvingKey<Bn254>) {
+        self.params = Some(Arc::new(params));
+        self.prepared_vk = Arc::new(OnceCell::new());
+        self.vk_digest = Arc::new(OnceCell::new());
+        self.digests.zkey = None;
+    }
+
+    /// Generate random (insecure) parameters from `rng`, as done by `from_bytes` without a
+    /// zkey. A seeded `rng` gives the same key on every run, see `testvectors`.
+    pub(crate) fn set_random_proving_key<R: Rng>(&mut self, rng: &mut R) -> Result<(), String> {
+        let setup = CircomCircuit::<Bn254> {
+            r1cs: self.r1cs()?.clone(),
+            witness: None,
+        };
+        let params = generate_random_parameters::<Bn254, _, _>(setup, rng)
+            .map_err(|e| format!("{:#}", e))?;
+        self.install_proving_key(params);
+
+        Ok(())
+    }
+
+    pub fn has_proving_key(&self) -> bool {
+        self.params.is_some()
+    }
+
+    /// Whether this was constructed with `verifier_only`
+    pub fn is_verifier_only(&self) -> bool {
+        self.r1cs.is_none()
+    }
+
+    fn r1cs(&self) -> Result<&R1CS<Bn254>, ProveError> {
+        self.r1cs.as_deref().ok_or(ProveError::NotAProver)
+    }
+
+    /// Keccak-256 identifying the prover's setup: the curve and proof scheme, and the
+    /// hashes of the r1cs, wasm and zkey bytes it was loaded from, so provers built from
+    /// the same artifacts share it. Keys that didn't come from a zkey (generated at random,
+    /// or set with `set_proving_key_params`) are hashed in their arkworks serialization
+    /// instead, and a prover without a key hashes 32 zero bytes in their place.
+    pub fn config_digest(&self) -> [u8; 32] {
+        let zkey = match (self.digests.zkey, self.params.as_deref()) {
+            (Some(digest), _) => digest,
+            (None, Some(params)) => {
+                let mut bytes = Vec::new();
+                params
+                    .serialize_uncompressed(&mut bytes)
+                    .expect("serializing to a Vec can't fail");
+                keccak256(&bytes)
+            }
+            (None, None) => [0; 32],
+        };
+
+        let mut keccak = Keccak::v256();
+        keccak.update(b"bn254");
+        keccak.update(&[ProofScheme::Groth16Bn254 as u8]);
+        for digest in [self.digests.r1cs, self.digests.wasm, zkey] {
+            keccak.update(&digest);
+        }
+        let mut out = [0; 32];
+        keccak.finalize(&mut out);
+
+        out
+    }
+
+    pub(crate) fn proving_key(&self) -> Result<&ProvingKey<Bn254>, ProveError> {
+        if self.is_verifier_only() {
+            return Err(ProveError::NotAProver);
+        }
+
+        self.params.as_deref().ok_or(ProveError::NoProvingKey)
+    }
+
+    /// The verifying key, of the proving key unless `verifier_only`
+    fn verifying_key(&self) -> Result<&VerifyingKey<Bn254>, String> {
+        match self.vk.as_deref() {
+            Some(vk) => Ok(vk),
+            None => self
+                .proving_key()
+                .map(|params| &params.vk)
+                .map_err(String::from),
+        }
+    }
+
+    /// Cross check the wire and public input counts of the r1cs against the proving key
+    /// and the witness size of the wasm, and with circuit params their hash function
+    /// against the circuit
+    pub fn check_artifacts(&self) -> Result<(), String> {
+        check_proving_key(self.r1cs()?, self.proving_key()?)?;
+
+        let wires = self.info.num_witness;
+        let witness_size = self.with_calculator(|w| {
+            w.instance
+                .get_witness_size()
+                .map_err(|e| format!("unable to read the wasm witness size: {:#}", e))
+        })? as usize;
+        if witness_size != wires {
+            return Err(format!(
+                "wasm computes {} witness values, but the r1cs has {} wires",
+                witness_size, wires
+            ));
+        }
+
+        // the r1cs doesn't record the hash function, but the circuit's constraints only
+        // hold for inputs hashed with the one it uses
+        if let Some(circuit) = &self.circuit {
+            let inputs = self_test_inputs(circuit, self.chunk_inputs)
+                .and_then(|inputs| self.circuit_inputs(&inputs).map_err(String::from))?;
+            self.with_calculator(|w| calculate_witness(w, &inputs))
+                .map_err(|e| {
+                    format!("circuit doesn't hash with {:?} as its params say: {}", circuit.hash, e)
+                })?;
+        }
+
+        Ok(())
+    }
+
+    /// Prove and verify a small built-in set of inputs, to check the loaded artifacts work
+    /// together end to end. The inputs are built for the circuit params given at load time,
+    /// or the defaults if there were none. Proving takes as long as for any other inputs.
+    pub fn self_test(&self) -> Result<(), SelfTestFailure> {
+        let stage = |stage| move |reason| SelfTestFailure { stage, reason };
+
+        self.check_artifacts()
+            .map_err(stage(SelfTestStage::Artifacts))?;
+        let inputs = self_test_inputs(&self.circuit.unwrap_or_default(), self.chunk_inputs)
+            .and_then(|inputs| self.circuit_inputs(&inputs).map_err(String::from))
+            .map_err(stage(SelfTestStage::Inputs))?;
+        let witness = self
+            .with_calculator(|w| calculate_witness(w, &inputs).map_err(String::from))
+            .map_err(stage(SelfTestStage::Witness))?;
+        let output = self
+            .prove_witness_with(witness, &mut StdRng::seed_from_u64(0))
+            .map_err(|e| stage(SelfTestStage::Prove)(e.to_string()))?;
+        self.verify(output.proof.as_slice(), output.public_inputs.as_slice())
+            .map_err(stage(SelfTestStage::Verify))
+    }
+
+    /// Swap out the proving key, to test how bad keys are handled
+    #[cfg(test)]
+    pub(crate) fn proving_key_mut(&mut self) -> &mut ProvingKey<Bn254> {
+        self.prepared_vk = Arc::new(OnceCell::new());
+        self.vk_digest = Arc::new(OnceCell::new());
+        Arc::make_mut(self.params.as_mut().expect("loaded with a proving key"))
+    }
+
+    /// How inputs that aren't below the field modulus are handled when decoding and
+    /// proving. `FieldMode::Strict` by default, which rejects them instead of letting the
+    /// circuit silently compute with their remainder.
+    pub fn set_field_mode(&mut self, mode: FieldMode) {
+        self.field_mode = mode;
+    }
+
+    /// Check `path` holds leaf indices of the circuit's tree, one per sample. Without circuit
+    /// params the tree isn't known, so only negative indices are rejected; either way the
+    /// witness calculation would fail on an index the circuit can't decompose into bits.
+    pub fn check_path(&self, path: &[i32]) -> Result<(), InvalidPath> {
+        if let Some(circuit) = &self.circuit {
+            if path.len() != circuit.n_samples {
+                return Err(InvalidPath::Length {
+                    expected: circuit.n_samples,
+                    actual: path.len(),
+                });
+            }
+        }
+        let leaves = self
+            .circuit
+            .map(|c| TREE_ARITY.checked_pow(c.tree_depth as u32).unwrap_or(usize::MAX));
+
+        for (position, index) in path.iter().copied().enumerate() {
+            if index < 0 {
+                return Err(InvalidPath::Negative { position, index });
+            }
+            if let Some(leaves) = leaves.filter(|leaves| index as usize >= *leaves) {
+                return Err(InvalidPath::OutOfRange {
+                    position,
+                    index,
+                    leaves,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Circuit inputs for `inputs`, checked against the circuit params if known
+    fn circuit_inputs(&self, inputs: &ProofInputs) -> Result<Zeroizing<Inputs>, ProveError> {
+        self.streamed_circuit_inputs(inputs, inputs.chunks.len())
+    }
+
+    /// Same as `circuit_inputs`, with `chunk_count` chunks pushed to the result afterwards
+    /// rather than given in `inputs.chunks`, see `prove_streaming`
+    fn streamed_circuit_inputs(
+        &self,
+        inputs: &ProofInputs,
+        chunk_count: usize,
+    ) -> Result<Zeroizing<Inputs>, ProveError> {
+        let reduced;
+        let inputs = if inputs.is_canonical() {
+            inputs
+        } else {
+            reduced = self
+                .reduced_inputs(inputs)
+                .map_err(|e| ProveError::Failed(e.to_string()))?;
+            &*reduced
+        };
+        if let Some(circuit) = &self.circuit {
+            circuit.check_inputs(inputs).map_err(ProveError::Failed)?;
+        }
+        self.check_chunk_inputs(chunk_count)?;
+        self.check_path(&inputs.path)
+            .map_err(|e| ProveError::Failed(e.to_string()))?;
+
+        proof_circuit_inputs(inputs, &self.circuit.unwrap_or_default())
+            .map_err(ProveError::Failed)
+    }
+
+    /// A copy of `inputs` reduced as `FieldMode::Reduce` does, or the first element out of
+    /// range in strict mode
+    fn reduced_inputs(
+        &self,
+        inputs: &ProofInputs,
+    ) -> Result<Zeroizing<ProofInputs>, FieldElementOutOfRange> {
+        let mut reduced = Zeroizing::new(inputs.clone());
+        reduced.canonicalize(self.field_mode)?;
+        Ok(reduced)
+    }
+
+    /// Input signal names of the circuit, the `StorageProver` ones without circuit params
+    fn signals(&self) -> SignalMap {
+        self.circuit.map(|c| c.signals).unwrap_or_default()
+    }
+
+    /// Check chunks are given to circuits digesting them and left out for those taking
+    /// the hashes directly, when known
+    fn check_chunk_inputs(&self, chunk_count: usize) -> Result<(), ProveError> {
+        match (self.chunk_inputs, chunk_count) {
+            (Some(ChunkInputs::Raw), 0) => Err(ProveError::Failed(
+                "the circuit digests the chunks itself, they can't be left out".to_string(),
+            )),
+            (Some(ChunkInputs::Hashed), n) if n > 0 => Err(ProveError::Failed(format!(
+                "the circuit takes the chunk hashes directly, leave out the {} chunk elements",
+                n
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether `claimed` is the digest of `chunk` the circuit checks, to tell hashing
+    /// mistakes apart from proving ones. Without circuit params, chunks of any length are
+    /// digested with the default config, otherwise ones of the wrong length never match.
+    pub fn check_chunk_digest(&self, chunk: &[U256], claimed: U256) -> bool {
+        let params = self.circuit.unwrap_or(CircuitParams {
+            chunk_len: chunk.len(),
+            ..CircuitParams::default()
+        });
+
+        // more chunks than a single Poseidon hash takes can't be digested
+        let max_len = params.digest_config.rate * POSEIDON_MAX_INPUTS;
+        if chunk.is_empty() || (params.hash == HashKind::Poseidon && chunk.len() > max_len) {
+            return false;
+        }
+
+        params.digest(chunk) == Ok(claimed)
+    }
+
+    pub fn circuit_params(&self) -> Option<CircuitParams> {
+        self.circuit
+    }
+
+    /// Whether the circuit takes the chunks or only their hashes, `None` when loaded
+    /// without circuit params
+    pub fn chunk_inputs(&self) -> Option<ChunkInputs> {
+        self.chunk_inputs
+    }
+
+    /// Constraint, input and wire counts of the loaded circuit, e.g. for sizing hardware
+    pub fn circuit_info(&self) -> CircuitInfo {
+        self.info
+    }
+
+    /// Name the public signals of a circuit other than the storer, as `(name, len)` in
+    /// circuit order, `len` being the number of field elements of array signals (1 for
+    /// single values). The lengths must add up to the circuit's public inputs.
+    pub fn set_public_signals(&mut self, signals: &[(&str, usize)]) -> Result<(), String> {
+        let total = signals.iter().map(|(_, len)| len).sum::<usize>();
+        if total != self.info.num_public_inputs {
+            return Err(format!(
+                "the circuit has {} public inputs, the signals add up to {}",
+                self.info.num_public_inputs, total
+            ));
+        }
+        if let Some((name, _)) = signals.iter().find(|(_, len)| *len == 0) {
+            return Err(format!("public signal {} has no elements", name));
+        }
+
+        self.public_signals = Some(
+            signals
+                .iter()
+                .map(|(name, len)| (name.to_string(), *len))
+                .collect(),
+        );
+        Ok(())
+    }
+
+    /// The public signals of the circuit, in order. The r1cs only carries their count, so
+    /// unless named with `set_public_signals` the names are those of the storer circuit
+    /// (`STORER_PUBLIC_SIGNALS`), and any further signals are named by their position,
+    /// e.g. `public[2]`.
+    pub fn public_input_layout(&self) -> Vec<PublicSignal> {
+        let default = || {
+            (0..self.info.num_public_inputs)
+                .map(|i| {
+                    let name = STORER_PUBLIC_SIGNALS
+                        .get(i)
+                        .map_or_else(|| format!("public[{}]", i), |n| n.to_string());
+                    (name, 1)
+                })
+                .collect()
+        };
+        let signals = self.public_signals.clone().unwrap_or_else(default);
+
+        let mut offset = std::mem::size_of::<u64>();
+        signals
+            .into_iter()
+            .map(|(name, len)| {
+                let signal = PublicSignal { name, offset, len };
+                offset += len * U256::BYTES;
+                signal
+            })
+            .collect()
+    }
+
+    /// Value of the public signal called `name` in serialized `public_inputs`, located
+    /// with `public_input_layout`. Array signals are an error, see
+    /// `get_public_signal_array`.
+    pub fn get_public_signal(
+        &self,
+        public_inputs: &[u8],
+        name: &str,
+    ) -> Result<U256, PublicSignalError> {
+        let values = self.get_public_signal_array(public_inputs, name)?;
+        match values[..] {
+            [value] => Ok(value),
+            _ => Err(PublicSignalError::Array {
+                name: name.to_string(),
+                len: values.len(),
+            }),
+        }
+    }
+
+    /// Values of the public signal called `name` in serialized `public_inputs`, all
+    /// elements of an array signal in order, or the single value of any other
+    pub fn get_public_signal_array(
+        &self,
+        public_inputs: &[u8],
+        name: &str,
+    ) -> Result<Vec<U256>, PublicSignalError> {
+        let signal = self
+            .public_input_layout()
+            .into_iter()
+            .find(|s| s.name == name)
+            .ok_or_else(|| PublicSignalError::NoSuchSignal(name.to_string()))?;
+        decode_public_inputs(public_inputs).map_err(PublicSignalError::Decode)?;
+
+        public_inputs
+            .get(signal.offset..signal.offset + signal.len * U256::BYTES)
+            .map(|bytes| bytes.chunks(U256::BYTES).map(U256::from_le_slice).collect())
+            .ok_or_else(|| {
+                PublicSignalError::Decode(format!(
+                    "{} bytes don't reach {} at offset {}",
+                    public_inputs.len(),
+                    name,
+                    signal.offset
+                ))
+            })
+    }
+
+    /// Decode and validate the `prove_mpack` argument map
+    pub fn decode_mpack(&self, inputs: &[u8]) -> Result<ProofInputs, MpackError> {
+        decode_mpack_args(inputs, self.circuit.map(|c| c.chunk_len), self.field_mode)
+    }
+
+    /// Decode and validate a `prove_json` document
+    pub fn decode_json(&self, json: &str) -> Result<ProofInputs, String> {
+        decode_json_args(json, self.circuit.map(|c| c.chunk_len), self.field_mode)
+    }
+
+    /// Instantiate witness calculators until `count` are idle, so that many concurrent
+    /// proofs don't pay for loading the wasm module on first use
+    pub fn warm_up(&self, count: usize) -> Result<(), String> {
+        let idle = self.calculators.lock().map_err(|e| e.to_string())?.len();
+        let fresh = (idle..count)
+            .map(|_| self.new_calculator())
+            .collect::<Result<Vec<WitnessCalculator>, ProveError>>()?;
+
+        self.calculators
+            .lock()
+            .map_err(|e| e.to_string())?
+            .extend(fresh);
+
+        Ok(())
+    }
+
+    /// Recycle the prover for an unrelated job: drop the idle witness calculators, and with
+    /// them the wasm memory still holding the signals of earlier proofs, and the public
+    /// inputs scratch buffer. The circuit and proving key are kept, the next proof
+    /// instantiates a fresh calculator. Idle calculators are shared with clones, which
+    /// start from fresh ones as well; calculators busy in a clone aren't affected.
+    pub fn reset(&mut self) {
+        self.calculators
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+
+        let mut scratch = self.scratch.lock().unwrap_or_else(|e| e.into_inner());
+        scratch.clear();
+        scratch.shrink_to_fit();
+    }
+
+    /// Calculate the witnesses of `prove_batch` and `calculate_witnesses` on a dedicated
+    /// pool of `threads` threads, each with its own witness calculator, instead of one
+    /// after the other. Keep `threads` well below the core count on a server that's
+    /// proving other requests at the same time; 0 picks the number of cores.
+    ///
+    /// This parallelizes across proofs rather than across the samples of one proof: the
+    /// witness calculator circom generates evaluates the whole circuit, every sampled cell
+    /// included, in one run of the wasm over a single memory, so there is no per-sample
+    /// work to hand out. Single proofs are no faster, only batches gain; `cargo bench
+    /// --features parallel` measures it in the `witness/batch` group.
+    #[cfg(feature = "parallel")]
+    pub fn set_witness_threads(&mut self, threads: usize) -> Result<(), String> {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|i| format!("codex-witness-{}", i))
+            .build()
+            .map_err(|e| format!("unable to start witness threads: {}", e))?;
+        self.witness_pool = Some(Arc::new(pool));
+
+        Ok(())
+    }
+
+    /// Go back to calculating the witnesses of a batch sequentially
+    #[cfg(feature = "parallel")]
+    pub fn clear_witness_threads(&mut self) {
+        self.witness_pool = None;
+    }
+
+    /// Solve the witnesses of single proofs (`prove`, `prove_inputs` and friends, and
+    /// `calculate_witness`) from the r1cs instead of running the circuit's wasm, see
+    /// `native_witness`. Batches and `check_artifacts` keep using the wasm.
+    #[cfg(feature = "native_witness")]
+    pub fn set_native_witness(&mut self, native: bool) {
+        self.native_witness = native;
+    }
+
+    /// The witness of `inputs` solved from the r1cs of a `StorageProver` or
+    /// `StorageProverHashed` circuit, without the wasm: the inputs are assigned to their
+    /// wires in the order circom numbers them, `root` and `salt` first, and all other
+    /// wires follow from the constraints. It's the same witness `calculate_witness` gives.
+    #[cfg(feature = "native_witness")]
+    pub fn native_witness(&self, inputs: &ProofInputs) -> Result<Vec<U256>, String> {
+        let inputs = self.circuit_inputs(inputs)?;
+        let witness = self.solve_native_witness(&inputs)?;
+
+        Ok(witness
+            .iter()
+            .map(|w| U256::from_limbs(w.into_repr().0))
+            .collect())
+    }
+
+    #[cfg(feature = "native_witness")]
+    fn solve_native_witness(&self, inputs: &Inputs) -> Result<Zeroizing<Vec<Fr>>, String> {
+        let _span = tracing::debug_span!("native witness").entered();
+        // the constant `1`, then the public `root` and `salt`
+        let r1cs = self.r1cs()?;
+        if r1cs.num_inputs != 3 {
+            return Err(format!(
+                "native witnesses need a circuit with root and salt as its public inputs, \
+                 this one has {}",
+                r1cs.num_inputs - 1
+            ));
+        }
+
+        let signals = self.signals();
+        let values = Zeroizing::new(
+            [
+                signals.root,
+                signals.salt,
+                signals.chunks,
+                signals.siblings,
+                signals.path,
+                signals.hashes,
+            ]
+            .iter()
+            .flat_map(|name| inputs.get(*name).map(Vec::as_slice).unwrap_or_default())
+            .map(native_witness::input_to_fr)
+            .collect::<Vec<Fr>>(),
+        );
+
+        native_witness::solve(r1cs, &values).map(Zeroizing::new)
+    }
+
+    /// The witness of `inputs`, from the wasm unless `set_native_witness` is on
+    fn witness(&self, inputs: &Inputs) -> Result<Zeroizing<Vec<Fr>>, ProveError> {
+        #[cfg(feature = "native_witness")]
+        if self.native_witness {
+            return self.solve_native_witness(inputs).map_err(ProveError::Failed);
+        }
+
+        self.with_calculator(|w| calculate_witness(w, inputs))
+    }
+
+    /// Same as `witness`, timing the calculation when collecting metrics
+    fn timed_witness(&self, inputs: &Inputs) -> Result<TimedWitness, ProveError> {
+        TimedWitness::new(|| self.witness(inputs))
+    }
+
+    fn new_calculator(&self) -> Result<WitnessCalculator, ProveError> {
+        #[cfg(test)]
+        WITNESS_CALCULATORS.with(|c| c.set(c.get() + 1));
+
+        let module = self.module.as_deref().ok_or(ProveError::NotAProver)?;
+        WitnessCalculator::from_module(module.clone())
+            .map_err(|e| ProveError::Failed(format!("unable to load witness calculator: {:#}", e)))
+    }
+
+    /// Run `f` with a witness calculator taken from the pool, instantiating one from the
+    /// compiled module if none is free.
+    ///
+    /// Calculators are reused between calls without resetting anything on our side: every
+    /// witness calculation starts with the circom runtime's `init`, which clears the signal
+    /// memory and input counters, so no inputs of a previous call can leak into the next.
+    /// A calculator is only returned to the pool when `f` succeeds, one that trapped or
+    /// errored midway (e.g. with `ProveError::WitnessMemoryExhausted`) is dropped instead,
+    /// so the next call starts from a fresh instance.
+    fn with_calculator<T, E: From<ProveError>>(
+        &self,
+        f: impl FnOnce(&mut WitnessCalculator) -> Result<T, E>,
+    ) -> Result<T, E> {
+        let pooled = self
+            .calculators
+            .lock()
+            .map_err(|e| ProveError::Failed(e.to_string()))?
+            .pop();
+        let mut wtns = match pooled {
+            Some(wtns) => wtns,
+            None => self.new_calculator()?,
+        };
+
+        let res = f(&mut wtns);
+        if res.is_ok() {
+            self.calculators
+                .lock()
+                .map_err(|e| ProveError::Failed(e.to_string()))?
+                .push(wtns);
+        }
+
+        res
+    }
+
+    pub fn prove_mpack(
+        &self,
+        inputs: &[u8],
+        proof_bytes: &mut Vec<u8>,
+        public_inputs_bytes: &mut Vec<u8>,
+    ) -> Result<(), ProveError> {
+        let inputs = Zeroizing::new(
+            self.decode_mpack(inputs)
+                .map_err(|e| ProveError::Failed(e.to_string()))?,
+        );
+
+        let output = self.prove_inputs(&inputs)?;
+        proof_bytes.extend(output.proof);
+        public_inputs_bytes.extend(output.public_inputs);
+
+        Ok(())
+    }
+
+    /// Same as `prove_mpack`, for a JSON document of the form
+    /// `{ "chunks": [[..]], "siblings": [..], "hashes": [..], "path": [..], "root": "..",
+    /// "salt": ".." }`. Field elements are decimal or `0x` prefixed hex strings, small
+    /// values may also be plain numbers. `siblings` may be flat or grouped per chunk,
+    /// `root` may be left out to have it computed, and `chunks` is left out for circuits
+    /// taking the hashes directly (`ChunkInputs::Hashed`).
+    pub fn prove_json(
+        &self,
+        json: &str,
+        proof_bytes: &mut Vec<u8>,
+        public_inputs_bytes: &mut Vec<u8>,
+    ) -> Result<(), ProveError> {
+        let inputs = Zeroizing::new(self.decode_json(json).map_err(ProveError::Failed)?);
+
+        let output = self.prove_inputs(&inputs)?;
+        proof_bytes.extend(output.proof);
+        public_inputs_bytes.extend(output.public_inputs);
+
+        Ok(())
+    }
+
+    /// Same as `prove_inputs`, for inputs given as separate slices
+    pub fn prove(
+        &self,
+        chunks: &[U256],
+        siblings: &[U256],
+        hashes: &[U256],
+        path: &[i32],
+        root: Root,
+        salt: Salt,
+        proof_bytes: &mut Vec<u8>,
+        public_inputs_bytes: &mut Vec<u8>,
+    ) -> Result<(), ProveError> {
+        let inputs = Zeroizing::new(ProofInputs {
+            chunks: chunks.to_vec(),
+            siblings: siblings.to_vec(),
+            hashes: hashes.to_vec(),
+            path: path.to_vec(),
+            root: Some(root.0),
+            salt: salt.0,
+            ..ProofInputs::default()
+        });
+
+        let output = self.prove_inputs(&inputs)?;
+        proof_bytes.extend(output.proof);
+        public_inputs_bytes.extend(output.public_inputs);
+
+        Ok(())
+    }
+
+    pub fn prove_inputs(&self, inputs: &ProofInputs) -> Result<ProofOutput, ProveError> {
+        let inputs = self.circuit_inputs(inputs)?;
+        self.prove_circuit_inputs(&inputs, &mut ThreadRng::default())
+    }
+
+    /// Prove that no cell is stored at `inputs.index`, with a non-membership circuit loaded
+    /// (without circuit params, they describe the storer circuit). The loaded circuit is
+    /// told apart by its number of private and public inputs, see `signal_count`; any
+    /// other circuit fails with `ProveError::AbsenceCircuitRequired`.
+    pub fn prove_absence(&self, inputs: &AbsenceInputs) -> Result<ProofOutput, ProveError> {
+        let depth = inputs.left.siblings.len();
+        let signals = self.info.num_private_inputs + self.info.num_public_inputs;
+        if self.circuit.is_some()
+            || self.info.num_public_inputs != 2
+            || signals != AbsenceInputs::signal_count(depth)
+        {
+            return Err(ProveError::AbsenceCircuitRequired);
+        }
+
+        self.prove_raw_inputs(inputs.raw_inputs().map_err(ProveError::Failed)?)
+    }
+
+    /// Prove inputs given the way the witness calculator takes them, signal name to values
+    /// (arrays flattened), for circuits with input layouts `ProofInputs` doesn't model.
+    /// Nothing is packed or reordered, but with circuit params every name must be one of
+    /// their `SignalMap` (`ProveError::UnknownSignal` otherwise), the values must be field
+    /// elements, and every input signal of the r1cs must be given: the number of values
+    /// must match, so a signal left out is an error before the witness calculator runs.
+    pub fn prove_raw_inputs(
+        &self,
+        mut inputs: HashMap<String, Vec<U256>>,
+    ) -> Result<ProofOutput, ProveError> {
+        if let Some(circuit) = &self.circuit {
+            let names = circuit.signals.names();
+            let unknown = inputs.keys().filter(|name| !names.contains(&name.as_str())).min();
+            if let Some(name) = unknown {
+                return Err(ProveError::UnknownSignal(name.clone()));
+            }
+        }
+        let given = inputs.values().map(Vec::len).sum::<usize>();
+        if given != self.input_values {
+            return Err(ProveError::Failed(format!(
+                "the circuit takes {} input values, got {}",
+                self.input_values, given
+            )));
+        }
+        for (name, values) in &inputs {
+            if let Some(index) = values.iter().position(|n| *n >= FIELD_MODULUS) {
+                return Err(ProveError::Failed(format!(
+                    "element {} of {} is not below the field modulus",
+                    index, name
+                )));
+            }
+        }
+
+        let circuit_inputs = Zeroizing::new(Inputs(
+            inputs
+                .iter()
+                .map(|(name, values)| (name.clone(), values.iter().map(BigInt::from).collect()))
+                .collect(),
+        ));
+        inputs.values_mut().for_each(zeroize_u256s);
+        self.prove_circuit_inputs(&circuit_inputs, &mut ThreadRng::default())
+    }
+
+    /// Same as `prove_inputs`, also returning the public inputs of the proof decoded, so
+    /// Rust callers don't need to know their offsets in `ProofOutput::public_inputs`
+    pub fn prove_typed(
+        &self,
+        inputs: &ProofInputs,
+    ) -> Result<(ProofOutput, PublicInputs), ProveError> {
+        let output = self.prove_inputs(inputs)?;
+        let public = PublicInputs::decode(&output.public_inputs)
+            .map_err(ProveError::Failed)?;
+
+        Ok((output, public))
+    }
+
+    /// Prove the chunks in `samples`, as `(index, chunk)`, with their paths taken from the
+    /// partial view of the tree a node holding only some of the leaves has. The leaves of
+    /// the sampled chunks and their siblings must be known to `tree`.
+    pub fn prove_partial<H: HashFn>(
+        &self,
+        tree: &PartialMerkleTree<H>,
+        samples: &[(usize, Vec<U256>)],
+        salt: Salt,
+    ) -> Result<ProofOutput, ProveError> {
+        let openings = samples
+            .iter()
+            .map(|(index, chunk)| {
+                let path = tree.proof(*index)?;
+                Ok(CellOpening {
+                    chunk: chunk.clone(),
+                    hash: tree.leaf(*index).expect("leaves with a path are known"),
+                    siblings: path.siblings,
+                    index: *index as i32,
+                })
+            })
+            .collect::<Result<Vec<CellOpening>, String>>()
+            .map_err(ProveError::Failed)?;
+        let root = Root(tree.root().map_err(ProveError::Failed)?);
+        let inputs = Zeroizing::new(ProofInputs::from_openings(&openings, Some(root), salt));
+
+        self.prove_inputs(&inputs)
+    }
+
+    /// Prove inputs read from a memory mapped file laid out as described by `layout`. Like
+    /// `prove_streaming`, chunks are decoded one at a time as they're passed to the witness
+    /// calculator, so a file of many large cells is never read into memory as a whole.
+    /// The file must not be modified while proving.
+    pub fn prove_from_mmap(
+        &self,
+        path: &Path,
+        layout: MmapLayout,
+    ) -> Result<ProofOutput, ProveError> {
+        let file = fs::File::open(path)
+            .map_err(|e| ProveError::Failed(format!("unable to open {}: {}", path.display(), e)))?;
+        // SAFETY: the mapping is only read, and callers keep the file unchanged meanwhile
+        let map = unsafe { Mmap::map(&file) }
+            .map_err(|e| ProveError::Failed(format!("unable to map {}: {}", path.display(), e)))?;
+
+        let elements = |name: &str, offset: u64, count: usize| {
+            mmap_section(&map, name, offset, count * U256::BYTES)
+                .map(|bytes| {
+                    bytes
+                        .chunks(U256::BYTES)
+                        .map(U256::from_le_slice)
+                        .collect::<Vec<U256>>()
+                })
+                .map_err(ProveError::Failed)
+        };
+
+        let n = layout.n_samples;
+        let siblings = elements("siblings", layout.siblings, n * layout.tree_depth)?;
+        let hashes = elements("hashes", layout.hashes, n)?;
+        let path_indices = mmap_section(&map, "path", layout.path, n * 4)
+            .map_err(ProveError::Failed)?
+            .chunks(4)
+            .map(|i| i32::from_le_bytes([i[0], i[1], i[2], i[3]]))
+            .collect::<Vec<i32>>();
+        let root = Root(elements("root", layout.root, 1)?[0]);
+        let salt = Salt(elements("salt", layout.salt, 1)?[0]);
+        // checked upfront, rather than after most chunks were passed to the calculator
+        let chunk_bytes = layout.chunk_len * U256::BYTES;
+        let chunks = mmap_section(&map, "chunks", layout.chunks, n * chunk_bytes)
+            .map_err(ProveError::Failed)?;
+        let chunk_count = if layout.chunk_len == 0 { 0 } else { n };
+
+        self.prove_streaming(
+            chunk_count,
+            |index| {
+                Ok(chunks[index * chunk_bytes..(index + 1) * chunk_bytes]
+                    .chunks(U256::BYTES)
+                    .map(U256::from_le_slice)
+                    .collect())
+            },
+            &siblings,
+            &hashes,
+            &path_indices,
+            Some(root),
+            salt,
+        )
+    }
+
+    /// Same as `prove`, but pulls the sampled chunks one at a time from `chunk` instead of
+    /// taking them as a single flat slice, so callers don't need to hold a copy of all of them.
+    /// The inputs are checked as `prove_inputs` checks them, each chunk as it's pulled, and
+    /// without a `root` it's computed from the hashes and siblings.
+    #[allow(clippy::too_many_arguments)]
+    pub fn prove_streaming<F>(
+        &self,
+        chunk_count: usize,
+        mut chunk: F,
+        siblings: &[U256],
+        hashes: &[U256],
+        path: &[i32],
+        root: Option<Root>,
+        salt: Salt,
+    ) -> Result<ProofOutput, ProveError>
+    where
+        F: FnMut(usize) -> Result<Vec<U256>, String>,
+    {
+        if let Some(circuit) = self.circuit {
+            if chunk_count != 0 && chunk_count != circuit.n_samples {
+                return Err(ProveError::Failed(format!(
+                    "circuit params {:?} expect {} chunks, got {}",
+                    circuit, circuit.n_samples, chunk_count
+                )));
+            }
+        }
+        let paths = Zeroizing::new(ProofInputs {
+            siblings: siblings.to_vec(),
+            hashes: hashes.to_vec(),
+            path: path.to_vec(),
+            root: root.map(|root| root.0),
+            salt: salt.0,
+            ..ProofInputs::default()
+        });
+        let mut inputs = self.streamed_circuit_inputs(&paths, chunk_count)?;
+
+        let signals = self.signals();
+        let mut elements = 0;
+        for index in 0..chunk_count {
+            let mut values = chunk(index).map_err(ProveError::Failed)?;
+            if let Some(circuit) = self.circuit {
+                if values.len() != circuit.chunk_len {
+                    return Err(ProveError::Failed(format!(
+                        "chunk {} has {} elements, expected {}",
+                        index,
+                        values.len(),
+                        circuit.chunk_len
+                    )));
+                }
+            }
+            canonicalize_field("chunks", &mut values, elements, self.field_mode)
+                .map_err(|e| ProveError::Failed(e.to_string()))?;
+            elements += values.len();
+
+            values
+                .into_iter()
+                .for_each(|v| push_input(&mut inputs, signals.chunks, v));
+        }
+
+        self.prove_circuit_inputs(&inputs, &mut ThreadRng::default())
+    }
+
+    /// Prove many inputs, reusing a single witness calculator instance. With witness
+    /// threads (see `set_witness_threads`) all witnesses are calculated in parallel first,
+    /// then proven one after the other.
+    pub fn prove_batch(&self, inputs: &[ProofInputs]) -> Result<Vec<ProofOutput>, ProveError> {
+        let inputs = inputs
+            .iter()
+            .map(|i| self.circuit_inputs(i))
+            .collect::<Result<Vec<Zeroizing<Inputs>>, ProveError>>()?;
+
+        #[cfg(feature = "parallel")]
+        if self.witness_pool.is_some() {
+            return self
+                .batch_witnesses(inputs)?
+                .into_iter()
+                .map(|w| self.prove_timed_witness(w))
+                .collect();
+        }
+
+        self.with_calculator(|w| {
+            inputs
+                .into_iter()
+                .map(|i| self.prove_timed_witness(TimedWitness::new(|| calculate_witness(w, &i))?))
+                .collect()
+        })
+    }
+
+    fn prove_timed_witness(&self, witness: TimedWitness) -> Result<ProofOutput, ProveError> {
+        self.prove_timed_witness_with(witness, &mut ThreadRng::default())
+    }
+
+    /// Prove `witness`, adding the time its calculation took to the timings
+    fn prove_timed_witness_with<R: Rng>(
+        &self,
+        witness: TimedWitness,
+        rng: &mut R,
+    ) -> Result<ProofOutput, ProveError> {
+        let output = self.prove_witness_with(witness.witness, rng)?;
+        #[cfg(feature = "metrics")]
+        let output = output.with_witness_time(witness.time);
+
+        Ok(output)
+    }
+
+    /// Witnesses for all of `inputs`, on the witness threads if there are any
+    fn batch_witnesses(
+        &self,
+        inputs: Vec<Zeroizing<Inputs>>,
+    ) -> Result<Vec<TimedWitness>, ProveError> {
+        #[cfg(feature = "parallel")]
+        if let Some(pool) = &self.witness_pool {
+            use rayon::prelude::*;
+
+            return pool.install(|| {
+                inputs
+                    .into_par_iter()
+                    .map(|i| {
+                        self.with_calculator(|w| TimedWitness::new(|| calculate_witness(w, &i)))
+                    })
+                    .collect()
+            });
+        }
+
+        self.with_calculator(|w| {
+            inputs
+                .into_iter()
+                .map(|i| TimedWitness::new(|| calculate_witness(w, &i)))
+                .collect()
+        })
+    }
+
+    /// Same as `prove_batch` but for mpack encoded arguments, see `prove_mpack`
+    pub fn prove_mpack_batch(&self, inputs: &[&[u8]]) -> Result<Vec<ProofOutput>, ProveError> {
+        let inputs = inputs
+            .iter()
+            .map(|i| {
+                self.decode_mpack(i)
+                    .map_err(|e| ProveError::Failed(e.to_string()))
+            })
+            .collect::<Result<Vec<ProofInputs>, ProveError>>()?;
+
+        self.prove_batch(&inputs)
+    }
+
+    /// Run the witness calculator over `inputs`, without proving
+    pub fn calculate_witness(&self, inputs: &ProofInputs) -> Result<Vec<U256>, ProveError> {
+        let inputs = self.circuit_inputs(inputs)?;
+        let witness = self.witness(&inputs)?;
+
+        Ok(witness
+            .iter()
+            .map(|w| U256::from_limbs(w.into_repr().0))
+            .collect())
+    }
+
+    /// Same as `calculate_witness` for many inputs, in parallel when witness threads are
+    /// set up with `set_witness_threads`. The witnesses are the same either way.
+    pub fn calculate_witnesses(
+        &self,
+        inputs: &[ProofInputs],
+    ) -> Result<Vec<Vec<U256>>, ProveError> {
+        let inputs = inputs
+            .iter()
+            .map(|i| self.circuit_inputs(i))
+            .collect::<Result<Vec<Zeroizing<Inputs>>, ProveError>>()?;
+
+        Ok(self
+            .batch_witnesses(inputs)?
+            .into_iter()
+            .map(|w| {
+                w.witness
+                    .iter()
+                    .map(|w| U256::from_limbs(w.into_repr().0))
+                    .collect()
+            })
+            .collect())
+    }
+
+    /// Prove a witness previously computed with `calculate_witness`
+    pub fn prove_with_witness(&self, witness: &[U256]) -> Result<ProofOutput, ProveError> {
+        let witness = witness_to_fr(witness).map_err(ProveError::Failed)?;
+        self.prove_witness(Zeroizing::new(witness))
+    }
+
+    /// Check witnesses against every constraint of the r1cs before proving them, so a
+    /// witness that doesn't satisfy the circuit fails with the first constraint it violates
+    /// (see `unsatisfied_constraint`) instead of giving a proof that won't verify. Costs
+    /// about as much as calculating the witness, for debugging circuits.
+    #[cfg(feature = "debug")]
+    pub fn set_debug_unsatisfied(&mut self, debug: bool) {
+        self.debug_unsatisfied = debug;
+    }
+
+    /// Evaluate each constraint of the r1cs on `witness`, as computed by
+    /// `calculate_witness`, returning the first one it doesn't satisfy, or `None` if it
+    /// satisfies them all
+    #[cfg(feature = "debug")]
+    pub fn unsatisfied_constraint(
+        &self,
+        witness: &[U256],
+    ) -> Result<Option<UnsatisfiedConstraint>, String> {
+        let r1cs = self.r1cs()?;
+        let witness = Zeroizing::new(witness_to_fr(witness)?);
+        check_witness(r1cs, &witness)?;
+
+        Ok(first_unsatisfied(r1cs, &witness))
+    }
+
+    /// Same as `prove_inputs`, also returning the witness that was proved, for inspecting
+    /// proofs that fail to verify. The witness includes the private inputs, which is why
+    /// the other `prove` variants drop it as soon as the proof is made.
+    pub fn prove_with_witness_out(
+        &self,
+        inputs: &ProofInputs,
+    ) -> Result<(ProofOutput, Vec<U256>), ProveError> {
+        let inputs = self.circuit_inputs(inputs)?;
+        let witness = self.timed_witness(&inputs)?;
+        let witness_out = witness
+            .witness
+            .iter()
+            .map(|w| U256::from_limbs(w.into_repr().0))
+            .collect();
+
+        Ok((self.prove_timed_witness(witness)?, witness_out))
+    }
+
+    /// Same as `prove_inputs`, reporting each `ProvePhase` to `progress` as it's reached.
+    /// Returning false from `progress` cancels proving at that point; a running witness
+    /// calculation or proof can't be interrupted, so cancelling takes effect at the next phase.
+    pub fn prove_with_progress<F>(
+        &self,
+        inputs: &ProofInputs,
+        mut progress: F,
+    ) -> Result<ProofOutput, ProveError>
+    where
+        F: FnMut(ProvePhase) -> bool,
+    {
+        let mut report = |phase| {
+            if progress(phase) {
+                Ok(())
+            } else {
+                Err(ProveError::Cancelled(phase))
+            }
+        };
+
+        if self.is_verifier_only() {
+            return Err(ProveError::NotAProver);
+        }
+        if !self.has_proving_key() {
+            return Err(ProveError::NoProvingKey);
+        }
+
+        report(ProvePhase::WitnessStart)?;
+        let witness = self.timed_witness(&self.circuit_inputs(inputs)?)?;
+        report(ProvePhase::WitnessDone)?;
+
+        report(ProvePhase::ProveStart)?;
+        let output = self.prove_timed_witness(witness)?;
+        report(ProvePhase::ProveDone)?;
+
+        Ok(output)
+    }
+
+    /// Same as `prove_with_progress`, on a background thread so event loop based hosts don't
+    /// block on it: returns right away with a handle to poll (`ProveHandle::is_ready`) or
+    /// wait on. The thread proves with a clone of this prover, sharing its key and idle
+    /// witness calculators.
+    pub fn prove_async(&self, inputs: &ProofInputs) -> ProveHandle {
+        let state = Arc::new(AsyncProof::default());
+        let prover = self.clone();
+        let inputs = Zeroizing::new(inputs.clone());
+        let worker = Arc::clone(&state);
+
+        let thread = thread::Builder::new()
+            .name("codex-prove".to_string())
+            .spawn(move || {
+                let res = prover.prove_with_progress(&inputs, |_| {
+                    !worker.cancelled.load(Ordering::Acquire)
+                });
+                worker.finish(res);
+            });
+        let thread = match thread {
+            Ok(thread) => Some(thread),
+            Err(e) => {
+                state.finish(Err(ProveError::Failed(format!(
+                    "unable to start proving thread: {}",
+                    e
+                ))));
+                None
+            }
+        };
+
+        ProveHandle { state, thread }
+    }
+
+    /// Same as `prove_inputs`, but with the prover randomness derived from `seed`, so the
+    /// same inputs and seed always give byte identical proofs. Only meant for test vectors
+    /// and debugging, a proof with known randomness doesn't hide the private inputs.
+    pub fn prove_with_seed(
+        &self,
+        inputs: &ProofInputs,
+        seed: u64,
+    ) -> Result<ProofOutput, ProveError> {
+        let inputs = self.circuit_inputs(inputs)?;
+        self.prove_circuit_inputs(&inputs, &mut StdRng::seed_from_u64(seed))
+    }
+
+    /// Calculate the witness for `inputs` and prove it
+    fn prove_circuit_inputs<R: Rng>(
+        &self,
+        inputs: &Inputs,
+        rng: &mut R,
+    ) -> Result<ProofOutput, ProveError> {
+        self.prove_timed_witness_with(self.timed_witness(inputs)?, rng)
+    }
+
+    fn prove_witness(&self, witness: Zeroizing<Vec<Fr>>) -> Result<ProofOutput, ProveError> {
+        self.prove_witness_with(witness, &mut ThreadRng::default())
+    }
+
+    fn prove_witness_with<R: Rng>(
+        &self,
+        mut witness: Zeroizing<Vec<Fr>>,
+        rng: &mut R,
+    ) -> Result<ProofOutput, ProveError> {
+        let _span = tracing::debug_span!("prove", witness = witness.len()).entered();
+        let r1cs = self.r1cs()?;
+        check_witness(r1cs, &witness).map_err(ProveError::Failed)?;
+        #[cfg(feature = "debug")]
+        if self.debug_unsatisfied {
+            if let Some(unsatisfied) = first_unsatisfied(r1cs, &witness) {
+                return Err(ProveError::Failed(unsatisfied.to_string()));
+            }
+        }
+        #[cfg(feature = "metrics")]
+        let start = Instant::now();
+        // arkworks takes the witness by value and drops it without wiping it
+        let circuit: CircomCircuit<Params256Ty> = CircomCircuit {
+            r1cs: r1cs.clone(),
+            witness: Some(std::mem::take(&mut *witness)),
+        };
+        let inputs = circuit
+            .get_public_inputs()
+            .ok_or_else(|| ProveError::Failed("Unable to get public inputs!".to_string()))?;
+        #[cfg(feature = "metrics")]
+        let build_circuit = start.elapsed();
+        let proof = prove(circuit, self.proving_key()?, rng)
+            .map_err(|e| ProveError::Failed(e.to_string()))?;
+        #[cfg(feature = "metrics")]
+        let proved = start.elapsed();
+
+        let mut output = ProofOutput::default();
+        proof
+            .serialize(&mut output.proof)
+            .map_err(|e| ProveError::Failed(e.to_string()))?;
+        inputs
+            .serialize(&mut output.public_inputs)
+            .map_err(|e| ProveError::Failed(e.to_string()))?;
+
+        #[cfg(feature = "metrics")]
+        {
+            output.timings = Some(ProveTimings {
+                build_circuit,
+                witness: Duration::ZERO,
+                prove: proved - build_circuit,
+                total: start.elapsed(),
+            });
+        }
+
+        Ok(output)
+    }
+
+    /// Verify a proof serialized with `ProofOutput::to_bytes`, in either format
+    pub fn verify_serialized(&self, bytes: &[u8]) -> Result<(), String> {
+        let output = ProofOutput::from_bytes(bytes)?;
+        self.verify(output.proof.as_slice(), output.public_inputs.as_slice())
+    }
+
+    pub fn verify<RR: Read>(
+        &self,
+        mut proof_bytes: RR,
+        mut public_inputs: RR,
+    ) -> Result<(), String> {
+        // read whole, a proof's serialization is told by its length
+        let (mut proof, mut inputs) = (Vec::new(), Vec::new());
+        proof_bytes
+            .read_to_end(&mut proof)
+            .and_then(|_| public_inputs.read_to_end(&mut inputs))
+            .map_err(|e| e.to_string())?;
+        let valid = self.cached_verify(&proof, &inputs, || {
+            verify_prepared(self.prepared_vk()?, proof.as_slice(), inputs.as_slice())
+        })?;
+        if !valid {
+            return Err("proof verification failed".to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Same as `verify`, for verifying many proofs: the proof and public inputs are borrowed
+    /// instead of read into copies, and the public inputs are decoded into a buffer reused
+    /// between calls (unless it's taken by a concurrent call), so only the pairing itself
+    /// allocates
+    pub fn verify_borrowed(&self, proof: &[u8], public_inputs: &[u8]) -> Result<(), String> {
+        let vk = self.prepared_vk()?;
+        let valid = self.cached_verify(proof, public_inputs, || {
+            self.with_scratch(|scratch| verify_prepared_with(vk, proof, public_inputs, scratch))
+        })?;
+        if !valid {
+            return Err("proof verification failed".to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Same as `verify_borrowed`, with the public inputs given as field elements rather than
+    /// serialized, e.g. the root and salt a verifier expects. An invalid proof is
+    /// `Ok(false)`, inputs that aren't field elements are an error.
+    pub fn verify_with_inputs(&self, proof: &[u8], public: &[U256]) -> Result<bool, String> {
+        let vk = self.prepared_vk()?;
+        let public_inputs = encode_public_inputs(public);
+
+        self.with_scratch(|scratch| verify_prepared_with(vk, proof, &public_inputs, scratch))
+    }
+
+    /// Hash committing to all public inputs, for verifiers that store a single value: the
+    /// circomlib `Poseidon(n)` of the `n` public signals in circuit order, i.e.
+    /// `Poseidon(2)([root, salt])` for the storer circuit, so it can be recomputed in a
+    /// circuit or contract alike. At most `POSEIDON_MAX_INPUTS` signals are supported.
+    pub fn public_input_hash(public_inputs: &[u8]) -> Result<U256, String> {
+        poseidon_hash(&decode_public_inputs(public_inputs)?)
+    }
+
+    /// Verify a proof whose public inputs are committed to by `input_hash`, see
+    /// `public_input_hash`. A Groth16 proof only verifies against the public inputs
+    /// themselves, so they still have to be given; they must hash to `input_hash` for the
+    /// proof to be accepted. Returns `Ok(false)` if they don't or the proof is invalid.
+    pub fn verify_with_input_hash(
+        &self,
+        proof: &[u8],
+        public_inputs: &[u8],
+        input_hash: U256,
+    ) -> Result<bool, String> {
+        if Self::public_input_hash(public_inputs)? != input_hash {
+            return Ok(false);
+        }
+
+        let vk = self.prepared_vk()?;
+        self.with_scratch(|scratch| verify_prepared_with(vk, proof, public_inputs, scratch))
+    }
+
+    /// Same as `verify_borrowed`, first checking the proof's public `salt` is
+    /// `expected_salt`, the hash of the block the proof was requested for. A proof made for
+    /// another block is rejected with `SaltMismatch` without verifying it. Without an
+    /// expected salt this only verifies.
+    pub fn verify_for_salt(
+        &self,
+        proof: &[u8],
+        public_inputs: &[u8],
+        expected_salt: Option<U256>,
+    ) -> Result<(), VerifyError> {
+        if let Some(expected) = expected_salt {
+            let actual = self
+                .get_public_signal(public_inputs, "salt")
+                .map_err(|e| VerifyError::Invalid(e.to_string()))?;
+            if actual != expected {
+                return Err(VerifyError::SaltMismatch { expected, actual });
+            }
+        }
+
+        self.verify_borrowed(proof, public_inputs)
+            .map_err(VerifyError::Invalid)
+    }
+
+    /// Memoize the results of `verify` and `verify_borrowed` for up to `capacity` proofs,
+    /// by their fingerprint, evicting the least recently verified ones. The key is that of
+    /// the proof and the verifying key digest, so a result is never reused for another key.
+    /// Malformed proofs aren't cached. Clones made afterwards share the cache, a capacity
+    /// of 0 disables it. Caching a new result in a full cache scans the whole cache for the
+    /// entry to evict, so keep `capacity` in the thousands.
+    pub fn set_verify_cache(&mut self, capacity: usize) {
+        self.verify_cache = match capacity {
+            0 => None,
+            capacity => Some(Arc::new(Mutex::new(VerifyCache::new(capacity)))),
+        };
+    }
+
+    /// Forget all results memoized by the verify cache, if enabled
+    pub fn clear_verify_cache(&self) {
+        if let Some(cache) = &self.verify_cache {
+            cache.lock().unwrap_or_else(|e| e.into_inner()).entries.clear();
+        }
+    }
+
+    /// Look up the proof in the verify cache, or run `verify` and remember its result
+    fn cached_verify(
+        &self,
+        proof: &[u8],
+        public_inputs: &[u8],
+        verify: impl FnOnce() -> Result<bool, String>,
+    ) -> Result<bool, String> {
+        let cache = match &self.verify_cache {
+            Some(cache) => cache,
+            None => return verify(),
+        };
+
+        let key = (*self.vk_digest()?, fingerprint(proof, public_inputs));
+        let cached = cache.lock().unwrap_or_else(|e| e.into_inner()).get(&key);
+        if let Some(valid) = cached {
+            #[cfg(test)]
+            VERIFY_CACHE_HITS.with(|c| c.set(c.get() + 1));
+            return Ok(valid);
+        }
+        // not holding the lock while verifying, concurrent misses just verify twice
+        let valid = verify()?;
+        cache
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(key, valid);
+
+        Ok(valid)
+    }
+
+    /// Keccak-256 of the serialized verifying key, kept until the proving key changes
+    fn vk_digest(&self) -> Result<&[u8; 32], String> {
+        self.vk_digest
+            .get_or_try_init(|| Ok(keccak256(&self.verification_key()?)))
+    }
+
+    /// The verifying key prepared for pairings, kept until the proving key changes
+    fn prepared_vk(&self) -> Result<&PreparedVerifyingKey<Bn254>, String> {
+        self.prepared_vk
+            .get_or_try_init(|| Ok(prepare_verifying_key(self.verifying_key()?)))
+    }
+
+    /// Run `f` with the shared public inputs buffer, or a fresh one while a concurrent call
+    /// holds it, so the buffer is only ever used by one call at a time
+    fn with_scratch<T>(&self, f: impl FnOnce(&mut Vec<Fr>) -> T) -> T {
+        let mut local = Vec::new();
+        let mut pooled = self.scratch.try_lock().ok();
+        f(pooled.as_deref_mut().unwrap_or(&mut local))
+    }
+
+    /// The verification key, in (compressed) arkworks canonical form. See `verify_with_vk`.
+    pub fn verification_key(&self) -> Result<Vec<u8>, String> {
+        let mut bytes = Vec::new();
+        self.verifying_key()?
+            .serialize(&mut bytes)
+            .map_err(|e| e.to_string())?;
+
+        Ok(bytes)
+    }
+
+    /// Verification key in the `verification_key.json` layout of
+    /// `snarkjs zkey export verificationkey`, for JS verifiers. Like `to_snarkjs_json`, G2
+    /// coordinates are `[c0, c1]` and points are given with their projective `z`.
+    pub fn verification_key_json(&self) -> Result<String, String> {
+        let vk = self.verifying_key()?;
+        let g1 = |p: &G1Affine| serde_json::json!([fq_to_decimal(&p.x), fq_to_decimal(&p.y), "1"]);
+        let fq2 = |f: &Fq2| serde_json::json!([fq_to_decimal(&f.c0), fq_to_decimal(&f.c1)]);
+        let g2 = |p: &G2Affine| serde_json::json!([fq2(&p.x), fq2(&p.y), ["1", "0"]]);
+        let fq6 = |f: &Fq6| serde_json::json!([fq2(&f.c0), fq2(&f.c1), fq2(&f.c2)]);
+        let alphabeta = Bn254::pairing(vk.alpha_g1, vk.beta_g2);
+
+        let json = serde_json::json!({
+            "protocol": "groth16",
+            "curve": "bn128",
+            "nPublic": vk.gamma_abc_g1.len().saturating_sub(1),
+            "vk_alpha_1": g1(&vk.alpha_g1),
+            "vk_beta_2": g2(&vk.beta_g2),
+            "vk_gamma_2": g2(&vk.gamma_g2),
+            "vk_delta_2": g2(&vk.delta_g2),
+            "vk_alphabeta_12": [fq6(&alphabeta.c0), fq6(&alphabeta.c1)],
+            "IC": vk.gamma_abc_g1.iter().map(g1).collect::<Vec<serde_json::Value>>(),
+        });
+
+        serde_json::to_string_pretty(&json).map_err(|e| e.to_string())
+    }
+
+    /// Verify many `(proof, public inputs)` pairs with the prepared verification key, see
+    /// `verify_borrowed`. The public inputs of every item are decoded into the same buffer,
+    /// held for the whole batch. Returns one entry per item, malformed items count as invalid.
+    pub fn verify_batch(&self, items: &[(&[u8], &[u8])]) -> Vec<bool> {
+        let vk = match self.prepared_vk() {
+            Ok(vk) => vk,
+            Err(_) => return vec![false; items.len()],
+        };
+
+        self.with_scratch(|scratch| {
+            items
+                .iter()
+                .map(|(proof, public_inputs)| {
+                    matches!(verify_prepared_with(vk, proof, public_inputs, scratch), Ok(true))
+                })
+                .collect()
+        })
+    }
+
+    /// Decode serialized public inputs into a JSON array of decimal strings
+    pub fn public_inputs_json(public_inputs: &[u8]) -> Result<String, String> {
+        if public_inputs.is_empty() {
+            return Ok("[]".to_string());
+        }
+
+        let inputs = decode_public_inputs(public_inputs)?
+            .iter()
+            .map(|i| i.to_string())
+            .collect::<Vec<String>>();
+
+        serde_json::to_string(&inputs).map_err(|e| e.to_string())
+    }
+
+    pub fn verify_detailed(
+        &self,
+        proof_bytes: &[u8],
+        public_inputs: &[u8],
+    ) -> Result<(), VerifyFailure> {
+        let inputs = decode_public_inputs(public_inputs).map_err(|reason| VerifyFailure {
+            index: None,
+            inputs: Vec::new(),
+            reason,
+        })?;
+
+        let fail = |index: Option<usize>, reason: String| VerifyFailure {
+            index,
+            inputs: inputs.clone(),
+            reason,
+        };
+
+        let expected = self
+            .verifying_key()
+            .map_err(|reason| fail(None, reason))?
+            .gamma_abc_g1
+            .len()
+            - 1;
+        if inputs.len() != expected {
+            return Err(fail(
+                None,
+                format!("expected {} public inputs, got {}", expected, inputs.len()),
+            ));
+        }
+
+        // a value outside the field is almost always a wrongly packed (e.g. big endian) input
+        if let Some(index) = inputs.iter().position(|i| *i >= FIELD_MODULUS) {
+            return Err(fail(
+                Some(index),
+                format!("public input {} is not a valid field element", index),
+            ));
+        }
+
+        check_lengths(proof_bytes, public_inputs).map_err(|reason| fail(None, reason))?;
+        self.verify(proof_bytes, public_inputs)
+            .map_err(|reason| fail(None, reason))
+    }
+}
+
+/// Openings of distinct, deterministic chunks in a full tree of `tree_depth` levels
+fn self_test_inputs(
+    params: &CircuitParams,
+    chunk_inputs: Option<ChunkInputs>,
+) -> Result<ProofInputs, String> {
+    let leaves = TREE_ARITY.pow(params.tree_depth as u32);
+    let chunks = (0..leaves)
+        .map(|i| {
+            (0..params.chunk_len)
+                .map(|j| U256::from(i * params.chunk_len + j))
+                .collect()
+        })
+        .collect::<Vec<Vec<U256>>>();
+    let hashes = chunks
+        .iter()
+        .map(|c| params.digest(c))
+        .collect::<Result<Vec<U256>, String>>()?;
+    let tree = MerkleTree::with_hash(&hashes, TREE_ARITY, params.hash)?;
+
+    let openings = (0..params.n_samples)
+        .map(|i| {
+            let index = i % leaves;
+            Ok(CellOpening {
+                chunk: chunks[index].clone(),
+                hash: hashes[index],
+                siblings: tree.proof(index)?.siblings,
+                index: index as i32,
+            })
+        })
+        .collect::<Result<Vec<CellOpening>, String>>()?;
+
+    let mut inputs =
+        ProofInputs::from_openings(&openings, Some(Root(tree.root())), Salt(U256::from(1)));
+    if chunk_inputs == Some(ChunkInputs::Hashed) {
+        inputs.chunks.clear();
+    }
+
+    Ok(inputs)
+}
+
+fn read_proving_key(zkey: &[u8]) -> Result<ProvingKey<Bn254>, String> {
+    Ok(read_zkey(&mut Cursor::new(zkey))
+        .map_err(|e| format!("unable to read zkey: {:#}", e))?
+        .0)
+}
+
+/// Check the public input and wire counts of `params` match the r1cs
+fn check_proving_key(r1cs: &R1CS<Bn254>, params: &ProvingKey<Bn254>) -> Result<(), String> {
+    let public_inputs = r1cs.num_inputs - 1;
+    let zkey_inputs = params.vk.gamma_abc_g1.len() - 1;
+    if zkey_inputs != public_inputs {
+        return Err(format!(
+            "zkey is for a circuit with {} public inputs, but the r1cs has {}",
+            zkey_inputs, public_inputs
+        ));
+    }
+
+    if params.a_query.len() != r1cs.num_variables {
+        return Err(format!(
+            "zkey is for a circuit with {} wires, but the r1cs has {}",
+            params.a_query.len(),
+            r1cs.num_variables
+        ));
+    }
+
+    Ok(())
+}
+
+/// Value of the linear combination `lc` over `witness`, `None` if the witness has no value
+/// for one of its wires. Wires are looked up through the r1cs wire mapping, if it has one.
+pub(crate) fn eval_lc(r1cs: &R1CS<Bn254>, witness: &[Fr], lc: &[(usize, Fr)]) -> Option<Fr> {
+    lc.iter()
+        .map(|(i, coeff)| {
+            let wire = r1cs.wire_mapping.as_ref().map_or(Some(*i), |m| m.get(*i).copied())?;
+            witness.get(wire).map(|w| *w * coeff)
+        })
+        .sum()
+}
+
+/// Check a witness has a value for each wire, which fails when the wasm and r1cs are from
+/// different builds of the circuit. The error names the first constraint the witness
+/// doesn't satisfy, counting those on wires it has no value for as unsatisfied.
+fn check_witness(r1cs: &R1CS<Bn254>, witness: &[Fr]) -> Result<(), String> {
+    if witness.len() == r1cs.num_variables {
+        return Ok(());
+    }
+
+    let mut msg = format!(
+        "expected {} witness signals, got {}",
+        r1cs.num_variables,
+        witness.len()
+    );
+    let eval = |lc: &[(usize, Fr)]| eval_lc(r1cs, witness, lc);
+    let unsatisfied = r1cs.constraints.iter().position(|(a, b, c)| {
+        match (eval(a), eval(b), eval(c)) {
+            (Some(a), Some(b), Some(c)) => a * b != c,
+            _ => true,
+        }
+    });
+    if let Some(index) = unsatisfied {
+        msg.push_str(&format!(", constraint {} is the first not satisfied", index));
+    }
+
+    Err(msg)
+}
+
+/// The first constraint `witness`, with a value for each wire (see `check_witness`),
+/// doesn't satisfy
+#[cfg(feature = "debug")]
+fn first_unsatisfied(r1cs: &R1CS<Bn254>, witness: &[Fr]) -> Option<UnsatisfiedConstraint> {
+    let eval = |lc: &[(usize, Fr)]| eval_lc(r1cs, witness, lc);
+    let to_u256 = |f: Fr| U256::from_limbs(f.into_repr().0);
+
+    r1cs.constraints
+        .iter()
+        .enumerate()
+        .find_map(|(index, (a, b, c))| {
+            let (a, b, c) = (eval(a)?, eval(b)?, eval(c)?);
+            (a * b != c).then(|| UnsatisfiedConstraint {
+                index,
+                a: to_u256(a),
+                b: to_u256(b),
+                c: to_u256(c),
+            })
+        })
+}
+
+/// Verify a proof against a verification key in the `verification_key.json` layout of
+/// `snarkjs zkey export verificationkey`, e.g. from a JS based setup. Otherwise the same
+/// as `verify_with_vk`: malformed arguments are an error, an invalid proof `Ok(false)`.
+pub fn verify_with_snarkjs_vk(
+    vk_json: &str,
+    proof: &[u8],
+    public_inputs: &[u8],
+) -> Result<bool, String> {
+    check_lengths(proof, public_inputs)?;
+    let vk = snarkjs_verifying_key(vk_json)?;
+
+    verify_prepared(&prepare_verifying_key(&vk), proof, public_inputs)
+}
+
+/// The inverse of `StorageProofs::verification_key_json`. snarkjs writes G2 coordinates
+/// as `[c0, c1]` too, it only swaps them in solidity calldata, and `vk_alphabeta_12` is
+/// derived from the other points, so it's not read.
+fn snarkjs_verifying_key(vk_json: &str) -> Result<VerifyingKey<Bn254>, String> {
+    let json: serde_json::Value = serde_json::from_str(vk_json)
+        .map_err(|e| format!("unable to decode verification key json: {}", e))?;
+    if json["protocol"] != "groth16" || json["curve"] != "bn128" {
+        return Err(format!(
+            "expected a groth16 bn128 verification key, got {} {}",
+            json["protocol"], json["curve"]
+        ));
+    }
+    let key = |name: &str| {
+        json.get(name)
+            .ok_or_else(|| format!("missing required key {}", name))
+    };
+
+    let gamma_abc_g1 = key("IC")?
+        .as_array()
+        .ok_or("IC is not an array")?
+        .iter()
+        .enumerate()
+        .map(|(i, p)| snarkjs_g1(p, &format!("IC[{}]", i)))
+        .collect::<Result<Vec<G1Affine>, String>>()?;
+    if let Some(n_public) = json.get("nPublic").and_then(|n| n.as_u64()) {
+        if gamma_abc_g1.len() as u64 != n_public + 1 {
+            return Err(format!(
+                "{} IC points for {} public inputs, expected {}",
+                gamma_abc_g1.len(),
+                n_public,
+                n_public + 1
+            ));
+        }
+    }
+
+    Ok(VerifyingKey {
+        alpha_g1: snarkjs_g1(key("vk_alpha_1")?, "vk_alpha_1")?,
+        beta_g2: snarkjs_g2(key("vk_beta_2")?, "vk_beta_2")?,
+        gamma_g2: snarkjs_g2(key("vk_gamma_2")?, "vk_gamma_2")?,
+        delta_g2: snarkjs_g2(key("vk_delta_2")?, "vk_delta_2")?,
+        gamma_abc_g1,
+    })
+}
+
+/// A base field element of a snarkjs point, as a decimal string
+fn snarkjs_fq(val: &serde_json::Value, name: &str) -> Result<Fq, String> {
+    let n = decode_json_number(val).map_err(|e| format!("{}: {}", name, e))?;
+    Fq::from_repr(BigInteger256::new(n.into_limbs()))
+        .ok_or_else(|| format!("{}: {} is not below the base field modulus", name, n))
+}
+
+fn snarkjs_fq2(val: &serde_json::Value, name: &str) -> Result<Fq2, String> {
+    match val.as_array().map(Vec::as_slice) {
+        Some([c0, c1]) => Ok(Fq2::new(snarkjs_fq(c0, name)?, snarkjs_fq(c1, name)?)),
+        _ => Err(format!("{}: expected a coordinate of 2 elements, got {}", name, val)),
+    }
+}
+
+/// The `[x, y, z]` coordinates of a snarkjs point, which are exported with `z` as 1
+fn snarkjs_point<'a>(
+    val: &'a serde_json::Value,
+    name: &str,
+) -> Result<&'a [serde_json::Value], String> {
+    match val.as_array().map(Vec::as_slice) {
+        Some(coords) if coords.len() == 3 => Ok(coords),
+        _ => Err(format!("{}: expected a point of 3 coordinates, got {}", name, val)),
+    }
+}
+
+fn snarkjs_g1(val: &serde_json::Value, name: &str) -> Result<G1Affine, String> {
+    let coords = snarkjs_point(val, name)?;
+    if !snarkjs_fq(&coords[2], name)?.is_one() {
+        return Err(format!("{}: expected an affine point, with z of 1", name));
+    }
+
+    let point = G1Affine::new(snarkjs_fq(&coords[0], name)?, snarkjs_fq(&coords[1], name)?, false);
+    if !point.is_on_curve() || !point.is_in_correct_subgroup_assuming_on_curve() {
+        return Err(format!("{}: not a point of the G1 subgroup", name));
+    }
+
+    Ok(point)
+}
+
+fn snarkjs_g2(val: &serde_json::Value, name: &str) -> Result<G2Affine, String> {
+    let coords = snarkjs_point(val, name)?;
+    if !snarkjs_fq2(&coords[2], name)?.is_one() {
+        return Err(format!("{}: expected an affine point, with z of [1, 0]", name));
+    }
+
+    let point =
+        G2Affine::new(snarkjs_fq2(&coords[0], name)?, snarkjs_fq2(&coords[1], name)?, false);
+    if !point.is_on_curve() || !point.is_in_correct_subgroup_assuming_on_curve() {
+        return Err(format!("{}: not a point of the G2 subgroup", name));
+    }
+
+    Ok(point)
+}
+
+fn witness_to_fr(witness: &[U256]) -> Result<Vec<Fr>, String> {
+    witness
+        .iter()
+        .enumerate()
+        .map(|(i, w)| {
+            Fr::from_repr(BigInteger256::new(w.into_limbs()))
+                .ok_or(format!("witness element {} is not a valid field element", i))
+        })
+        .collect()
+}
+
+/// See `ProofOutput::fingerprint`
+fn fingerprint(proof: &[u8], public_inputs: &[u8]) -> [u8; 32] {
+    let mut bytes = Vec::with_capacity(16 + proof.len() + public_inputs.len());
+    for part in [proof, public_inputs] {
+        bytes.extend_from_slice(&(part.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(part);
+    }
+
+    hash_bytes(&bytes).to_le_bytes()
+}
+
+fn keccak256(bytes: &[u8]) -> [u8; 32] {
+    let mut keccak = Keccak::v256();
+    keccak.update(bytes);
+    let mut out = [0; 32];
+    keccak.finalize(&mut out);
+
+    out
+}
+
+fn fq_to_decimal(f: &Fq) -> String {
+    U256::from_limbs(f.into_repr().0).to_string()
+}
+
+/// Quoted, zero padded 32 byte hex word
+fn u256_to_hex(n: &U256) -> String {
+    let digits = n
+        .to_be_bytes_vec()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+
+    format!("\"0x{}\"", digits)
+}
+
+/// The `len` bytes at `offset` of a mapped `prove_from_mmap` input file
+fn mmap_section<'a>(
+    file: &'a [u8],
+    name: &str,
+    offset: u64,
+    len: usize,
+) -> Result<&'a [u8], String> {
+    match offset.checked_add(len as u64) {
+        Some(end) if end <= file.len() as u64 => Ok(&file[offset as usize..end as usize]),
+        _ => Err(format!(
+            "{} needs {} bytes at offset {}, but the file is only {} bytes",
+            name,
+            len,
+            offset,
+            file.len()
+        )),
+    }
+}
+
+/// Serialize field elements in the public inputs layout read by `decode_public_inputs`
+fn encode_public_inputs(inputs: &[U256]) -> Vec<u8> {
+    let mut public_inputs = (inputs.len() as u64).to_le_bytes().to_vec();
+    inputs
+        .iter()
+        .for_each(|i| public_inputs.extend(i.to_le_bytes::<32>()));
+
+    public_inputs
+}
+
+/// Decode serialized public inputs into raw `U256`s, without checking they are valid field elements
+fn decode_public_inputs(mut public_inputs: &[u8]) -> Result<Vec<U256>, String> {
+    let len = u64::deserialize(&mut public_inputs).map_err(|e| e.to_string())? as usize;
+    if public_inputs.len() != len * U256::BYTES {
+        return Err(format!(
+            "expected {} bytes for {} public inputs, got {}",
+            len * U256::BYTES,
+            len,
+            public_inputs.len()
+        ));
+    }
+
+    Ok(public_inputs
+        .chunks(U256::BYTES)
+        .map(U256::from_le_slice)
+        .collect())
+}
+
+/// A calculated witness, with how long that took when collecting metrics
+struct TimedWitness {
+    witness: Zeroizing<Vec<Fr>>,
+    #[cfg(feature = "metrics")]
+    time: Duration,
+}
+
+impl TimedWitness {
+    /// Run `calculate`, the one place witness calculations are timed
+    fn new(
+        calculate: impl FnOnce() -> Result<Zeroizing<Vec<Fr>>, ProveError>,
+    ) -> Result<Self, ProveError> {
+        #[cfg(feature = "metrics")]
+        let start = Instant::now();
+        let witness = calculate()?;
+
+        Ok(Self {
+            witness,
+            #[cfg(feature = "metrics")]
+            time: start.elapsed(),
+        })
+    }
+}
+
+/// The circom witness of `inputs`. The calculator consumes its own copy of the inputs,
+/// which it drops without wiping.
+fn calculate_witness(
+    wtns: &mut WitnessCalculator,
+    inputs: &Inputs,
+) -> Result<Zeroizing<Vec<Fr>>, ProveError> {
+    let _span = tracing::debug_span!(
+        "witness",
+        inputs = inputs.values().map(|v| v.len()).sum::<usize>()
+    )
+    .entered();
+
+    let copy = inputs.iter().map(|(name, values)| (name.clone(), values.clone()));
+    let witness = wtns
+        .calculate_witness_element::<Bn254, _>(copy, false)
+        .map_err(|e| {
+            // only a stack overflow trap or memory that couldn't be grown mean the
+            // circuit is too big, any other trap (out of bounds accesses included) is a
+            // bug in the circuit or its inputs
+            let exhausted = e.chain().any(|cause| {
+                cause.is::<MemoryError>()
+                    || cause
+                        .downcast_ref::<RuntimeError>()
+                        .and_then(|trap| trap.clone().to_trap())
+                        == Some(TrapCode::StackOverflow)
+            });
+            if exhausted {
+                ProveError::WitnessMemoryExhausted
+            } else {
+                ProveError::Failed(format!("{:#}", e))
+            }
+        })?;
+    tracing::debug!(elements = witness.len(), "calculated witness");
+
+    Ok(Zeroizing::new(witness))
+}
+
+fn push_input<T: Into<BigInt>>(inputs: &mut Inputs, name: &str, val: T) {
+    inputs
+        .entry(name.to_string())
+        .or_insert_with(Vec::new)
+        .push(val.into());
+}
+
+fn proof_circuit_inputs(
+    inputs: &ProofInputs,
+    params: &CircuitParams,
+) -> Result<Zeroizing<Inputs>, String> {
+    Ok(circuit_inputs(
+        &params.signals,
+        &inputs.chunks,
+        &inputs.siblings,
+        &inputs.hashes,
+        &inputs.path,
+        Root(inputs.resolve_root_with(&params.hash)?),
+        Salt(inputs.salt),
+    ))
+}
+
+fn circuit_inputs(
+    signals: &SignalMap,
+    chunks: &[U256],
+    siblings: &[U256],
+    hashes: &[U256],
+    path: &[i32],
+    root: Root,
+    salt: Salt,
+) -> Zeroizing<Inputs> {
+    let mut inputs = Zeroizing::new(Inputs::default());
+
+    // vec of vecs is flattened, since wasm expects a contiguous array in memory
+    chunks
+        .iter()
+        .for_each(|c| push_input(&mut inputs, signals.chunks, *c));
+
+    siblings
+        .iter()
+        .for_each(|c| push_input(&mut inputs, signals.siblings, *c));
+
+    hashes
+        .iter()
+        .for_each(|c| push_input(&mut inputs, signals.hashes, *c));
+    path.iter().for_each(|c| push_input(&mut inputs, signals.path, *c));
+
+    push_input(&mut inputs, signals.root, root.0);
+    push_input(&mut inputs, signals.salt, salt.0);
+
+    inputs
+}
+
+fn mpack_numbers(key: &str, vals: &[rmpv::Value]) -> Result<Vec<U256>, MpackError> {
+    vals.iter()
+        .enumerate()
+        .map(|(index, val)| {
+            decode_u256(val).map_err(|reason| MpackError::InvalidElement {
+                key: key.to_string(),
+                index,
+                reason,
+            })
+        })
+        .collect()
+}
+
+fn mpack_array<'a>(
+    args: &'a [(rmpv::Value, rmpv::Value)],
+    key: &'static str,
+) -> Result<&'a Vec<rmpv::Value>, MpackError> {
+    match mpack_value(args, key)? {
+        rmpv::Value::Array(vals) => Ok(vals),
+        _ => Err(MpackError::InvalidKind(key)),
+    }
+}
+
+fn mpack_value<'a>(
+    args: &'a [(rmpv::Value, rmpv::Value)],
+    key: &'static str,
+) -> Result<&'a rmpv::Value, MpackError> {
+    args.iter()
+        .find(|(k, _)| k.as_str() == Some(key))
+        .map(|(_, v)| v)
+        .ok_or(MpackError::MissingKey(key))
+}
+
+fn decode_json_number(val: &serde_json::Value) -> Result<U256, String> {
+    match val {
+        serde_json::Value::String(s) => {
+            let hex = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X"));
+            match hex {
+                Some(hex) => U256::from_str_radix(hex, 16),
+                None => U256::from_str_radix(s, 10),
+            }
+            .map_err(|e| format!("invalid number {:?}: {}", s, e))
+        }
+        serde_json::Value::Number(n) => n
+            .as_u64()
+            .map(U256::from)
+            .ok_or_else(|| format!("expected an unsigned integer, got {}", n)),
+        _ => Err(format!("expected a decimal or hex string, got {}", val)),
+    }
+}
+
+fn json_array<'a>(
+    args: &'a serde_json::Value,
+    key: &str,
+) -> Result<&'a Vec<serde_json::Value>, String> {
+    args.get(key)
+        .ok_or_else(|| format!("missing required key {}", key))?
+        .as_array()
+        .ok_or_else(|| format!("expected an array for key {}", key))
+}
+
+fn json_numbers(key: &str, vals: &[serde_json::Value]) -> Result<Vec<U256>, String> {
+    vals.iter()
+        .enumerate()
+        .map(|(index, val)| {
+            decode_json_number(val)
+                .map_err(|reason| format!("invalid element {} of {}: {}", index, key, reason))
+        })
+        .collect()
+}
+
+/// JSON counterpart of `decode_mpack_args`, with the same checks
+fn decode_json_args(
+    json: &str,
+    chunk_len: Option<usize>,
+    mode: FieldMode,
+) -> Result<ProofInputs, String> {
+    let args: serde_json::Value =
+        serde_json::from_str(json).map_err(|e| format!("unable to decode json: {}", e))?;
+    if !args.is_object() {
+        return Err("args must be an object of string to arrays".to_string());
+    }
+
+    // left out for circuits taking the hashes directly
+    let chunk_args = match args.get("chunks") {
+        None | Some(serde_json::Value::Null) => &[][..],
+        Some(_) => json_array(&args, "chunks")?.as_slice(),
+    };
+
+    let mut chunks = Vec::new();
+    let mut expected_len = chunk_len;
+    for (index, chunk) in chunk_args.iter().enumerate() {
+        let chunk = chunk.as_array().ok_or_else(|| {
+            format!("invalid element {} of chunks: expected inner array of numbers", index)
+        })?;
+
+        let expected = *expected_len.get_or_insert(chunk.len());
+        if chunk.len() != expected {
+            return Err(format!(
+                "chunk {} has {} elements, expected {}",
+                index,
+                chunk.len(),
+                expected
+            ));
+        }
+
+        chunks.extend(json_numbers(&format!("chunks[{}]", index), chunk)?);
+    }
+
+    // siblings may be passed flat or grouped per sampled chunk
+    let mut siblings = Vec::new();
+    for (index, val) in json_array(&args, "siblings")?.iter().enumerate() {
+        match val.as_array() {
+            Some(inner) => siblings.extend(json_numbers(&format!("siblings[{}]", index), inner)?),
+            None => siblings.push(decode_json_number(val).map_err(|reason| {
+                format!("invalid element {} of siblings: {}", index, reason)
+            })?),
+        }
+    }
+
+    let hashes = json_numbers("hashes", json_array(&args, "hashes")?)?;
+
+    let path = json_array(&args, "path")?
+        .iter()
+        .enumerate()
+        .map(|(index, val)| {
+            val.as_i64()
+                .and_then(|i| i32::try_from(i).ok())
+                .ok_or_else(|| format!("invalid element {} of path: expected i32 integer", index))
+        })
+        .collect::<Result<Vec<i32>, String>>()?;
+
+    let scalar = |key: &str| {
+        decode_json_number(args.get(key).ok_or_else(|| format!("missing required key {}", key))?)
+            .map_err(|reason| format!("invalid {}: {}", key, reason))
+    };
+
+    let mut inputs = Zeroizing::new(ProofInputs {
+        chunks,
+        siblings,
+        hashes,
+        path,
+        root: match args.get("root") {
+            None | Some(serde_json::Value::Null) => None,
+            Some(_) => Some(scalar("root")?),
+        },
+        salt: scalar("salt")?,
+        pubkey: U256::ZERO,
+    });
+    inputs.canonicalize(mode).map_err(|e| e.to_string())?;
+
+    Ok(std::mem::take(&mut *inputs))
+}
+
+/// Decode and validate the `prove_mpack` argument map. When `chunk_len` is given,
+/// every chunk must be exactly that long, otherwise all chunks must share a length.
+/// Field elements not below the modulus are rejected or reduced according to `mode`.
+fn decode_mpack_args(
+    mut inputs: &[u8],
+    chunk_len: Option<usize>,
+    mode: FieldMode,
+) -> Result<ProofInputs, MpackError> {
+    let values: rmpv::Value =
+        read_value(&mut inputs).map_err(|e| MpackError::Decode(e.to_string()))?;
+    let args = values.as_map().ok_or(MpackError::NotAMap)?;
+
+    // left out for circuits taking the hashes directly
+    let chunk_args = if args.iter().any(|(k, _)| k.as_str() == Some("chunks")) {
+        mpack_array(args, "chunks")?.as_slice()
+    } else {
+        &[]
+    };
+
+    let mut chunks = Vec::new();
+    let mut expected_len = chunk_len;
+    for (index, chunk) in chunk_args.iter().enumerate() {
+        let chunk = chunk.as_array().ok_or(MpackError::InvalidElement {
+            key: "chunks".to_string(),
+            index,
+            reason: "expected inner array of u256".to_string(),
+        })?;
+
+        let expected = *expected_len.get_or_insert(chunk.len());
+        if chunk.len() != expected {
+            return Err(MpackError::ChunkLength {
+                index,
+                expected,
+                actual: chunk.len(),
+            });
+        }
+
+        chunks.extend(mpack_numbers(&format!("chunks[{}]", index), chunk)?);
+    }
+
+    // siblings may be passed flat or grouped per sampled chunk
+    let mut siblings = Vec::new();
+    for (index, val) in mpack_array(args, "siblings")?.iter().enumerate() {
+        match val.as_array() {
+            Some(inner) => siblings.extend(mpack_numbers(&format!("siblings[{}]", index), inner)?),
+            None => siblings.push(decode_u256(val).map_err(|reason| {
+                MpackError::InvalidElement {
+                    key: "siblings".to_string(),
+                    index,
+                    reason,
+                }
+            })?),
+        }
+    }
+
+    let hashes = mpack_numbers("hashes", mpack_array(args, "hashes")?)?;
+
+    let path = mpack_array(args, "path")?
+        .iter()
+        .enumerate()
+        .map(|(index, val)| {
+            val.as_i64()
+                .and_then(|i| i32::try_from(i).ok())
+                .ok_or(MpackError::InvalidElement {
+                    key: "path".to_string(),
+                    index,
+                    reason: "expected i32 integer".to_string(),
+                })
+        })
+        .collect::<Result<Vec<i32>, MpackError>>()?;
+
+    let scalar = |key: &'static str| {
+        decode_u256(mpack_value(args, key)?).map_err(|reason| MpackError::InvalidElement {
+            key: key.to_string(),
+            index: 0,
+            reason,
+        })
+    };
+
+    let mut inputs = Zeroizing::new(ProofInputs {
+        chunks,
+        siblings,
+        hashes,
+        path,
+        root: if args.iter().any(|(k, _)| k.as_str() == Some("root")) {
+            Some(scalar("root")?)
+        } else {
+            None
+        },
+        salt: scalar("salt")?,
+        pubkey: U256::ZERO,
+    });
+    inputs
+        .canonicalize(mode)
+        .map_err(MpackError::FieldElementOutOfRange)?;
+
+    Ok(std::mem::take(&mut *inputs))
+}
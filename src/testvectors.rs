@@ -0,0 +1,157 @@
+//! Reproducible proofs, for checking other implementations (e.g. the Nim side) against
+//! this one. Everything random, from the chunk data to the proving key and the prover
+//! randomness, is drawn from a single seed, so a vector is byte identical across runs
+//! and machines given the same circuit artifacts.
+
+use ark_std::rand::{rngs::StdRng, Rng, SeedableRng};
+use ruint::aliases::U256;
+
+use crate::merkle::MerkleTree;
+use crate::storage_proofs::{
+    CellOpening, ChunkInputs, CircuitParams, ProofInputs, ProofOutput, Root, Salt, StorageProofs,
+    TREE_ARITY,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestVector {
+    pub seed: u64,
+    pub inputs: ProofInputs,
+    /// serialized with `StorageProofs::verification_key`
+    pub verification_key: Vec<u8>,
+    pub output: ProofOutput,
+}
+
+impl TestVector {
+    /// Prove seeded inputs for the circuit in `wasm` and `r1cs`, which `params` describe.
+    /// The proving key is generated from the seed as well, so it's only good for testing.
+    pub fn generate(
+        wasm: &[u8],
+        r1cs: &[u8],
+        params: CircuitParams,
+        seed: u64,
+    ) -> Result<Self, String> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut prover = StorageProofs::without_proving_key(wasm, r1cs, Some(params))?;
+        prover.set_random_proving_key(&mut rng)?;
+
+        let mut inputs = seeded_inputs(&params, &mut rng)?;
+        if prover.chunk_inputs() == Some(ChunkInputs::Hashed) {
+            inputs.chunks.clear();
+        }
+        let output = prover.prove_with_seed(&inputs, rng.gen())?;
+
+        Ok(Self {
+            seed,
+            inputs,
+            verification_key: prover.verification_key()?,
+            output,
+        })
+    }
+
+    /// The vector as a JSON document. `inputs` uses the `prove_json` layout without the
+    /// root, which is given separately as the value implementations are expected to
+    /// compute; byte strings are lowercase hex.
+    pub fn to_json(&self) -> Result<String, String> {
+        let root = self.inputs.root.ok_or("test vector inputs have no root")?;
+        let decimal = |ns: &[U256]| ns.iter().map(|n| n.to_string()).collect::<Vec<String>>();
+        let per_sample = |ns: &[U256]| {
+            ns.chunks((ns.len() / self.inputs.hashes.len().max(1)).max(1))
+                .map(decimal)
+                .collect::<Vec<Vec<String>>>()
+        };
+
+        let json = serde_json::json!({
+            "seed": self.seed,
+            "inputs": {
+                "chunks": per_sample(&self.inputs.chunks),
+                "siblings": per_sample(&self.inputs.siblings),
+                "hashes": decimal(&self.inputs.hashes),
+                "path": self.inputs.path.clone(),
+                "salt": self.inputs.salt.to_string(),
+            },
+            "root": root.to_string(),
+            "proof": hex::encode(&self.output.proof),
+            "public_inputs": hex::encode(&self.output.public_inputs),
+            "verification_key": hex::encode(&self.verification_key),
+        });
+
+        serde_json::to_string_pretty(&json).map_err(|e| e.to_string())
+    }
+}
+
+/// Openings of `params.n_samples` cells with fixed contents, the first leaves of the
+/// circuit's tree (padded to `tree_depth`), for tests and benchmarks that need valid inputs
+/// but no seed. Errors if the tree has fewer leaves than `n_samples`.
+pub fn openings_inputs(params: &CircuitParams) -> Result<ProofInputs, String> {
+    let leaves = TREE_ARITY.checked_pow(params.tree_depth as u32).unwrap_or(usize::MAX);
+    if params.n_samples > leaves {
+        return Err(format!(
+            "{} samples don't fit a tree of {} leaves",
+            params.n_samples, leaves
+        ));
+    }
+
+    let chunks = (0..params.n_samples)
+        .map(|i| (0..params.chunk_len).map(|j| U256::from(i * 1000 + j)).collect())
+        .collect::<Vec<Vec<U256>>>();
+    let hashes = chunks
+        .iter()
+        .map(|c| params.digest(c))
+        .collect::<Result<Vec<U256>, String>>()?;
+    let tree = params.tree(&hashes)?;
+    let openings = chunks
+        .into_iter()
+        .zip(hashes)
+        .enumerate()
+        .map(|(i, (chunk, hash))| {
+            Ok(CellOpening {
+                chunk,
+                hash,
+                siblings: tree.proof(i)?.siblings,
+                index: i as i32,
+            })
+        })
+        .collect::<Result<Vec<CellOpening>, String>>()?;
+
+    Ok(ProofInputs::from_openings(
+        &openings,
+        Some(Root(tree.root())),
+        Salt(U256::from(42)),
+    ))
+}
+
+/// Random chunks filling a full tree of `tree_depth` levels, with `n_samples` of them
+/// opened at random positions
+fn seeded_inputs<R: Rng>(params: &CircuitParams, rng: &mut R) -> Result<ProofInputs, String> {
+    let leaves = TREE_ARITY.pow(params.tree_depth as u32);
+    let chunks = (0..leaves)
+        .map(|_| {
+            (0..params.chunk_len)
+                .map(|_| U256::from(rng.gen::<u64>()))
+                .collect()
+        })
+        .collect::<Vec<Vec<U256>>>();
+    let hashes = chunks
+        .iter()
+        .map(|c| params.digest(c))
+        .collect::<Result<Vec<U256>, String>>()?;
+    let tree = MerkleTree::with_hash(&hashes, TREE_ARITY, params.hash)?;
+
+    let openings = (0..params.n_samples)
+        .map(|_| {
+            let index = rng.gen_range(0..leaves);
+            Ok(CellOpening {
+                chunk: chunks[index].clone(),
+                hash: hashes[index],
+                siblings: tree.proof(index)?.siblings,
+                index: index as i32,
+            })
+        })
+        .collect::<Result<Vec<CellOpening>, String>>()?;
+
+    Ok(ProofInputs::from_openings(
+        &openings,
+        Some(Root(tree.root())),
+        Salt(U256::from(rng.gen::<u64>())),
+    ))
+}
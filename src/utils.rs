@@ -0,0 +1,318 @@
+use rs_poseidon::poseidon::hash;
+use ruint::{aliases::U256, uint};
+
+use crate::hash::{HashFn, Poseidon};
+use crate::merkle::MerkleTree;
+use crate::storage_proofs::{CircuitParams, DIGEST_CHUNK, FIELD_MODULUS, TREE_ARITY};
+
+/// Most inputs a single Poseidon hash takes (width `t = 17`), same as circomlib
+pub const POSEIDON_MAX_INPUTS: usize = 16;
+
+/// Bytes packed into a field element by `hash_bytes` and `chunkify`, the most that always
+/// stay below the BN254 modulus
+pub const BYTES_PER_ELEMENT: usize = 31;
+
+/// Poseidon hash of 1 to `POSEIDON_MAX_INPUTS` elements, using the parameter set of width
+/// `t = inputs.len() + 1`. Matches circomlib's `Poseidon(n)`.
+pub fn poseidon_hash(inputs: &[U256]) -> Result<U256, String> {
+    if inputs.is_empty() || inputs.len() > POSEIDON_MAX_INPUTS {
+        return Err(format!(
+            "poseidon takes 1 to {} inputs, got {}",
+            POSEIDON_MAX_INPUTS,
+            inputs.len()
+        ));
+    }
+
+    Ok(hash(inputs))
+}
+
+/// Check that a circuit is compiled for the BN254 scalar field, e.g. from the prime recorded
+/// in its r1cs. `rs-poseidon` only has circomlib's constants for BN254, and a circuit over
+/// another field (e.g. compiled with `circom -p bls12381`) hashes with different ones, so
+/// hashes computed here would never match its own.
+pub fn check_field(prime: U256) -> Result<(), String> {
+    if prime != FIELD_MODULUS {
+        return Err(format!(
+            "no Poseidon parameters for the field of prime {}, only BN254's are available",
+            prime
+        ));
+    }
+
+    Ok(())
+}
+
+/// Poseidon hash of arbitrary bytes. They're packed into field elements 31 bytes at a time
+/// and absorbed `POSEIDON_MAX_INPUTS - 1` elements per hash, starting from the byte length.
+pub fn hash_bytes(bytes: &[u8]) -> U256 {
+    let elements = bytes
+        .chunks(BYTES_PER_ELEMENT)
+        .map(U256::from_le_slice)
+        .collect::<Vec<U256>>();
+
+    elements
+        .chunks(POSEIDON_MAX_INPUTS - 1)
+        .fold(U256::from(bytes.len()), |acc, chunk| {
+            let mut inputs = vec![acc];
+            inputs.extend_from_slice(chunk);
+            poseidon_hash(&inputs).expect("at most POSEIDON_MAX_INPUTS inputs")
+        })
+}
+
+/// Split `data` into chunks of `chunk_len` field elements, packing `BYTES_PER_ELEMENT`
+/// little endian bytes into each. The data is followed by a `0x01` byte and zeros up to
+/// the end of the last chunk, so the same data always gives the same chunks, and data
+/// only differing in trailing zeros doesn't. Empty data gives a single chunk of padding.
+///
+/// Panics if `chunk_len` is zero
+pub fn chunkify(data: &[u8], params: &CircuitParams) -> Vec<Vec<U256>> {
+    assert!(params.chunk_len > 0, "can't split data into empty chunks");
+    let chunk_bytes = params.chunk_len * BYTES_PER_ELEMENT;
+
+    let mut padded = Vec::with_capacity(data.len() + chunk_bytes);
+    padded.extend_from_slice(data);
+    padded.push(1);
+    let rem = padded.len() % chunk_bytes;
+    if rem != 0 {
+        padded.resize(padded.len() + chunk_bytes - rem, 0);
+    }
+
+    padded
+        .chunks(chunk_bytes)
+        .map(|chunk| {
+            chunk
+                .chunks(BYTES_PER_ELEMENT)
+                .map(U256::from_le_slice)
+                .collect()
+        })
+        .collect()
+}
+
+/// The first `original_len` bytes packed into `chunks` by `chunkify`, i.e. the data it
+/// split with the padding trimmed off. Errors if an element holds more than
+/// `BYTES_PER_ELEMENT` bytes, or the chunks hold fewer than `original_len`.
+pub fn dechunkify(chunks: &[Vec<U256>], original_len: usize) -> Result<Vec<u8>, String> {
+    let capacity = chunks.iter().map(Vec::len).sum::<usize>() * BYTES_PER_ELEMENT;
+    if original_len > capacity {
+        return Err(format!(
+            "original length {} exceeds the {} bytes the chunks hold",
+            original_len, capacity
+        ));
+    }
+
+    let mut data = Vec::with_capacity(capacity);
+    for element in chunks.iter().flatten() {
+        let bytes = element.to_le_bytes::<32>();
+        if bytes[BYTES_PER_ELEMENT..].iter().any(|b| *b != 0) {
+            return Err(format!(
+                "element {} doesn't fit in {} bytes",
+                element, BYTES_PER_ELEMENT
+            ));
+        }
+        data.extend_from_slice(&bytes[..BYTES_PER_ELEMENT]);
+    }
+    data.truncate(original_len);
+
+    Ok(data)
+}
+
+/// How a chunk is digested, see `PoseidonDigest` in `circuits/poseidon-digest.circom`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DigestConfig {
+    /// number of elements hashed at once (`DIGEST_CHUNK`)
+    pub rate: usize,
+    /// domain tag, when nonzero the digest is hashed once more as `poseidon(domain, digest)`.
+    /// The storer circuit doesn't take one, so proofs need the zero default.
+    pub domain: U256,
+}
+
+impl Default for DigestConfig {
+    /// the digest checked by the storer circuit
+    fn default() -> Self {
+        Self {
+            rate: DIGEST_CHUNK,
+            domain: U256::ZERO,
+        }
+    }
+}
+
+/// Same as `digest_with`, with no domain tag. `chunk_size` defaults to 4.
+///
+/// Panics if `chunk_size`, or the number of chunks, is larger than `POSEIDON_MAX_INPUTS`
+pub fn digest(input: &[U256], chunk_size: Option<usize>) -> U256 {
+    digest_with(
+        input,
+        &DigestConfig {
+            rate: chunk_size.unwrap_or(4),
+            domain: U256::ZERO,
+        },
+    )
+}
+
+/// Hash `input` in chunks of `config.rate` elements, then hash the chunk hashes together.
+///
+/// Panics if the rate, or the number of chunks, is larger than `POSEIDON_MAX_INPUTS`
+pub fn digest_with(input: &[U256], config: &DigestConfig) -> U256 {
+    digest_with_hash(input, config, &Poseidon)
+}
+
+/// Same as `digest_with`, hashing with `hash` instead of Poseidon
+///
+/// Panics if `hash` doesn't take the rate, or the number of chunks, as inputs
+pub fn digest_with_hash<H: HashFn>(input: &[U256], config: &DigestConfig, hash: &H) -> U256 {
+    let chunk_size = config.rate;
+    let chunks = ((input.len() as f32) / (chunk_size as f32)).ceil() as usize;
+    let mut concat: Vec<U256> = vec![];
+
+    for i in 0..chunks {
+        let range = (i * chunk_size)..std::cmp::min((i + 1) * chunk_size, input.len());
+        let mut chunk = input[range].to_vec();
+        if chunk.len() < chunk_size {
+            chunk.resize(chunk_size, uint!(0_U256));
+        }
+
+        concat.push(hash.hash(chunk.as_slice()).expect("unsupported digest chunk size"));
+    }
+
+    let digest = if concat.len() > 1 {
+        hash.hash(concat.as_slice()).expect("too many chunks to digest")
+    } else {
+        concat[0]
+    };
+
+    if config.domain != U256::ZERO {
+        return hash_pair(config.domain, digest, hash);
+    }
+
+    digest
+}
+
+/// Root of a binary Poseidon tree over `leafs`, see `MerkleTree` for how leaf counts that
+/// aren't a power of two are padded
+pub fn treehash(leafs: &[U256]) -> Result<U256, String> {
+    treehash_with(leafs, &Poseidon)
+}
+
+/// Same as `treehash`, hashing with `hash` instead of Poseidon
+pub fn treehash_with<H: HashFn + Clone>(leafs: &[U256], hash: &H) -> Result<U256, String> {
+    Ok(MerkleTree::with_hash(leafs, TREE_ARITY, hash.clone())?.root())
+}
+
+fn hash_pair<H: HashFn>(left: U256, right: U256, hash: &H) -> U256 {
+    hash.hash(&[left, right]).expect("two inputs are always supported")
+}
+
+#[cfg(test)]
+mod tests {
+    use ruint::{aliases::U256, uint};
+
+    use super::{
+        check_field, chunkify, dechunkify, digest, digest_with, hash_bytes, poseidon_hash,
+        DigestConfig, BYTES_PER_ELEMENT, POSEIDON_MAX_INPUTS,
+    };
+    use crate::storage_proofs::{CircuitParams, FIELD_MODULUS};
+
+    #[test]
+    fn test_poseidon_hash() {
+        // circomlibjs test vectors
+        assert_eq!(
+            poseidon_hash(&[U256::from(1)]).unwrap(),
+            uint!(18586133768512220936620570745912940619677854269274689475585506675881198879027_U256)
+        );
+        assert_eq!(
+            poseidon_hash(&[U256::from(1), U256::from(2)]).unwrap(),
+            uint!(7853200120776062878684798364095072458815029376092732009249414926327459813530_U256)
+        );
+        assert_eq!(
+            poseidon_hash(&(1..=4).map(U256::from).collect::<Vec<U256>>()).unwrap(),
+            uint!(18821383157269793795438455681495246036402687001665670618754263018637548127333_U256)
+        );
+
+        assert!(poseidon_hash(&[]).is_err());
+        assert!(poseidon_hash(&vec![U256::ZERO; POSEIDON_MAX_INPUTS]).is_ok());
+        assert!(poseidon_hash(&vec![U256::ZERO; POSEIDON_MAX_INPUTS + 1]).is_err());
+    }
+
+    #[test]
+    fn test_check_field() {
+        // BLS12-381 scalar field
+        let bls12_381 =
+            uint!(52435875175126190479447740508185965837690552500527637822603658699938581184513_U256);
+
+        assert_eq!(check_field(FIELD_MODULUS), Ok(()));
+        let err = check_field(bls12_381).unwrap_err();
+        assert!(err.contains(&bls12_381.to_string()), "{}", err);
+    }
+
+    #[test]
+    fn test_hash_bytes() {
+        let bytes = (0..=255).collect::<Vec<u8>>();
+
+        assert_eq!(hash_bytes(&bytes), hash_bytes(&bytes));
+        assert_ne!(hash_bytes(&bytes), hash_bytes(&bytes[1..]));
+        // the length is absorbed, so trailing zeros still change the hash
+        assert_ne!(hash_bytes(&[1]), hash_bytes(&[1, 0]));
+    }
+
+    #[test]
+    fn test_chunkify() {
+        let params = CircuitParams {
+            chunk_len: 4,
+            ..CircuitParams::default()
+        };
+        let chunk_bytes = params.chunk_len * BYTES_PER_ELEMENT;
+
+        for len in [0, 1, 30, 31, 123, 124, 300] {
+            let data = (0..len).map(|i| (i * 7) as u8).collect::<Vec<u8>>();
+            let chunks = chunkify(&data, &params);
+            assert_eq!(chunks.len(), len / chunk_bytes + 1, "{} bytes", len);
+            assert!(chunks.iter().all(|c| c.len() == params.chunk_len));
+            assert!(chunks.iter().flatten().all(|n| *n < FIELD_MODULUS));
+            assert_eq!(dechunkify(&chunks, len).unwrap(), data);
+        }
+        // trailing zeros are data, not padding
+        for data in [vec![0; 5], vec![1, 0, 0], vec![0, 1]] {
+            assert_eq!(dechunkify(&chunkify(&data, &params), data.len()).unwrap(), data);
+        }
+        assert_eq!(chunkify(b"abc", &params), chunkify(b"abc", &params));
+        assert_ne!(chunkify(b"abc", &params), chunkify(b"abc\0", &params));
+
+        let err = dechunkify(&chunkify(b"abc", &params), chunk_bytes + 1).unwrap_err();
+        assert_eq!(err, "original length 125 exceeds the 124 bytes the chunks hold");
+        assert_eq!(dechunkify(&[], 0).unwrap(), Vec::<u8>::new());
+        let err = dechunkify(&[vec![FIELD_MODULUS - U256::from(1)]], 0).unwrap_err();
+        assert!(err.ends_with("doesn't fit in 31 bytes"), "{}", err);
+    }
+
+    #[test]
+    fn test_dechunkify_trims_padding() {
+        let params = CircuitParams::default();
+        let data = b"stored by a codex node".to_vec();
+
+        let chunks = chunkify(&data, &params);
+        assert_eq!(chunks.len(), 1);
+        let padded = dechunkify(&chunks, params.chunk_len * BYTES_PER_ELEMENT).unwrap();
+        assert_eq!(padded[..data.len()], data[..]);
+        assert_eq!(padded[data.len()], 1);
+        assert!(padded[data.len() + 1..].iter().all(|b| *b == 0));
+
+        assert_eq!(dechunkify(&chunks, data.len()).unwrap(), data);
+        assert_eq!(dechunkify(&chunks, 6).unwrap(), b"stored");
+    }
+
+    #[test]
+    fn test_digest_domain() {
+        let input = (0..256).map(U256::from).collect::<Vec<U256>>();
+        let config = |domain: u64| DigestConfig {
+            domain: U256::from(domain),
+            ..DigestConfig::default()
+        };
+
+        assert_eq!(digest_with(&input, &DigestConfig::default()), digest(&input, Some(16)));
+        assert_eq!(digest_with(&input, &config(0)), digest(&input, Some(16)));
+        assert_ne!(digest_with(&input, &config(1)), digest_with(&input, &config(0)));
+        assert_ne!(digest_with(&input, &config(1)), digest_with(&input, &config(2)));
+
+        let tagged = poseidon_hash(&[U256::from(1), digest(&input, Some(16))]).unwrap();
+        assert_eq!(digest_with(&input, &config(1)), tagged);
+    }
+}
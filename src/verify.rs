@@ -0,0 +1,118 @@
+//! Proof verification, the part of the crate that builds without `std`. It only needs
+//! `alloc` and the arkworks curve and Groth16 crates, none of the witness calculator or
+//! file I/O that proving depends on.
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use ark_bn254::{Bn254, Fr};
+use ark_groth16::{prepare_verifying_key, verify_proof, PreparedVerifyingKey, Proof, VerifyingKey};
+use ark_serialize::CanonicalDeserialize;
+
+/// Length of a serialized proof, the compressed `a` and `c` G1 points and `b` G2 point
+pub const PROOF_LEN: usize = 128;
+
+/// Length of a proof serialized with its points uncompressed, as in the uncompressed
+/// `ProofFormat`
+pub const UNCOMPRESSED_PROOF_LEN: usize = 256;
+
+/// Check `proof` and `public_inputs` have the lengths of a serialized proof, compressed or
+/// not, and public inputs before handing them to arkworks, so empty or truncated buffers
+/// get an error saying so rather than whatever deserialization trips over first
+pub fn check_lengths(proof: &[u8], public_inputs: &[u8]) -> Result<(), String> {
+    if proof.len() != PROOF_LEN && proof.len() != UNCOMPRESSED_PROOF_LEN {
+        return Err(format!(
+            "expected a {} byte proof ({} uncompressed), got {}",
+            PROOF_LEN,
+            UNCOMPRESSED_PROOF_LEN,
+            proof.len()
+        ));
+    }
+
+    if public_inputs.len() < 8 {
+        return Err(format!(
+            "public inputs need at least an 8 byte length, got {} bytes",
+            public_inputs.len()
+        ));
+    }
+
+    let (len, inputs) = public_inputs.split_at(8);
+    let len = u64::from_le_bytes(len.try_into().expect("split at 8 bytes"));
+    if len.checked_mul(32) != Some(inputs.len() as u64) {
+        return Err(format!(
+            "expected {} public inputs, got {} bytes after the length",
+            len,
+            inputs.len()
+        ));
+    }
+
+    Ok(())
+}
+
+/// A proof in either serialization, told apart by its length
+fn read_proof(proof: &[u8]) -> Result<Proof<Bn254>, String> {
+    if proof.len() == UNCOMPRESSED_PROOF_LEN {
+        Proof::<Bn254>::deserialize_uncompressed(proof)
+    } else {
+        Proof::<Bn254>::deserialize(proof)
+    }
+    .map_err(|e| e.to_string())
+}
+
+pub(crate) fn verify_prepared(
+    vk: &PreparedVerifyingKey<Bn254>,
+    proof: &[u8],
+    mut public_inputs: &[u8],
+) -> Result<bool, String> {
+    let span = tracing::debug_span!("verify", public_inputs = tracing::field::Empty);
+    let _span = span.enter();
+
+    let inputs: Vec<Fr> =
+        CanonicalDeserialize::deserialize(&mut public_inputs).map_err(|e| e.to_string())?;
+    span.record("public_inputs", &inputs.len());
+    let proof = read_proof(proof)?;
+
+    let valid = verify_proof(vk, &proof, inputs.as_slice()).map_err(|e| e.to_string())?;
+    tracing::debug!(valid, "verified proof");
+
+    Ok(valid)
+}
+
+/// Same as `verify_prepared` for byte slices, with the public inputs decoded into
+/// `scratch`, which is cleared first. Verifying many proofs with the same `scratch` only
+/// allocates for the pairing.
+pub(crate) fn verify_prepared_with(
+    vk: &PreparedVerifyingKey<Bn254>,
+    proof: &[u8],
+    public_inputs: &[u8],
+    scratch: &mut Vec<Fr>,
+) -> Result<bool, String> {
+    check_lengths(proof, public_inputs)?;
+    let span = tracing::debug_span!("verify", public_inputs = tracing::field::Empty);
+    let _span = span.enter();
+
+    scratch.clear();
+    // past the length prefix, which check_lengths matched against the rest
+    for bytes in public_inputs[8..].chunks(32) {
+        scratch.push(Fr::deserialize(bytes).map_err(|e| e.to_string())?);
+    }
+    span.record("public_inputs", &scratch.len());
+    let proof = read_proof(proof)?;
+
+    let valid = verify_proof(vk, &proof, scratch.as_slice()).map_err(|e| e.to_string())?;
+    tracing::debug!(valid, "verified proof");
+
+    Ok(valid)
+}
+
+/// Verify a proof against a verification key obtained from `StorageProofs::verification_key`,
+/// without loading any proving material. Malformed arguments are an error, a well formed but
+/// invalid proof returns `Ok(false)`.
+pub fn verify_with_vk(vk: &[u8], proof: &[u8], public_inputs: &[u8]) -> Result<bool, String> {
+    check_lengths(proof, public_inputs)?;
+    let vk = VerifyingKey::<Bn254>::deserialize(vk)
+        .map_err(|e| format!("unable to read verification key: {}", e))?;
+
+    verify_prepared(&prepare_verifying_key(&vk), proof, public_inputs)
+}
@@ -0,0 +1,170 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+use codex_storage_proofs::{merkle::MerkleTree, utils::digest};
+use ruint::aliases::U256;
+
+const STORER_R1CS: &str = "./src/circuit_tests/artifacts/storer-test.r1cs";
+const STORER_WASM: &str = "./src/circuit_tests/artifacts/storer-test_js/storer-test.wasm";
+const STORER_ZKEY: &str = "./src/circuit_tests/artifacts/storer-test.zkey";
+const STORER_VK_JSON: &str = "./src/circuit_tests/artifacts/storer-test_verification_key.json";
+
+fn codex_proof(args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_codex-proof"))
+        .args(args)
+        .output()
+        .expect("unable to run codex-proof")
+}
+
+fn inputs_json() -> String {
+    let chunks = (0..4)
+        .map(|i| (0..256).map(|j| U256::from(i * 1000 + j)).collect())
+        .collect::<Vec<Vec<U256>>>();
+    let hashes = chunks
+        .iter()
+        .map(|c| digest(c, Some(16)))
+        .collect::<Vec<U256>>();
+    let tree = MerkleTree::new(&hashes, 2).unwrap();
+    let siblings = (0..hashes.len())
+        .map(|i| tree.proof(i).unwrap().siblings)
+        .collect::<Vec<Vec<U256>>>();
+
+    let decimal = |ns: &[U256]| ns.iter().map(|n| n.to_string()).collect::<Vec<String>>();
+    serde_json::json!({
+        "chunks": chunks.iter().map(|c| decimal(c)).collect::<Vec<_>>(),
+        "siblings": siblings.iter().map(|s| decimal(s)).collect::<Vec<_>>(),
+        "hashes": decimal(&hashes),
+        "path": [0, 1, 2, 3],
+        "root": tree.root().to_string(),
+        "salt": "42",
+    })
+    .to_string()
+}
+
+#[test]
+fn test_cli_prove_verify() {
+    let dir = std::env::temp_dir().join(format!("codex-proof-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = |name: &str| -> String {
+        let path: PathBuf = dir.join(name);
+        path.to_str().unwrap().to_string()
+    };
+
+    std::fs::write(file("inputs.json"), inputs_json()).unwrap();
+
+    let prove = codex_proof(&[
+        "prove",
+        "--r1cs",
+        STORER_R1CS,
+        "--wasm",
+        STORER_WASM,
+        "--inputs",
+        &file("inputs.json"),
+        "--out",
+        &file("proof.bin"),
+        "--public",
+        &file("public.bin"),
+        "--vk",
+        &file("vk.bin"),
+    ]);
+    assert!(prove.status.success(), "{}", String::from_utf8_lossy(&prove.stderr));
+
+    let verify = codex_proof(&[
+        "verify",
+        "--vk",
+        &file("vk.bin"),
+        "--proof",
+        &file("proof.bin"),
+        "--public",
+        &file("public.bin"),
+    ]);
+    assert!(verify.status.success(), "{}", String::from_utf8_lossy(&verify.stderr));
+
+    let prove_json = codex_proof(&[
+        "prove",
+        "--r1cs",
+        STORER_R1CS,
+        "--wasm",
+        STORER_WASM,
+        "--inputs",
+        &file("inputs.json"),
+        "--out",
+        &file("proof.json"),
+        "--public",
+        &file("public.json"),
+        "--vk",
+        &file("other-vk.bin"),
+        "--format",
+        "json",
+    ]);
+    assert!(prove_json.status.success(), "{}", String::from_utf8_lossy(&prove_json.stderr));
+
+    let proof: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(file("proof.json")).unwrap()).unwrap();
+    assert_eq!(proof["protocol"], "groth16");
+    let public: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(file("public.json")).unwrap()).unwrap();
+    assert_eq!(public[1], "42");
+
+    // a proof checked against another key is invalid, not an error
+    let verify = codex_proof(&[
+        "verify",
+        "--vk",
+        &file("other-vk.bin"),
+        "--proof",
+        &file("proof.bin"),
+        "--public",
+        &file("public.bin"),
+    ]);
+    assert_eq!(verify.status.code(), Some(1));
+
+    let missing = codex_proof(&["verify", "--vk", &file("vk.bin")]);
+    assert_eq!(missing.status.code(), Some(2));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// The snarkjs JSON output checked by snarkjs itself, against the verification key its own
+/// setup exported (see `scripts/snarkjs-setup.sh`)
+#[test]
+#[ignore = "needs scripts/snarkjs-setup.sh, run with `cargo test --test cli -- --ignored`"]
+fn test_cli_snarkjs_verify() {
+    let dir = std::env::temp_dir().join(format!("codex-proof-snarkjs-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = |name: &str| -> String {
+        let path: PathBuf = dir.join(name);
+        path.to_str().unwrap().to_string()
+    };
+
+    std::fs::write(file("inputs.json"), inputs_json()).unwrap();
+
+    let prove = codex_proof(&[
+        "prove",
+        "--r1cs",
+        STORER_R1CS,
+        "--wasm",
+        STORER_WASM,
+        "--zkey",
+        STORER_ZKEY,
+        "--inputs",
+        &file("inputs.json"),
+        "--out",
+        &file("proof.json"),
+        "--public",
+        &file("public.json"),
+        "--format",
+        "json",
+    ]);
+    assert!(prove.status.success(), "{}", String::from_utf8_lossy(&prove.stderr));
+
+    let verify = Command::new("npx")
+        .args(["snarkjs", "groth16", "verify", STORER_VK_JSON])
+        .args([file("public.json"), file("proof.json")])
+        .output()
+        .expect("unable to run snarkjs");
+    let stdout = String::from_utf8_lossy(&verify.stdout);
+    assert!(verify.status.success(), "{}{}", stdout, String::from_utf8_lossy(&verify.stderr));
+    assert!(stdout.contains("OK"), "{}", stdout);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -18,7 +18,8 @@ suite "storage proofs ffi":
       r1cs_buff = unsafeBufferPath(r1csPath)
       wasm_buff = unsafeBufferPath(wasmPath)
 
-    let storage_ctx = init_storage_proofs(r1cs_buff, wasm_buff, nil)
+    var err: int32
+    let storage_ctx = init_storage_proofs(r1cs_buff, wasm_buff, nil, addr err)
 
     echo "storage_ctx: ", storage_ctx.repr
     check storage_ctx != nil
@@ -27,7 +28,8 @@ suite "storage proofs ffi":
       mpack_arg_path = "tests/proof_test.mpack"
       proofBuff = unsafeBufferFromFile(mpack_arg_path)
     echo "proofArgs:size: ", proofBuff.len()
-    let res = prove_mpack_ext(storage_ctx, addr proofBuff)
+    let res = prove_mpack_ext(storage_ctx, addr proofBuff, addr err)
 
     echo "result: ", res.repr
     check res != nil
+    check err == ProofError.Ok.int32
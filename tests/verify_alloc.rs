@@ -0,0 +1,122 @@
+//! Allocations made by `verify_borrowed` compared to `verify`, and by `verify_batch`
+//! compared to `verify_borrowed` one item at a time, counted with a global allocator. Lives
+//! in its own test binary so the allocator doesn't affect other tests.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use codex_storage_proofs::{
+    storage_proofs::{CircuitParams, StorageProofs},
+    testvectors::openings_inputs,
+};
+
+const STORER_R1CS: &str = "./src/circuit_tests/artifacts/storer-test.r1cs";
+const STORER_WASM: &str = "./src/circuit_tests/artifacts/storer-test_js/storer-test.wasm";
+
+struct Counting;
+
+thread_local! {
+    /// allocations made on the current thread, so parallel tests don't interfere
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+fn allocations<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = ALLOCATIONS.with(|n| n.get());
+    let res = f();
+
+    (res, ALLOCATIONS.with(|n| n.get()) - before)
+}
+
+#[test]
+fn test_verify_borrowed_allocations() {
+    let params = CircuitParams::default();
+    let prover = StorageProofs::new(
+        STORER_WASM.to_string(),
+        STORER_R1CS.to_string(),
+        None,
+        Some(params),
+    )
+    .unwrap();
+    let output = prover.prove_inputs(&openings_inputs(&params).unwrap()).unwrap();
+    let (proof, public_inputs) = (output.proof.as_slice(), output.public_inputs.as_slice());
+
+    // the first call prepares the key and sizes the scratch buffer
+    prover.verify_borrowed(proof, public_inputs).unwrap();
+
+    let (res, copied) = allocations(|| prover.verify(proof, public_inputs));
+    res.unwrap();
+    let (res, borrowed) = allocations(|| prover.verify_borrowed(proof, public_inputs));
+    res.unwrap();
+    // both use the prepared key, verify also copies the proof and public inputs and
+    // decodes the latter into a fresh buffer
+    assert!(borrowed + 3 <= copied, "{} allocations, {} with verify", borrowed, copied);
+
+    let mut tampered = public_inputs.to_vec();
+    tampered[8] ^= 1;
+    assert!(prover.verify_borrowed(proof, &tampered).is_err());
+    assert!(prover.verify_borrowed(&proof[1..], public_inputs).is_err());
+}
+
+#[test]
+fn test_verify_batch_allocations() {
+    let params = CircuitParams::default();
+    let prover = StorageProofs::new(
+        STORER_WASM.to_string(),
+        STORER_R1CS.to_string(),
+        None,
+        Some(params),
+    )
+    .unwrap();
+    let mut outputs = (0..4)
+        .map(|_| prover.prove_inputs(&openings_inputs(&params).unwrap()).unwrap())
+        .collect::<Vec<_>>();
+    // an output with tampered public inputs, every 10th item fails
+    let mut tampered = outputs[0].clone();
+    tampered.public_inputs[8] ^= 1;
+    outputs.push(tampered);
+
+    let items = (0..1000)
+        .map(|i| {
+            let output = if i % 10 == 0 { &outputs[4] } else { &outputs[i % 4] };
+            (output.proof.as_slice(), output.public_inputs.as_slice())
+        })
+        .collect::<Vec<(&[u8], &[u8])>>();
+
+    // prepares the key and sizes the scratch buffer
+    prover.verify_batch(&items[..1]);
+
+    let (valid, batch) = allocations(|| prover.verify_batch(&items));
+    assert_eq!(valid.len(), 1000);
+    for (i, valid) in valid.iter().enumerate() {
+        assert_eq!(*valid, i % 10 != 0, "item {}", i);
+    }
+
+    let (borrowed, one_by_one) = allocations(|| {
+        items
+            .iter()
+            .map(|(proof, public_inputs)| prover.verify_borrowed(proof, public_inputs).is_ok())
+            .collect::<Vec<bool>>()
+    });
+    assert_eq!(valid, borrowed);
+    // the batch takes the scratch buffer once rather than per item, and allocates no more
+    assert!(
+        batch <= one_by_one,
+        "{} allocations, {} verifying one by one with verify_borrowed",
+        batch,
+        one_by_one
+    );
+}